@@ -20,7 +20,7 @@ use std::{collections::HashMap, time::Duration};
 
 use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Value, json};
 use tokio::sync::{RwLock, mpsc};
 use ulid::Ulid;
 
@@ -33,6 +33,7 @@ use crate::{
         },
         alert_traits::AlertTrait,
         target::{NotificationConfig, TARGETS},
+        template::NotificationTemplate,
     },
     metastore::metastore_traits::MetastoreObject,
     query::resolve_stream_names,
@@ -66,6 +67,8 @@ const RESERVED_FIELDS: &[&str] = &[
     "tags",
     "lastTriggeredAt",
     "last_triggered_at",
+    "createdBy",
+    "created_by",
 ];
 
 /// Helper struct for basic alert fields during migration
@@ -87,6 +90,12 @@ pub struct Context {
     pub deployment_info: DeploymentInfo,
     pub message: String,
     pub notification_config: NotificationConfig,
+    /// groups which breached the condition in the evaluation that triggered the alert
+    pub breached_groups: Vec<BreachedGroup>,
+    /// template of the notification, set by the target being called
+    pub template: Option<NotificationTemplate>,
+    /// origin of the console the notifications link to, the deployment instance by default
+    pub console_origin: String,
 }
 
 impl Context {
@@ -97,13 +106,75 @@ impl Context {
         message: String,
     ) -> Self {
         Self {
+            console_origin: deployment_info.deployment_instance.clone(),
             alert_info,
             deployment_info,
             message,
             notification_config,
+            breached_groups: vec![],
+            template: None,
         }
     }
 
+    /// Link to the alert in the console
+    pub fn alert_link(&self) -> String {
+        format!(
+            "{}/alerts/{}",
+            self.console_origin.trim_end_matches('/'),
+            self.alert_info.alert_id
+        )
+    }
+
+    /// Notification rendered from the template of the target, if it has one
+    pub(crate) fn render_template(&self) -> Option<String> {
+        self.template
+            .as_ref()
+            .map(|template| template.render(&self.template_data()))
+    }
+
+    /// Text of the notification, rendered from the template of the target
+    /// or the default message for the state of the alert
+    pub(crate) fn notification_text(&self) -> String {
+        self.render_template()
+            .unwrap_or_else(|| match self.alert_info.alert_state {
+                AlertState::Triggered => self.message.clone(),
+                AlertState::NotTriggered => self.default_resolved_string(),
+                AlertState::Disabled => self.default_disabled_string(),
+            })
+    }
+
+    /// Key identifying the alert to incident management tools, so that resolving
+    /// the alert closes the incident it opened
+    pub(crate) fn dedup_key(&self) -> String {
+        format!("parseable-alert-{}", self.alert_info.alert_id)
+    }
+
+    /// Data available to notification templates
+    fn template_data(&self) -> Value {
+        let state = self.alert_info.alert_state;
+        json!({
+            "alert": {
+                "id": self.alert_info.alert_id.to_string(),
+                "name": self.alert_info.alert_name,
+                "state": state.to_string(),
+                "severity": self.alert_info.severity,
+                "notificationState": self.alert_info.notification_state.to_string(),
+            },
+            "deployment": {
+                "instance": self.deployment_info.deployment_instance,
+                "id": self.deployment_info.deployment_id.to_string(),
+                "mode": self.deployment_info.deployment_mode,
+            },
+            "message": self.message,
+            "groups": self.breached_groups,
+            "link": self.alert_link(),
+            "triggered": state == AlertState::Triggered,
+            "resolved": state == AlertState::NotTriggered,
+            "disabled": state == AlertState::Disabled,
+            "timestamp": Utc::now().to_rfc3339(),
+        })
+    }
+
     pub(crate) fn default_resolved_string(&self) -> String {
         format!("{} is now `not-triggered` ", self.alert_info.alert_name)
     }
@@ -371,6 +442,7 @@ impl AlertRequest {
             created: created_timestamp,
             tags: self.tags,
             last_triggered_at: None,
+            created_by: None,
             other_fields,
         };
 
@@ -400,6 +472,9 @@ pub struct AlertConfig {
     pub created: DateTime<Utc>,
     pub tags: Option<Vec<String>>,
     pub last_triggered_at: Option<DateTime<Utc>>,
+    /// user who created or last modified the alert, it is evaluated with their row filters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
     #[serde(flatten)]
     pub other_fields: Option<serde_json::Map<String, Value>>,
 }
@@ -428,6 +503,9 @@ pub struct AlertConfigResponse {
     pub created: DateTime<Utc>,
     pub tags: Option<Vec<String>>,
     pub last_triggered_at: Option<DateTime<Utc>>,
+    /// user who created or last modified the alert, it is evaluated with their row filters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
     #[serde(flatten)]
     pub other_fields: Option<serde_json::Map<String, Value>>,
 }
@@ -488,6 +566,7 @@ impl AlertConfig {
             created: self.created,
             tags: self.tags,
             last_triggered_at: self.last_triggered_at,
+            created_by: self.created_by,
             other_fields: self.other_fields,
         }
     }
@@ -522,6 +601,19 @@ pub struct AlertsInfo {
 pub struct ForecastConfig {
     pub historic_duration: String,
     pub forecast_duration: String,
+    #[serde(default)]
+    pub model: ForecastModel,
+}
+
+/// Trend model fitted over the historical series of a forecast alert
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ForecastModel {
+    /// Least squares linear regression over the whole historic duration
+    #[default]
+    Linear,
+    /// Holt's double exponential smoothing, weighs recent windows more than older ones
+    Holt,
 }
 
 impl ForecastConfig {
@@ -550,9 +642,17 @@ impl ForecastConfig {
 #[serde(rename_all = "camelCase")]
 pub struct AnomalyConfig {
     pub historic_duration: String,
+    /// Number of (robust) standard deviations the current value is allowed to
+    /// deviate from the historical baseline before the alert triggers
+    #[serde(default = "AnomalyConfig::default_sensitivity")]
+    pub sensitivity: f64,
 }
 
 impl AnomalyConfig {
+    fn default_sensitivity() -> f64 {
+        3.0
+    }
+
     pub fn calculate_eval_window(&self) -> Result<String, AlertError> {
         let parsed_historic_duration =
             if let Ok(historic_duration) = humantime::parse_duration(&self.historic_duration) {
@@ -583,6 +683,74 @@ pub struct AlertQueryResult {
     pub is_simple_query: bool,
 }
 
+/// Notification sent out when an alert changes state
+#[derive(Debug, Clone, Default)]
+pub struct AlertNotification {
+    pub message: String,
+    pub breached_groups: Vec<BreachedGroup>,
+}
+
+impl From<String> for AlertNotification {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            breached_groups: vec![],
+        }
+    }
+}
+
+impl From<&str> for AlertNotification {
+    fn from(message: &str) -> Self {
+        message.to_owned().into()
+    }
+}
+
+/// Group which breached the alert condition, as exposed to notification templates
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BreachedGroup {
+    /// The group-by column values (empty for non-GROUP BY queries)
+    pub group_values: HashMap<String, String>,
+    /// The aggregate value, or the forecast value for forecast alerts
+    pub value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lower_bound: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upper_bound: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forecast_time: Option<DateTime<Utc>>,
+}
+
+impl BreachedGroup {
+    pub fn new(group_values: HashMap<String, String>, value: f64) -> Self {
+        Self {
+            group_values,
+            value,
+            lower_bound: None,
+            upper_bound: None,
+            forecast_time: None,
+        }
+    }
+
+    /// Expected range of an anomaly alert
+    pub fn with_bounds(mut self, lower: f64, upper: f64) -> Self {
+        self.lower_bound = Some(lower);
+        self.upper_bound = Some(upper);
+        self
+    }
+
+    pub fn with_forecast_time(mut self, forecast_time: DateTime<Utc>) -> Self {
+        self.forecast_time = Some(forecast_time);
+        self
+    }
+}
+
+impl From<&GroupResult> for BreachedGroup {
+    fn from(group: &GroupResult) -> Self {
+        Self::new(group.group_values.clone(), group.aggregate_value)
+    }
+}
+
 /// Result for a single group in a GROUP BY query
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GroupResult {
@@ -592,6 +760,19 @@ pub struct GroupResult {
     pub aggregate_value: f64,
 }
 
+impl GroupResult {
+    /// Stable key identifying the group across multiple query executions
+    pub fn group_key(&self) -> Vec<(String, String)> {
+        let mut key: Vec<(String, String)> = self
+            .group_values
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        key.sort();
+        key
+    }
+}
+
 impl AlertQueryResult {
     /// Get the single aggregate value for simple queries (backward compatibility)
     pub fn get_single_value(&self) -> f64 {
@@ -20,7 +20,7 @@ use crate::{
     alerts::{
         AlertConfig, AlertError, AlertState, AlertType, EvalConfig, Severity,
         alert_enums::NotificationState,
-        alert_structs::{Context, ThresholdConfig},
+        alert_structs::{AlertNotification, Context, ThresholdConfig},
     },
     metastore::metastore_traits::MetastoreObject,
     rbac::map::SessionKey,
@@ -49,7 +49,7 @@ pub trait MessageCreation {
 
 #[async_trait]
 pub trait AlertTrait: Debug + Send + Sync + MetastoreObject {
-    async fn eval_alert(&self) -> Result<Option<String>, AlertError>;
+    async fn eval_alert(&self) -> Result<Option<AlertNotification>, AlertError>;
     async fn validate(&self, session_key: &SessionKey) -> Result<(), AlertError>;
     async fn update_notification_state(
         &mut self,
@@ -58,7 +58,7 @@ pub trait AlertTrait: Debug + Send + Sync + MetastoreObject {
     async fn update_state(
         &mut self,
         alert_state: AlertState,
-        trigger_notif: Option<String>,
+        trigger_notif: Option<AlertNotification>,
     ) -> Result<(), AlertError>;
     fn get_id(&self) -> &Ulid;
     fn get_severity(&self) -> &Severity;
@@ -74,6 +74,7 @@ pub trait AlertTrait: Debug + Send + Sync + MetastoreObject {
     fn get_created(&self) -> String;
     fn get_tags(&self) -> &Option<Vec<String>>;
     fn get_datasets(&self) -> &[String];
+    fn get_created_by(&self) -> Option<&str>;
     fn to_alert_config(&self) -> AlertConfig;
     fn clone_box(&self) -> Box<dyn AlertTrait>;
 }
@@ -92,7 +93,7 @@ pub trait AlertManagerTrait: Send + Sync {
         &self,
         alert_id: Ulid,
         new_state: AlertState,
-        trigger_notif: Option<String>,
+        trigger_notif: Option<AlertNotification>,
     ) -> Result<(), AlertError>;
     async fn update_notification_state(
         &self,
@@ -16,9 +16,9 @@
  *
  */
 
-use std::{str::FromStr, time::Duration};
+use std::{collections::HashMap, str::FromStr, time::Duration};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use serde_json::Value;
 use tonic::async_trait;
 use tracing::{info, trace, warn};
@@ -29,9 +29,15 @@ use crate::{
         AlertConfig, AlertError, AlertState, AlertType, AlertVersion, EvalConfig, Severity,
         ThresholdConfig,
         alert_enums::NotificationState,
-        alert_structs::{AlertStateEntry, GroupResult},
+        alert_structs::{
+            AlertNotification, AlertStateEntry, AnomalyConfig, BreachedGroup, ForecastConfig,
+            GroupResult,
+        },
         alert_traits::{AlertTrait, MessageCreation},
-        alerts_utils::{evaluate_condition, execute_alert_query, extract_time_range},
+        alerts_utils::{
+            anomaly_bounds, evaluate_condition, execute_alert_query, execute_alert_query_series,
+            extract_time_range, fit_trend,
+        },
         get_number_of_agg_exprs,
         target::{self, NotificationConfig},
     },
@@ -66,6 +72,9 @@ pub struct ThresholdAlert {
     pub tags: Option<Vec<String>>,
     pub datasets: Vec<String>,
     pub last_triggered_at: Option<DateTime<Utc>>,
+    /// user who created or last modified the alert, it is evaluated with their row filters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
     #[serde(flatten)]
     pub other_fields: Option<serde_json::Map<String, Value>>,
 }
@@ -82,9 +91,10 @@ impl MetastoreObject for ThresholdAlert {
 
 #[async_trait]
 impl AlertTrait for ThresholdAlert {
-    async fn eval_alert(&self) -> Result<Option<String>, AlertError> {
+    async fn eval_alert(&self) -> Result<Option<AlertNotification>, AlertError> {
         let time_range = extract_time_range(&self.eval_config)?;
-        let query_result = execute_alert_query(self.get_query(), &time_range).await?;
+        let query_result =
+            execute_alert_query(self.get_query(), &time_range, self.get_created_by()).await?;
 
         if query_result.is_simple_query {
             // Handle simple queries
@@ -95,12 +105,15 @@ impl AlertTrait for ThresholdAlert {
                 self.threshold_config.value,
             );
 
-            let message = if result {
-                Some(self.create_threshold_message(final_value)?)
+            let notification = if result {
+                Some(AlertNotification {
+                    message: self.create_threshold_message(final_value)?,
+                    breached_groups: vec![BreachedGroup::new(HashMap::new(), final_value)],
+                })
             } else {
                 None
             };
-            Ok(message)
+            Ok(notification)
         } else {
             // Handle GROUP BY queries - evaluate each group
             let mut breached_groups = Vec::new();
@@ -117,68 +130,26 @@ impl AlertTrait for ThresholdAlert {
                 }
             }
 
-            let message = if !breached_groups.is_empty() {
-                Some(self.create_group_message(&breached_groups)?)
+            let notification = if !breached_groups.is_empty() {
+                Some(AlertNotification {
+                    message: self.create_group_message(&breached_groups)?,
+                    breached_groups: breached_groups.iter().map(BreachedGroup::from).collect(),
+                })
             } else {
                 None
             };
-            Ok(message)
+            Ok(notification)
         }
     }
 
     async fn validate(&self, session_key: &SessionKey) -> Result<(), AlertError> {
-        // validate evalType
-        let eval_frequency = match &self.eval_config {
-            EvalConfig::RollingWindow(rolling_window) => {
-                if humantime::parse_duration(&rolling_window.eval_start).is_err() {
-                    return Err(AlertError::Metadata(
-                        "evalStart should be of type humantime",
-                    ));
-                }
-                rolling_window.eval_frequency
-            }
-        };
-
-        // validate that target repeat notifs !> eval_frequency
-        match &self.notification_config.times {
-            target::Retry::Infinite => {}
-            target::Retry::Finite(repeat) => {
-                let notif_duration =
-                    Duration::from_secs(60 * self.notification_config.interval) * *repeat as u32;
-                if (notif_duration.as_secs_f64()).gt(&((eval_frequency * 60) as f64)) {
-                    return Err(AlertError::Metadata(
-                        "evalFrequency should be greater than target repetition  interval",
-                    ));
-                }
-            }
-        }
-
-        // validate that the query is valid
-        if self.query.is_empty() {
-            return Err(AlertError::InvalidAlertQuery("Empty query".into()));
-        }
-
-        let tables = resolve_stream_names(&self.query)?;
-        if tables.is_empty() {
-            return Err(AlertError::InvalidAlertQuery(
-                "No tables found in query".into(),
-            ));
-        }
-        create_streams_for_distributed(tables)
-            .await
-            .map_err(|_| AlertError::InvalidAlertQuery("Invalid tables".into()))?;
-
-        // validate that the user has access to the tables mentioned in the query
-        user_auth_for_query(session_key, &self.query).await?;
-
-        // validate that the alert query is valid and can be evaluated
-        let num_aggrs = get_number_of_agg_exprs(&self.query).await?;
-        if num_aggrs != 1 {
-            return Err(AlertError::InvalidAlertQuery(format!(
-                "Found {num_aggrs} aggregate expressions, only 1 allowed"
-            )));
-        }
-        Ok(())
+        validate_alert_base(
+            &self.eval_config,
+            &self.notification_config,
+            &self.query,
+            session_key,
+        )
+        .await
     }
 
     async fn update_notification_state(
@@ -199,81 +170,11 @@ impl AlertTrait for ThresholdAlert {
     async fn update_state(
         &mut self,
         new_state: AlertState,
-        trigger_notif: Option<String>,
+        trigger_notif: Option<AlertNotification>,
     ) -> Result<(), AlertError> {
-        if self.state.eq(&AlertState::Disabled) {
-            warn!(
-                "Alert- {} is currently Disabled. Updating state to {new_state}.",
-                self.id
-            );
-            // update state in memory
-            self.state = new_state;
-
-            // if new state is `Triggered`, change triggered at
-            if new_state.eq(&AlertState::Triggered) {
-                self.last_triggered_at = Some(Utc::now());
-            }
-
-            // update on disk
-            PARSEABLE
-                .metastore
-                .put_alert(&self.to_alert_config())
-                .await?;
-            let state_entry = AlertStateEntry::new(self.id, self.state);
-            PARSEABLE
-                .metastore
-                .put_alert_state(&state_entry as &dyn MetastoreObject)
-                .await?;
-            return Ok(());
-        }
-
-        match &mut self.notification_state {
-            NotificationState::Notify => {}
-            NotificationState::Mute(till_time) => {
-                // if now > till_time, modify notif state to notify and proceed
-                let now = Utc::now();
-                let till = match till_time.as_str() {
-                    "indefinite" => DateTime::<Utc>::MAX_UTC,
-                    _ => DateTime::<Utc>::from_str(till_time)
-                        .map_err(|e| AlertError::CustomError(e.to_string()))?,
-                };
-                if now > till {
-                    info!(
-                        "Modifying alert notif state from snoozed to notify- Now= {now}, Snooze till= {till}"
-                    );
-                    self.notification_state = NotificationState::Notify;
-                }
-            }
-        }
-
-        // update state in memory
-        self.state = new_state;
-
-        // if new state is `Triggered`, change triggered at
-        if new_state.eq(&AlertState::Triggered) {
-            self.last_triggered_at = Some(Utc::now());
-        }
-
-        // update on disk
-        PARSEABLE
-            .metastore
-            .put_alert(&self.to_alert_config())
-            .await?;
-        let state_entry = AlertStateEntry::new(self.id, self.state);
-
-        PARSEABLE
-            .metastore
-            .put_alert_state(&state_entry as &dyn MetastoreObject)
-            .await?;
-
-        if let Some(trigger_notif) = trigger_notif
-            && self.notification_state.eq(&NotificationState::Notify)
-        {
-            trace!("trigger notif on-\n{}", self.state);
-            self.to_alert_config()
-                .trigger_notifications(trigger_notif)
-                .await?;
-        }
+        let mut config = self.to_alert_config();
+        transition_alert_state(&mut config, new_state, trigger_notif).await?;
+        *self = config.into();
         Ok(())
     }
 
@@ -337,6 +238,10 @@ impl AlertTrait for ThresholdAlert {
         &self.datasets
     }
 
+    fn get_created_by(&self) -> Option<&str> {
+        self.created_by.as_deref()
+    }
+
     fn to_alert_config(&self) -> AlertConfig {
         let clone = self.clone();
         clone.into()
@@ -413,6 +318,7 @@ impl From<AlertConfig> for ThresholdAlert {
             tags: value.tags,
             datasets: value.datasets,
             last_triggered_at: value.last_triggered_at,
+            created_by: value.created_by,
             other_fields: value.other_fields,
         }
     }
@@ -437,6 +343,7 @@ impl From<ThresholdAlert> for AlertConfig {
             tags: val.tags,
             datasets: val.datasets,
             last_triggered_at: val.last_triggered_at,
+            created_by: val.created_by,
             other_fields: val.other_fields,
         }
     }
@@ -475,3 +382,980 @@ impl ThresholdAlert {
         Ok(message)
     }
 }
+
+/// Struct which defines the anomaly type alerts
+///
+/// The aggregate value of the alert query over the evaluation window is compared against a
+/// baseline built from the same query executed over the historical windows. The alert
+/// triggers when the value deviates from the baseline beyond the configured sensitivity.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct AnomalyAlert {
+    pub version: AlertVersion,
+    #[serde(default)]
+    pub id: Ulid,
+    pub severity: Severity,
+    pub title: String,
+    pub query: String,
+    pub alert_type: AlertType,
+    pub anomaly_config: AnomalyConfig,
+    pub threshold_config: ThresholdConfig,
+    pub eval_config: EvalConfig,
+    pub targets: Vec<Ulid>,
+    // for new alerts, state should be resolved
+    #[serde(default)]
+    pub state: AlertState,
+    pub notification_state: NotificationState,
+    pub notification_config: NotificationConfig,
+    pub created: DateTime<Utc>,
+    pub tags: Option<Vec<String>>,
+    pub datasets: Vec<String>,
+    pub last_triggered_at: Option<DateTime<Utc>>,
+    /// user who created or last modified the alert, it is evaluated with their row filters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(flatten)]
+    pub other_fields: Option<serde_json::Map<String, Value>>,
+}
+
+impl MetastoreObject for AnomalyAlert {
+    fn get_object_path(&self) -> String {
+        alert_json_path(self.id).to_string()
+    }
+
+    fn get_object_id(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+#[async_trait]
+impl AlertTrait for AnomalyAlert {
+    async fn eval_alert(&self) -> Result<Option<AlertNotification>, AlertError> {
+        let time_range = extract_time_range(&self.eval_config)?;
+        let historic_duration = self.historic_duration()?;
+        let query_result =
+            execute_alert_query(self.get_query(), &time_range, self.get_created_by()).await?;
+        let history = execute_alert_query_series(
+            self.get_query(),
+            self.get_created_by(),
+            time_range.end - time_range.start,
+            historic_duration,
+            time_range.start,
+        )
+        .await?;
+
+        if query_result.is_simple_query {
+            let final_value = query_result.get_single_value();
+            let historic_values: Vec<f64> = history
+                .iter()
+                .map(|(_, result)| result.get_single_value())
+                .collect();
+
+            let notification =
+                match anomaly_bounds(&historic_values, self.anomaly_config.sensitivity) {
+                    Some((lower, upper)) if final_value < lower || final_value > upper => {
+                        Some(AlertNotification {
+                            message: self.create_anomaly_message(final_value, lower, upper)?,
+                            breached_groups: vec![
+                                BreachedGroup::new(HashMap::new(), final_value)
+                                    .with_bounds(lower, upper),
+                            ],
+                        })
+                    }
+                    _ => None,
+                };
+            Ok(notification)
+        } else {
+            // build the historical series for each group
+            let mut historic_values: HashMap<Vec<(String, String)>, Vec<f64>> = HashMap::new();
+            for (_, result) in &history {
+                for group in &result.groups {
+                    historic_values
+                        .entry(group.group_key())
+                        .or_default()
+                        .push(group.aggregate_value);
+                }
+            }
+
+            let mut breached_groups = Vec::new();
+            for group in &query_result.groups {
+                let Some(values) = historic_values.get(&group.group_key()) else {
+                    continue;
+                };
+                if let Some((lower, upper)) =
+                    anomaly_bounds(values, self.anomaly_config.sensitivity)
+                    && (group.aggregate_value < lower || group.aggregate_value > upper)
+                {
+                    breached_groups.push((group.clone(), lower, upper));
+                }
+            }
+
+            let notification = if !breached_groups.is_empty() {
+                Some(AlertNotification {
+                    message: self.create_group_message(&breached_groups)?,
+                    breached_groups: breached_groups
+                        .iter()
+                        .map(|(group, lower, upper)| {
+                            BreachedGroup::from(group).with_bounds(*lower, *upper)
+                        })
+                        .collect(),
+                })
+            } else {
+                None
+            };
+            Ok(notification)
+        }
+    }
+
+    async fn validate(&self, session_key: &SessionKey) -> Result<(), AlertError> {
+        if !self.anomaly_config.sensitivity.is_finite() || self.anomaly_config.sensitivity <= 0.0 {
+            return Err(AlertError::ValidationFailure(
+                "sensitivity should be a positive number".into(),
+            ));
+        }
+
+        let historic_duration = self.historic_duration()?;
+        let eval_window = parse_humantime_delta(
+            self.get_eval_window(),
+            "evalStart should be of type humantime",
+        )?;
+        if historic_duration < eval_window * 3 {
+            return Err(AlertError::ValidationFailure(
+                "historicDuration should be at least three times the evaluation window".into(),
+            ));
+        }
+
+        validate_alert_base(
+            &self.eval_config,
+            &self.notification_config,
+            &self.query,
+            session_key,
+        )
+        .await
+    }
+
+    async fn update_notification_state(
+        &mut self,
+        new_notification_state: NotificationState,
+    ) -> Result<(), AlertError> {
+        // update state in memory
+        self.notification_state = new_notification_state;
+
+        // update on disk
+        PARSEABLE
+            .metastore
+            .put_alert(&self.to_alert_config())
+            .await?;
+        Ok(())
+    }
+
+    async fn update_state(
+        &mut self,
+        new_state: AlertState,
+        trigger_notif: Option<AlertNotification>,
+    ) -> Result<(), AlertError> {
+        let mut config = self.to_alert_config();
+        transition_alert_state(&mut config, new_state, trigger_notif).await?;
+        *self = config.try_into()?;
+        Ok(())
+    }
+
+    fn get_id(&self) -> &Ulid {
+        &self.id
+    }
+
+    fn get_query(&self) -> &str {
+        &self.query
+    }
+
+    fn get_severity(&self) -> &Severity {
+        &self.severity
+    }
+
+    fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    fn get_alert_type(&self) -> &AlertType {
+        &self.alert_type
+    }
+
+    fn get_threshold_config(&self) -> &ThresholdConfig {
+        &self.threshold_config
+    }
+
+    fn get_eval_config(&self) -> &EvalConfig {
+        &self.eval_config
+    }
+
+    fn get_targets(&self) -> &[Ulid] {
+        &self.targets
+    }
+
+    fn get_state(&self) -> &AlertState {
+        &self.state
+    }
+
+    fn get_eval_frequency(&self) -> u64 {
+        match &self.eval_config {
+            EvalConfig::RollingWindow(rolling_window) => rolling_window.eval_frequency,
+        }
+    }
+
+    fn get_eval_window(&self) -> &str {
+        match &self.eval_config {
+            EvalConfig::RollingWindow(rolling_window) => rolling_window.eval_start.as_str(),
+        }
+    }
+
+    fn get_created(&self) -> String {
+        self.created.to_rfc3339()
+    }
+
+    fn get_tags(&self) -> &Option<Vec<String>> {
+        &self.tags
+    }
+
+    fn get_datasets(&self) -> &[String] {
+        &self.datasets
+    }
+
+    fn get_created_by(&self) -> Option<&str> {
+        self.created_by.as_deref()
+    }
+
+    fn to_alert_config(&self) -> AlertConfig {
+        let clone = self.clone();
+        clone.into()
+    }
+
+    fn clone_box(&self) -> Box<dyn AlertTrait> {
+        Box::new(self.clone())
+    }
+}
+
+impl MessageCreation for AnomalyAlert {
+    fn get_message_header(&self) -> Result<String, AlertError> {
+        Ok(format!(
+            "Alert Name:         {}\nAlert Type:         Anomaly alert\nSeverity:           {}\nTriggered at:       {}\nSensitivity:        {}\nHistoric Duration:  {}\nAlert ID:           {}\nEvaluation Window:  {}\nFrequency:          {}\n\nValues outside the expected range:",
+            self.title,
+            self.severity,
+            Utc::now().to_rfc3339(),
+            self.anomaly_config.sensitivity,
+            self.anomaly_config.historic_duration,
+            self.id,
+            self.get_eval_window(),
+            self.get_eval_frequency()
+        ))
+    }
+
+    fn create_threshold_message(&self, _actual_value: f64) -> Result<String, AlertError> {
+        Err(AlertError::Unimplemented(
+            "Threshold message creation is not allowed for Anomaly alert".into(),
+        ))
+    }
+
+    fn create_anomaly_message(
+        &self,
+        actual_value: f64,
+        lower_bound: f64,
+        upper_bound: f64,
+    ) -> Result<String, AlertError> {
+        let header = self.get_message_header()?;
+        Ok(format!(
+            "{header}\nValue: {actual_value}\nExpected Range: [{lower_bound}, {upper_bound}]\n\nQuery:\n{}",
+            self.get_query()
+        ))
+    }
+
+    fn create_forecast_message(
+        &self,
+        _forecast_time: DateTime<Utc>,
+        _forecast_value: f64,
+    ) -> Result<String, AlertError> {
+        Err(AlertError::Unimplemented(
+            "Forecast message creation is not allowed for Anomaly alert".into(),
+        ))
+    }
+}
+
+impl TryFrom<AlertConfig> for AnomalyAlert {
+    type Error = AlertError;
+
+    fn try_from(value: AlertConfig) -> Result<Self, Self::Error> {
+        let AlertType::Anomaly(anomaly_config) = &value.alert_type else {
+            return Err(AlertError::CustomError(format!(
+                "Alert {} is not an anomaly alert",
+                value.id
+            )));
+        };
+
+        Ok(Self {
+            version: value.version,
+            id: value.id,
+            severity: value.severity,
+            title: value.title,
+            query: value.query,
+            anomaly_config: anomaly_config.clone(),
+            alert_type: value.alert_type,
+            threshold_config: value.threshold_config,
+            eval_config: value.eval_config,
+            targets: value.targets,
+            state: value.state,
+            notification_state: value.notification_state,
+            notification_config: value.notification_config,
+            created: value.created,
+            tags: value.tags,
+            datasets: value.datasets,
+            last_triggered_at: value.last_triggered_at,
+            created_by: value.created_by,
+            other_fields: value.other_fields,
+        })
+    }
+}
+
+impl From<AnomalyAlert> for AlertConfig {
+    fn from(val: AnomalyAlert) -> Self {
+        AlertConfig {
+            version: val.version,
+            id: val.id,
+            severity: val.severity,
+            title: val.title,
+            query: val.query,
+            alert_type: AlertType::Anomaly(val.anomaly_config),
+            threshold_config: val.threshold_config,
+            eval_config: val.eval_config,
+            targets: val.targets,
+            state: val.state,
+            notification_state: val.notification_state,
+            notification_config: val.notification_config,
+            created: val.created,
+            tags: val.tags,
+            datasets: val.datasets,
+            last_triggered_at: val.last_triggered_at,
+            created_by: val.created_by,
+            other_fields: val.other_fields,
+        }
+    }
+}
+
+impl AnomalyAlert {
+    fn historic_duration(&self) -> Result<TimeDelta, AlertError> {
+        parse_humantime_delta(
+            &self.anomaly_config.historic_duration,
+            "historicDuration should be of type humantime",
+        )
+    }
+
+    fn create_group_message(
+        &self,
+        breached_groups: &[(GroupResult, f64, f64)],
+    ) -> Result<String, AlertError> {
+        let header = self.get_message_header()?;
+        let mut message = format!("{header}\n");
+
+        message.push_str(&format!(
+            "Alerting Groups ({} total):\n",
+            breached_groups.len()
+        ));
+
+        for (index, (group, lower, upper)) in breached_groups.iter().enumerate() {
+            message.push_str(&format!("{}. ", index + 1));
+
+            let group_desc = group
+                .group_values
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect::<Vec<_>>()
+                .join(", ");
+            message.push_str(&group_desc);
+
+            message.push_str(&format!(
+                " → Value: {} (expected range: [{lower}, {upper}])\n",
+                group.aggregate_value
+            ));
+        }
+
+        message.push_str(&format!("\nQuery:\n{}", self.get_query()));
+
+        Ok(message)
+    }
+}
+
+/// Struct which defines the forecast type alerts
+///
+/// A trend is fitted over the aggregate values of the alert query in the historical windows
+/// and projected over the forecast duration. The alert triggers when the projected value
+/// crosses the threshold within the forecast duration.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct ForecastAlert {
+    pub version: AlertVersion,
+    #[serde(default)]
+    pub id: Ulid,
+    pub severity: Severity,
+    pub title: String,
+    pub query: String,
+    pub alert_type: AlertType,
+    pub forecast_config: ForecastConfig,
+    pub threshold_config: ThresholdConfig,
+    pub eval_config: EvalConfig,
+    pub targets: Vec<Ulid>,
+    // for new alerts, state should be resolved
+    #[serde(default)]
+    pub state: AlertState,
+    pub notification_state: NotificationState,
+    pub notification_config: NotificationConfig,
+    pub created: DateTime<Utc>,
+    pub tags: Option<Vec<String>>,
+    pub datasets: Vec<String>,
+    pub last_triggered_at: Option<DateTime<Utc>>,
+    /// user who created or last modified the alert, it is evaluated with their row filters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(flatten)]
+    pub other_fields: Option<serde_json::Map<String, Value>>,
+}
+
+impl MetastoreObject for ForecastAlert {
+    fn get_object_path(&self) -> String {
+        alert_json_path(self.id).to_string()
+    }
+
+    fn get_object_id(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+#[async_trait]
+impl AlertTrait for ForecastAlert {
+    async fn eval_alert(&self) -> Result<Option<AlertNotification>, AlertError> {
+        let time_range = extract_time_range(&self.eval_config)?;
+        let window = time_range.end - time_range.start;
+        let historic_duration = parse_humantime_delta(
+            &self.forecast_config.historic_duration,
+            "historicDuration should be of type humantime",
+        )?;
+        let forecast_duration = parse_humantime_delta(
+            &self.forecast_config.forecast_duration,
+            "forecastDuration should be of type humantime",
+        )?;
+
+        let query_result =
+            execute_alert_query(self.get_query(), &time_range, self.get_created_by()).await?;
+        let mut history = execute_alert_query_series(
+            self.get_query(),
+            self.get_created_by(),
+            window,
+            historic_duration,
+            time_range.start,
+        )
+        .await?;
+        // the current window is the latest sample of the series
+        history.push((time_range.end, query_result.clone()));
+
+        if query_result.is_simple_query {
+            let series: Vec<(DateTime<Utc>, f64)> = history
+                .iter()
+                .map(|(time, result)| (*time, result.get_single_value()))
+                .collect();
+
+            let notification = match self.forecast_breach(&series, window, forecast_duration) {
+                Some((forecast_time, forecast_value)) => Some(AlertNotification {
+                    message: self.create_forecast_message(forecast_time, forecast_value)?,
+                    breached_groups: vec![
+                        BreachedGroup::new(HashMap::new(), forecast_value)
+                            .with_forecast_time(forecast_time),
+                    ],
+                }),
+                None => None,
+            };
+            Ok(notification)
+        } else {
+            // build the historical series for each group
+            let mut group_series: HashMap<Vec<(String, String)>, Vec<(DateTime<Utc>, f64)>> =
+                HashMap::new();
+            for (time, result) in &history {
+                for group in &result.groups {
+                    group_series
+                        .entry(group.group_key())
+                        .or_default()
+                        .push((*time, group.aggregate_value));
+                }
+            }
+
+            let mut breached_groups = Vec::new();
+            for group in &query_result.groups {
+                let Some(series) = group_series.get(&group.group_key()) else {
+                    continue;
+                };
+                if let Some((forecast_time, forecast_value)) =
+                    self.forecast_breach(series, window, forecast_duration)
+                {
+                    breached_groups.push((group.clone(), forecast_time, forecast_value));
+                }
+            }
+
+            let notification = if !breached_groups.is_empty() {
+                Some(AlertNotification {
+                    message: self.create_group_message(&breached_groups)?,
+                    breached_groups: breached_groups
+                        .iter()
+                        .map(|(group, forecast_time, forecast_value)| {
+                            BreachedGroup::new(group.group_values.clone(), *forecast_value)
+                                .with_forecast_time(*forecast_time)
+                        })
+                        .collect(),
+                })
+            } else {
+                None
+            };
+            Ok(notification)
+        }
+    }
+
+    async fn validate(&self, session_key: &SessionKey) -> Result<(), AlertError> {
+        let historic_duration = parse_humantime_delta(
+            &self.forecast_config.historic_duration,
+            "historicDuration should be of type humantime",
+        )?;
+        let forecast_duration = parse_humantime_delta(
+            &self.forecast_config.forecast_duration,
+            "forecastDuration should be of type humantime",
+        )?;
+        let eval_window = parse_humantime_delta(
+            self.get_eval_window(),
+            "evalStart should be of type humantime",
+        )?;
+
+        if historic_duration < eval_window * 3 {
+            return Err(AlertError::ValidationFailure(
+                "historicDuration should be at least three times the evaluation window".into(),
+            ));
+        }
+        if forecast_duration < eval_window {
+            return Err(AlertError::ValidationFailure(
+                "forecastDuration should be at least the evaluation window".into(),
+            ));
+        }
+
+        validate_alert_base(
+            &self.eval_config,
+            &self.notification_config,
+            &self.query,
+            session_key,
+        )
+        .await
+    }
+
+    async fn update_notification_state(
+        &mut self,
+        new_notification_state: NotificationState,
+    ) -> Result<(), AlertError> {
+        // update state in memory
+        self.notification_state = new_notification_state;
+
+        // update on disk
+        PARSEABLE
+            .metastore
+            .put_alert(&self.to_alert_config())
+            .await?;
+        Ok(())
+    }
+
+    async fn update_state(
+        &mut self,
+        new_state: AlertState,
+        trigger_notif: Option<AlertNotification>,
+    ) -> Result<(), AlertError> {
+        let mut config = self.to_alert_config();
+        transition_alert_state(&mut config, new_state, trigger_notif).await?;
+        *self = config.try_into()?;
+        Ok(())
+    }
+
+    fn get_id(&self) -> &Ulid {
+        &self.id
+    }
+
+    fn get_query(&self) -> &str {
+        &self.query
+    }
+
+    fn get_severity(&self) -> &Severity {
+        &self.severity
+    }
+
+    fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    fn get_alert_type(&self) -> &AlertType {
+        &self.alert_type
+    }
+
+    fn get_threshold_config(&self) -> &ThresholdConfig {
+        &self.threshold_config
+    }
+
+    fn get_eval_config(&self) -> &EvalConfig {
+        &self.eval_config
+    }
+
+    fn get_targets(&self) -> &[Ulid] {
+        &self.targets
+    }
+
+    fn get_state(&self) -> &AlertState {
+        &self.state
+    }
+
+    fn get_eval_frequency(&self) -> u64 {
+        match &self.eval_config {
+            EvalConfig::RollingWindow(rolling_window) => rolling_window.eval_frequency,
+        }
+    }
+
+    fn get_eval_window(&self) -> &str {
+        match &self.eval_config {
+            EvalConfig::RollingWindow(rolling_window) => rolling_window.eval_start.as_str(),
+        }
+    }
+
+    fn get_created(&self) -> String {
+        self.created.to_rfc3339()
+    }
+
+    fn get_tags(&self) -> &Option<Vec<String>> {
+        &self.tags
+    }
+
+    fn get_datasets(&self) -> &[String] {
+        &self.datasets
+    }
+
+    fn get_created_by(&self) -> Option<&str> {
+        self.created_by.as_deref()
+    }
+
+    fn to_alert_config(&self) -> AlertConfig {
+        let clone = self.clone();
+        clone.into()
+    }
+
+    fn clone_box(&self) -> Box<dyn AlertTrait> {
+        Box::new(self.clone())
+    }
+}
+
+impl MessageCreation for ForecastAlert {
+    fn get_message_header(&self) -> Result<String, AlertError> {
+        Ok(format!(
+            "Alert Name:         {}\nAlert Type:         Forecast alert\nSeverity:           {}\nTriggered at:       {}\nThreshold:          {}\nForecast Duration:  {}\nHistoric Duration:  {}\nAlert ID:           {}\nEvaluation Window:  {}\nFrequency:          {}\n\nValues forecasted to cross the threshold:",
+            self.title,
+            self.severity,
+            Utc::now().to_rfc3339(),
+            format_args!(
+                "{} {}",
+                self.threshold_config.operator, self.threshold_config.value
+            ),
+            self.forecast_config.forecast_duration,
+            self.forecast_config.historic_duration,
+            self.id,
+            self.get_eval_window(),
+            self.get_eval_frequency()
+        ))
+    }
+
+    fn create_threshold_message(&self, _actual_value: f64) -> Result<String, AlertError> {
+        Err(AlertError::Unimplemented(
+            "Threshold message creation is not allowed for Forecast alert".into(),
+        ))
+    }
+
+    fn create_anomaly_message(
+        &self,
+        _forecast_value: f64,
+        _lower_bound: f64,
+        _upper_bound: f64,
+    ) -> Result<String, AlertError> {
+        Err(AlertError::Unimplemented(
+            "Anomaly message creation is not allowed for Forecast alert".into(),
+        ))
+    }
+
+    fn create_forecast_message(
+        &self,
+        forecasted_time: DateTime<Utc>,
+        forecasted_value: f64,
+    ) -> Result<String, AlertError> {
+        let header = self.get_message_header()?;
+        Ok(format!(
+            "{header}\nForecasted Value: {forecasted_value}\nForecasted At: {}\n\nQuery:\n{}",
+            forecasted_time.to_rfc3339(),
+            self.get_query()
+        ))
+    }
+}
+
+impl TryFrom<AlertConfig> for ForecastAlert {
+    type Error = AlertError;
+
+    fn try_from(value: AlertConfig) -> Result<Self, Self::Error> {
+        let AlertType::Forecast(forecast_config) = &value.alert_type else {
+            return Err(AlertError::CustomError(format!(
+                "Alert {} is not a forecast alert",
+                value.id
+            )));
+        };
+
+        Ok(Self {
+            version: value.version,
+            id: value.id,
+            severity: value.severity,
+            title: value.title,
+            query: value.query,
+            forecast_config: forecast_config.clone(),
+            alert_type: value.alert_type,
+            threshold_config: value.threshold_config,
+            eval_config: value.eval_config,
+            targets: value.targets,
+            state: value.state,
+            notification_state: value.notification_state,
+            notification_config: value.notification_config,
+            created: value.created,
+            tags: value.tags,
+            datasets: value.datasets,
+            last_triggered_at: value.last_triggered_at,
+            created_by: value.created_by,
+            other_fields: value.other_fields,
+        })
+    }
+}
+
+impl From<ForecastAlert> for AlertConfig {
+    fn from(val: ForecastAlert) -> Self {
+        AlertConfig {
+            version: val.version,
+            id: val.id,
+            severity: val.severity,
+            title: val.title,
+            query: val.query,
+            alert_type: AlertType::Forecast(val.forecast_config),
+            threshold_config: val.threshold_config,
+            eval_config: val.eval_config,
+            targets: val.targets,
+            state: val.state,
+            notification_state: val.notification_state,
+            notification_config: val.notification_config,
+            created: val.created,
+            tags: val.tags,
+            datasets: val.datasets,
+            last_triggered_at: val.last_triggered_at,
+            created_by: val.created_by,
+            other_fields: val.other_fields,
+        }
+    }
+}
+
+impl ForecastAlert {
+    /// Projects the series over the forecast duration, one evaluation window at a time,
+    /// and returns the first projected point which crosses the threshold
+    fn forecast_breach(
+        &self,
+        series: &[(DateTime<Utc>, f64)],
+        window: TimeDelta,
+        forecast_duration: TimeDelta,
+    ) -> Option<(DateTime<Utc>, f64)> {
+        let trend = fit_trend(series, self.forecast_config.model)?;
+        let (origin, _) = series.last()?;
+
+        let steps = (forecast_duration.num_seconds() / window.num_seconds().max(1)).max(1);
+        (1..=steps).find_map(|step| {
+            let forecast_time = *origin + window * step as i32;
+            let forecast_value = trend.project(forecast_time);
+            evaluate_condition(
+                &self.threshold_config.operator,
+                forecast_value,
+                self.threshold_config.value,
+            )
+            .then_some((forecast_time, forecast_value))
+        })
+    }
+
+    fn create_group_message(
+        &self,
+        breached_groups: &[(GroupResult, DateTime<Utc>, f64)],
+    ) -> Result<String, AlertError> {
+        let header = self.get_message_header()?;
+        let mut message = format!("{header}\n");
+
+        message.push_str(&format!(
+            "Alerting Groups ({} total):\n",
+            breached_groups.len()
+        ));
+
+        for (index, (group, forecast_time, forecast_value)) in breached_groups.iter().enumerate() {
+            message.push_str(&format!("{}. ", index + 1));
+
+            let group_desc = group
+                .group_values
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect::<Vec<_>>()
+                .join(", ");
+            message.push_str(&group_desc);
+
+            message.push_str(&format!(
+                " → Value: {} (forecasted: {forecast_value} at {})\n",
+                group.aggregate_value,
+                forecast_time.to_rfc3339()
+            ));
+        }
+
+        message.push_str(&format!("\nQuery:\n{}", self.get_query()));
+
+        Ok(message)
+    }
+}
+
+/// Parses a humantime duration into a [`TimeDelta`]
+fn parse_humantime_delta(duration: &str, err: &'static str) -> Result<TimeDelta, AlertError> {
+    let duration = humantime::parse_duration(duration).map_err(|_| AlertError::Metadata(err))?;
+    TimeDelta::from_std(duration).map_err(|e| AlertError::CustomError(e.to_string()))
+}
+
+/// Validations shared by all alert types- evaluation config, notification config
+/// and the alert query itself
+async fn validate_alert_base(
+    eval_config: &EvalConfig,
+    notification_config: &NotificationConfig,
+    query: &str,
+    session_key: &SessionKey,
+) -> Result<(), AlertError> {
+    // validate evalType
+    let eval_frequency = match eval_config {
+        EvalConfig::RollingWindow(rolling_window) => {
+            if humantime::parse_duration(&rolling_window.eval_start).is_err() {
+                return Err(AlertError::Metadata(
+                    "evalStart should be of type humantime",
+                ));
+            }
+            rolling_window.eval_frequency
+        }
+    };
+
+    // validate that target repeat notifs !> eval_frequency
+    match &notification_config.times {
+        target::Retry::Infinite => {}
+        target::Retry::Finite(repeat) => {
+            let notif_duration =
+                Duration::from_secs(60 * notification_config.interval) * *repeat as u32;
+            if (notif_duration.as_secs_f64()).gt(&((eval_frequency * 60) as f64)) {
+                return Err(AlertError::Metadata(
+                    "evalFrequency should be greater than target repetition  interval",
+                ));
+            }
+        }
+    }
+
+    // validate that the query is valid
+    if query.is_empty() {
+        return Err(AlertError::InvalidAlertQuery("Empty query".into()));
+    }
+
+    let tables = resolve_stream_names(query)?;
+    if tables.is_empty() {
+        return Err(AlertError::InvalidAlertQuery(
+            "No tables found in query".into(),
+        ));
+    }
+    create_streams_for_distributed(tables)
+        .await
+        .map_err(|_| AlertError::InvalidAlertQuery("Invalid tables".into()))?;
+
+    // validate that the user has access to the tables mentioned in the query
+    user_auth_for_query(session_key, query).await?;
+
+    // validate that the alert query is valid and can be evaluated
+    let num_aggrs = get_number_of_agg_exprs(query).await?;
+    if num_aggrs != 1 {
+        return Err(AlertError::InvalidAlertQuery(format!(
+            "Found {num_aggrs} aggregate expressions, only 1 allowed"
+        )));
+    }
+    Ok(())
+}
+
+/// Moves the alert to `new_state`, persists the alert and its state entry and
+/// sends out notifications if the alert is not muted
+async fn transition_alert_state(
+    config: &mut AlertConfig,
+    new_state: AlertState,
+    trigger_notif: Option<AlertNotification>,
+) -> Result<(), AlertError> {
+    if config.state.eq(&AlertState::Disabled) {
+        warn!(
+            "Alert- {} is currently Disabled. Updating state to {new_state}.",
+            config.id
+        );
+        // update state in memory
+        config.state = new_state;
+
+        // if new state is `Triggered`, change triggered at
+        if new_state.eq(&AlertState::Triggered) {
+            config.last_triggered_at = Some(Utc::now());
+        }
+
+        // update on disk
+        PARSEABLE.metastore.put_alert(&*config).await?;
+        let state_entry = AlertStateEntry::new(config.id, config.state);
+        PARSEABLE
+            .metastore
+            .put_alert_state(&state_entry as &dyn MetastoreObject)
+            .await?;
+        return Ok(());
+    }
+
+    match &mut config.notification_state {
+        NotificationState::Notify => {}
+        NotificationState::Mute(till_time) => {
+            // if now > till_time, modify notif state to notify and proceed
+            let now = Utc::now();
+            let till = match till_time.as_str() {
+                "indefinite" => DateTime::<Utc>::MAX_UTC,
+                _ => DateTime::<Utc>::from_str(till_time)
+                    .map_err(|e| AlertError::CustomError(e.to_string()))?,
+            };
+            if now > till {
+                info!(
+                    "Modifying alert notif state from snoozed to notify- Now= {now}, Snooze till= {till}"
+                );
+                config.notification_state = NotificationState::Notify;
+            }
+        }
+    }
+
+    // update state in memory
+    config.state = new_state;
+
+    // if new state is `Triggered`, change triggered at
+    if new_state.eq(&AlertState::Triggered) {
+        config.last_triggered_at = Some(Utc::now());
+    }
+
+    // update on disk
+    PARSEABLE.metastore.put_alert(&*config).await?;
+    let state_entry = AlertStateEntry::new(config.id, config.state);
+
+    PARSEABLE
+        .metastore
+        .put_alert_state(&state_entry as &dyn MetastoreObject)
+        .await?;
+
+    if let Some(trigger_notif) = trigger_notif
+        && config.notification_state.eq(&NotificationState::Notify)
+    {
+        trace!("trigger notif on-\n{}", config.state);
+        config.trigger_notifications(trigger_notif).await?;
+    }
+    Ok(())
+}
@@ -20,18 +20,28 @@ use std::{collections::HashMap, fmt::Display};
 
 use actix_web::Either;
 use arrow_array::{Array, Float64Array, Int64Array, RecordBatch};
+use chrono::{DateTime, TimeDelta, Utc};
 use datafusion::{
     logical_expr::{Literal, LogicalPlan},
     prelude::{Expr, lit},
+    sql::sqlparser::{
+        ast::{GroupByExpr, Ident, SelectItem, SetExpr, Statement},
+        dialect::GenericDialect,
+        parser::Parser,
+    },
 };
 use tracing::trace;
 
 use crate::{
     alerts::{
         AlertTrait, LogicalOperator, WhereConfigOperator,
-        alert_structs::{AlertQueryResult, ConditionConfig, Conditions, GroupResult},
+        alert_structs::{
+            AlertNotification, AlertQueryResult, ConditionConfig, Conditions, ForecastModel,
+            GroupResult,
+        },
         extract_aggregate_aliases,
     },
+    event::DEFAULT_TIMESTAMP_KEY,
     handlers::http::{
         cluster::send_query_request,
         query::{Query, create_streams_for_distributed},
@@ -39,6 +49,7 @@ use crate::{
     option::Mode,
     parseable::PARSEABLE,
     query::{QUERY_SESSION, execute, resolve_stream_names},
+    rbac::{Users, apply_access_policies, reads_unrestricted},
     utils::time::TimeRange,
 };
 
@@ -58,9 +69,9 @@ use super::{ALERTS, AlertError, AlertOperator, AlertState};
 pub async fn evaluate_alert(alert: &dyn AlertTrait) -> Result<(), AlertError> {
     trace!("RUNNING EVAL TASK FOR- {alert:?}");
 
-    let message = alert.eval_alert().await?;
+    let notification = alert.eval_alert().await?;
 
-    update_alert_state(alert, message).await
+    update_alert_state(alert, notification).await
 }
 
 /// Extract time range from alert evaluation configuration
@@ -73,14 +84,16 @@ pub fn extract_time_range(eval_config: &super::EvalConfig) -> Result<TimeRange,
         .map_err(|err| AlertError::CustomError(err.to_string()))
 }
 
-/// Execute the alert query based on the current mode and return structured group results
+/// Execute the alert query based on the current mode and return structured group results,
+/// restricted to the rows the user who created the alert can query
 pub async fn execute_alert_query(
     query: &str,
     time_range: &TimeRange,
+    created_by: Option<&str>,
 ) -> Result<AlertQueryResult, AlertError> {
     match PARSEABLE.options.mode {
-        Mode::All | Mode::Query => execute_local_query(query, time_range).await,
-        Mode::Prism => execute_remote_query(query, time_range).await,
+        Mode::All | Mode::Query => execute_local_query(query, time_range, created_by).await,
+        Mode::Prism => execute_remote_query(query, time_range, created_by).await,
         _ => Err(AlertError::CustomError(format!(
             "Unsupported mode '{:?}' for alert evaluation",
             PARSEABLE.options.mode
@@ -88,10 +101,273 @@ pub async fn execute_alert_query(
     }
 }
 
+/// Column of the bucketed alert query holding the start of the window of every row,
+/// in seconds since the epoch
+const WINDOW_START_COLUMN: &str = "p_window_start";
+
+/// Minimum number of historical samples required to compute a baseline
+const MIN_HISTORIC_SAMPLES: usize = 3;
+
+/// Scale factor which makes the median absolute deviation a consistent
+/// estimator of the standard deviation for normally distributed data
+const MAD_SCALE_FACTOR: f64 = 1.4826;
+
+/// Execute the alert query over consecutive historical windows of length `window`,
+/// covering `historic_duration` before `end`.
+///
+/// The query runs once over the whole historic duration, with its rows grouped
+/// by window with `date_bin`. Windows without any row have no groups.
+///
+/// Returns `(window_end, result)` pairs ordered from the oldest to the latest window
+pub async fn execute_alert_query_series(
+    query: &str,
+    created_by: Option<&str>,
+    window: TimeDelta,
+    historic_duration: TimeDelta,
+    end: DateTime<Utc>,
+) -> Result<Vec<(DateTime<Utc>, AlertQueryResult)>, AlertError> {
+    if window.num_seconds() <= 0 {
+        return Err(AlertError::CustomError(
+            "Evaluation window should be greater than zero".into(),
+        ));
+    }
+
+    let total_windows = (historic_duration.num_seconds() / window.num_seconds()).max(0) as i32;
+    if total_windows == 0 {
+        return Ok(vec![]);
+    }
+
+    // rows are windowed on the column the time range of the query applies to
+    let time_column = resolve_stream_names(query)?
+        .first()
+        .and_then(|stream| PARSEABLE.get_stream(stream).ok())
+        .and_then(|stream| stream.get_time_partition())
+        .unwrap_or_else(|| DEFAULT_TIMESTAMP_KEY.to_owned());
+    let bucketed = bucketed_query(query, &time_column, window, end)?;
+    let time_range = TimeRange::new(end - window * total_windows, end);
+    let result = execute_alert_query(&bucketed, &time_range, created_by).await?;
+
+    Ok(split_windows(result, window, end, total_windows))
+}
+
+/// Rewrites the alert query to also group its rows by the windows of length `window`
+/// aligned on `end`, selecting the start of the window as [`WINDOW_START_COLUMN`]
+fn bucketed_query(
+    query: &str,
+    time_column: &str,
+    window: TimeDelta,
+    end: DateTime<Utc>,
+) -> Result<String, AlertError> {
+    let invalid = |reason: String| {
+        AlertError::CustomError(format!("Failed to window the alert query: {reason}"))
+    };
+    let dialect = GenericDialect {};
+
+    let mut statements =
+        Parser::parse_sql(&dialect, query).map_err(|err| invalid(err.to_string()))?;
+    let select = match statements.as_mut_slice() {
+        [Statement::Query(query)] => match query.body.as_mut() {
+            SetExpr::Select(select) => select,
+            _ => return Err(invalid("expected a single SELECT".to_owned())),
+        },
+        _ => return Err(invalid("expected a single SELECT".to_owned())),
+    };
+
+    let window_start = format!(
+        "to_unixtime(date_bin(INTERVAL '{} seconds', \"{}\", TIMESTAMP '{}'))",
+        window.num_seconds(),
+        time_column.replace('"', "\"\""),
+        end.naive_utc().format("%Y-%m-%dT%H:%M:%S")
+    );
+    let window_start = Parser::new(&dialect)
+        .try_with_sql(&window_start)
+        .and_then(|mut parser| parser.parse_expr())
+        .map_err(|err| invalid(err.to_string()))?;
+
+    select.projection.push(SelectItem::ExprWithAlias {
+        expr: window_start.clone(),
+        alias: Ident::new(WINDOW_START_COLUMN),
+    });
+    match &mut select.group_by {
+        GroupByExpr::Expressions(exprs, _) => exprs.push(window_start),
+        // the window is grouped by along with the other selected columns
+        GroupByExpr::All(_) => {}
+    }
+
+    Ok(statements[0].to_string())
+}
+
+/// Splits the result of the bucketed alert query by window, from the oldest to the latest
+fn split_windows(
+    result: AlertQueryResult,
+    window: TimeDelta,
+    end: DateTime<Utc>,
+    total_windows: i32,
+) -> Vec<(DateTime<Utc>, AlertQueryResult)> {
+    let mut windows: HashMap<i64, Vec<GroupResult>> = HashMap::new();
+    for mut group in result.groups {
+        let Some(start) = group
+            .group_values
+            .remove(WINDOW_START_COLUMN)
+            .and_then(|start| start.parse().ok())
+        else {
+            continue;
+        };
+        windows.entry(start).or_default().push(group);
+    }
+    // the window is the only group by column of queries without GROUP BY
+    let is_simple_query = windows
+        .values()
+        .flatten()
+        .all(|group| group.group_values.is_empty());
+
+    (0..total_windows)
+        .rev()
+        .map(|index| {
+            let window_end = end - window * index;
+            let groups = windows
+                .remove(&(window_end - window).timestamp())
+                .unwrap_or_default();
+            (
+                window_end,
+                AlertQueryResult {
+                    groups,
+                    is_simple_query,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Compute the `(lower, upper)` bounds of the expected range of values from historical samples.
+///
+/// The baseline is the median of the samples and the spread is the scaled median absolute
+/// deviation, which keeps the bounds stable in presence of outliers in the history.
+/// Falls back to the standard deviation when more than half of the samples are identical.
+///
+/// Returns `None` if there are not enough samples to compute a meaningful baseline
+pub fn anomaly_bounds(values: &[f64], sensitivity: f64) -> Option<(f64, f64)> {
+    if values.len() < MIN_HISTORIC_SAMPLES {
+        return None;
+    }
+
+    let baseline = median(values);
+    let deviations: Vec<f64> = values.iter().map(|v| (v - baseline).abs()).collect();
+    let mut spread = MAD_SCALE_FACTOR * median(&deviations);
+
+    if spread == 0.0 {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        spread = variance.sqrt();
+    }
+
+    Some((
+        baseline - sensitivity * spread,
+        baseline + sensitivity * spread,
+    ))
+}
+
+/// Smoothing factor for the level in Holt's method
+const HOLT_LEVEL_SMOOTHING: f64 = 0.5;
+
+/// Smoothing factor for the trend in Holt's method
+const HOLT_TREND_SMOOTHING: f64 = 0.3;
+
+/// A trend fitted over a historical series, used to project values into the future
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trend {
+    origin: DateTime<Utc>,
+    value: f64,
+    slope_per_second: f64,
+}
+
+impl Trend {
+    /// Projected value of the series at the given time
+    pub fn project(&self, at: DateTime<Utc>) -> f64 {
+        let elapsed = (at - self.origin).num_milliseconds() as f64 / 1000.0;
+        self.value + self.slope_per_second * elapsed
+    }
+}
+
+/// Fit a trend over a time ordered series of `(time, value)` samples.
+///
+/// The trend originates at the latest sample of the series.
+///
+/// Returns `None` if there are not enough samples or if all the samples are at the same time
+pub fn fit_trend(series: &[(DateTime<Utc>, f64)], model: ForecastModel) -> Option<Trend> {
+    if series.len() < MIN_HISTORIC_SAMPLES {
+        return None;
+    }
+
+    let (first_time, _) = series[0];
+    let (last_time, _) = series[series.len() - 1];
+    let span = (last_time - first_time).num_milliseconds() as f64 / 1000.0;
+    if span <= 0.0 {
+        return None;
+    }
+
+    match model {
+        ForecastModel::Linear => {
+            let xs: Vec<f64> = series
+                .iter()
+                .map(|(time, _)| (*time - first_time).num_milliseconds() as f64 / 1000.0)
+                .collect();
+            let n = series.len() as f64;
+            let mean_x = xs.iter().sum::<f64>() / n;
+            let mean_y = series.iter().map(|(_, value)| value).sum::<f64>() / n;
+
+            let mut covariance = 0.0;
+            let mut variance = 0.0;
+            for (x, (_, y)) in xs.iter().zip(series) {
+                covariance += (x - mean_x) * (y - mean_y);
+                variance += (x - mean_x).powi(2);
+            }
+            let slope = covariance / variance;
+
+            Some(Trend {
+                origin: last_time,
+                value: mean_y + slope * (span - mean_x),
+                slope_per_second: slope,
+            })
+        }
+        ForecastModel::Holt => {
+            let mut level = series[0].1;
+            let mut trend = series[1].1 - series[0].1;
+            for (_, value) in &series[1..] {
+                let previous_level = level;
+                level = HOLT_LEVEL_SMOOTHING * value
+                    + (1.0 - HOLT_LEVEL_SMOOTHING) * (previous_level + trend);
+                trend = HOLT_TREND_SMOOTHING * (level - previous_level)
+                    + (1.0 - HOLT_TREND_SMOOTHING) * trend;
+            }
+
+            // samples are evenly spaced, so the trend is per sample interval
+            let interval = span / (series.len() - 1) as f64;
+            Some(Trend {
+                origin: last_time,
+                value: level,
+                slope_per_second: trend / interval,
+            })
+        }
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
 /// Execute alert query locally (Query/All mode)
 async fn execute_local_query(
     query: &str,
     time_range: &TimeRange,
+    created_by: Option<&str>,
 ) -> Result<AlertQueryResult, AlertError> {
     let session_state = QUERY_SESSION.state();
 
@@ -101,10 +377,19 @@ async fn execute_local_query(
         .map_err(|err| AlertError::CustomError(format!("Failed to create streams: {err}")))?;
 
     let raw_logical_plan = session_state.create_logical_plan(query).await?;
+    // alerts read the streams with the access of their creator, an alert without one doesn't run
+    let Some(userid) = created_by else {
+        return Err(AlertError::Unauthorized);
+    };
+    let filtered_plan = apply_access_policies(
+        raw_logical_plan.clone(),
+        &Users.get_user_permissions(userid),
+    )?;
     let query = crate::query::Query {
-        raw_logical_plan: raw_logical_plan.clone(),
+        raw_logical_plan: filtered_plan,
         time_range: time_range.clone(),
         filter_tag: None,
+        bytes_scanned: Default::default(),
     };
 
     let (records, _) = execute(query, false)
@@ -127,10 +412,26 @@ async fn execute_local_query(
 async fn execute_remote_query(
     query: &str,
     time_range: &TimeRange,
+    created_by: Option<&str>,
 ) -> Result<AlertQueryResult, AlertError> {
     let session_state = QUERY_SESSION.state();
     let raw_logical_plan = session_state.create_logical_plan(query).await?;
 
+    // the querier runs the query with the access of the cluster, so alerts of a
+    // creator restricted by row filters or column masks can't be evaluated there
+    let Some(userid) = created_by else {
+        return Err(AlertError::Unauthorized);
+    };
+    let permissions = Users.get_user_permissions(userid);
+    if !resolve_stream_names(query)?
+        .iter()
+        .all(|stream| reads_unrestricted(&permissions, stream))
+    {
+        return Err(AlertError::CustomError(
+            "Queries restricted by row filters or column masks can't run in Prism mode".to_string(),
+        ));
+    }
+
     let query_request = Query {
         query: query.to_string(),
         start_time: time_range.start.to_rfc3339(),
@@ -139,6 +440,7 @@ async fn execute_remote_query(
         send_null: false,
         fields: false,
         filter_tags: None,
+        filter_id: None,
     };
 
     let (result_value, _) = send_query_request(&query_request)
@@ -265,7 +567,7 @@ pub fn evaluate_condition(operator: &AlertOperator, actual: f64, expected: f64)
 
 async fn update_alert_state(
     alert: &dyn AlertTrait,
-    message: Option<String>,
+    notification: Option<AlertNotification>,
 ) -> Result<(), AlertError> {
     // Get the alert manager reference while holding the lock briefly
     let alerts = {
@@ -278,9 +580,9 @@ async fn update_alert_state(
     };
 
     // Now perform the state update
-    if let Some(msg) = message {
+    if let Some(notification) = notification {
         alerts
-            .update_state(*alert.get_id(), AlertState::Triggered, Some(msg))
+            .update_state(*alert.get_id(), AlertState::Triggered, Some(notification))
             .await
     } else if alerts
         .get_state(*alert.get_id())
@@ -530,3 +832,149 @@ impl Display for ValueType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_query_by_window() {
+        let end = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let window = TimeDelta::minutes(10);
+
+        let query = bucketed_query(
+            "SELECT COUNT(*) AS errors FROM app WHERE level = 'error'",
+            DEFAULT_TIMESTAMP_KEY,
+            window,
+            end,
+        )
+        .unwrap();
+        let window_start = "to_unixtime(date_bin(INTERVAL '600 seconds', \"p_timestamp\", TIMESTAMP '2023-11-14T22:13:20'))";
+        assert!(query.contains(&format!("{window_start} AS {WINDOW_START_COLUMN}")));
+        assert!(query.ends_with(&format!("GROUP BY {window_start}")));
+
+        let query = bucketed_query(
+            "SELECT host, AVG(latency) FROM app GROUP BY host",
+            DEFAULT_TIMESTAMP_KEY,
+            window,
+            end,
+        )
+        .unwrap();
+        assert!(query.ends_with(&format!("GROUP BY host, {window_start}")));
+
+        assert!(
+            bucketed_query(
+                "SELECT COUNT(*) FROM app UNION ALL SELECT COUNT(*) FROM web",
+                DEFAULT_TIMESTAMP_KEY,
+                window,
+                end,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn splits_results_by_window() {
+        let end = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let window = TimeDelta::minutes(10);
+        let group = |start: DateTime<Utc>, host: Option<&str>, value: f64| {
+            let mut group_values = HashMap::from([(
+                WINDOW_START_COLUMN.to_owned(),
+                start.timestamp().to_string(),
+            )]);
+            if let Some(host) = host {
+                group_values.insert("host".to_owned(), host.to_owned());
+            }
+            GroupResult {
+                group_values,
+                aggregate_value: value,
+            }
+        };
+
+        let result = AlertQueryResult {
+            groups: vec![
+                group(end - window, None, 3.0),
+                group(end - window * 3, None, 1.0),
+            ],
+            is_simple_query: false,
+        };
+        let series = split_windows(result, window, end, 3);
+        let windows: Vec<_> = series.iter().map(|(window_end, _)| *window_end).collect();
+        assert_eq!(windows, [end - window * 2, end - window, end]);
+        let values: Vec<_> = series
+            .iter()
+            .map(|(_, result)| result.get_single_value())
+            .collect();
+        assert_eq!(values, [1.0, 0.0, 3.0]);
+
+        let result = AlertQueryResult {
+            groups: vec![group(end - window, Some("web-1"), 2.0)],
+            is_simple_query: false,
+        };
+        let series = split_windows(result, window, end, 2);
+        assert!(!series[1].1.is_simple_query);
+        assert!(series[0].1.groups.is_empty());
+        assert_eq!(
+            series[1].1.groups[0].group_key(),
+            [("host".to_owned(), "web-1".to_owned())]
+        );
+    }
+
+    #[test]
+    fn anomaly_bounds_need_minimum_samples() {
+        assert_eq!(anomaly_bounds(&[1.0, 2.0], 3.0), None);
+    }
+
+    #[test]
+    fn anomaly_bounds_ignore_outliers() {
+        let values = [10.0, 11.0, 9.0, 10.0, 12.0, 8.0, 10.0, 500.0];
+        let (lower, upper) = anomaly_bounds(&values, 3.0).unwrap();
+        assert!(lower < 10.0 && lower > 0.0);
+        assert!(upper > 10.0 && upper < 20.0);
+    }
+
+    #[test]
+    fn anomaly_bounds_fall_back_to_std_dev() {
+        let values = [5.0, 5.0, 5.0, 5.0, 9.0];
+        let (lower, upper) = anomaly_bounds(&values, 1.0).unwrap();
+        assert!(lower < 5.0);
+        assert!(upper > 5.0);
+    }
+
+    fn series(values: &[f64]) -> Vec<(DateTime<Utc>, f64)> {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (start + TimeDelta::minutes(10 * index as i64), *value))
+            .collect()
+    }
+
+    #[test]
+    fn linear_trend_projects_growth() {
+        let series = series(&[10.0, 20.0, 30.0, 40.0]);
+        let trend = fit_trend(&series, ForecastModel::Linear).unwrap();
+        let (last_time, _) = series[3];
+        assert!((trend.project(last_time) - 40.0).abs() < 1e-9);
+        assert!((trend.project(last_time + TimeDelta::minutes(20)) - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn holt_trend_follows_growth() {
+        let series = series(&[10.0, 20.0, 30.0, 40.0, 50.0]);
+        let trend = fit_trend(&series, ForecastModel::Holt).unwrap();
+        let (last_time, _) = series[4];
+        assert!((trend.project(last_time + TimeDelta::minutes(10)) - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn trend_needs_minimum_samples() {
+        assert_eq!(fit_trend(&series(&[1.0, 2.0]), ForecastModel::Linear), None);
+    }
+
+    #[test]
+    fn anomaly_bounds_flat_series() {
+        let values = [4.0, 4.0, 4.0];
+        assert_eq!(anomaly_bounds(&values, 3.0), Some((4.0, 4.0)));
+    }
+}
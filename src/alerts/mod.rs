@@ -43,18 +43,19 @@ pub mod alert_traits;
 pub mod alert_types;
 pub mod alerts_utils;
 pub mod target;
+pub mod template;
 
 pub use crate::alerts::alert_enums::{
     AggregateFunction, AlertOperator, AlertState, AlertTask, AlertType, AlertVersion, EvalConfig,
     LogicalOperator, NotificationState, Severity, WhereConfigOperator,
 };
 pub use crate::alerts::alert_structs::{
-    AlertConfig, AlertInfo, AlertRequest, AlertStateEntry, Alerts, AlertsInfo, AlertsInfoByState,
-    AlertsSummary, BasicAlertFields, Context, DeploymentInfo, RollingWindow, StateTransition,
-    ThresholdConfig,
+    AlertConfig, AlertInfo, AlertNotification, AlertRequest, AlertStateEntry, Alerts, AlertsInfo,
+    AlertsInfoByState, AlertsSummary, BasicAlertFields, Context, DeploymentInfo, RollingWindow,
+    StateTransition, ThresholdConfig,
 };
 use crate::alerts::alert_traits::{AlertManagerTrait, AlertTrait};
-use crate::alerts::alert_types::ThresholdAlert;
+use crate::alerts::alert_types::{AnomalyAlert, ForecastAlert, ThresholdAlert};
 use crate::alerts::target::{NotificationConfig, TARGETS};
 use crate::handlers::http::fetch_schema;
 use crate::metastore::MetastoreError;
@@ -133,6 +134,8 @@ impl AlertConfig {
             created: Utc::now(),
             tags: None,
             last_triggered_at: None,
+            // v1 alerts predate their creators being recorded, they are owned by the admin user
+            created_by: Some(PARSEABLE.options.username.clone()),
             other_fields: None,
         };
 
@@ -589,7 +592,7 @@ impl AlertConfig {
         let deployment_id = storage::StorageMetadata::global().deployment_id;
         let deployment_mode = storage::StorageMetadata::global().mode.to_string();
 
-        Context::new(
+        let mut context = Context::new(
             AlertInfo::new(
                 self.id,
                 self.title.clone(),
@@ -600,12 +603,21 @@ impl AlertConfig {
             DeploymentInfo::new(deployment_instance, deployment_id, deployment_mode),
             self.notification_config.clone(),
             String::default(),
-        )
+        );
+        // links of the notifications point to the console, served from `P_ORIGIN_URI` if set
+        if let Some(domain_address) = &PARSEABLE.options.domain_address {
+            context.console_origin = domain_address.to_string();
+        }
+        context
     }
 
-    pub async fn trigger_notifications(&self, message: String) -> Result<(), AlertError> {
+    pub async fn trigger_notifications(
+        &self,
+        notification: AlertNotification,
+    ) -> Result<(), AlertError> {
         let mut context = self.get_context();
-        context.message.clone_from(&message);
+        context.message = notification.message;
+        context.breached_groups = notification.breached_groups;
 
         for target_id in &self.targets {
             let target = TARGETS.get_target_by_id(target_id).await?;
@@ -977,8 +989,6 @@ pub enum AlertError {
     InvalidQueryParameter(String),
     #[error("{0}")]
     ArrowError(#[from] ArrowError),
-    #[error("Upgrade to Parseable Enterprise for {0} type alerts")]
-    NotPresentInOSS(&'static str),
     #[error("{0}")]
     Unimplemented(String),
     #[error("{0}")]
@@ -1011,7 +1021,6 @@ impl actix_web::ResponseError for AlertError {
             Self::ValidationFailure(_) => StatusCode::BAD_REQUEST,
             Self::ArrowError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Unimplemented(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Self::NotPresentInOSS(_) => StatusCode::BAD_REQUEST,
             Self::MetastoreError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -1059,7 +1068,20 @@ impl AlertManagerTrait for Alerts {
                 } else {
                     // Try to parse as v2
                     match serde_json::from_value::<AlertConfig>(json_value) {
-                        Ok(alert) => alert,
+                        Ok(mut alert) => {
+                            // alerts saved before their creators were recorded are owned
+                            // by the admin user, as alerts only run with the access of their creator
+                            if alert.created_by.is_none() {
+                                alert.created_by = Some(PARSEABLE.options.username.clone());
+                                if let Err(e) = PARSEABLE.metastore.put_alert(&alert).await {
+                                    warn!(
+                                        "Failed to record the creator of alert {}: {e}",
+                                        alert.id
+                                    );
+                                }
+                            }
+                            alert
+                        }
                         Err(e) => {
                             error!("Failed to parse v2 alert: {e}");
                             continue;
@@ -1082,16 +1104,20 @@ impl AlertManagerTrait for Alerts {
                 AlertType::Threshold => {
                     Box::new(ThresholdAlert::from(alert)) as Box<dyn AlertTrait>
                 }
-                AlertType::Anomaly(_) => {
-                    return Err(anyhow::Error::msg(
-                        AlertError::NotPresentInOSS("anomaly").to_string(),
-                    ));
-                }
-                AlertType::Forecast(_) => {
-                    return Err(anyhow::Error::msg(
-                        AlertError::NotPresentInOSS("forecast").to_string(),
-                    ));
-                }
+                AlertType::Anomaly(_) => match AnomalyAlert::try_from(alert) {
+                    Ok(alert) => Box::new(alert) as Box<dyn AlertTrait>,
+                    Err(e) => {
+                        error!("Failed to load anomaly alert: {e}");
+                        continue;
+                    }
+                },
+                AlertType::Forecast(_) => match ForecastAlert::try_from(alert) {
+                    Ok(alert) => Box::new(alert) as Box<dyn AlertTrait>,
+                    Err(e) => {
+                        error!("Failed to load forecast alert: {e}");
+                        continue;
+                    }
+                },
             };
 
             // Create alert task iff alert's state is not paused
@@ -1213,7 +1239,7 @@ impl AlertManagerTrait for Alerts {
         &self,
         alert_id: Ulid,
         new_state: AlertState,
-        trigger_notif: Option<String>,
+        trigger_notif: Option<AlertNotification>,
     ) -> Result<(), AlertError> {
         let (mut alert, should_delete_task, should_create_task) = {
             let read_access = self.alerts.read().await;
@@ -1222,10 +1248,12 @@ impl AlertManagerTrait for Alerts {
                     AlertType::Threshold => Box::new(ThresholdAlert::from(alert.to_alert_config()))
                         as Box<dyn AlertTrait>,
                     AlertType::Anomaly(_) => {
-                        return Err(AlertError::NotPresentInOSS("anomaly"));
+                        Box::new(AnomalyAlert::try_from(alert.to_alert_config())?)
+                            as Box<dyn AlertTrait>
                     }
                     AlertType::Forecast(_) => {
-                        return Err(AlertError::NotPresentInOSS("forecast"));
+                        Box::new(ForecastAlert::try_from(alert.to_alert_config())?)
+                            as Box<dyn AlertTrait>
                     }
                 }
             } else {
@@ -1287,11 +1315,11 @@ impl AlertManagerTrait for Alerts {
                 AlertType::Threshold => {
                     Box::new(ThresholdAlert::from(alert.to_alert_config())) as Box<dyn AlertTrait>
                 }
-                AlertType::Anomaly(_) => {
-                    return Err(AlertError::NotPresentInOSS("anomaly"));
-                }
+                AlertType::Anomaly(_) => Box::new(AnomalyAlert::try_from(alert.to_alert_config())?)
+                    as Box<dyn AlertTrait>,
                 AlertType::Forecast(_) => {
-                    return Err(AlertError::NotPresentInOSS("forecast"));
+                    Box::new(ForecastAlert::try_from(alert.to_alert_config())?)
+                        as Box<dyn AlertTrait>
                 }
             }
         } else {
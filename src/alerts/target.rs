@@ -22,11 +22,17 @@ use std::{
     time::Duration,
 };
 
+use anyhow::anyhow;
 use async_trait::async_trait;
 use base64::Engine;
 use chrono::Utc;
 use http::{HeaderMap, HeaderValue, header::AUTHORIZATION};
 use itertools::Itertools;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, MessageBuilder, MultiPart, SinglePart, header::ContentType},
+    transport::smtp::{self, authentication::Credentials},
+};
 use once_cell::sync::Lazy;
 use reqwest::ClientBuilder;
 use serde_json::{Value, json};
@@ -36,10 +42,14 @@ use ulid::Ulid;
 use url::Url;
 
 use crate::{
-    alerts::{AlertError, AlertState, Context, alert_traits::CallableTarget},
+    alerts::{
+        AlertError, AlertState, Context, alert_traits::CallableTarget,
+        template::NotificationTemplate,
+    },
     metastore::metastore_traits::MetastoreObject,
     parseable::PARSEABLE,
     storage::object_storage::target_json_path,
+    users::reports::ReportDelivery,
 };
 
 use super::ALERTS;
@@ -141,13 +151,16 @@ pub struct Target {
     pub name: String,
     #[serde(flatten)]
     pub target: TargetType,
+    /// template of the notifications, the default message is sent if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<NotificationTemplate>,
     #[serde(default = "Ulid::new")]
     pub id: Ulid,
 }
 
 impl Target {
     pub fn mask(self) -> Value {
-        match self.target {
+        let mut masked = match self.target {
             TargetType::Slack(slack_web_hook) => {
                 let endpoint = slack_web_hook.endpoint.to_string();
                 let masked_endpoint = if endpoint.len() > 20 {
@@ -208,10 +221,61 @@ impl Target {
                     })
                 }
             }
+            TargetType::PagerDuty(pagerduty) => {
+                json!({
+                    "name":self.name,
+                    "type":"pagerduty",
+                    "endpoint":pagerduty.endpoint,
+                    "routingKey":"********",
+                    "id":self.id
+                })
+            }
+            TargetType::Opsgenie(opsgenie) => {
+                json!({
+                    "name":self.name,
+                    "type":"opsgenie",
+                    "endpoint":opsgenie.endpoint,
+                    "apiKey":"********",
+                    "id":self.id
+                })
+            }
+            TargetType::Teams(teams) => {
+                let endpoint = teams.endpoint.to_string();
+                let masked_endpoint = if endpoint.len() > 20 {
+                    format!("{}********", &endpoint[..20])
+                } else {
+                    "********".to_string()
+                };
+                json!({
+                    "name":self.name,
+                    "type":"teams",
+                    "endpoint":masked_endpoint,
+                    "id":self.id
+                })
+            }
+            TargetType::Email(email) => {
+                json!({
+                    "name":self.name,
+                    "type":"email",
+                    "host":email.host,
+                    "port":email.port,
+                    "tls":email.tls,
+                    "username":email.auth.as_ref().map(|auth| &auth.username),
+                    "password":email.auth.as_ref().map(|_| "********"),
+                    "from":email.from,
+                    "to":email.to,
+                    "id":self.id
+                })
+            }
+        };
+        if let Some(template) = self.template {
+            masked["template"] = String::from(template).into();
         }
+        masked
     }
 
-    pub fn call(&self, context: Context) {
+    pub fn call(&self, mut context: Context) {
+        context.template.clone_from(&self.template);
         trace!("target.call context- {context:?}");
         let timeout = context.notification_config.clone();
         let resolves = context.alert_info.alert_state;
@@ -361,6 +425,8 @@ pub struct TargetVerifier {
     pub target: TargetType,
     #[serde(default)]
     pub notification_config: Option<NotificationConfigVerifier>,
+    #[serde(default)]
+    pub template: Option<NotificationTemplate>,
     #[serde(default = "Ulid::new")]
     pub id: Ulid,
 }
@@ -376,6 +442,12 @@ impl TryFrom<TargetVerifier> for Target {
             timeout.times = Retry::Infinite
         }
 
+        if let TargetType::Email(email) = &value.target
+            && email.to.is_empty()
+        {
+            return Err("Email targets need at least one recipient".to_owned());
+        }
+
         if let Some(notification_config) = value.notification_config {
             let interval = notification_config.interval.map(|ref interval| *interval);
 
@@ -391,6 +463,7 @@ impl TryFrom<TargetVerifier> for Target {
         Ok(Target {
             name: value.name,
             target: value.target,
+            template: value.template,
             id: value.id,
         })
     }
@@ -407,6 +480,14 @@ pub enum TargetType {
     Other(OtherWebHook),
     #[serde(rename = "alertManager")]
     AlertManager(AlertManager),
+    #[serde(rename = "pagerduty")]
+    PagerDuty(PagerDuty),
+    #[serde(rename = "opsgenie")]
+    Opsgenie(Opsgenie),
+    #[serde(rename = "teams")]
+    Teams(Teams),
+    #[serde(rename = "email")]
+    Email(Email),
 }
 
 impl TargetType {
@@ -415,6 +496,50 @@ impl TargetType {
             TargetType::Slack(target) => target.call(payload).await,
             TargetType::Other(target) => target.call(payload).await,
             TargetType::AlertManager(target) => target.call(payload).await,
+            TargetType::PagerDuty(target) => target.call(payload).await,
+            TargetType::Opsgenie(target) => target.call(payload).await,
+            TargetType::Teams(target) => target.call(payload).await,
+            TargetType::Email(target) => target.call(payload).await,
+        }
+    }
+
+    /// Whether scheduled reports can be delivered to the target,
+    /// incident management tools only take alerts
+    pub fn supports_reports(&self) -> bool {
+        matches!(
+            self,
+            TargetType::Slack(_)
+                | TargetType::Other(_)
+                | TargetType::Teams(_)
+                | TargetType::Email(_)
+        )
+    }
+
+    /// Delivers a scheduled report, slack and teams only receive the summary of the report
+    pub async fn deliver_report(&self, report: &ReportDelivery) -> anyhow::Result<()> {
+        match self {
+            TargetType::Slack(target) => target.deliver_report(report).await,
+            TargetType::Other(target) => target.deliver_report(report).await,
+            TargetType::Teams(target) => target.deliver_report(report).await,
+            TargetType::Email(target) => target.deliver_report(report).await,
+            TargetType::AlertManager(_) | TargetType::PagerDuty(_) | TargetType::Opsgenie(_) => {
+                Err(anyhow!(
+                    "Reports can't be delivered to {} targets",
+                    self.name()
+                ))
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            TargetType::Slack(_) => "slack",
+            TargetType::Other(_) => "webhook",
+            TargetType::AlertManager(_) => "alertmanager",
+            TargetType::PagerDuty(_) => "pagerduty",
+            TargetType::Opsgenie(_) => "opsgenie",
+            TargetType::Teams(_) => "teams",
+            TargetType::Email(_) => "email",
         }
     }
 }
@@ -435,17 +560,7 @@ impl CallableTarget for SlackWebHook {
             .build()
             .expect("Client can be constructed on this system");
 
-        let alert = match payload.alert_info.alert_state {
-            AlertState::Triggered => {
-                serde_json::json!({ "text": payload.message })
-            }
-            AlertState::NotTriggered => {
-                serde_json::json!({ "text": payload.default_resolved_string() })
-            }
-            AlertState::Disabled => {
-                serde_json::json!({ "text": payload.default_disabled_string() })
-            }
-        };
+        let alert = serde_json::json!({ "text": payload.notification_text() });
 
         if let Err(e) = client.post(self.endpoint.clone()).json(&alert).send().await {
             error!("Couldn't make call to webhook, error: {}", e)
@@ -453,6 +568,21 @@ impl CallableTarget for SlackWebHook {
     }
 }
 
+impl SlackWebHook {
+    async fn deliver_report(&self, report: &ReportDelivery) -> anyhow::Result<()> {
+        let client = default_client_builder().build()?;
+
+        client
+            .post(self.endpoint.clone())
+            .json(&json!({ "text": report.summary }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OtherWebHook {
@@ -475,11 +605,7 @@ impl CallableTarget for OtherWebHook {
             .build()
             .expect("Client can be constructed on this system");
 
-        let alert = match payload.alert_info.alert_state {
-            AlertState::Triggered => payload.message.clone(),
-            AlertState::NotTriggered => payload.default_resolved_string(),
-            AlertState::Disabled => payload.default_disabled_string(),
-        };
+        let alert = payload.notification_text();
 
         let request = client
             .post(self.endpoint.clone())
@@ -491,6 +617,26 @@ impl CallableTarget for OtherWebHook {
     }
 }
 
+impl OtherWebHook {
+    async fn deliver_report(&self, report: &ReportDelivery) -> anyhow::Result<()> {
+        let mut builder = default_client_builder();
+        if self.skip_tls_check {
+            builder = builder.danger_accept_invalid_certs(true)
+        }
+        let client = builder.build()?;
+
+        client
+            .post(self.endpoint.clone())
+            .headers((&self.headers).try_into()?)
+            .json(report)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AlertManager {
@@ -553,6 +699,9 @@ impl CallableTarget for AlertManager {
             }
             AlertState::Disabled => alert["labels"]["status"] = "disabled".into(),
         };
+        if let Some(message) = payload.render_template() {
+            alert["annotations"]["message"] = message.into();
+        }
 
         if let Err(e) = client
             .post(self.endpoint.clone())
@@ -565,6 +714,359 @@ impl CallableTarget for AlertManager {
     }
 }
 
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+const OPSGENIE_API_URL: &str = "https://api.opsgenie.com/";
+
+fn pagerduty_endpoint() -> Url {
+    Url::parse(PAGERDUTY_EVENTS_URL).expect("valid url")
+}
+
+fn opsgenie_endpoint() -> Url {
+    Url::parse(OPSGENIE_API_URL).expect("valid url")
+}
+
+/// Truncates to the limits of the fields of the incident management APIs
+fn truncate(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect()
+}
+
+/// PagerDuty Events API v2 integration, incidents are deduplicated by the
+/// id of the alert so that a resolved alert resolves its incident
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PagerDuty {
+    routing_key: String,
+    #[serde(default = "pagerduty_endpoint")]
+    endpoint: Url,
+}
+
+impl PagerDuty {
+    fn event(&self, payload: &Context) -> Value {
+        match payload.alert_info.alert_state {
+            AlertState::Triggered => json!({
+                "routing_key": self.routing_key,
+                "event_action": "trigger",
+                "dedup_key": payload.dedup_key(),
+                "payload": {
+                    "summary": truncate(&payload.notification_text(), 1024),
+                    "source": payload.deployment_info.deployment_instance,
+                    "severity": pagerduty_severity(&payload.alert_info.severity),
+                    "component": payload.alert_info.alert_name,
+                    "custom_details": {
+                        "alertId": payload.alert_info.alert_id,
+                        "message": payload.message,
+                        "breachedGroups": payload.breached_groups,
+                        "deploymentId": payload.deployment_info.deployment_id,
+                        "deploymentMode": payload.deployment_info.deployment_mode,
+                    }
+                },
+                "links": [{ "href": payload.alert_link(), "text": "View alert" }]
+            }),
+            AlertState::NotTriggered | AlertState::Disabled => json!({
+                "routing_key": self.routing_key,
+                "event_action": "resolve",
+                "dedup_key": payload.dedup_key(),
+            }),
+        }
+    }
+}
+
+fn pagerduty_severity(severity: &str) -> &'static str {
+    match severity.to_lowercase().as_str() {
+        "critical" => "critical",
+        "high" => "error",
+        "medium" => "warning",
+        _ => "info",
+    }
+}
+
+#[async_trait]
+impl CallableTarget for PagerDuty {
+    async fn call(&self, payload: &Context) {
+        let client = default_client_builder()
+            .build()
+            .expect("Client can be constructed on this system");
+
+        if let Err(e) = client
+            .post(self.endpoint.clone())
+            .json(&self.event(payload))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+        {
+            error!("Couldn't make call to pagerduty, error: {}", e)
+        }
+    }
+}
+
+/// Opsgenie Alert API integration, alerts are created and closed by an alias
+/// derived from the id of the alert
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Opsgenie {
+    api_key: String,
+    /// API of the instance, `https://api.eu.opsgenie.com/` for the EU instance
+    #[serde(default = "opsgenie_endpoint")]
+    endpoint: Url,
+}
+
+fn opsgenie_priority(severity: &str) -> &'static str {
+    match severity.to_lowercase().as_str() {
+        "critical" => "P1",
+        "high" => "P2",
+        "medium" => "P3",
+        _ => "P4",
+    }
+}
+
+#[async_trait]
+impl CallableTarget for Opsgenie {
+    async fn call(&self, payload: &Context) {
+        let client = default_client_builder()
+            .build()
+            .expect("Client can be constructed on this system");
+
+        let alias = payload.dedup_key();
+        let (path, body) = match payload.alert_info.alert_state {
+            AlertState::Triggered => (
+                "v2/alerts".to_owned(),
+                json!({
+                    "message": truncate(
+                        &format!("[{}] {}", payload.alert_info.severity, payload.alert_info.alert_name),
+                        130
+                    ),
+                    "alias": alias,
+                    "description": truncate(&payload.notification_text(), 15000),
+                    "priority": opsgenie_priority(&payload.alert_info.severity),
+                    "source": payload.deployment_info.deployment_instance,
+                    "details": {
+                        "alertId": payload.alert_info.alert_id.to_string(),
+                        "link": payload.alert_link(),
+                        "deploymentId": payload.deployment_info.deployment_id.to_string(),
+                    }
+                }),
+            ),
+            AlertState::NotTriggered | AlertState::Disabled => (
+                format!("v2/alerts/{alias}/close"),
+                json!({
+                    "source": payload.deployment_info.deployment_instance,
+                    "note": payload.notification_text(),
+                }),
+            ),
+        };
+
+        let endpoint = match self.endpoint.join(&path) {
+            Ok(endpoint) => endpoint,
+            Err(e) => {
+                error!("Invalid opsgenie endpoint, error: {}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = client
+            .post(endpoint)
+            .query(&[("identifierType", "alias")])
+            .header(AUTHORIZATION, format!("GenieKey {}", self.api_key))
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+        {
+            error!("Couldn't make call to opsgenie, error: {}", e)
+        }
+    }
+}
+
+/// Microsoft Teams webhook (workflow or incoming webhook), notified with adaptive cards
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Teams {
+    endpoint: Url,
+}
+
+impl Teams {
+    fn card(title: &str, color: &str, text: &str, facts: Value, link: Option<String>) -> Value {
+        let actions = match link {
+            Some(url) => json!([{ "type": "Action.OpenUrl", "title": "View alert", "url": url }]),
+            None => json!([]),
+        };
+
+        json!({
+            "type": "message",
+            "attachments": [{
+                "contentType": "application/vnd.microsoft.card.adaptive",
+                "contentUrl": null,
+                "content": {
+                    "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                    "type": "AdaptiveCard",
+                    "version": "1.4",
+                    "body": [
+                        {
+                            "type": "TextBlock",
+                            "text": title,
+                            "weight": "Bolder",
+                            "size": "Medium",
+                            "color": color,
+                            "wrap": true
+                        },
+                        { "type": "TextBlock", "text": text, "wrap": true },
+                        { "type": "FactSet", "facts": facts }
+                    ],
+                    "actions": actions
+                }
+            }]
+        })
+    }
+
+    async fn post(&self, card: &Value) -> reqwest::Result<()> {
+        default_client_builder()
+            .build()?
+            .post(self.endpoint.clone())
+            .json(card)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn deliver_report(&self, report: &ReportDelivery) -> anyhow::Result<()> {
+        let facts = json!([
+            { "title": "From", "value": report.start_time.to_rfc3339() },
+            { "title": "To", "value": report.end_time.to_rfc3339() }
+        ]);
+        let card = Self::card(&report.title, "Default", &report.summary, facts, None);
+        self.post(&card).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CallableTarget for Teams {
+    async fn call(&self, payload: &Context) {
+        let state = payload.alert_info.alert_state;
+        let color = match state {
+            AlertState::Triggered => "Attention",
+            AlertState::NotTriggered => "Good",
+            AlertState::Disabled => "Default",
+        };
+        let facts = json!([
+            { "title": "Severity", "value": payload.alert_info.severity },
+            { "title": "State", "value": state.to_string() },
+            { "title": "Deployment", "value": payload.deployment_info.deployment_instance }
+        ]);
+        let card = Teams::card(
+            &payload.alert_info.alert_name,
+            color,
+            &payload.notification_text(),
+            facts,
+            Some(payload.alert_link()),
+        );
+
+        if let Err(e) = self.post(&card).await {
+            error!("Couldn't make call to teams, error: {}", e)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SmtpTls {
+    /// plain text, only meant for local relays
+    None,
+    #[default]
+    StartTls,
+    /// implicit TLS, usually on port 465
+    Tls,
+}
+
+/// Email sent through an SMTP relay
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Email {
+    host: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    port: Option<u16>,
+    #[serde(default)]
+    tls: SmtpTls,
+    #[serde(flatten)]
+    auth: Option<Auth>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl Email {
+    fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, smtp::Error> {
+        let mut builder = match self.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?,
+        };
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        if let Some(Auth { username, password }) = &self.auth {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(builder.build())
+    }
+
+    fn message(&self, subject: String) -> MessageBuilder {
+        self.to
+            .iter()
+            .fold(Message::builder().from(self.from.clone()), |message, to| {
+                message.to(to.clone())
+            })
+            .subject(subject)
+    }
+
+    async fn send(&self, message: Message) -> anyhow::Result<()> {
+        self.transport()?.send(message).await?;
+        Ok(())
+    }
+
+    async fn deliver_report(&self, report: &ReportDelivery) -> anyhow::Result<()> {
+        let mut body = MultiPart::mixed().singlepart(SinglePart::plain(report.summary.clone()));
+        for attachment in &report.attachments {
+            body = body.singlepart(
+                lettre::message::Attachment::new(attachment.filename.clone()).body(
+                    attachment.content.clone(),
+                    ContentType::parse(attachment.content_type)?,
+                ),
+            );
+        }
+
+        let message = self.message(report.title.clone()).multipart(body)?;
+        self.send(message).await
+    }
+}
+
+#[async_trait]
+impl CallableTarget for Email {
+    async fn call(&self, payload: &Context) {
+        let subject = format!(
+            "[{}] {} is {}",
+            payload.alert_info.severity,
+            payload.alert_info.alert_name,
+            payload.alert_info.alert_state
+        );
+        let body = format!(
+            "{}\n\n{}",
+            payload.notification_text(),
+            payload.alert_link()
+        );
+
+        let result = match self.message(subject).singlepart(SinglePart::plain(body)) {
+            Ok(message) => self.send(message).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            error!("Couldn't send alert email, error: {}", e)
+        }
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
 pub struct NotificationConfig {
     pub interval: u64,
@@ -596,3 +1098,341 @@ pub struct Auth {
     username: String,
     password: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    use crate::alerts::{AlertInfo, DeploymentInfo, NotificationState};
+
+    use super::*;
+
+    /// Request received by a mock server
+    struct Request {
+        /// method and path, e.g. `POST /v2/enqueue`
+        target: String,
+        /// headers, by lowercase name
+        headers: HashMap<String, String>,
+        body: Value,
+    }
+
+    /// Serves a single HTTP request with a 202, returns the url of the server
+    /// and the request it received
+    async fn mock_http() -> (Url, JoinHandle<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            stream.read_line(&mut request_line).await.unwrap();
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                let Some((name, value)) = line.trim_end().split_once(':') else {
+                    break;
+                };
+                headers.insert(name.to_lowercase(), value.trim().to_owned());
+            }
+            let length = headers
+                .get("content-length")
+                .map_or(0, |length| length.parse().unwrap());
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).await.unwrap();
+            stream
+                .get_mut()
+                .write_all(
+                    b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+
+            let target = request_line
+                .split_whitespace()
+                .take(2)
+                .collect_vec()
+                .join(" ");
+            Request {
+                target,
+                headers,
+                body: serde_json::from_slice(&body).unwrap(),
+            }
+        });
+
+        (url, server)
+    }
+
+    /// Accepts a single mail over plain SMTP, returns the port of the server and the
+    /// content of the mail it received
+    async fn mock_smtp() -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut read = BufReader::new(read);
+            write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+            let mut mail = String::new();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if read.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        write.write_all(b"250 OK\r\n").await.unwrap();
+                    } else {
+                        mail.push_str(&line);
+                    }
+                    continue;
+                }
+                let reply: &[u8] = match line.get(..4).map(str::to_uppercase).as_deref() {
+                    Some("EHLO") => b"250 localhost\r\n",
+                    Some("DATA") => {
+                        in_data = true;
+                        b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                    }
+                    Some("QUIT") => {
+                        write.write_all(b"221 Bye\r\n").await.unwrap();
+                        break;
+                    }
+                    _ => b"250 OK\r\n",
+                };
+                write.write_all(reply).await.unwrap();
+            }
+
+            mail
+        });
+
+        (port, server)
+    }
+
+    fn context(alert_state: AlertState) -> Context {
+        Context::new(
+            AlertInfo::new(
+                Ulid::nil(),
+                "High latency".to_owned(),
+                alert_state,
+                NotificationState::Notify,
+                "Critical".to_owned(),
+            ),
+            DeploymentInfo::new(
+                "http://localhost:8000".to_owned(),
+                Ulid::nil(),
+                "All".to_owned(),
+            ),
+            NotificationConfig::default(),
+            "latency above 500ms".to_owned(),
+        )
+    }
+
+    #[test]
+    fn deserializes_new_targets() {
+        let pagerduty: Target = serde_json::from_value(json!({
+            "name": "paging",
+            "type": "pagerduty",
+            "routingKey": "abc",
+        }))
+        .unwrap();
+        assert!(
+            matches!(&pagerduty.target, TargetType::PagerDuty(target) if target.endpoint.as_str() == PAGERDUTY_EVENTS_URL)
+        );
+        assert_eq!(pagerduty.mask()["routingKey"], "********");
+
+        let email: Target = serde_json::from_value(json!({
+            "name": "oncall",
+            "type": "email",
+            "host": "localhost",
+            "port": 2525,
+            "tls": "none",
+            "from": "Parseable <alerts@example.com>",
+            "to": ["oncall@example.com"],
+        }))
+        .unwrap();
+        assert!(email.target.supports_reports());
+
+        assert!(
+            serde_json::from_value::<Target>(json!({
+                "name": "oncall",
+                "type": "email",
+                "host": "localhost",
+                "from": "alerts@example.com",
+                "to": [],
+            }))
+            .is_err()
+        );
+        assert!(
+            serde_json::from_value::<Target>(json!({
+                "name": "oncall",
+                "type": "email",
+                "host": "localhost",
+                "from": "not an address",
+                "to": ["oncall@example.com"],
+            }))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn pagerduty_resolves_the_incident_of_the_alert() {
+        let pagerduty = PagerDuty {
+            routing_key: "abc".to_owned(),
+            endpoint: pagerduty_endpoint(),
+        };
+
+        let event = pagerduty.event(&context(AlertState::NotTriggered));
+        assert_eq!(event["event_action"], "resolve");
+        assert_eq!(
+            event["dedup_key"],
+            format!("parseable-alert-{}", Ulid::nil())
+        );
+        assert_eq!(pagerduty_severity("Critical"), "critical");
+        assert_eq!(opsgenie_priority("Low"), "P4");
+    }
+
+    #[tokio::test]
+    async fn pagerduty_triggers_and_resolves_incidents() {
+        let dedup_key = format!("parseable-alert-{}", Ulid::nil());
+
+        let (url, server) = mock_http().await;
+        let pagerduty = PagerDuty {
+            routing_key: "abc".to_owned(),
+            endpoint: url.join("v2/enqueue").unwrap(),
+        };
+        pagerduty.call(&context(AlertState::Triggered)).await;
+        let request = server.await.unwrap();
+        assert_eq!(request.target, "POST /v2/enqueue");
+        assert_eq!(request.body["routing_key"], "abc");
+        assert_eq!(request.body["event_action"], "trigger");
+        assert_eq!(request.body["dedup_key"], dedup_key);
+        assert_eq!(request.body["payload"]["summary"], "latency above 500ms");
+        assert_eq!(request.body["payload"]["severity"], "critical");
+        assert_eq!(
+            request.body["links"][0]["href"],
+            format!("http://localhost:8000/alerts/{}", Ulid::nil())
+        );
+
+        let (url, server) = mock_http().await;
+        let pagerduty = PagerDuty {
+            endpoint: url.join("v2/enqueue").unwrap(),
+            ..pagerduty
+        };
+        pagerduty.call(&context(AlertState::NotTriggered)).await;
+        let request = server.await.unwrap();
+        assert_eq!(
+            request.body,
+            json!({"routing_key": "abc", "event_action": "resolve", "dedup_key": dedup_key})
+        );
+    }
+
+    #[tokio::test]
+    async fn opsgenie_creates_and_closes_alerts() {
+        let alias = format!("parseable-alert-{}", Ulid::nil());
+
+        let (url, server) = mock_http().await;
+        let opsgenie = Opsgenie {
+            api_key: "key".to_owned(),
+            endpoint: url,
+        };
+        opsgenie.call(&context(AlertState::Triggered)).await;
+        let request = server.await.unwrap();
+        assert_eq!(request.target, "POST /v2/alerts?identifierType=alias");
+        assert_eq!(request.headers["authorization"], "GenieKey key");
+        assert_eq!(request.body["message"], "[Critical] High latency");
+        assert_eq!(request.body["alias"], alias);
+        assert_eq!(request.body["priority"], "P1");
+        assert_eq!(request.body["description"], "latency above 500ms");
+
+        let (url, server) = mock_http().await;
+        let opsgenie = Opsgenie {
+            endpoint: url,
+            ..opsgenie
+        };
+        opsgenie.call(&context(AlertState::NotTriggered)).await;
+        let request = server.await.unwrap();
+        assert_eq!(
+            request.target,
+            format!("POST /v2/alerts/{alias}/close?identifierType=alias")
+        );
+        assert_eq!(request.body["note"], "High latency is now `not-triggered` ");
+    }
+
+    #[tokio::test]
+    async fn teams_posts_cards_of_the_alert_state() {
+        for (state, color, text) in [
+            (AlertState::Triggered, "Attention", "latency above 500ms"),
+            (
+                AlertState::NotTriggered,
+                "Good",
+                "High latency is now `not-triggered` ",
+            ),
+        ] {
+            let (url, server) = mock_http().await;
+            let teams = Teams {
+                endpoint: url.join("webhook").unwrap(),
+            };
+            teams.call(&context(state)).await;
+            let request = server.await.unwrap();
+            assert_eq!(request.target, "POST /webhook");
+
+            let card = &request.body["attachments"][0]["content"];
+            assert_eq!(card["body"][0]["text"], "High latency");
+            assert_eq!(card["body"][0]["color"], color);
+            assert_eq!(card["body"][1]["text"], text);
+            assert_eq!(
+                card["actions"][0]["url"],
+                format!("http://localhost:8000/alerts/{}", Ulid::nil())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn email_sends_the_alert_state() {
+        for (state, subject, text) in [
+            (
+                AlertState::Triggered,
+                "Subject: [Critical] High latency is triggered",
+                "latency above 500ms",
+            ),
+            (
+                AlertState::NotTriggered,
+                "Subject: [Critical] High latency is not-triggered",
+                "High latency is now `not-triggered`",
+            ),
+        ] {
+            let (port, server) = mock_smtp().await;
+            let email: Email = serde_json::from_value(json!({
+                "host": "127.0.0.1",
+                "port": port,
+                "tls": "none",
+                "from": "alerts@example.com",
+                "to": ["oncall@example.com"],
+            }))
+            .unwrap();
+            email.call(&context(state)).await;
+            let mail = server.await.unwrap();
+
+            assert!(mail.contains("To: oncall@example.com"), "{mail}");
+            assert!(mail.contains(subject), "{mail}");
+            assert!(mail.contains(text), "{mail}");
+            assert!(
+                mail.contains(&format!("http://localhost:8000/alerts/{}", Ulid::nil())),
+                "{mail}"
+            );
+        }
+    }
+}
@@ -0,0 +1,372 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Templates of alert notifications, in a small subset of the handlebars syntax:
+//!
+//! - `{{alert.name}}` writes a value, strings as they are and anything else as JSON
+//! - `{{json message}}` writes a value as JSON, e.g. to embed it in a webhook payload
+//! - `{{#each groups}}...{{/each}}` repeats for each item, available as `this` and
+//!   its position as `@index`
+//! - `{{#if resolved}}...{{else}}...{{/if}}` renders when the value is set, i.e. not
+//!   null, false, zero or empty
+//!
+//! Templates are parsed when the target is saved, so that a broken template is
+//! rejected then instead of failing at notification time.
+
+use std::borrow::Cow;
+
+use serde_json::Value;
+
+/// Template of the notifications sent by a target
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NotificationTemplate {
+    source: String,
+    nodes: Vec<Node>,
+}
+
+impl NotificationTemplate {
+    pub fn render(&self, data: &Value) -> String {
+        let mut out = String::with_capacity(self.source.len());
+        render_nodes(
+            &self.nodes,
+            &mut vec![Scope {
+                value: data,
+                index: None,
+            }],
+            &mut out,
+        );
+        out
+    }
+}
+
+impl TryFrom<String> for NotificationTemplate {
+    type Error = TemplateError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let nodes = parse(&source)?;
+        Ok(Self { source, nodes })
+    }
+}
+
+impl From<NotificationTemplate> for String {
+    fn from(template: NotificationTemplate) -> Self {
+        template.source
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Value(Vec<String>),
+    Json(Vec<String>),
+    Each(Vec<String>, Vec<Node>),
+    If(Vec<String>, Vec<Node>, Vec<Node>),
+}
+
+/// Block that is still open while parsing, along with the nodes preceding it
+enum Block {
+    Each(Vec<String>),
+    /// the nodes before `{{else}}`, once it is reached
+    If(Vec<String>, Option<Vec<Node>>),
+}
+
+impl Block {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Each(_) => "each",
+            Self::If(..) => "if",
+        }
+    }
+
+    fn close(self, nodes: Vec<Node>) -> Node {
+        match self {
+            Self::Each(path) => Node::Each(path, nodes),
+            Self::If(path, None) => Node::If(path, nodes, vec![]),
+            Self::If(path, Some(then)) => Node::If(path, then, nodes),
+        }
+    }
+}
+
+fn parse(source: &str) -> Result<Vec<Node>, TemplateError> {
+    let mut open: Vec<(Block, Vec<Node>)> = vec![];
+    let mut nodes = vec![];
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            nodes.push(Node::Text(rest[..start].to_owned()));
+        }
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or(TemplateError::UnclosedTag)?;
+        let tag = after[..end].trim();
+        rest = &after[end + 2..];
+
+        if let Some(block) = tag.strip_prefix('#') {
+            let (name, path) = block
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| TemplateError::MissingPath(block.to_owned()))?;
+            let path = parse_path(path)?;
+            let block = match name {
+                "each" => Block::Each(path),
+                "if" => Block::If(path, None),
+                _ => return Err(TemplateError::UnknownBlock(name.to_owned())),
+            };
+            open.push((block, std::mem::take(&mut nodes)));
+        } else if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            let Some((block, preceding)) = open.pop() else {
+                return Err(TemplateError::UnexpectedClose(name.to_owned()));
+            };
+            if block.name() != name {
+                return Err(TemplateError::MismatchedClose {
+                    expected: block.name(),
+                    found: name.to_owned(),
+                });
+            }
+            let children = std::mem::replace(&mut nodes, preceding);
+            nodes.push(block.close(children));
+        } else if tag == "else" {
+            match open.last_mut() {
+                Some((Block::If(_, then @ None), _)) => *then = Some(std::mem::take(&mut nodes)),
+                _ => return Err(TemplateError::UnexpectedElse),
+            }
+        } else if let Some(path) = tag.strip_prefix("json ") {
+            nodes.push(Node::Json(parse_path(path)?));
+        } else {
+            nodes.push(Node::Value(parse_path(tag)?));
+        }
+    }
+
+    if !rest.is_empty() {
+        nodes.push(Node::Text(rest.to_owned()));
+    }
+    if let Some((block, _)) = open.pop() {
+        return Err(TemplateError::UnclosedBlock(block.name()));
+    }
+
+    Ok(nodes)
+}
+
+fn parse_path(path: &str) -> Result<Vec<String>, TemplateError> {
+    let path = path.trim();
+    let segments: Vec<String> = path.split('.').map(str::to_owned).collect();
+    let valid = segments.iter().enumerate().all(|(i, segment)| {
+        let segment = if i == 0 {
+            segment.strip_prefix('@').unwrap_or(segment)
+        } else {
+            segment
+        };
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    });
+    if !valid {
+        return Err(TemplateError::InvalidPath(path.to_owned()));
+    }
+
+    Ok(segments)
+}
+
+/// Item being rendered, the root data or an item of an `{{#each}}` block
+struct Scope<'a> {
+    value: &'a Value,
+    index: Option<usize>,
+}
+
+fn render_nodes<'a>(nodes: &'a [Node], scopes: &mut Vec<Scope<'a>>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value(path) => match resolve(path, scopes).as_deref() {
+                None | Some(Value::Null) => {}
+                Some(Value::String(value)) => out.push_str(value),
+                Some(value) => out.push_str(&value.to_string()),
+            },
+            Node::Json(path) => {
+                let value = resolve(path, scopes);
+                out.push_str(&value.as_deref().unwrap_or(&Value::Null).to_string());
+            }
+            Node::Each(path, children) => {
+                let Some(Value::Array(items)) = lookup(path, scopes) else {
+                    continue;
+                };
+                for (index, value) in items.iter().enumerate() {
+                    scopes.push(Scope {
+                        value,
+                        index: Some(index),
+                    });
+                    render_nodes(children, scopes, out);
+                    scopes.pop();
+                }
+            }
+            Node::If(path, then, otherwise) => {
+                let branch = if resolve(path, scopes).as_deref().is_some_and(is_truthy) {
+                    then
+                } else {
+                    otherwise
+                };
+                render_nodes(branch, scopes, out);
+            }
+        }
+    }
+}
+
+/// Value of a path, including `@index` of the innermost `{{#each}}` block
+fn resolve<'a>(path: &[String], scopes: &[Scope<'a>]) -> Option<Cow<'a, Value>> {
+    if let [segment] = path
+        && segment == "@index"
+    {
+        return scopes
+            .last()
+            .and_then(|scope| scope.index)
+            .map(|index| Cow::Owned(Value::from(index)));
+    }
+
+    lookup(path, scopes).map(Cow::Borrowed)
+}
+
+/// Looks the path up in the innermost scope that has its first segment,
+/// or in the innermost scope only if the path starts with `this`
+fn lookup<'a>(path: &[String], scopes: &[Scope<'a>]) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    let mut value = if first == "this" {
+        scopes.last()?.value
+    } else {
+        scopes
+            .iter()
+            .rev()
+            .find_map(|scope| field(scope.value, first))?
+    };
+    for segment in rest {
+        value = field(value, segment)?;
+    }
+
+    Some(value)
+}
+
+fn field<'a>(value: &'a Value, segment: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(value) => !value.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("Tag is not closed with }}}}")]
+    UnclosedTag,
+    #[error("Invalid path {0:?} in template")]
+    InvalidPath(String),
+    #[error("Block {{{{#{0}}}}} needs a path")]
+    MissingPath(String),
+    #[error("Unknown block {{{{#{0}}}}}, expected each or if")]
+    UnknownBlock(String),
+    #[error("Unexpected {{{{/{0}}}}} without an open block")]
+    UnexpectedClose(String),
+    #[error("Expected {{{{/{expected}}}}} but found {{{{/{found}}}}}")]
+    MismatchedClose {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("Unexpected {{{{else}}}} outside of an if block")]
+    UnexpectedElse,
+    #[error("Block {{{{#{0}}}}} is not closed")]
+    UnclosedBlock(&'static str),
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn render(template: &str, data: Value) -> String {
+        NotificationTemplate::try_from(template.to_owned())
+            .unwrap()
+            .render(&data)
+    }
+
+    #[test]
+    fn renders_values_and_blocks() {
+        let data = json!({
+            "alert": {"name": "High latency", "severity": "critical"},
+            "resolved": false,
+            "link": "https://logs.example.com/alerts/1",
+            "groups": [
+                {"groupValues": {"host": "a"}, "value": 12.5},
+                {"groupValues": {"host": "b"}, "value": 3},
+            ],
+        });
+
+        assert_eq!(
+            render(
+                "[{{alert.severity}}] {{alert.name}}{{#each groups}}\n{{@index}}. {{groupValues.host}}={{value}} ({{alert.name}}){{/each}}\n{{link}}",
+                data.clone()
+            ),
+            "[critical] High latency\n0. a=12.5 (High latency)\n1. b=3 (High latency)\nhttps://logs.example.com/alerts/1"
+        );
+        assert_eq!(
+            render("{{#if resolved}}ok{{else}}firing{{/if}}{{missing}}", data),
+            "firing"
+        );
+    }
+
+    #[test]
+    fn renders_json() {
+        let data = json!({"message": "say \"hi\"", "groups": [{"value": 1}]});
+        assert_eq!(
+            render(
+                r#"{"text": {{json message}}, "groups": {{json groups}}, "none": {{json nothing}}}"#,
+                data
+            ),
+            r#"{"text": "say \"hi\"", "groups": [{"value":1}], "none": null}"#
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for template in [
+            "{{alert.name",
+            "{{#each groups}}",
+            "{{/if}}",
+            "{{#each groups}}{{/if}}",
+            "{{else}}",
+            "{{#unless resolved}}{{/unless}}",
+            "{{alert..name}}",
+        ] {
+            assert!(
+                NotificationTemplate::try_from(template.to_owned()).is_err(),
+                "{template}"
+            );
+        }
+    }
+}
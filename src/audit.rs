@@ -0,0 +1,493 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Audit log, the authorized requests made to the server are recorded into the internal
+//! `paudit` stream, which can be queried like any other stream.
+//!
+//! Events are buffered in memory and flushed into the stream every few seconds, so that
+//! recording an event never holds up the request it is about.
+
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use actix_web::{dev::ServiceRequest, http::StatusCode};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tracing::{info, warn};
+use ulid::Ulid;
+
+use crate::{
+    event::format::{LogSource, LogSourceEntry},
+    handlers::{
+        STREAM_NAME_HEADER_KEY, TelemetryType,
+        http::ingest::{PostError, ingest_internal_stream},
+    },
+    parseable::PARSEABLE,
+    rbac::role::Action,
+    storage::{StreamType, retention::Retention},
+};
+
+pub const AUDIT_STREAM_NAME: &str = "paudit";
+
+/// Header carrying the id of a request, set by the client or generated by the server
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Events beyond this many waiting to be flushed are dropped, so that a failing
+/// stream doesn't grow the buffer without bounds
+const MAX_BUFFERED_EVENTS: usize = 100_000;
+
+static AUDIT_EVENTS: Lazy<Mutex<Vec<AuditEvent>>> = Lazy::new(Mutex::default);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// the user was not authorized for the action
+    Denied,
+}
+
+impl From<StatusCode> for Outcome {
+    fn from(status: StatusCode) -> Self {
+        if status.is_success() || status.is_redirection() {
+            Self::Success
+        } else {
+            Self::Failure
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    pub request_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+    pub method: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub outcome: Outcome,
+    pub duration_ms: u64,
+    /// SQL of the query, for queries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// Bytes read to answer the query, unknown for streamed results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_scanned: Option<u64>,
+}
+
+/// Query run by a request, put in the extensions of the request by the handler
+#[derive(Debug, Clone)]
+pub struct AuditQuery {
+    pub sql: String,
+    pub bytes_scanned: Arc<AtomicU64>,
+    /// results are streamed after the response is sent, along with the bytes scanned
+    pub streaming: bool,
+}
+
+/// Request being audited, from the time it was authorized
+#[derive(Debug)]
+pub struct AuditRequest {
+    pub request_id: String,
+    action: Action,
+    resource: Option<String>,
+    method: String,
+    path: String,
+    started: Instant,
+}
+
+impl AuditRequest {
+    pub fn new(
+        request_id: Option<&str>,
+        action: Action,
+        resource: Option<&str>,
+        method: &str,
+        path: &str,
+    ) -> Self {
+        Self {
+            request_id: request_id
+                .filter(|id| !id.is_empty())
+                .map_or_else(|| Ulid::new().to_string(), str::to_owned),
+            action,
+            resource: resource.map(str::to_owned),
+            method: method.to_owned(),
+            path: path.to_owned(),
+            started: Instant::now(),
+        }
+    }
+
+    /// The resource of a request is its stream if any, else the last parameter of its path
+    pub fn from_service_request(req: &ServiceRequest, action: Action) -> Self {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let resource = req
+            .match_info()
+            .get("logstream")
+            .or_else(|| header(STREAM_NAME_HEADER_KEY))
+            .or_else(|| req.match_info().iter().last().map(|(_, value)| value));
+
+        Self::new(
+            header(REQUEST_ID_HEADER),
+            action,
+            resource,
+            req.method().as_str(),
+            req.path(),
+        )
+    }
+
+    /// Records the request once done, along with the query it ran if any
+    pub fn record(
+        self,
+        user_id: Option<String>,
+        status: Option<StatusCode>,
+        outcome: Outcome,
+        query: Option<&AuditQuery>,
+    ) {
+        let duration_ms = self.started.elapsed().as_millis() as u64;
+        record(AuditEvent {
+            timestamp: Utc::now(),
+            request_id: self.request_id,
+            user_id,
+            action: format!("{:?}", self.action),
+            resource: self.resource,
+            method: self.method,
+            path: self.path,
+            status: status.map(|status| status.as_u16()),
+            outcome,
+            duration_ms,
+            query: query.map(|query| query.sql.clone()),
+            bytes_scanned: query
+                .filter(|query| !query.streaming)
+                .map(|query| query.bytes_scanned.load(Ordering::Relaxed)),
+        });
+    }
+}
+
+/// Whether requests for the action are recorded, ingestion is left out as it would
+/// record every batch of events sent to the server
+pub fn is_audited(action: Action) -> bool {
+    PARSEABLE.options.audit_log && action != Action::Ingest
+}
+
+/// Buffers the event for the next flush, callers check [`is_audited`] first
+pub fn record(event: AuditEvent) {
+    let mut events = AUDIT_EVENTS.lock().expect("not poisoned");
+    if events.len() >= MAX_BUFFERED_EVENTS {
+        warn!(
+            "Audit log buffer is full, dropping event {}",
+            event.request_id
+        );
+        return;
+    }
+    events.push(event);
+}
+
+pub fn init_scheduler() {
+    if !PARSEABLE.options.audit_log {
+        return;
+    }
+    info!("Setting up audit log flush");
+    tokio::spawn(async {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            if let Err(err) = flush().await {
+                warn!("Failed to flush audit log: {err}");
+            }
+        }
+    });
+}
+
+async fn flush() -> Result<(), PostError> {
+    let events = std::mem::take(&mut *AUDIT_EVENTS.lock().expect("not poisoned"));
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    if let Err(err) = write(&events).await {
+        requeue(&mut AUDIT_EVENTS.lock().expect("not poisoned"), events);
+        return Err(err);
+    }
+    Ok(())
+}
+
+async fn write(events: &[AuditEvent]) -> Result<(), PostError> {
+    create_stream_if_not_exists().await?;
+    let body = serde_json::to_vec(events)?;
+    ingest_internal_stream(AUDIT_STREAM_NAME.to_owned(), Bytes::from(body)).await
+}
+
+/// Puts the events of a failed flush back ahead of the ones recorded since, dropping
+/// the oldest of them if the buffer can't hold them all
+fn requeue(buffer: &mut Vec<AuditEvent>, mut events: Vec<AuditEvent>) {
+    let room = MAX_BUFFERED_EVENTS.saturating_sub(buffer.len());
+    if events.len() > room {
+        let dropped = events.len() - room;
+        warn!("Audit log buffer is full, dropping {dropped} unflushed events");
+        events.drain(..dropped);
+    }
+    buffer.splice(0..0, events);
+}
+
+/// Creates the audit stream, with a retention of its own
+async fn create_stream_if_not_exists() -> Result<(), PostError> {
+    let log_source_entry = LogSourceEntry::new(LogSource::Json, HashSet::new());
+    let exists = PARSEABLE
+        .create_stream_if_not_exists(
+            AUDIT_STREAM_NAME,
+            StreamType::Internal,
+            None,
+            vec![log_source_entry],
+            TelemetryType::Logs,
+        )
+        .await?;
+    if exists {
+        return Ok(());
+    }
+
+    let days = PARSEABLE.options.audit_log_retention_days;
+    let retention = Retention::delete_after(
+        &format!("delete audit events after {days} days"),
+        Duration::from_secs(days * 24 * 60 * 60),
+    );
+    PARSEABLE
+        .storage
+        .get_object_store()
+        .put_retention(AUDIT_STREAM_NAME, &retention)
+        .await?;
+    PARSEABLE
+        .get_stream(AUDIT_STREAM_NAME)?
+        .set_retention(retention);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, Error, HttpMessage, HttpRequest, HttpResponse, test, web};
+
+    use crate::{
+        handlers::http::middleware::Auth,
+        rbac::{
+            self,
+            map::{SESSIONS, SessionKey, mut_sessions},
+            role::{ParseableResourceType, RoleBuilder, model::DefaultPrivilege},
+        },
+        utils::user_auth_for_datasets,
+    };
+
+    use super::*;
+
+    fn event(request_id: &str) -> AuditEvent {
+        AuditEvent {
+            timestamp: Utc::now(),
+            request_id: request_id.to_owned(),
+            user_id: None,
+            action: format!("{:?}", Action::Query),
+            resource: None,
+            method: "POST".to_owned(),
+            path: "/api/v1/query".to_owned(),
+            status: None,
+            outcome: Outcome::Success,
+            duration_ms: 0,
+            query: None,
+            bytes_scanned: None,
+        }
+    }
+
+    fn recorded(request_id: &str) -> Vec<AuditEvent> {
+        AUDIT_EVENTS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event.request_id == request_id)
+            .cloned()
+            .collect()
+    }
+
+    fn deny(_: &mut ServiceRequest, _: Action) -> Result<rbac::Response, Error> {
+        Ok(rbac::Response::UnAuthorized)
+    }
+
+    fn allow(_: &mut ServiceRequest, _: Action) -> Result<rbac::Response, Error> {
+        Ok(rbac::Response::Authorized)
+    }
+
+    async fn query(req: HttpRequest) -> HttpResponse {
+        req.extensions_mut().insert(AuditQuery {
+            sql: "select * from app".to_owned(),
+            bytes_scanned: Arc::new(AtomicU64::new(42)),
+            streaming: false,
+        });
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn records_denied_and_query_requests() {
+        SESSIONS.get_or_init(Default::default);
+        mut_sessions().track_new(
+            "alice".to_owned(),
+            SessionKey::BasicAuth {
+                username: "alice".to_owned(),
+                password: "secret".to_owned(),
+            },
+            DateTime::<Utc>::MAX_UTC,
+            vec![],
+        );
+        let app = test::init_service(
+            App::new()
+                .route(
+                    "/logstream/{logstream}",
+                    web::delete().to(HttpResponse::Ok).wrap(Auth {
+                        action: Action::DeleteStream,
+                        method: deny,
+                        audited: |_| true,
+                    }),
+                )
+                .route(
+                    "/query",
+                    web::post().to(query).wrap(Auth {
+                        action: Action::Query,
+                        method: allow,
+                        audited: |_| true,
+                    }),
+                ),
+        )
+        .await;
+        // basic auth of alice:secret
+        let auth = ("Authorization", "Basic YWxpY2U6c2VjcmV0");
+
+        let req = test::TestRequest::delete()
+            .uri("/logstream/app")
+            .insert_header(auth)
+            .insert_header((REQUEST_ID_HEADER, "audit-denied"))
+            .to_request();
+        assert!(test::try_call_service(&app, req).await.is_err());
+        let events = recorded("audit-denied");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].user_id.as_deref(), Some("alice"));
+        assert_eq!(events[0].action, "DeleteStream");
+        assert_eq!(events[0].resource.as_deref(), Some("app"));
+        assert_eq!(events[0].status, Some(403));
+        assert_eq!(events[0].outcome, Outcome::Denied);
+        assert_eq!(events[0].query, None);
+
+        let req = test::TestRequest::post()
+            .uri("/query")
+            .insert_header(auth)
+            .insert_header((REQUEST_ID_HEADER, "audit-query"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "audit-query");
+        let events = recorded("audit-query");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].user_id.as_deref(), Some("alice"));
+        assert_eq!(events[0].status, Some(200));
+        assert_eq!(events[0].outcome, Outcome::Success);
+        assert_eq!(events[0].query.as_deref(), Some("select * from app"));
+        assert_eq!(events[0].bytes_scanned, Some(42));
+    }
+
+    #[actix_web::test]
+    async fn only_admins_query_audit_events() {
+        let tables = [AUDIT_STREAM_NAME.to_owned()];
+        let reader = RoleBuilder::from(&DefaultPrivilege::Reader {
+            resource: ParseableResourceType::Stream("*".to_owned()),
+            filter: None,
+            masks: Default::default(),
+        })
+        .build();
+        let editor = RoleBuilder::from(&DefaultPrivilege::Editor).build();
+        let admin = RoleBuilder::from(&DefaultPrivilege::Admin).build();
+
+        assert!(user_auth_for_datasets(&reader, &tables).await.is_err());
+        assert!(user_auth_for_datasets(&editor, &tables).await.is_err());
+        assert!(user_auth_for_datasets(&admin, &tables).await.is_ok());
+    }
+
+    #[test]
+    fn requeues_unflushed_events_first() {
+        let mut buffer = vec![event("c")];
+        requeue(&mut buffer, vec![event("a"), event("b")]);
+        let ids: Vec<_> = buffer
+            .iter()
+            .map(|event| event.request_id.as_str())
+            .collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+
+        let mut buffer = vec![event("c"); MAX_BUFFERED_EVENTS - 1];
+        requeue(&mut buffer, vec![event("a"), event("b")]);
+        assert_eq!(buffer.len(), MAX_BUFFERED_EVENTS);
+        assert_eq!(buffer[0].request_id, "b");
+        assert_eq!(buffer[1].request_id, "c");
+    }
+
+    #[test]
+    fn serializes_events() {
+        let event = AuditEvent {
+            timestamp: DateTime::from_timestamp(0, 0).unwrap(),
+            request_id: "01J".to_owned(),
+            user_id: Some("admin".to_owned()),
+            action: format!("{:?}", Action::PutRetention),
+            resource: Some("app".to_owned()),
+            method: "PUT".to_owned(),
+            path: "/api/v1/logstream/app/retention".to_owned(),
+            status: Some(StatusCode::OK.as_u16()),
+            outcome: Outcome::from(StatusCode::OK),
+            duration_ms: 12,
+            query: None,
+            bytes_scanned: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "timestamp": "1970-01-01T00:00:00Z",
+                "request_id": "01J",
+                "user_id": "admin",
+                "action": "PutRetention",
+                "resource": "app",
+                "method": "PUT",
+                "path": "/api/v1/logstream/app/retention",
+                "status": 200,
+                "outcome": "success",
+                "duration_ms": 12
+            })
+        );
+        assert_eq!(
+            Outcome::from(StatusCode::INTERNAL_SERVER_ERROR),
+            Outcome::Failure
+        );
+    }
+}
@@ -18,10 +18,11 @@
 
 use std::collections::HashMap;
 
+use bytes::Bytes;
 use itertools::Itertools;
 use parquet::file::{
     metadata::{RowGroupMetaData, SortingColumn},
-    reader::FileReader,
+    reader::{ChunkReader, FileReader},
 };
 
 use crate::metastore::metastore_traits::MetastoreObject;
@@ -115,7 +116,25 @@ pub fn create_from_parquet_file(
     let file = std::fs::File::open(fs_file_path)?;
     manifest_file.file_size = file.metadata()?.len();
 
-    let file = parquet::file::serialized_reader::SerializedFileReader::new(file)?;
+    populate_from_parquet(manifest_file, file)
+}
+
+/// Same as [`create_from_parquet_file`], for a parquet file which is already in memory
+pub fn create_from_parquet_bytes(object_store_path: String, bytes: Bytes) -> anyhow::Result<File> {
+    let manifest_file = File {
+        file_path: object_store_path,
+        file_size: bytes.len() as u64,
+        ..File::default()
+    };
+
+    populate_from_parquet(manifest_file, bytes)
+}
+
+fn populate_from_parquet<R: ChunkReader + 'static>(
+    mut manifest_file: File,
+    reader: R,
+) -> anyhow::Result<File> {
+    let file = parquet::file::serialized_reader::SerializedFileReader::new(reader)?;
     let file_meta = file.metadata().file_metadata();
     let row_groups = file.metadata().row_groups();
 
@@ -20,12 +20,13 @@ use std::{collections::HashMap, sync::Arc};
 
 use chrono::{DateTime, Local, NaiveTime, Utc};
 use column::Column;
+use itertools::Itertools;
 use manifest::Manifest;
 use rayon::prelude::*;
 use relative_path::RelativePathBuf;
 use snapshot::ManifestItem;
 use std::io::Error as IOError;
-use tracing::error;
+use tracing::{error, warn};
 
 use crate::{
     event::DEFAULT_TIMESTAMP_KEY,
@@ -33,13 +34,18 @@ use crate::{
         self,
         http::{base_path_without_preceding_slash, cluster::for_each_live_ingestor},
     },
+    metastore::MetastoreError,
     metrics::{EVENTS_INGESTED_DATE, EVENTS_INGESTED_SIZE_DATE, EVENTS_STORAGE_SIZE_DATE},
     option::Mode,
     parseable::PARSEABLE,
     query::PartialTimeFilter,
-    stats::{event_labels_date, get_current_stats, storage_size_labels_date, update_deleted_stats},
+    stats::{
+        event_labels_date, get_current_stats, storage_size_labels_date, update_deleted_stats,
+        update_deleted_totals,
+    },
     storage::{
-        ObjectStorage, ObjectStorageError, ObjectStoreFormat, object_storage::manifest_path,
+        MANIFEST_FILE, ObjectStorage, ObjectStorageError, ObjectStoreFormat,
+        object_storage::manifest_path,
     },
 };
 pub use manifest::create_from_parquet_file;
@@ -301,32 +307,15 @@ async fn handle_existing_partition(
     let should_update = manifests[pos].manifest_path.contains(&manifest_file_name);
 
     if should_update {
-        if let Some(mut manifest) = PARSEABLE
-            .metastore
-            .get_manifest(
-                stream_name,
-                manifests[pos].time_lower_bound,
-                manifests[pos].time_upper_bound,
-                Some(manifests[pos].manifest_path.clone()),
-            )
-            .await
-            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?
-        {
-            // Update existing manifest
-            for change in partition_changes {
+        // Update existing manifest, compaction and retention may update it as well
+        let updated = update_manifest(&manifests[pos].manifest_path, |manifest| {
+            for change in partition_changes.iter().cloned() {
                 manifest.apply_change(change);
             }
-            PARSEABLE
-                .metastore
-                .put_manifest(
-                    &manifest,
-                    stream_name,
-                    manifests[pos].time_lower_bound,
-                    manifests[pos].time_upper_bound,
-                )
-                .await
-                .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?;
-
+            true
+        })
+        .await?;
+        if updated.is_some() {
             manifests[pos].events_ingested = events_ingested;
             manifests[pos].ingestion_size = ingestion_size;
             manifests[pos].storage_size = storage_size;
@@ -361,6 +350,49 @@ async fn handle_existing_partition(
     }
 }
 
+/// Attempts at writing a manifest which other nodes keep updating
+const MANIFEST_UPDATE_ATTEMPTS: usize = 5;
+
+/// Applies `update` to the manifest at `manifest_url` and writes it back only if no one
+/// else wrote it in the meantime, otherwise the manifest is read again and `update` applied
+/// to it anew. Ingestion, compaction and retention all update manifests this way so that
+/// none of them loses the files added or replaced by another.
+///
+/// `update` returns false to leave the manifest as it is.
+/// Returns the written manifest, or `None` if it doesn't exist or was left as is.
+pub async fn update_manifest(
+    manifest_url: &str,
+    mut update: impl FnMut(&mut Manifest) -> bool + Send,
+) -> Result<Option<Manifest>, ObjectStorageError> {
+    for _ in 0..MANIFEST_UPDATE_ATTEMPTS {
+        let Some((mut manifest, version)) = PARSEABLE
+            .metastore
+            .get_manifest_versioned(manifest_url)
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?
+        else {
+            return Ok(None);
+        };
+        if !update(&mut manifest) {
+            return Ok(None);
+        }
+
+        match PARSEABLE
+            .metastore
+            .put_manifest_if_unchanged(&manifest, manifest_url, version)
+            .await
+        {
+            Ok(()) => return Ok(Some(manifest)),
+            Err(MetastoreError::ObjectStorageError(ObjectStorageError::Conflict(_))) => {
+                warn!("Manifest {manifest_url} was updated concurrently, retrying");
+            }
+            Err(e) => return Err(ObjectStorageError::MetastoreError(Box::new(e.to_detail()))),
+        }
+    }
+
+    Err(ObjectStorageError::Conflict(manifest_url.to_owned()))
+}
+
 /// Finalizes the snapshot update by adding new entries and updating metadata
 async fn finalize_snapshot_update(
     mut meta: ObjectStoreFormat,
@@ -523,6 +555,167 @@ pub async fn remove_manifest_from_snapshot(
     Ok(())
 }
 
+/// Removes the files under the given partitions of a date, such as `date=2024-01-01/hour=05`
+/// or `date=2024-01-01/hour=06/minute=10`, from the manifests and updates the snapshot.
+/// Unlike `remove_manifest_from_snapshot` this allows expiring part of a day.
+pub async fn remove_partitions_from_snapshot(
+    storage: Arc<dyn ObjectStorage>,
+    stream_name: &str,
+    partitions: Vec<String>,
+) -> Result<(), ObjectStorageError> {
+    if partitions.is_empty() {
+        return Ok(());
+    }
+
+    let mut meta: ObjectStoreFormat = serde_json::from_slice(
+        &PARSEABLE
+            .metastore
+            .get_stream_json(stream_name, false)
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?,
+    )?;
+
+    let mut num_row = 0;
+    let mut ingestion_size = 0;
+    let mut storage_size = 0;
+    let mut emptied_manifests = Vec::new();
+    for item in meta.snapshot.manifest_list.iter_mut() {
+        let prefixes: Vec<String> = partitions
+            .iter()
+            .filter(|partition| {
+                partition
+                    .split('/')
+                    .next()
+                    .is_some_and(|date| item.manifest_path.contains(date))
+            })
+            .map(|partition| format!("{stream_name}/{partition}/"))
+            .collect();
+        if prefixes.is_empty() {
+            continue;
+        }
+
+        // an emptied manifest is left as is, it is dropped from the snapshot
+        let mut removed = Vec::new();
+        let updated = update_manifest(&item.manifest_path, |manifest| {
+            let (matched, retained): (Vec<_>, Vec<_>) = std::mem::take(&mut manifest.files)
+                .into_iter()
+                .partition(|file| {
+                    prefixes
+                        .iter()
+                        .any(|prefix| file.file_path.contains(prefix))
+                });
+            manifest.files = retained;
+            removed = matched;
+            !removed.is_empty() && !manifest.files.is_empty()
+        })
+        .await?;
+        if removed.is_empty() {
+            continue;
+        }
+
+        let removed_rows: u64 = removed.iter().map(|file| file.num_rows).sum();
+        let removed_ingestion_size: u64 = removed.iter().map(|file| file.ingestion_size).sum();
+        let removed_storage_size: u64 = removed.iter().map(|file| file.file_size).sum();
+        num_row += removed_rows as i64;
+        ingestion_size += removed_ingestion_size as i64;
+        storage_size += removed_storage_size as i64;
+
+        if updated.is_none() {
+            emptied_manifests.push(item.manifest_path.clone());
+            continue;
+        }
+
+        item.events_ingested = item.events_ingested.saturating_sub(removed_rows);
+        item.ingestion_size = item.ingestion_size.saturating_sub(removed_ingestion_size);
+        item.storage_size = item.storage_size.saturating_sub(removed_storage_size);
+    }
+    meta.snapshot
+        .manifest_list
+        .retain(|item| !emptied_manifests.contains(&item.manifest_path));
+
+    update_deleted_totals(
+        storage.clone(),
+        stream_name,
+        num_row,
+        ingestion_size,
+        storage_size,
+    )
+    .await?;
+    PARSEABLE.get_stream(stream_name)?.reset_first_event_at();
+    meta.first_event_at = None;
+    storage.put_snapshot(stream_name, meta.snapshot).await?;
+
+    // ingestors prune their own snapshots, the cleanup endpoint accepts partitions as well as dates
+    if matches!(PARSEABLE.options.mode, Mode::Query | Mode::Prism) {
+        let stream_name_clone = stream_name.to_string();
+
+        for_each_live_ingestor(move |ingestor| {
+            let stream_name = stream_name_clone.clone();
+            let partitions = partitions.clone();
+            async move {
+                let url = format!(
+                    "{}{}/logstream/{}/retention/cleanup",
+                    ingestor.domain_name,
+                    base_path_without_preceding_slash(),
+                    stream_name
+                );
+                handlers::http::cluster::send_retention_cleanup_request(
+                    &url,
+                    ingestor,
+                    &partitions,
+                )
+                .await?;
+                Ok::<(), ObjectStorageError>(())
+            }
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Lists the manifests (one per node) stored under a date prefix of the stream
+pub async fn list_manifests(
+    store: &Arc<dyn ObjectStorage>,
+    stream_name: &str,
+    date: &str,
+) -> Result<Vec<(RelativePathBuf, Manifest)>, ObjectStorageError> {
+    let date_path = object_store::path::Path::from(format!("{stream_name}/{date}"));
+    let manifest_paths = store
+        .list_with_delimiter(Some(date_path))
+        .await?
+        .objects
+        .into_iter()
+        .filter(|meta| {
+            meta.location
+                .filename()
+                .is_some_and(|name| name.ends_with(MANIFEST_FILE))
+        })
+        .map(|meta| RelativePathBuf::from(meta.location.as_ref()))
+        .collect_vec();
+
+    let mut manifests = Vec::with_capacity(manifest_paths.len());
+    for path in manifest_paths {
+        if let Some((manifest, _)) = PARSEABLE
+            .metastore
+            .get_manifest_versioned(path.as_str())
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?
+        {
+            manifests.push((path, manifest));
+        }
+    }
+    Ok(manifests)
+}
+
+/// Manifests store the absolute location of the parquet files,
+/// this gets back the path relative to the root of the object store
+pub fn relative_data_path(stream_name: &str, file_path: &str) -> Option<RelativePathBuf> {
+    let file_path = file_path.replace('\\', "/");
+    let start = file_path.find(&format!("{stream_name}/date="))?;
+    Some(RelativePathBuf::from(&file_path[start..]))
+}
+
 /// Partition the path to which this manifest belongs.
 /// Useful when uploading the manifest file.
 pub fn partition_path(
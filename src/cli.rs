@@ -138,6 +138,13 @@ pub struct Options {
     #[arg(long, env = "P_PASSWORD", help = "Admin password to be set for this Parseable server", default_value = DEFAULT_PASSWORD)]
     pub password: String,
 
+    #[arg(
+        long,
+        env = "P_COLUMN_MASK_SECRET",
+        help = "Secret mixed into the values of hash masked columns, derived from the admin credentials if not set"
+    )]
+    pub column_mask_secret: Option<String>,
+
     // Server configuration
     #[arg(
         long,
@@ -197,6 +204,23 @@ pub struct Options {
     )]
     pub mask_pii: bool,
 
+    #[arg(
+        long,
+        env = "P_AUDIT_LOG",
+        default_value = "true",
+        help = "Enable/Disable recording of the requests made to the server into the paudit stream"
+    )]
+    pub audit_log: bool,
+
+    #[arg(
+        long,
+        env = "P_AUDIT_LOG_RETENTION_DAYS",
+        default_value = "90",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Number of days the audit log is retained for, when the paudit stream is created"
+    )]
+    pub audit_log_retention_days: u64,
+
     #[arg(
         long,
         env = "P_METRICS_ENDPOINT_AUTH",
@@ -295,6 +319,14 @@ pub struct Options {
     )]
     pub flight_port: u16,
 
+    #[arg(
+        long,
+        env = "P_OTLP_GRPC_PORT",
+        default_value = "4317",
+        help = "Port for the OpenTelemetry (OTLP) gRPC receiver"
+    )]
+    pub otlp_grpc_port: u16,
+
     // Performance settings
     #[arg(
         long,
@@ -339,6 +371,22 @@ pub struct Options {
     )]
     pub parquet_compression: Compression,
 
+    #[arg(
+        long,
+        env = "P_COMPACTION",
+        default_value = "false",
+        help = "Enable/Disable background compaction of small parquet files in object storage"
+    )]
+    pub compaction: bool,
+
+    #[arg(
+        long,
+        env = "P_COMPACTION_TARGET_FILE_SIZE",
+        default_value = "128",
+        help = "Target size of compacted parquet files in MiB"
+    )]
+    pub compaction_target_file_size: u64,
+
     // Resource monitoring
     #[arg(
         long,
@@ -84,7 +84,7 @@ pub enum BadData {
     #[default]
     Fail,
     Drop,
-    Dlt, //TODO: Implement Dead Letter Topic support when needed
+    Dlt,
 }
 
 impl FromStr for BadData {
@@ -63,6 +63,12 @@ pub struct KafkaConfig {
     #[command(flatten)]
     pub security: Option<SecurityConfig>,
 
+    #[command(flatten)]
+    pub routing: RoutingConfig,
+
+    #[command(flatten)]
+    pub metadata: MetadataConfig,
+
     #[arg(
         value_enum,
         long = "bad-data-policy",
@@ -72,6 +78,14 @@ pub struct KafkaConfig {
         help = "Policy for handling bad data"
     )]
     pub bad_data: BadData,
+
+    #[arg(
+        long = "dlt-topic",
+        env = "P_KAFKA_DLT_TOPIC",
+        required = false,
+        help = "Dead-letter topic bad records are produced to, used with the dlt bad data policy"
+    )]
+    pub dlt_topic: Option<String>,
 }
 
 #[derive(Debug, Clone, Args)]
@@ -441,6 +455,129 @@ pub struct ProducerConfig {
     pub retry_backoff_max_ms: u32,
 }
 
+#[derive(Debug, Clone, Args)]
+pub struct RoutingConfig {
+    #[clap(
+        value_enum,
+        long = "routing-strategy",
+        env = "P_KAFKA_ROUTING_STRATEGY",
+        required = false,
+        default_value_t = RoutingStrategy::Topic,
+        help = "Where the stream name of a record is taken from: topic, header, key or field"
+    )]
+    pub strategy: RoutingStrategy,
+
+    #[arg(
+        long = "routing-header",
+        env = "P_KAFKA_ROUTING_HEADER",
+        required = false,
+        default_value_t = String::from("x-p-stream"),
+        help = "Record header holding the stream name, used with the header routing strategy"
+    )]
+    pub header: String,
+
+    #[arg(
+        long = "routing-field",
+        env = "P_KAFKA_ROUTING_FIELD",
+        required = false,
+        help = "Top level JSON field holding the stream name, used with the field routing strategy"
+    )]
+    pub field: Option<String>,
+
+    #[arg(
+        long = "routing-log-source-header",
+        env = "P_KAFKA_ROUTING_LOG_SOURCE_HEADER",
+        required = false,
+        default_value_t = String::from("x-p-log-source"),
+        help = "Record header holding the log source of the record, e.g. otel-logs"
+    )]
+    pub log_source_header: String,
+
+    #[arg(
+        long = "routing-topic-mapping",
+        env = "P_KAFKA_ROUTING_TOPIC_MAPPING",
+        required = false,
+        value_delimiter = ',',
+        help = "Comma-separated list of topic=stream[:log-source] mappings. Used when no stream name is found with the routing strategy"
+    )]
+    pub topic_mapping: Vec<TopicRoute>,
+
+    #[arg(
+        long = "routing-log-source",
+        env = "P_KAFKA_ROUTING_LOG_SOURCE",
+        required = false,
+        default_value_t = String::from("json"),
+        help = "Log source of records without a log source header or mapping"
+    )]
+    pub log_source: String,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct MetadataConfig {
+    #[arg(
+        long = "metadata-columns",
+        env = "P_KAFKA_METADATA_COLUMNS",
+        required = false,
+        default_value_t = false,
+        help = "Add the topic, partition, offset, key and timestamp of the records as p_kafka_* columns, except to streams with a static schema"
+    )]
+    pub enabled: bool,
+
+    #[arg(
+        long = "metadata-headers",
+        env = "P_KAFKA_METADATA_HEADERS",
+        required = false,
+        value_delimiter = ',',
+        help = "Comma-separated list of record headers added as p_kafka_header_* columns"
+    )]
+    pub headers: Vec<String>,
+}
+
+/// Maps a topic to a stream, optionally with the log source of the payloads on the topic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopicRoute {
+    pub topic: String,
+    pub stream: String,
+    pub log_source: Option<String>,
+}
+
+impl std::str::FromStr for TopicRoute {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((topic, route)) = s.split_once('=') else {
+            return Err(format!("Invalid topic mapping: {s}, expected topic=stream"));
+        };
+        let (stream, log_source) = match route.split_once(':') {
+            Some((stream, log_source)) => (stream, Some(log_source.trim().to_owned())),
+            None => (route, None),
+        };
+        let (topic, stream) = (topic.trim(), stream.trim());
+        if topic.is_empty() || stream.is_empty() {
+            return Err(format!("Invalid topic mapping: {s}, expected topic=stream"));
+        }
+
+        Ok(TopicRoute {
+            topic: topic.to_owned(),
+            stream: stream.to_owned(),
+            log_source,
+        })
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RoutingStrategy {
+    /// Stream named after the topic, or its mapping
+    #[default]
+    Topic,
+    /// Stream named by a record header
+    Header,
+    /// Stream named by the record key
+    Key,
+    /// Stream named by a field of the JSON payload
+    Field,
+}
+
 #[derive(Debug, Clone, Args)]
 pub struct SecurityConfig {
     #[clap(
@@ -624,6 +761,39 @@ impl KafkaConfig {
             security.validate()?;
         }
 
+        self.routing.validate()?;
+
+        if self.bad_data == BadData::Dlt {
+            let Some(dlt_topic) = self.dlt_topic.as_ref().filter(|topic| !topic.is_empty()) else {
+                anyhow::bail!("Dead-letter topic is required for the dlt bad data policy");
+            };
+            if self.producer.is_none() {
+                anyhow::bail!("Producer configuration is required for the dlt bad data policy");
+            }
+            if self
+                .consumer
+                .as_ref()
+                .is_some_and(|consumer| consumer.topics.contains(dlt_topic))
+            {
+                anyhow::bail!("Dead-letter topic must not be one of the consumer topics");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RoutingConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        match self.strategy {
+            RoutingStrategy::Header if self.header.is_empty() => {
+                anyhow::bail!("Routing header must not be empty for the header routing strategy");
+            }
+            RoutingStrategy::Field if self.field.as_ref().is_none_or(|f| f.is_empty()) => {
+                anyhow::bail!("Routing field is required for the field routing strategy");
+            }
+            _ => {}
+        }
         Ok(())
     }
 }
@@ -883,7 +1053,12 @@ impl Default for KafkaConfig {
             producer: Some(ProducerConfig::default()),
             // Security configuration with plaintext protocol
             security: Some(SecurityConfig::default()),
+            // Streams named after the topics
+            routing: RoutingConfig::default(),
+            // No record metadata columns
+            metadata: MetadataConfig::default(),
             bad_data: BadData::default(),
+            dlt_topic: None,
         }
     }
 }
@@ -955,6 +1130,19 @@ impl Default for ConsumerConfig {
     }
 }
 
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        Self {
+            strategy: RoutingStrategy::Topic,
+            header: "x-p-stream".to_string(),
+            field: None,
+            log_source_header: "x-p-log-source".to_string(),
+            topic_mapping: vec![],
+            log_source: "json".to_string(),
+        }
+    }
+}
+
 impl Default for SecurityConfig {
     fn default() -> Self {
         Self {
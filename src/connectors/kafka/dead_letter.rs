@@ -0,0 +1,147 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::FutureRecord;
+use tracing::{error, warn};
+
+use super::{
+    ConsumerRecord, FutureProducer, KafkaContext, config::KafkaConfig, metrics::BAD_RECORD_METRICS,
+};
+
+pub const DLT_ERROR_HEADER: &str = "x-p-dlt-error";
+pub const DLT_REASON_HEADER: &str = "x-p-dlt-reason";
+pub const DLT_TOPIC_HEADER: &str = "x-p-dlt-topic";
+pub const DLT_PARTITION_HEADER: &str = "x-p-dlt-partition";
+pub const DLT_OFFSET_HEADER: &str = "x-p-dlt-offset";
+
+const DLT_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Why a record could not be ingested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BadRecordReason {
+    /// Payload is not valid JSON
+    InvalidJson,
+    /// Payload was rejected by the stream, e.g. it doesn't match the static schema
+    Rejected,
+    /// Record is routed to a stream which can't be ingested into, e.g. an internal stream
+    InvalidStream,
+}
+
+impl BadRecordReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BadRecordReason::InvalidJson => "invalid-json",
+            BadRecordReason::Rejected => "rejected",
+            BadRecordReason::InvalidStream => "invalid-stream",
+        }
+    }
+}
+
+/// Produces records which could not be ingested to the dead-letter topic,
+/// as is, with headers describing the error and where the record came from
+pub struct DeadLetterProducer {
+    producer: FutureProducer,
+    topic: String,
+}
+
+impl DeadLetterProducer {
+    pub fn new(config: &KafkaConfig) -> anyhow::Result<Self> {
+        let Some(topic) = config.dlt_topic.clone() else {
+            anyhow::bail!("Dead-letter topic is not configured");
+        };
+        // the producer never takes part in a rebalance, the receiver can be dropped
+        let (context, _) = KafkaContext::new(Arc::new(config.clone()));
+        let producer = config
+            .to_rdkafka_producer_config()
+            .create_with_context(context)?;
+
+        Ok(Self { producer, topic })
+    }
+
+    pub async fn send(
+        &self,
+        record: &ConsumerRecord,
+        reason: BadRecordReason,
+        error: &str,
+    ) -> anyhow::Result<()> {
+        let partition = record.partition.to_string();
+        let offset = record.offset.to_string();
+
+        let mut headers = OwnedHeaders::new();
+        for (key, value) in &record.headers {
+            headers = headers.insert(Header {
+                key,
+                value: value.as_deref(),
+            });
+        }
+        let headers = [
+            (DLT_ERROR_HEADER, error),
+            (DLT_REASON_HEADER, reason.as_str()),
+            (DLT_TOPIC_HEADER, record.topic.as_str()),
+            (DLT_PARTITION_HEADER, partition.as_str()),
+            (DLT_OFFSET_HEADER, offset.as_str()),
+        ]
+        .into_iter()
+        .fold(headers, |headers, (key, value)| {
+            headers.insert(Header {
+                key,
+                value: Some(value),
+            })
+        });
+
+        let mut dlt_record = FutureRecord::<Vec<u8>, Vec<u8>>::to(&self.topic).headers(headers);
+        if let Some(payload) = record.payload.as_ref() {
+            dlt_record = dlt_record.payload(payload);
+        }
+        if let Some(key) = record.key.as_ref() {
+            dlt_record = dlt_record.key(key);
+        }
+        if let Some(timestamp) = record.timestamp {
+            dlt_record = dlt_record.timestamp(timestamp);
+        }
+
+        match self.producer.send(dlt_record, DLT_QUEUE_TIMEOUT).await {
+            Ok(_) => {
+                warn!(
+                    "Produced record {}/{}@{} to dead-letter topic {}: {}",
+                    record.topic, record.partition, record.offset, self.topic, error
+                );
+                BAD_RECORD_METRICS
+                    .dlt_produced
+                    .with_label_values(&[record.topic.as_str(), reason.as_str()])
+                    .inc();
+                Ok(())
+            }
+            Err((e, _)) => {
+                error!(
+                    "Failed to produce record {}/{}@{} to dead-letter topic {}: {}",
+                    record.topic, record.partition, record.offset, self.topic, e
+                );
+                BAD_RECORD_METRICS
+                    .dlt_errors
+                    .with_label_values(&[record.topic.as_str()])
+                    .inc();
+                Err(e.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use chrono::DateTime;
+use serde_json::Value;
+
+use super::{ConsumerRecord, config::MetadataConfig};
+
+pub const KAFKA_TOPIC_KEY: &str = "p_kafka_topic";
+pub const KAFKA_PARTITION_KEY: &str = "p_kafka_partition";
+pub const KAFKA_OFFSET_KEY: &str = "p_kafka_offset";
+pub const KAFKA_KEY_KEY: &str = "p_kafka_key";
+pub const KAFKA_TIMESTAMP_KEY: &str = "p_kafka_timestamp";
+pub const KAFKA_HEADER_KEY_PREFIX: &str = "p_kafka_header_";
+
+impl MetadataConfig {
+    /// Config used for the events of a stream, static schemas have no columns to hold
+    /// the metadata, so none is added to their events
+    pub fn for_stream(&self, static_schema: bool) -> Self {
+        if static_schema {
+            Self::default()
+        } else {
+            self.clone()
+        }
+    }
+
+    /// Metadata columns of a record, all values are strings so that the columns
+    /// have the same type whichever way the payload is ingested
+    pub fn fields(&self, record: &ConsumerRecord) -> Vec<(String, String)> {
+        let mut fields = Vec::new();
+        if self.enabled {
+            fields.push((KAFKA_TOPIC_KEY.to_owned(), record.topic.clone()));
+            fields.push((KAFKA_PARTITION_KEY.to_owned(), record.partition.to_string()));
+            fields.push((KAFKA_OFFSET_KEY.to_owned(), record.offset.to_string()));
+            if let Some(key) = record.key.as_ref() {
+                fields.push((
+                    KAFKA_KEY_KEY.to_owned(),
+                    String::from_utf8_lossy(key).into_owned(),
+                ));
+            }
+            if let Some(timestamp) = record.timestamp.and_then(DateTime::from_timestamp_millis) {
+                fields.push((KAFKA_TIMESTAMP_KEY.to_owned(), timestamp.to_rfc3339()));
+            }
+        }
+
+        for header in &self.headers {
+            if let Some(value) = record.header(header) {
+                fields.push((header_column(header), value.to_owned()));
+            }
+        }
+
+        fields
+    }
+
+    /// Adds the metadata columns of the record to the JSON payload,
+    /// to every object of the payload when it is an array
+    pub fn enrich(&self, record: &ConsumerRecord, payload: &mut Value) {
+        let fields = self.fields(record);
+        if fields.is_empty() {
+            return;
+        }
+
+        let objects = match payload {
+            Value::Object(object) => vec![object],
+            Value::Array(values) => values.iter_mut().filter_map(Value::as_object_mut).collect(),
+            _ => return,
+        };
+        for object in objects {
+            for (key, value) in &fields {
+                object.insert(key.clone(), Value::String(value.clone()));
+            }
+        }
+    }
+}
+
+/// Column name of a header, e.g. `trace-id` is stored as `p_kafka_header_trace_id`
+fn header_column(header: &str) -> String {
+    let name: String = header
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{KAFKA_HEADER_KEY_PREFIX}{name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use arrow_schema::{DataType, Field};
+    use chrono::Utc;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        event::format::{EventFormat, json},
+        metadata::SchemaVersion,
+    };
+
+    fn record() -> ConsumerRecord {
+        ConsumerRecord {
+            payload: None,
+            key: Some(b"user-1".to_vec()),
+            topic: "orders".to_string(),
+            partition: 3,
+            offset: 42,
+            timestamp: Some(0),
+            headers: vec![("Trace-Id".to_string(), Some("abc".to_string()))],
+        }
+    }
+
+    #[test]
+    fn enriches_objects_and_arrays() {
+        let config = MetadataConfig {
+            enabled: true,
+            headers: vec!["Trace-Id".to_string(), "missing".to_string()],
+        };
+
+        let mut payload = json!({"msg": "hello"});
+        config.enrich(&record(), &mut payload);
+        assert_eq!(
+            payload,
+            json!({
+                "msg": "hello",
+                "p_kafka_topic": "orders",
+                "p_kafka_partition": "3",
+                "p_kafka_offset": "42",
+                "p_kafka_key": "user-1",
+                "p_kafka_timestamp": "1970-01-01T00:00:00+00:00",
+                "p_kafka_header_trace_id": "abc",
+            })
+        );
+
+        let mut payload = json!([{"msg": "a"}, 1, {"msg": "b"}]);
+        config.enrich(&record(), &mut payload);
+        assert_eq!(payload[0]["p_kafka_offset"], "42");
+        assert_eq!(payload[1], 1);
+        assert_eq!(payload[2]["p_kafka_offset"], "42");
+    }
+
+    #[test]
+    fn skips_static_schemas() {
+        let config = MetadataConfig {
+            enabled: true,
+            headers: vec!["Trace-Id".to_string()],
+        };
+        let schema = HashMap::from([(
+            "msg".to_owned(),
+            Arc::new(Field::new("msg", DataType::Utf8, true)),
+        )]);
+        let ingest = |config: &MetadataConfig| {
+            let mut payload = json!({"msg": "hello"});
+            config.enrich(&record(), &mut payload);
+            json::Event::new(payload, Utc::now()).into_recordbatch(
+                &schema,
+                true,
+                None,
+                SchemaVersion::V1,
+                &HashMap::new(),
+            )
+        };
+
+        assert!(ingest(&config).is_err());
+        let (rb, _) = ingest(&config.for_stream(true)).unwrap();
+        assert!(rb.schema().column_with_name(KAFKA_OFFSET_KEY).is_none());
+        assert!(config.for_stream(false).enabled);
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut payload = json!({"msg": "hello"});
+        MetadataConfig::default().enrich(&record(), &mut payload);
+        assert_eq!(payload, json!({"msg": "hello"}));
+    }
+}
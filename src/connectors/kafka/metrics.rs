@@ -16,6 +16,7 @@
  *
  */
 
+use once_cell::sync::Lazy;
 use prometheus::core::{Collector, Desc};
 use prometheus::{
     Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
@@ -24,6 +25,17 @@ use prometheus::{
 use rdkafka::Statistics;
 use std::sync::{Arc, RwLock};
 
+/// Bad records handled by the connector, updated by the processor
+/// and collected along with the librdkafka statistics
+pub static BAD_RECORD_METRICS: Lazy<BadRecordMetrics> = Lazy::new(BadRecordMetrics::new);
+
+#[derive(Debug)]
+pub struct BadRecordMetrics {
+    pub dlt_produced: IntCounterVec,
+    pub dlt_errors: IntCounterVec,
+    pub dropped: IntCounterVec,
+}
+
 #[derive(Debug)]
 pub struct KafkaMetricsCollector {
     stats: Arc<RwLock<Statistics>>,
@@ -724,6 +736,47 @@ impl CoreMetrics {
     }
 }
 
+impl BadRecordMetrics {
+    fn new() -> Self {
+        let counter_vec = |name: &str, help: &str, labels: &[&str]| {
+            IntCounterVec::new(Opts::new(name, help), labels).unwrap()
+        };
+        Self {
+            dlt_produced: counter_vec(
+                "kafka_dlt_produced_total",
+                "Total records produced to the dead-letter topic",
+                &["topic", "reason"],
+            ),
+            dlt_errors: counter_vec(
+                "kafka_dlt_errors_total",
+                "Total records which could not be produced to the dead-letter topic",
+                &["topic"],
+            ),
+            dropped: counter_vec(
+                "kafka_bad_records_dropped_total",
+                "Total bad records dropped",
+                &["topic", "reason"],
+            ),
+        }
+    }
+
+    fn descs(&self) -> impl Iterator<Item = Desc> + '_ {
+        [&self.dlt_produced, &self.dlt_errors, &self.dropped]
+            .into_iter()
+            .flat_map(|counter| counter.desc().into_iter().cloned())
+    }
+
+    fn collect_metrics(&self) -> Vec<proto::MetricFamily> {
+        let mut mfs = Vec::new();
+
+        mfs.extend(self.dlt_produced.collect());
+        mfs.extend(self.dlt_errors.collect());
+        mfs.extend(self.dropped.collect());
+
+        mfs
+    }
+}
+
 impl ConsumerGroupMetrics {
     fn new() -> anyhow::Result<Self> {
         Ok(Self {
@@ -756,6 +809,7 @@ impl KafkaMetricsCollector {
         let partition_metrics = PartitionMetrics::new(partition_labels, &mut descs);
         let consumer_metrics = ConsumerGroupMetrics::new()?;
         let eos_metrics = EosMetrics::new()?;
+        descs.extend(BAD_RECORD_METRICS.descs());
 
         Ok(KafkaMetricsCollector {
             stats,
@@ -848,6 +902,9 @@ impl Collector for KafkaMetricsCollector {
             mfs.extend(self.eos_metrics.collect_metrics(eos));
         }
 
+        // Collect bad record metrics
+        mfs.extend(BAD_RECORD_METRICS.collect_metrics());
+
         mfs
     }
 }
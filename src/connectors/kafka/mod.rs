@@ -26,7 +26,6 @@ use rdkafka::producer::ProducerContext;
 use rdkafka::topic_partition_list::TopicPartitionListElem;
 use rdkafka::{ClientContext, Message, Offset, Statistics};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::error::Error;
 use std::sync::{Arc, RwLock};
 use tokio::sync::mpsc;
@@ -35,15 +34,17 @@ use tracing::{error, info, warn};
 
 pub mod config;
 pub mod consumer;
+pub mod dead_letter;
+pub mod metadata;
 pub mod metrics;
 mod partition_stream;
 pub mod processor;
 pub mod rebalance_listener;
+pub mod routing;
 pub mod sink;
 pub mod state;
 #[allow(dead_code)]
 type BaseConsumer = rdkafka::consumer::BaseConsumer<KafkaContext>;
-#[allow(dead_code)]
 type FutureProducer = rdkafka::producer::FutureProducer<KafkaContext>;
 type StreamConsumer = rdkafka::consumer::StreamConsumer<KafkaContext>;
 
@@ -145,7 +146,7 @@ pub struct ConsumerRecord {
     pub partition: i32,
     pub offset: i64,
     pub timestamp: Option<i64>,
-    //pub headers: Option<HashMap<String, Option<String>>>,
+    pub headers: Vec<(String, Option<String>)>,
 }
 
 impl ConsumerRecord {
@@ -157,10 +158,18 @@ impl ConsumerRecord {
             partition: msg.partition(),
             offset: msg.offset(),
             timestamp: msg.timestamp().to_millis(),
-            //headers: extract_headers(&msg),
+            headers: extract_headers(&msg),
         }
     }
 
+    /// Value of the first header with the given key
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
     pub fn key_str(&self) -> String {
         self.key.clone().map_or_else(
             || String::from("null"),
@@ -179,19 +188,20 @@ impl ConsumerRecord {
     }
 }
 
-#[allow(unused)]
-fn extract_headers(msg: &BorrowedMessage<'_>) -> Option<HashMap<String, Option<String>>> {
-    msg.headers().map(|headers| {
-        headers
-            .iter()
-            .map(|header| {
-                (
-                    header.key.to_string(),
-                    header.value.map(|v| String::from_utf8_lossy(v).to_string()),
-                )
-            })
-            .collect()
-    })
+fn extract_headers(msg: &BorrowedMessage<'_>) -> Vec<(String, Option<String>)> {
+    msg.headers()
+        .map(|headers| {
+            headers
+                .iter()
+                .map(|header| {
+                    (
+                        header.key.to_string(),
+                        header.value.map(|v| String::from_utf8_lossy(v).to_string()),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 impl ConsumerContext for KafkaContext {
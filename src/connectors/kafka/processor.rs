@@ -17,12 +17,12 @@
  */
 
 use crate::{
-    connectors::common::processor::Processor,
+    connectors::common::{BadData, processor::Processor},
     event::{
-        Event as ParseableEvent, USER_AGENT_KEY,
-        format::{EventFormat, LogSourceEntry, json},
+        Event as ParseableEvent, FORMAT_KEY, USER_AGENT_KEY,
+        format::{EventFormat, LogSource, json},
     },
-    handlers::TelemetryType,
+    handlers::http::modal::utils::ingest_utils::flatten_and_push_logs,
     parseable::PARSEABLE,
     storage::StreamType,
 };
@@ -34,55 +34,205 @@ use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio_stream::wrappers::ReceiverStream;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
-use super::{ConsumerRecord, StreamConsumer, TopicPartition, config::BufferConfig};
+use super::{
+    ConsumerRecord, StreamConsumer, TopicPartition,
+    config::{BufferConfig, KafkaConfig, MetadataConfig, RoutingConfig},
+    dead_letter::{BadRecordReason, DeadLetterProducer},
+    metrics::BAD_RECORD_METRICS,
+    routing::Route,
+};
 
-#[derive(Default, Debug, Clone)]
-pub struct ParseableSinkProcessor;
+#[derive(Default, Clone)]
+pub struct ParseableSinkProcessor {
+    routing: RoutingConfig,
+    metadata: MetadataConfig,
+    bad_data: BadData,
+    dead_letter: Option<Arc<DeadLetterProducer>>,
+}
 
 impl ParseableSinkProcessor {
-    async fn build_event_from_chunk(
+    pub fn new(config: &KafkaConfig) -> anyhow::Result<Self> {
+        let dead_letter = match config.bad_data {
+            BadData::Dlt => Some(Arc::new(DeadLetterProducer::new(config)?)),
+            _ => None,
+        };
+
+        Ok(Self {
+            routing: config.routing.clone(),
+            metadata: config.metadata.clone(),
+            bad_data: config.bad_data.clone(),
+            dead_letter,
+        })
+    }
+
+    async fn ingest(
         &self,
-        records: &[ConsumerRecord],
-    ) -> anyhow::Result<ParseableEvent> {
-        let stream_name = records
-            .first()
-            .map(|r| r.topic.as_str())
-            .unwrap_or_default();
-        let log_source_entry = LogSourceEntry::default();
+        route: &Route,
+        records: Vec<(&ConsumerRecord, Value)>,
+    ) -> anyhow::Result<()> {
+        let log_source_entry = route.log_source_entry();
         PARSEABLE
             .create_stream_if_not_exists(
-                stream_name,
+                &route.stream_name,
                 StreamType::UserDefined,
                 None,
-                vec![log_source_entry],
-                TelemetryType::default(),
+                vec![log_source_entry.clone()],
+                route.telemetry_type(),
             )
             .await?;
 
-        let stream = PARSEABLE.get_stream(stream_name)?;
-        let schema = stream.get_schema_raw();
-        let time_partition = stream.get_time_partition();
-        let custom_partition = stream.get_custom_partition();
-        let static_schema_flag = stream.get_static_schema_flag();
-        let schema_version = stream.get_schema_version();
+        let mut p_custom_fields = HashMap::new();
+        p_custom_fields.insert(USER_AGENT_KEY.to_string(), "kafka".to_string());
+        p_custom_fields.insert(FORMAT_KEY.to_string(), route.log_source.to_string());
 
-        let mut json_vec = Vec::with_capacity(records.len());
-        let mut total_payload_size = 0u64;
+        let stream = PARSEABLE.get_stream(&route.stream_name)?;
+        let metadata = self.metadata.for_stream(stream.get_static_schema_flag());
+        match route.log_source {
+            // formats which need flattening are ingested the same way as over HTTP, record by record
+            LogSource::Kinesis
+            | LogSource::OtelLogs
+            | LogSource::OtelMetrics
+            | LogSource::OtelTraces => {
+                PARSEABLE
+                    .add_update_log_source(&route.stream_name, log_source_entry)
+                    .await?;
+                let time_partition = stream.get_time_partition();
+                for (record, payload) in records {
+                    // flattened rows only carry the custom fields
+                    let mut p_custom_fields = p_custom_fields.clone();
+                    p_custom_fields.extend(metadata.fields(record));
+                    if let Err(e) = flatten_and_push_logs(
+                        payload,
+                        &route.stream_name,
+                        &route.log_source,
+                        &p_custom_fields,
+                        time_partition.clone(),
+                        route.telemetry_type(),
+                    )
+                    .await
+                    {
+                        self.reject(record, BadRecordReason::Rejected, e.into())
+                            .await?;
+                    }
+                }
+            }
+            // events of streams with an ingest pipeline go through it, record by record
+            _ if stream.get_pipeline().is_some() => {
+                let time_partition = stream.get_time_partition();
+                for (record, mut payload) in records {
+                    metadata.enrich(record, &mut payload);
+                    if let Err(e) = flatten_and_push_logs(
+                        payload,
+                        &route.stream_name,
+                        &route.log_source,
+                        &p_custom_fields,
+                        time_partition.clone(),
+                        route.telemetry_type(),
+                    )
+                    .await
+                    {
+                        self.reject(record, BadRecordReason::Rejected, e.into())
+                            .await?;
+                    }
+                }
+            }
+            _ => {
+                let total_payload_size =
+                    records.iter().map(|(record, _)| payload_size(record)).sum();
+                let (records, json_vec): (Vec<_>, Vec<_>) = records
+                    .into_iter()
+                    .map(|(record, mut value)| {
+                        metadata.enrich(record, &mut value);
+                        (record, value)
+                    })
+                    .unzip();
+                let Err(e) = self
+                    .build_event_from_chunk(route, json_vec, total_payload_size, &p_custom_fields)
+                    .and_then(|event| Ok(event.process()?))
+                else {
+                    return Ok(());
+                };
+                if self.bad_data == BadData::Fail {
+                    return Err(e);
+                }
 
-        for record in records.iter().filter_map(|r| r.payload.as_ref()) {
-            total_payload_size += record.len() as u64;
-            if let Ok(value) = serde_json::from_slice::<Value>(record) {
-                json_vec.push(value);
+                // find the records which can't be ingested by retrying them one by one
+                warn!(
+                    "Failed to ingest {} records into stream {}, retrying them one by one: {e}",
+                    records.len(),
+                    route.stream_name
+                );
+                for record in records {
+                    let Some(mut value) = record
+                        .payload
+                        .as_ref()
+                        .and_then(|payload| serde_json::from_slice::<Value>(payload).ok())
+                    else {
+                        continue;
+                    };
+                    metadata.enrich(record, &mut value);
+                    if let Err(e) = self
+                        .build_event_from_chunk(
+                            route,
+                            vec![value],
+                            payload_size(record),
+                            &p_custom_fields,
+                        )
+                        .and_then(|event| Ok(event.process()?))
+                    {
+                        self.reject(record, BadRecordReason::Rejected, e).await?;
+                    }
+                }
             }
         }
 
-        let mut p_custom_fields = HashMap::new();
-        p_custom_fields.insert(USER_AGENT_KEY.to_string(), "kafka".to_string());
+        Ok(())
+    }
+
+    /// Handles a record which can't be ingested as per the bad data policy
+    async fn reject(
+        &self,
+        record: &ConsumerRecord,
+        reason: BadRecordReason,
+        error: anyhow::Error,
+    ) -> anyhow::Result<()> {
+        match (&self.bad_data, &self.dead_letter) {
+            (BadData::Fail, _) => Err(error),
+            (BadData::Dlt, Some(dead_letter)) => {
+                dead_letter.send(record, reason, &error.to_string()).await
+            }
+            _ => {
+                warn!(
+                    "Dropping record {}/{}@{}: {error}",
+                    record.topic, record.partition, record.offset
+                );
+                BAD_RECORD_METRICS
+                    .dropped
+                    .with_label_values(&[record.topic.as_str(), reason.as_str()])
+                    .inc();
+                Ok(())
+            }
+        }
+    }
+
+    fn build_event_from_chunk(
+        &self,
+        route: &Route,
+        json_vec: Vec<Value>,
+        total_payload_size: u64,
+        p_custom_fields: &HashMap<String, String>,
+    ) -> anyhow::Result<ParseableEvent> {
+        let stream = PARSEABLE.get_stream(&route.stream_name)?;
+        let schema = stream.get_schema_raw();
+        let time_partition = stream.get_time_partition();
+        let custom_partition = stream.get_custom_partition();
+        let static_schema_flag = stream.get_static_schema_flag();
+        let schema_version = stream.get_schema_version();
 
         let p_event = json::Event::new(Value::Array(json_vec), Utc::now()).into_event(
-            stream_name.to_string(),
+            route.stream_name.clone(),
             total_payload_size,
             &schema,
             static_schema_flag,
@@ -90,21 +240,59 @@ impl ParseableSinkProcessor {
             time_partition.as_ref(),
             schema_version,
             StreamType::UserDefined,
-            &p_custom_fields,
-            TelemetryType::Logs,
+            p_custom_fields,
+            route.telemetry_type(),
         )?;
 
         Ok(p_event)
     }
 }
 
+fn payload_size(record: &ConsumerRecord) -> u64 {
+    record
+        .payload
+        .as_ref()
+        .map_or(0, |payload| payload.len() as u64)
+}
+
 #[async_trait]
 impl Processor<Vec<ConsumerRecord>, ()> for ParseableSinkProcessor {
     async fn process(&self, records: Vec<ConsumerRecord>) -> anyhow::Result<()> {
         let len = records.len();
         debug!("Processing {len} records");
 
-        self.build_event_from_chunk(&records).await?.process()?;
+        // records of a chunk can belong to several streams
+        let mut routes: HashMap<Route, Vec<(&ConsumerRecord, Value)>> = HashMap::new();
+        let internal_streams = PARSEABLE.streams.list_internal_streams();
+        for record in records.iter() {
+            let Some(payload) = record.payload.as_ref() else {
+                continue;
+            };
+            let value = match serde_json::from_slice::<Value>(payload) {
+                Ok(value) => value,
+                // unparsable records have always been skipped with the fail policy
+                Err(e) if self.bad_data == BadData::Fail => {
+                    debug!("Skipping record which is not valid JSON: {e}");
+                    continue;
+                }
+                Err(e) => {
+                    self.reject(record, BadRecordReason::InvalidJson, e.into())
+                        .await?;
+                    continue;
+                }
+            };
+            let route = self.routing.route(record, Some(&value));
+            if let Err(e) = route.validate(&internal_streams) {
+                self.reject(record, BadRecordReason::InvalidStream, e)
+                    .await?;
+                continue;
+            }
+            routes.entry(route).or_default().push((record, value));
+        }
+
+        for (route, records) in routes {
+            self.ingest(&route, records).await?;
+        }
 
         debug!("Processed {len} records");
         Ok(())
@@ -177,3 +365,124 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rdkafka::ClientConfig;
+    use rdkafka::consumer::{BaseConsumer, Consumer};
+    use rdkafka::mocking::MockCluster;
+
+    use super::*;
+    use crate::connectors::kafka::dead_letter::{
+        DLT_ERROR_HEADER, DLT_OFFSET_HEADER, DLT_PARTITION_HEADER, DLT_REASON_HEADER,
+        DLT_TOPIC_HEADER,
+    };
+
+    fn record(offset: i64, payload: &[u8]) -> ConsumerRecord {
+        ConsumerRecord {
+            payload: Some(payload.to_vec()),
+            key: Some(b"user-1".to_vec()),
+            topic: "orders".to_string(),
+            partition: 2,
+            offset,
+            timestamp: Some(1_700_000_000_000),
+            headers: vec![("trace-id".to_string(), Some("abc".to_string()))],
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_records_reach_the_dead_letter_topic() {
+        let cluster = MockCluster::new(1).unwrap();
+        cluster.create_topic("orders-dlt", 1, 1).unwrap();
+
+        let config = KafkaConfig {
+            bootstrap_servers: Some(cluster.bootstrap_servers()),
+            producer: None,
+            security: None,
+            bad_data: BadData::Dlt,
+            dlt_topic: Some("orders-dlt".to_string()),
+            ..Default::default()
+        };
+        let processor = ParseableSinkProcessor::new(&config).unwrap();
+
+        let rejected = [
+            (
+                record(7, b"{not json"),
+                BadRecordReason::InvalidJson,
+                "expected value",
+            ),
+            (
+                record(8, br#"{"level": 1}"#),
+                BadRecordReason::Rejected,
+                "schema mismatch",
+            ),
+        ];
+        for (record, reason, error) in &rejected {
+            processor
+                .reject(record, *reason, anyhow::anyhow!(*error))
+                .await
+                .unwrap();
+        }
+
+        let consumer: BaseConsumer = ClientConfig::new()
+            .set("bootstrap.servers", cluster.bootstrap_servers())
+            .set("group.id", "dlt-test")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .unwrap();
+        consumer.subscribe(&["orders-dlt"]).unwrap();
+
+        let mut produced = Vec::new();
+        while produced.len() < rejected.len() {
+            let message = consumer
+                .poll(Duration::from_secs(10))
+                .expect("dead-letter record within the timeout")
+                .unwrap();
+            produced.push(ConsumerRecord::from_borrowed_msg(message));
+        }
+
+        for (dlt_record, (record, reason, error)) in produced.iter().zip(&rejected) {
+            // the record is produced as is, with headers on where it came from
+            assert_eq!(dlt_record.payload, record.payload);
+            assert_eq!(dlt_record.key, record.key);
+            assert_eq!(dlt_record.timestamp, record.timestamp);
+            assert_eq!(dlt_record.header("trace-id"), Some("abc"));
+            assert_eq!(dlt_record.header(DLT_ERROR_HEADER), Some(*error));
+            assert_eq!(dlt_record.header(DLT_REASON_HEADER), Some(reason.as_str()));
+            assert_eq!(dlt_record.header(DLT_TOPIC_HEADER), Some("orders"));
+            assert_eq!(dlt_record.header(DLT_PARTITION_HEADER), Some("2"));
+            assert_eq!(
+                dlt_record.header(DLT_OFFSET_HEADER),
+                Some(record.offset.to_string().as_str())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_records_fail_or_drop_without_dead_letter_topic() {
+        let record = record(7, b"{not json");
+        for reason in [BadRecordReason::InvalidJson, BadRecordReason::Rejected] {
+            let fail = ParseableSinkProcessor {
+                bad_data: BadData::Fail,
+                ..Default::default()
+            };
+            assert!(
+                fail.reject(&record, reason, anyhow::anyhow!("bad record"))
+                    .await
+                    .is_err()
+            );
+
+            let drop = ParseableSinkProcessor {
+                bad_data: BadData::Drop,
+                ..Default::default()
+            };
+            assert!(
+                drop.reject(&record, reason, anyhow::anyhow!("bad record"))
+                    .await
+                    .is_ok()
+            );
+        }
+    }
+}
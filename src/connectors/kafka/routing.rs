@@ -0,0 +1,225 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use serde_json::Value;
+
+use crate::{
+    event::format::{LogSource, LogSourceEntry},
+    handlers::{TelemetryType, http::ingest::PostError},
+    otel::{
+        logs::OTEL_LOG_KNOWN_FIELD_LIST, metrics::OTEL_METRICS_KNOWN_FIELD_LIST,
+        traces::OTEL_TRACES_KNOWN_FIELD_LIST,
+    },
+    storage::StreamType,
+    validator,
+};
+
+use super::{
+    ConsumerRecord,
+    config::{RoutingConfig, RoutingStrategy},
+};
+
+/// Stream a record is ingested into, along with the format of its payload
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Route {
+    pub stream_name: String,
+    pub log_source: LogSource,
+}
+
+impl Route {
+    /// Checks that records can be ingested into the stream, as for HTTP ingestion. Names routed
+    /// from headers, keys or fields are set by producers, so they can't be trusted.
+    pub fn validate(&self, internal_streams: &[String]) -> anyhow::Result<()> {
+        validator::stream_name(&self.stream_name, StreamType::UserDefined)?;
+        if internal_streams.contains(&self.stream_name) {
+            return Err(PostError::InternalStream(self.stream_name.clone()).into());
+        }
+
+        Ok(())
+    }
+
+    pub fn telemetry_type(&self) -> TelemetryType {
+        match self.log_source {
+            LogSource::OtelMetrics => TelemetryType::Metrics,
+            LogSource::OtelTraces => TelemetryType::Traces,
+            _ => TelemetryType::Logs,
+        }
+    }
+
+    pub fn log_source_entry(&self) -> LogSourceEntry {
+        let known_fields: &[&str] = match self.log_source {
+            LogSource::OtelLogs => &OTEL_LOG_KNOWN_FIELD_LIST,
+            LogSource::OtelMetrics => &OTEL_METRICS_KNOWN_FIELD_LIST,
+            LogSource::OtelTraces => &OTEL_TRACES_KNOWN_FIELD_LIST,
+            _ => &[],
+        };
+        LogSourceEntry::new(
+            self.log_source.clone(),
+            known_fields.iter().map(|&s| s.to_string()).collect(),
+        )
+    }
+}
+
+impl RoutingConfig {
+    /// Resolves the route of a record, `payload` being its parsed JSON payload.
+    /// Falls back to the topic mapping, and then to the topic name,
+    /// when the routing strategy finds no stream name on the record.
+    pub fn route(&self, record: &ConsumerRecord, payload: Option<&Value>) -> Route {
+        let mapping = self
+            .topic_mapping
+            .iter()
+            .find(|mapping| mapping.topic == record.topic);
+
+        let stream_name = match self.strategy {
+            RoutingStrategy::Topic => None,
+            RoutingStrategy::Header => record.header(&self.header).map(str::to_owned),
+            RoutingStrategy::Key => record
+                .key
+                .as_ref()
+                .map(|key| String::from_utf8_lossy(key).into_owned()),
+            RoutingStrategy::Field => self
+                .field
+                .as_ref()
+                .and_then(|field| payload?.get(field)?.as_str())
+                .map(str::to_owned),
+        }
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .or_else(|| mapping.map(|mapping| mapping.stream.clone()))
+        .unwrap_or_else(|| record.topic.clone());
+
+        let log_source = record
+            .header(&self.log_source_header)
+            .or_else(|| mapping.and_then(|mapping| mapping.log_source.as_deref()))
+            .unwrap_or(&self.log_source);
+
+        Route {
+            stream_name,
+            log_source: LogSource::from(log_source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::connectors::kafka::config::TopicRoute;
+
+    fn record(key: Option<&str>, headers: &[(&str, &str)]) -> ConsumerRecord {
+        ConsumerRecord {
+            payload: None,
+            key: key.map(|k| k.as_bytes().to_vec()),
+            topic: "shared".to_string(),
+            partition: 0,
+            offset: 0,
+            timestamp: None,
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), Some(v.to_string())))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn routes_by_topic_and_mapping() {
+        let mut config = RoutingConfig::default();
+        let route = config.route(&record(None, &[]), None);
+        assert_eq!(route.stream_name, "shared");
+        assert_eq!(route.log_source, LogSource::Json);
+
+        config.topic_mapping = vec!["shared=app:otel-logs".parse().unwrap()];
+        let route = config.route(&record(None, &[]), None);
+        assert_eq!(route.stream_name, "app");
+        assert_eq!(route.log_source, LogSource::OtelLogs);
+        assert_eq!(route.telemetry_type(), TelemetryType::Logs);
+    }
+
+    #[test]
+    fn routes_by_header_key_and_field() {
+        let mut config = RoutingConfig {
+            strategy: RoutingStrategy::Header,
+            ..Default::default()
+        };
+        let route = config.route(
+            &record(
+                None,
+                &[("x-p-stream", "billing"), ("x-p-log-source", "otel-traces")],
+            ),
+            None,
+        );
+        assert_eq!(route.stream_name, "billing");
+        assert_eq!(route.log_source, LogSource::OtelTraces);
+        assert_eq!(route.telemetry_type(), TelemetryType::Traces);
+
+        config.strategy = RoutingStrategy::Key;
+        let route = config.route(&record(Some("orders"), &[]), None);
+        assert_eq!(route.stream_name, "orders");
+
+        config.strategy = RoutingStrategy::Field;
+        config.field = Some("app".to_string());
+        let payload = json!({"app": "checkout", "msg": "hello"});
+        let route = config.route(&record(None, &[]), Some(&payload));
+        assert_eq!(route.stream_name, "checkout");
+    }
+
+    #[test]
+    fn falls_back_when_strategy_finds_nothing() {
+        let config = RoutingConfig {
+            strategy: RoutingStrategy::Header,
+            topic_mapping: vec!["shared=fallback".parse().unwrap()],
+            ..Default::default()
+        };
+        let route = config.route(&record(None, &[("x-p-stream", " ")]), None);
+        assert_eq!(route.stream_name, "fallback");
+    }
+
+    #[test]
+    fn rejects_invalid_and_internal_streams() {
+        let config = RoutingConfig {
+            strategy: RoutingStrategy::Header,
+            ..Default::default()
+        };
+        let internal_streams = vec!["pmeta".to_string()];
+        let validate = |stream: &str| {
+            config
+                .route(&record(None, &[("x-p-stream", stream)]), None)
+                .validate(&internal_streams)
+        };
+
+        assert!(validate("billing").is_ok());
+        assert!(validate("pmeta").is_err());
+        assert!(validate("../billing").is_err());
+        assert!(validate("select").is_err());
+    }
+
+    #[test]
+    fn parses_topic_mapping() {
+        assert_eq!(
+            "orders = orders-stream".parse::<TopicRoute>().unwrap(),
+            TopicRoute {
+                topic: "orders".to_string(),
+                stream: "orders-stream".to_string(),
+                log_source: None,
+            }
+        );
+        assert!("orders".parse::<TopicRoute>().is_err());
+        assert!("=stream".parse::<TopicRoute>().is_err());
+    }
+}
@@ -44,7 +44,7 @@ pub async fn init(prometheus: &PrometheusMetrics) -> anyhow::Result<()> {
                 let config = PARSEABLE.kafka_config.clone();
                 let shutdown_handle = Shutdown::default();
                 let registry = prometheus.registry.clone();
-                let processor = ParseableSinkProcessor;
+                let processor = ParseableSinkProcessor::new(&config)?;
 
                 tokio::spawn({
                     let shutdown_handle = shutdown_handle.clone();
@@ -117,6 +117,7 @@ impl Correlations {
         session_key: &SessionKey,
     ) -> Result<CorrelationConfig, CorrelationError> {
         correlation.id = get_hash(Utc::now().timestamp_micros().to_string().as_str());
+        correlation.version = correlation.join_config.version();
         correlation.validate(session_key).await?;
 
         // Update in metastore
@@ -133,11 +134,11 @@ impl Correlations {
     /// Update existing correlation for the user and with the same ID
     pub async fn update(
         &self,
-        mut updated_correlation: CorrelationConfig,
+        updated_correlation: CorrelationConfig,
         session_key: &SessionKey,
     ) -> Result<CorrelationConfig, CorrelationError> {
         // validate whether user has access to this correlation object or not
-        let correlation = self.get_correlation(&updated_correlation.id).await?;
+        let mut correlation = self.get_correlation(&updated_correlation.id).await?;
         if correlation.user_id != updated_correlation.user_id {
             return Err(CorrelationError::AnyhowError(anyhow::Error::msg(format!(
                 r#"User "{}" isn't authorized to update correlation with ID - {}"#,
@@ -145,22 +146,39 @@ impl Correlations {
             ))));
         }
 
+        correlation.update(updated_correlation);
         correlation.validate(session_key).await?;
-        updated_correlation.update(correlation);
 
         // Update in metastore
-        PARSEABLE
-            .metastore
-            .put_correlation(&updated_correlation)
-            .await?;
+        PARSEABLE.metastore.put_correlation(&correlation).await?;
 
         // Update in memory
-        self.write().await.insert(
-            updated_correlation.id.to_owned(),
-            updated_correlation.clone(),
-        );
+        self.write()
+            .await
+            .insert(correlation.id.to_owned(), correlation.clone());
 
-        Ok(updated_correlation)
+        Ok(correlation)
+    }
+
+    /// Save a correlation imported from a bundle with its own ID,
+    /// replacing the correlation with the same ID if there is one
+    pub async fn import(
+        &self,
+        mut correlation: CorrelationConfig,
+        session_key: &SessionKey,
+    ) -> Result<CorrelationConfig, CorrelationError> {
+        correlation.version = correlation.join_config.version();
+        correlation.validate(session_key).await?;
+
+        // Update in metastore
+        PARSEABLE.metastore.put_correlation(&correlation).await?;
+
+        // Update in memory
+        self.write()
+            .await
+            .insert(correlation.id.to_owned(), correlation.clone());
+
+        Ok(correlation)
     }
 
     /// Delete correlation from memory and storage
@@ -191,6 +209,8 @@ impl Correlations {
 pub enum CorrelationVersion {
     #[default]
     V1,
+    /// Joins of any number of tables, see [`Join`]
+    V2,
 }
 
 type CorrelationId = String;
@@ -237,6 +257,7 @@ impl CorrelationConfig {
         self.title = update.title;
         self.table_configs = update.table_configs;
         self.join_config = update.join_config;
+        self.version = self.join_config.version();
         self.filter = update.filter;
         self.start_time = update.start_time;
         self.end_time = update.end_time;
@@ -244,30 +265,7 @@ impl CorrelationConfig {
 
     /// This function will validate the TableConfigs, JoinConfig, and user auth
     pub async fn validate(&self, session_key: &SessionKey) -> Result<(), CorrelationError> {
-        let ctx = &QUERY_SESSION;
-
-        let h1: HashSet<&String> = self.table_configs.iter().map(|t| &t.table_name).collect();
-        let h2: HashSet<&String> = self
-            .join_config
-            .join_conditions
-            .iter()
-            .map(|j| &j.table_name)
-            .collect();
-
-        // check if table config tables are the same
-        if h1.len() != 2 {
-            return Err(CorrelationError::Metadata(
-                "Must provide config for two unique tables",
-            ));
-        }
-
-        // check that the tables mentioned in join config are
-        // the same as those in table config
-        if h1 != h2 {
-            return Err(CorrelationError::Metadata(
-                "Must provide same tables for join config and table config",
-            ));
-        }
+        self.validate_joins()?;
 
         // check if user has access to table
         let permissions = Users.get_permissions(session_key);
@@ -279,37 +277,151 @@ impl CorrelationConfig {
 
         user_auth_for_datasets(&permissions, tables).await?;
 
-        // to validate table config, we need to check whether the mentioned fields
-        // are present in the table or not
-        for table_config in self.table_configs.iter() {
-            // table config check
-            let df = ctx.table(&table_config.table_name).await?;
+        // planning the query checks that the selected and joined fields
+        // are present in the tables, and that the conditions can be evaluated
+        QUERY_SESSION
+            .state()
+            .create_logical_plan(&self.query()?)
+            .await?;
 
-            let mut selected_fields = table_config
-                .selected_fields
-                .iter()
-                .map(|c| c.as_str())
-                .collect_vec();
+        Ok(())
+    }
 
-            // unwrap because we have determined that the tables in table config are the same as those in join config
-            let condition = self
-                .join_config
-                .join_conditions
-                .iter()
-                .find(|j| j.table_name == table_config.table_name)
-                .unwrap();
-            let join_field = condition.field.as_str();
+    /// Checks that every table following the first one is joined exactly once,
+    /// with conditions on fields of the joined table and of the tables joined before it
+    fn validate_joins(&self) -> Result<(), CorrelationError> {
+        let tables: HashSet<&String> = self.table_configs.iter().map(|t| &t.table_name).collect();
+        if tables.len() != self.table_configs.len() {
+            return Err(CorrelationError::Metadata(
+                "Must provide config for unique tables",
+            ));
+        }
+        if tables.len() < 2 {
+            return Err(CorrelationError::Metadata(
+                "Must provide config for at least two tables",
+            ));
+        }
 
-            if !selected_fields.contains(&join_field) {
-                selected_fields.push(join_field);
+        let (base, joins) = self.joins()?;
+        let mut joined = HashSet::from([base]);
+        for join in &joins {
+            if !tables.contains(&join.table_name) {
+                return Err(CorrelationError::Metadata(
+                    "Must provide same tables for join config and table config",
+                ));
+            }
+            if !joined.insert(&join.table_name) {
+                return Err(CorrelationError::Metadata("Tables can only be joined once"));
+            }
+            if join.on.is_empty() {
+                return Err(CorrelationError::Metadata(
+                    "Must provide at least one condition per join",
+                ));
+            }
+            for predicate in &join.on {
+                let (left, right) = predicate.fields();
+                let (own, other) = if left.table_name == join.table_name {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                if own.table_name != join.table_name
+                    || other.table_name == join.table_name
+                    || !joined.contains(&other.table_name)
+                {
+                    return Err(CorrelationError::Metadata(
+                        "Join conditions must compare the joined table with a table joined before it",
+                    ));
+                }
             }
+        }
 
-            // if this errors out then the table config is incorrect or join config is incorrect
-            df.select_columns(selected_fields.as_slice())?;
+        if joined.len() != tables.len() {
+            return Err(CorrelationError::Metadata(
+                "Must provide same tables for join config and table config",
+            ));
         }
 
         Ok(())
     }
+
+    /// The table the others are joined to, and the joins in order.
+    /// Correlations of two tables may be configured with a field of each table to compare.
+    fn joins(&self) -> Result<(&String, Vec<Join>), CorrelationError> {
+        if !self.join_config.joins.is_empty() {
+            let base = self
+                .table_configs
+                .first()
+                .ok_or(CorrelationError::Metadata(
+                    "Must provide config for the joined tables",
+                ))?;
+            return Ok((&base.table_name, self.join_config.joins.clone()));
+        }
+
+        match self.join_config.join_conditions.as_slice() {
+            [left, right] => Ok((
+                &left.table_name,
+                vec![Join {
+                    table_name: right.table_name.clone(),
+                    join_type: JoinType::Inner,
+                    on: vec![JoinPredicate::Equal {
+                        left: left.clone(),
+                        right: right.clone(),
+                    }],
+                }],
+            )),
+            _ => Err(CorrelationError::Metadata(
+                "Must provide a join condition for each of the two tables",
+            )),
+        }
+    }
+
+    /// SQL query correlating the tables, selects the fields of every table
+    /// as `<table>.<field>` so that fields with the same name don't collide,
+    /// or all the fields when none are selected
+    pub fn query(&self) -> Result<String, CorrelationError> {
+        let (base, joins) = self.joins()?;
+
+        let projection = self
+            .table_configs
+            .iter()
+            .flat_map(|t| {
+                t.selected_fields.iter().map(|field| {
+                    format!(
+                        "{} AS {}",
+                        qualified(&t.table_name, field),
+                        quote(&format!("{}.{field}", t.table_name))
+                    )
+                })
+            })
+            .join(", ");
+        let projection = if projection.is_empty() {
+            "*".to_owned()
+        } else {
+            projection
+        };
+
+        let mut query = format!("SELECT {projection} FROM {}", quote(base));
+        for join in joins {
+            let conditions = join.on.iter().map(JoinPredicate::to_sql).join(" AND ");
+            query.push_str(&format!(
+                " {} {} ON {conditions}",
+                join.join_type.to_sql(),
+                quote(&join.table_name)
+            ));
+        }
+
+        Ok(query)
+    }
+}
+
+/// Quotes an SQL identifier
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn qualified(table_name: &str, field: &str) -> String {
+    format!("{}.{}", quote(table_name), quote(field))
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -377,8 +489,180 @@ pub struct JoinCondition {
     pub field: String,
 }
 
+impl JoinConfig {
+    fn version(&self) -> CorrelationVersion {
+        if self.joins.is_empty() {
+            CorrelationVersion::V1
+        } else {
+            CorrelationVersion::V2
+        }
+    }
+}
+
+impl JoinCondition {
+    fn to_sql(&self) -> String {
+        qualified(&self.table_name, &self.field)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JoinConfig {
+    /// A field of each of the two correlated tables, joined on equality
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub join_conditions: Vec<JoinCondition>,
+    /// Joins of the tables following the first table config, takes precedence over `join_conditions`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub joins: Vec<Join>,
+}
+
+/// Join of a table to the tables joined before it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Join {
+    pub table_name: String,
+    #[serde(default)]
+    pub join_type: JoinType,
+    /// Conditions which all have to hold
+    pub on: Vec<JoinPredicate>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JoinType {
+    #[default]
+    Inner,
+    /// Keeps the rows without a match in the joined table
+    Left,
+}
+
+impl JoinType {
+    fn to_sql(self) -> &'static str {
+        match self {
+            Self::Inner => "INNER JOIN",
+            Self::Left => "LEFT JOIN",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "operator", rename_all = "camelCase")]
+pub enum JoinPredicate {
+    /// Values of both fields are equal
+    Equal {
+        left: JoinCondition,
+        right: JoinCondition,
+    },
+    /// Timestamps of both fields are at most `seconds` apart, e.g. ±5s on `p_timestamp`
+    Within {
+        left: JoinCondition,
+        right: JoinCondition,
+        seconds: u64,
+    },
+}
+
+impl JoinPredicate {
+    fn fields(&self) -> (&JoinCondition, &JoinCondition) {
+        match self {
+            Self::Equal { left, right } | Self::Within { left, right, .. } => (left, right),
+        }
+    }
+
+    fn to_sql(&self) -> String {
+        match self {
+            Self::Equal { left, right } => format!("{} = {}", left.to_sql(), right.to_sql()),
+            Self::Within {
+                left,
+                right,
+                seconds,
+            } => {
+                let left = left.to_sql();
+                format!(
+                    "{} BETWEEN {left} - INTERVAL '{seconds} seconds' AND {left} + INTERVAL '{seconds} seconds'",
+                    right.to_sql()
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn config(join_config: serde_json::Value) -> CorrelationConfig {
+        serde_json::from_value(json!({
+            "title": "checkout",
+            "tableConfigs": [
+                {"tableName": "ingress", "selectedFields": ["status"]},
+                {"tableName": "app", "selectedFields": ["message"]},
+                {"tableName": "traces", "selectedFields": ["span_id"]}
+            ],
+            "joinConfig": join_config,
+            "filter": null,
+            "startTime": null,
+            "endTime": null
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn generates_n_way_query() {
+        let correlation = config(json!({"joins": [
+            {"tableName": "app", "on": [
+                {"operator": "equal", "left": {"tableName": "ingress", "field": "request_id"}, "right": {"tableName": "app", "field": "request_id"}}
+            ]},
+            {"tableName": "traces", "joinType": "left", "on": [
+                {"operator": "within", "seconds": 5, "left": {"tableName": "app", "field": "p_timestamp"}, "right": {"tableName": "traces", "field": "p_timestamp"}}
+            ]}
+        ]}));
+        correlation.validate_joins().unwrap();
+        assert!(matches!(
+            correlation.join_config.version(),
+            CorrelationVersion::V2
+        ));
+
+        assert_eq!(
+            correlation.query().unwrap(),
+            r#"SELECT "ingress"."status" AS "ingress.status", "app"."message" AS "app.message", "traces"."span_id" AS "traces.span_id" FROM "ingress" INNER JOIN "app" ON "ingress"."request_id" = "app"."request_id" LEFT JOIN "traces" ON "traces"."p_timestamp" BETWEEN "app"."p_timestamp" - INTERVAL '5 seconds' AND "app"."p_timestamp" + INTERVAL '5 seconds'"#
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_joins() {
+        // traces is never joined
+        let correlation = config(json!({"joins": [
+            {"tableName": "app", "on": [
+                {"operator": "equal", "left": {"tableName": "ingress", "field": "id"}, "right": {"tableName": "app", "field": "id"}}
+            ]}
+        ]}));
+        assert!(correlation.validate_joins().is_err());
+
+        // traces is compared with app before app is joined
+        let correlation = config(json!({"joins": [
+            {"tableName": "traces", "on": [
+                {"operator": "equal", "left": {"tableName": "app", "field": "id"}, "right": {"tableName": "traces", "field": "id"}}
+            ]},
+            {"tableName": "app", "on": [
+                {"operator": "equal", "left": {"tableName": "ingress", "field": "id"}, "right": {"tableName": "app", "field": "id"}}
+            ]}
+        ]}));
+        assert!(correlation.validate_joins().is_err());
+    }
+
+    #[test]
+    fn supports_two_table_join_conditions() {
+        let mut correlation = config(json!({"joinConditions": [
+            {"tableName": "ingress", "field": "request_id"},
+            {"tableName": "app", "field": "request_id"}
+        ]}));
+        correlation.table_configs.pop();
+        correlation.validate_joins().unwrap();
+
+        assert!(correlation.query().unwrap().ends_with(
+            r#"FROM "ingress" INNER JOIN "app" ON "ingress"."request_id" = "app"."request_id""#
+        ));
+    }
 }
@@ -0,0 +1,418 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ *
+ */
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use arrow::compute::{CastOptions, can_cast_types, cast_with_options};
+use arrow::util::display::array_value_to_string;
+use arrow_array::{
+    Array, RecordBatch, TimestampMillisecondArray, UInt32Array, cast::AsArray,
+    types::TimestampMillisecondType,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use arrow_select::take::take_record_batch;
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+
+use super::EventFormat;
+use crate::{handlers::TelemetryType, metadata::SchemaVersion, storage::StreamType};
+
+/// Record batch received as is, e.g. over Arrow Flight, no JSON involved
+pub struct Event {
+    pub rb: RecordBatch,
+    pub p_timestamp: DateTime<Utc>,
+}
+
+impl Event {
+    pub fn new(rb: RecordBatch, p_timestamp: DateTime<Utc>) -> Self {
+        Self { rb, p_timestamp }
+    }
+}
+
+impl EventFormat for Event {
+    type Data = RecordBatch;
+
+    /// Returns the time at ingestion, i.e. the `p_timestamp` value
+    fn get_p_timestamp(&self) -> DateTime<Utc> {
+        self.p_timestamp
+    }
+
+    // fields already known to the stream take the type of the stream schema,
+    // the batch is rejected if its columns can't be cast to that type
+    fn to_data(
+        self,
+        schema: &HashMap<String, Arc<Field>>,
+        _time_partition: Option<&String>,
+        _schema_version: SchemaVersion,
+        _static_schema_flag: bool,
+    ) -> Result<(Self::Data, Vec<Arc<Field>>, bool), anyhow::Error> {
+        let mut is_first = false;
+        let mut fields = Vec::with_capacity(self.rb.num_columns());
+        for field in self.rb.schema().fields() {
+            let field = match schema.get(field.name()) {
+                Some(existing) if existing.data_type() == field.data_type() => existing.clone(),
+                Some(existing) if can_cast_types(field.data_type(), existing.data_type()) => {
+                    existing.clone()
+                }
+                Some(existing) => {
+                    return Err(anyhow!(
+                        "Could not process this event due to mismatch in datatype of field {}, expected {} but got {}",
+                        field.name(),
+                        existing.data_type(),
+                        field.data_type()
+                    ));
+                }
+                None => {
+                    is_first = true;
+                    Arc::new(Field::new(field.name(), field.data_type().clone(), true))
+                }
+            };
+            fields.push(field);
+        }
+
+        Ok((self.rb, fields, is_first))
+    }
+
+    // Cast the columns of the batch to the types of the derived schema
+    fn decode(data: Self::Data, schema: Arc<Schema>) -> Result<RecordBatch, anyhow::Error> {
+        let options = CastOptions {
+            safe: false,
+            ..Default::default()
+        };
+        let columns = data
+            .columns()
+            .iter()
+            .zip(schema.fields())
+            .map(|(column, field)| cast_with_options(column, field.data_type(), &options))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+
+    /// Converts a record batch into a Parseable Event, the batch is expected to hold a single
+    /// value of the custom partition and a single minute of the time partition, see
+    /// [`split_by_custom_partition`] and [`split_by_time_partition`]
+    fn into_event(
+        self,
+        stream_name: String,
+        origin_size: u64,
+        storage_schema: &HashMap<String, Arc<Field>>,
+        static_schema_flag: bool,
+        custom_partitions: Option<&String>,
+        time_partition: Option<&String>,
+        schema_version: SchemaVersion,
+        stream_type: StreamType,
+        p_custom_fields: &HashMap<String, String>,
+        telemetry_type: TelemetryType,
+    ) -> Result<super::Event, anyhow::Error> {
+        let custom_partition_values = match custom_partitions {
+            Some(custom_partition) => extract_custom_partition_values(&self.rb, custom_partition)?,
+            None => HashMap::new(),
+        };
+        let parsed_timestamp = match time_partition {
+            Some(time_partition) => {
+                let times = partition_times(&self.rb, time_partition)?;
+                if times.is_empty() {
+                    self.p_timestamp.naive_utc()
+                } else {
+                    row_time(&times, 0, time_partition)?
+                }
+            }
+            None => self.p_timestamp.naive_utc(),
+        };
+
+        let (rb, is_first_event) = self.into_recordbatch(
+            storage_schema,
+            static_schema_flag,
+            time_partition,
+            schema_version,
+            p_custom_fields,
+        )?;
+
+        Ok(super::Event {
+            rb,
+            stream_name,
+            origin_format: "arrow",
+            origin_size,
+            is_first_event,
+            parsed_timestamp,
+            time_partition: None,
+            custom_partition_values,
+            stream_type,
+            telemetry_type,
+        })
+    }
+}
+
+/// Splits the batch so that every resulting batch holds rows with the same custom partition values
+pub fn split_by_custom_partition(
+    rb: RecordBatch,
+    custom_partition: &str,
+) -> Result<Vec<RecordBatch>, anyhow::Error> {
+    let mut groups: Vec<(Vec<String>, Vec<u32>)> = Vec::new();
+    let columns = custom_partition
+        .split(',')
+        .map(|name| {
+            rb.column_by_name(name.trim())
+                .ok_or_else(|| anyhow!("Missing field for custom partition: {}", name.trim()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for row in 0..rb.num_rows() {
+        let values = columns
+            .iter()
+            .map(|column| array_value_to_string(column, row))
+            .collect::<Result<Vec<_>, _>>()?;
+        match groups.iter_mut().find(|(key, _)| *key == values) {
+            Some((_, rows)) => rows.push(row as u32),
+            None => groups.push((values, vec![row as u32])),
+        }
+    }
+
+    if groups.len() <= 1 {
+        return Ok(vec![rb]);
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, rows)| Ok(take_record_batch(&rb, &UInt32Array::from(rows))?))
+        .collect()
+}
+
+/// Splits the batch so that every resulting batch holds rows of the same minute of the time
+/// partition, the batch is rejected if any row has no time or one older than the limit in days
+pub fn split_by_time_partition(
+    rb: RecordBatch,
+    time_partition: &str,
+    limit_days: i64,
+) -> Result<Vec<RecordBatch>, anyhow::Error> {
+    let times = partition_times(&rb, time_partition)?;
+    let cutoff = (Utc::now() - TimeDelta::days(limit_days)).naive_utc();
+
+    let mut groups: BTreeMap<i64, Vec<u32>> = BTreeMap::new();
+    for row in 0..rb.num_rows() {
+        if row_time(&times, row, time_partition)? < cutoff {
+            return Err(anyhow!(
+                "Time partition {time_partition} of row {row} is more than {limit_days} days old"
+            ));
+        }
+        let minute = times.value(row).div_euclid(60_000);
+        groups.entry(minute).or_default().push(row as u32);
+    }
+
+    if groups.len() <= 1 {
+        return Ok(vec![rb]);
+    }
+
+    groups
+        .into_values()
+        .map(|rows| Ok(take_record_batch(&rb, &UInt32Array::from(rows))?))
+        .collect()
+}
+
+/// Values of the time partition column, as timestamps
+fn partition_times(
+    rb: &RecordBatch,
+    time_partition: &str,
+) -> Result<TimestampMillisecondArray, anyhow::Error> {
+    let column = rb
+        .column_by_name(time_partition)
+        .ok_or_else(|| anyhow!("Missing field for time partition: {time_partition}"))?;
+    let options = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    let times = cast_with_options(
+        column,
+        &DataType::Timestamp(TimeUnit::Millisecond, None),
+        &options,
+    )
+    .map_err(|err| anyhow!("Time partition {time_partition} should hold timestamps: {err}"))?;
+
+    Ok(times.as_primitive::<TimestampMillisecondType>().clone())
+}
+
+fn row_time(
+    times: &TimestampMillisecondArray,
+    row: usize,
+    time_partition: &str,
+) -> Result<NaiveDateTime, anyhow::Error> {
+    if times.is_null(row) {
+        return Err(anyhow!(
+            "Missing value for time partition {time_partition} in row {row}"
+        ));
+    }
+    DateTime::from_timestamp_millis(times.value(row))
+        .map(|time| time.naive_utc())
+        .ok_or_else(|| anyhow!("Time partition {time_partition} of row {row} is out of range"))
+}
+
+/// Extracts custom partition values from the first row of the batch
+fn extract_custom_partition_values(
+    rb: &RecordBatch,
+    custom_partition: &str,
+) -> Result<HashMap<String, String>, anyhow::Error> {
+    let mut custom_partition_values = HashMap::new();
+    for name in custom_partition.split(',').map(str::trim) {
+        let column = rb
+            .column_by_name(name)
+            .ok_or_else(|| anyhow!("Missing field for custom partition: {name}"))?;
+        let value = if rb.num_rows() == 0 || column.is_null(0) {
+            String::new()
+        } else {
+            array_value_to_string(column, 0)?
+        };
+        custom_partition_values.insert(name.to_owned(), value);
+    }
+
+    Ok(custom_partition_values)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int64Array, StringArray};
+
+    use super::*;
+
+    fn batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("status", DataType::Utf8, false),
+            Field::new("latency", DataType::Int64, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["200", "500", "200"])),
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn casts_to_stream_schema() {
+        let storage_schema = HashMap::from([(
+            "latency".to_owned(),
+            Arc::new(Field::new("latency", DataType::Float64, true)),
+        )]);
+        let (rb, is_first) = Event::new(batch(), Utc::now())
+            .into_recordbatch(
+                &storage_schema,
+                false,
+                None,
+                SchemaVersion::V1,
+                &HashMap::new(),
+            )
+            .unwrap();
+
+        assert!(is_first);
+        assert_eq!(
+            rb.schema().field_with_name("latency").unwrap().data_type(),
+            &DataType::Float64
+        );
+        assert!(rb.column_by_name("p_timestamp").is_some());
+    }
+
+    #[test]
+    fn rejects_static_schema_mismatch() {
+        let storage_schema = HashMap::from([(
+            "latency".to_owned(),
+            Arc::new(Field::new("latency", DataType::Int64, true)),
+        )]);
+        let result = Event::new(batch(), Utc::now()).into_recordbatch(
+            &storage_schema,
+            true,
+            None,
+            SchemaVersion::V1,
+            &HashMap::new(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn splits_by_time_partition() {
+        let now = Utc::now();
+        let times = [now, now - TimeDelta::minutes(5), now];
+        let schema = Arc::new(Schema::new(vec![Field::new("time", DataType::Utf8, false)]));
+        let rb = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(StringArray::from_iter_values(
+                times.iter().map(|time| time.to_rfc3339()),
+            ))],
+        )
+        .unwrap();
+
+        let batches = split_by_time_partition(rb.clone(), "time", 30).unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].num_rows(), 1);
+        assert_eq!(batches[1].num_rows(), 2);
+
+        let event = Event::new(batches[0].clone(), now)
+            .into_event(
+                "logs".to_owned(),
+                0,
+                &HashMap::new(),
+                false,
+                None,
+                Some(&"time".to_owned()),
+                SchemaVersion::V1,
+                StreamType::UserDefined,
+                &HashMap::new(),
+                TelemetryType::Logs,
+            )
+            .unwrap();
+        assert_eq!(
+            event.parsed_timestamp,
+            DateTime::from_timestamp_millis(times[1].timestamp_millis())
+                .unwrap()
+                .naive_utc()
+        );
+        assert!(
+            event
+                .rb
+                .schema()
+                .field_with_name("time")
+                .unwrap()
+                .data_type()
+                .is_temporal()
+        );
+
+        assert!(split_by_time_partition(rb.clone(), "missing", 30).is_err());
+        let old = Arc::new(StringArray::from(vec![
+            (now - TimeDelta::days(40)).to_rfc3339(),
+        ]));
+        let old = RecordBatch::try_new(rb.schema(), vec![old]).unwrap();
+        assert!(split_by_time_partition(old, "time", 30).is_err());
+    }
+
+    #[test]
+    fn splits_by_custom_partition() {
+        let batches = split_by_custom_partition(batch(), "status").unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].num_rows(), 2);
+        assert_eq!(batches[1].num_rows(), 1);
+
+        let values = extract_custom_partition_values(&batches[1], "status").unwrap();
+        assert_eq!(values.get("status").unwrap(), "500");
+    }
+}
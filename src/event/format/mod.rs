@@ -39,6 +39,7 @@ use crate::{
 
 use super::{DEFAULT_TIMESTAMP_KEY, Event};
 
+pub mod arrow;
 pub mod json;
 pub mod known_schema;
 
@@ -0,0 +1,724 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Ingest pipelines, an ordered list of processors run over the events sent to a stream
+//! before they are pushed into it. Processors drop, keep, rename, extract and cast fields,
+//! enrich events with static fields, and drop or route to another stream the events
+//! matching conditions.
+//!
+//! Fields are addressed by name, or by a `.` separated path into nested objects. Events
+//! routed to another stream are pushed into it as they are when routed, without going
+//! through the pipeline of that stream.
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Number, Value};
+
+use crate::alerts::{
+    alert_enums::{LogicalOperator, WhereConfigOperator},
+    alert_structs::{ConditionConfig, Conditions},
+};
+
+/// Grok patterns which can be referenced as `%{NAME}`, they may reference one another
+static GROK_PATTERNS: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
+    HashMap::from([
+        ("USERNAME", r"[a-zA-Z0-9._-]+"),
+        ("USER", r"%{USERNAME}"),
+        ("INT", r"(?:[+-]?(?:[0-9]+))"),
+        ("BASE10NUM", r"(?:[+-]?(?:[0-9]+(?:\.[0-9]+)?|\.[0-9]+))"),
+        ("NUMBER", r"(?:%{BASE10NUM})"),
+        ("POSINT", r"\b(?:[1-9][0-9]*)\b"),
+        ("NONNEGINT", r"\b(?:[0-9]+)\b"),
+        ("WORD", r"\b\w+\b"),
+        ("NOTSPACE", r"\S+"),
+        ("SPACE", r"\s*"),
+        ("DATA", r".*?"),
+        ("GREEDYDATA", r".*"),
+        ("QUOTEDSTRING", r#"(?:"(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*')"#),
+        (
+            "UUID",
+            r"[A-Fa-f0-9]{8}-(?:[A-Fa-f0-9]{4}-){3}[A-Fa-f0-9]{12}",
+        ),
+        (
+            "IPV4",
+            r"(?:(?:25[0-5]|2[0-4][0-9]|1?[0-9]{1,2})\.){3}(?:25[0-5]|2[0-4][0-9]|1?[0-9]{1,2})",
+        ),
+        ("IPV6", r"(?:[0-9A-Fa-f]{0,4}:){2,7}[0-9A-Fa-f]{0,4}"),
+        ("IP", r"(?:%{IPV6}|%{IPV4})"),
+        (
+            "HOSTNAME",
+            r"\b(?:[0-9A-Za-z][0-9A-Za-z-]{0,62})(?:\.(?:[0-9A-Za-z][0-9A-Za-z-]{0,62}))*\.?\b",
+        ),
+        ("IPORHOST", r"(?:%{IP}|%{HOSTNAME})"),
+        ("PATH", r"(?:/[\w%!$@:.,+~-]*)+"),
+        ("URIPATH", r"(?:/[A-Za-z0-9$.+!*'(){},~:;=@#%&_-]*)+"),
+        ("URIPARAM", r"\?[A-Za-z0-9$.+!*'|(){},~@#%&/=:;_?\[\]<>-]*"),
+        ("URIPATHPARAM", r"%{URIPATH}(?:%{URIPARAM})?"),
+        (
+            "EMAILADDRESS",
+            r"[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\.[a-zA-Z]{2,}",
+        ),
+        (
+            "LOGLEVEL",
+            r"(?:[Tt]race|TRACE|[Dd]ebug|DEBUG|[Nn]otice|NOTICE|[Ii]nfo|INFO|[Ww]arn(?:ing)?|WARN(?:ING)?|[Ee]rr(?:or)?|ERR(?:OR)?|[Cc]rit(?:ical)?|CRIT(?:ICAL)?|[Ff]atal|FATAL|[Ss]evere|SEVERE|[Aa]lert|ALERT|[Ee]merg(?:ency)?|EMERG(?:ENCY)?)",
+        ),
+        (
+            "TIMESTAMP_ISO8601",
+            r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(?::\d{2}(?:[.,]\d+)?)?(?:Z|[+-]\d{2}:?\d{2})?",
+        ),
+        ("HTTPDATE", r"\d{2}/\w{3}/\d{4}:\d{2}:\d{2}:\d{2} [+-]\d{4}"),
+        ("SYSLOGTIMESTAMP", r"\w{3} +\d{1,2} \d{2}:\d{2}:\d{2}"),
+    ])
+});
+
+/// `%{NAME}`, `%{NAME:field}` or `%{NAME:field:type}`
+static GROK_REFERENCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"%\{(\w+)(?::(\w+))?(?::(\w+))?\}").expect("grok reference regex is valid")
+});
+
+/// Depth of grok patterns referencing one another
+const MAX_GROK_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pipeline {
+    pub processors: Vec<Processor>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Processor {
+    /// Removes the fields from events
+    Drop { fields: Vec<String> },
+    /// Removes all but the listed top level fields from events
+    Keep { fields: Vec<String> },
+    /// Renames fields, by their current name
+    Rename { fields: BTreeMap<String, String> },
+    /// Extracts the named groups of a regex matching a text field into fields of their name
+    Regex {
+        field: String,
+        #[serde(deserialize_with = "deserialize_regex")]
+        pattern: Pattern,
+    },
+    /// Extracts the named references of a grok pattern matching a text field, e.g.
+    /// `%{IP:client} %{WORD:method} %{NUMBER:bytes:int}`
+    Grok {
+        field: String,
+        #[serde(deserialize_with = "deserialize_grok")]
+        pattern: Pattern,
+    },
+    /// Casts fields to a type, values which can't be cast are set to null
+    Cast { fields: BTreeMap<String, CastType> },
+    /// Sets fields to static values
+    Enrich { fields: Map<String, Value> },
+    /// Drops the events matching the conditions
+    DropWhere { conditions: Conditions },
+    /// Routes the events matching the conditions to another stream
+    Route {
+        stream: String,
+        conditions: Conditions,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CastType {
+    String,
+    Int,
+    Float,
+    Boolean,
+}
+
+/// Compiled regex along with the pattern it was compiled from
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    regex: Regex,
+    /// types given to the fields of a grok pattern, cast once extracted
+    casts: Vec<(String, CastType)>,
+}
+
+impl Pattern {
+    pub fn regex(source: &str) -> Result<Self, String> {
+        let regex = Regex::new(source).map_err(|err| err.to_string())?;
+        if regex.capture_names().flatten().next().is_none() {
+            return Err(format!(
+                "Pattern {source:?} should capture at least one named group"
+            ));
+        }
+
+        Ok(Self {
+            source: source.to_owned(),
+            regex,
+            casts: vec![],
+        })
+    }
+
+    pub fn grok(source: &str) -> Result<Self, String> {
+        let mut casts = vec![];
+        let expanded = expand_grok(source, 0, &mut casts)?;
+        let mut pattern = Self::regex(&expanded)?;
+        pattern.source = source.to_owned();
+        pattern.casts = casts;
+
+        Ok(pattern)
+    }
+
+    /// Adds the fields captured from the text to the event, if the pattern matches it
+    fn extract(&self, text: &str, event: &mut Map<String, Value>) {
+        let Some(captures) = self.regex.captures(text) else {
+            return;
+        };
+        for name in self.regex.capture_names().flatten() {
+            if let Some(value) = captures.name(name) {
+                event.insert(name.to_owned(), Value::String(value.as_str().to_owned()));
+            }
+        }
+        for (field, cast_type) in &self.casts {
+            if let Some(value) = event.get_mut(field) {
+                *value = cast_type.cast(value.take());
+            }
+        }
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+    let source = String::deserialize(deserializer)?;
+    Pattern::regex(&source).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_grok<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+    let source = String::deserialize(deserializer)?;
+    Pattern::grok(&source).map_err(serde::de::Error::custom)
+}
+
+/// Replaces the references of a grok pattern with their regex, named ones as named groups
+fn expand_grok(
+    pattern: &str,
+    depth: usize,
+    casts: &mut Vec<(String, CastType)>,
+) -> Result<String, String> {
+    if depth > MAX_GROK_DEPTH {
+        return Err(format!(
+            "Grok pattern {pattern:?} nests more than {MAX_GROK_DEPTH} references"
+        ));
+    }
+
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut last = 0;
+    for captures in GROK_REFERENCE.captures_iter(pattern) {
+        let reference = captures.get(0).expect("whole match is always present");
+        let name = &captures[1];
+        let definition = GROK_PATTERNS
+            .get(name)
+            .ok_or_else(|| format!("Unknown grok pattern %{{{name}}}"))?;
+        let definition = expand_grok(definition, depth + 1, casts)?;
+
+        expanded.push_str(&pattern[last..reference.start()]);
+        match captures.get(2) {
+            Some(field) => {
+                expanded.push_str(&format!("(?P<{}>{definition})", field.as_str()));
+                if let Some(cast_type) = captures.get(3) {
+                    let cast_type = match cast_type.as_str() {
+                        "int" => CastType::Int,
+                        "float" => CastType::Float,
+                        other => return Err(format!("Unknown grok type {other:?}")),
+                    };
+                    casts.push((field.as_str().to_owned(), cast_type));
+                }
+            }
+            None => expanded.push_str(&format!("(?:{definition})")),
+        }
+        last = reference.end();
+    }
+    expanded.push_str(&pattern[last..]);
+
+    Ok(expanded)
+}
+
+impl CastType {
+    fn cast(&self, value: Value) -> Value {
+        match (self, value) {
+            (_, Value::Null) => Value::Null,
+            (Self::String, Value::String(text)) => Value::String(text),
+            (Self::String, value @ (Value::Array(_) | Value::Object(_))) => {
+                Value::String(value.to_string())
+            }
+            (Self::String, value) => Value::String(text_of(&value)),
+            (Self::Int, Value::Number(number)) => number
+                .as_i64()
+                .or_else(|| number.as_f64().map(|number| number.trunc() as i64))
+                .map_or(Value::Null, Value::from),
+            (Self::Int, Value::String(text)) => {
+                let text = text.trim();
+                text.parse::<i64>()
+                    .ok()
+                    .or_else(|| text.parse::<f64>().ok().map(|number| number.trunc() as i64))
+                    .map_or(Value::Null, Value::from)
+            }
+            (Self::Int, Value::Bool(flag)) => Value::from(flag as i64),
+            (Self::Float, Value::Number(number)) => number
+                .as_f64()
+                .and_then(Number::from_f64)
+                .map_or(Value::Null, Value::Number),
+            (Self::Float, Value::String(text)) => text
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map_or(Value::Null, Value::Number),
+            (Self::Float, Value::Bool(flag)) => Value::from(flag as u8 as f64),
+            (Self::Boolean, Value::Bool(flag)) => Value::Bool(flag),
+            (Self::Boolean, Value::Number(number)) => {
+                Value::Bool(number.as_f64().is_some_and(|number| number != 0.0))
+            }
+            (Self::Boolean, Value::String(text)) => match text.trim().to_lowercase().as_str() {
+                "true" | "1" | "yes" => Value::Bool(true),
+                "false" | "0" | "no" => Value::Bool(false),
+                _ => Value::Null,
+            },
+            _ => Value::Null,
+        }
+    }
+}
+
+/// Events left once the pipeline is run
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineOutput {
+    /// events to push into the stream
+    pub events: Vec<Value>,
+    /// events to push into other streams, by stream name
+    pub routed: BTreeMap<String, Vec<Value>>,
+    pub dropped: usize,
+}
+
+enum Verdict<'a> {
+    Keep,
+    Drop,
+    Route(&'a str),
+}
+
+impl Pipeline {
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// Runs the processors over the events, an object or an array of them. Values which
+    /// aren't objects are left as they are.
+    pub fn run(&self, json: Value) -> PipelineOutput {
+        let events = match json {
+            Value::Array(events) => events,
+            event => vec![event],
+        };
+
+        let mut output = PipelineOutput::default();
+        for event in events {
+            let Value::Object(mut event) = event else {
+                output.events.push(event);
+                continue;
+            };
+            match self.process(&mut event) {
+                Verdict::Keep => output.events.push(Value::Object(event)),
+                Verdict::Drop => output.dropped += 1,
+                Verdict::Route(stream) => output
+                    .routed
+                    .entry(stream.to_owned())
+                    .or_default()
+                    .push(Value::Object(event)),
+            }
+        }
+
+        output
+    }
+
+    fn process(&self, event: &mut Map<String, Value>) -> Verdict<'_> {
+        for processor in &self.processors {
+            match processor {
+                Processor::Drop { fields } => {
+                    for field in fields {
+                        remove(event, field);
+                    }
+                }
+                Processor::Keep { fields } => event.retain(|key, _| fields.contains(key)),
+                Processor::Rename { fields } => {
+                    for (from, to) in fields {
+                        if let Some(value) = remove(event, from) {
+                            event.insert(to.to_owned(), value);
+                        }
+                    }
+                }
+                Processor::Regex { field, pattern } | Processor::Grok { field, pattern } => {
+                    if let Some(text) = get(event, field).and_then(Value::as_str).map(str::to_owned)
+                    {
+                        pattern.extract(&text, event);
+                    }
+                }
+                Processor::Cast { fields } => {
+                    for (field, cast_type) in fields {
+                        if let Some(value) = get_mut(event, field) {
+                            *value = cast_type.cast(value.take());
+                        }
+                    }
+                }
+                Processor::Enrich { fields } => {
+                    event.extend(fields.clone());
+                }
+                Processor::DropWhere { conditions } => {
+                    if matches_conditions(conditions, event) {
+                        return Verdict::Drop;
+                    }
+                }
+                Processor::Route { stream, conditions } => {
+                    if matches_conditions(conditions, event) {
+                        return Verdict::Route(stream);
+                    }
+                }
+            }
+        }
+
+        Verdict::Keep
+    }
+
+    /// Checks the processors, `stream_name` being the stream the pipeline is set on
+    pub fn validate(&self, stream_name: &str) -> Result<(), PipelineError> {
+        for processor in &self.processors {
+            match processor {
+                Processor::Drop { fields } | Processor::Keep { fields } => {
+                    if fields.is_empty() || fields.iter().any(|field| field.trim().is_empty()) {
+                        return Err(PipelineError::EmptyField);
+                    }
+                }
+                Processor::Rename { fields } => {
+                    if fields
+                        .iter()
+                        .any(|(from, to)| from.trim().is_empty() || to.trim().is_empty())
+                    {
+                        return Err(PipelineError::EmptyField);
+                    }
+                }
+                Processor::Regex { field, .. } | Processor::Grok { field, .. } => {
+                    if field.trim().is_empty() {
+                        return Err(PipelineError::EmptyField);
+                    }
+                }
+                Processor::Cast { fields } => {
+                    if fields.keys().any(|field| field.trim().is_empty()) {
+                        return Err(PipelineError::EmptyField);
+                    }
+                }
+                Processor::Enrich { fields } => {
+                    if fields.keys().any(|field| field.trim().is_empty()) {
+                        return Err(PipelineError::EmptyField);
+                    }
+                }
+                Processor::DropWhere { conditions } => validate_conditions(conditions)?,
+                Processor::Route { stream, conditions } => {
+                    if stream == stream_name {
+                        return Err(PipelineError::RouteToItself(stream.to_owned()));
+                    }
+                    validate_conditions(conditions)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Streams the pipeline routes events to
+    pub fn routes(&self) -> impl Iterator<Item = &str> {
+        self.processors
+            .iter()
+            .filter_map(|processor| match processor {
+                Processor::Route { stream, .. } => Some(stream.as_str()),
+                _ => None,
+            })
+    }
+}
+
+fn validate_conditions(conditions: &Conditions) -> Result<(), PipelineError> {
+    if conditions.condition_config.is_empty() {
+        return Err(PipelineError::EmptyConditions);
+    }
+    if conditions
+        .condition_config
+        .iter()
+        .any(|condition| condition.column.trim().is_empty())
+    {
+        return Err(PipelineError::EmptyField);
+    }
+
+    Ok(())
+}
+
+fn get<'a>(event: &'a Map<String, Value>, field: &str) -> Option<&'a Value> {
+    if let Some(value) = event.get(field) {
+        return Some(value);
+    }
+    let (parent, rest) = field.split_once('.')?;
+    get(event.get(parent)?.as_object()?, rest)
+}
+
+fn get_mut<'a>(event: &'a mut Map<String, Value>, field: &str) -> Option<&'a mut Value> {
+    if event.contains_key(field) {
+        return event.get_mut(field);
+    }
+    let (parent, rest) = field.split_once('.')?;
+    get_mut(event.get_mut(parent)?.as_object_mut()?, rest)
+}
+
+fn remove(event: &mut Map<String, Value>, field: &str) -> Option<Value> {
+    if let Some(value) = event.remove(field) {
+        return Some(value);
+    }
+    let (parent, rest) = field.split_once('.')?;
+    remove(event.get_mut(parent)?.as_object_mut()?, rest)
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Conditions are combined with their operator, a single condition has none
+fn matches_conditions(conditions: &Conditions, event: &Map<String, Value>) -> bool {
+    let mut results = conditions
+        .condition_config
+        .iter()
+        .map(|condition| matches_condition(condition, event));
+    match &conditions.operator {
+        Some(LogicalOperator::Or) => results.any(|matched| matched),
+        Some(LogicalOperator::And) | None => results.all(|matched| matched),
+    }
+}
+
+fn matches_condition(condition: &ConditionConfig, event: &Map<String, Value>) -> bool {
+    let value = get(event, &condition.column).filter(|value| !value.is_null());
+    let expected = condition.value.as_deref().unwrap_or_default();
+    let Some(value) = value else {
+        return condition.operator == WhereConfigOperator::IsNull;
+    };
+    let text = text_of(value);
+
+    match condition.operator {
+        WhereConfigOperator::IsNull => false,
+        WhereConfigOperator::IsNotNull => true,
+        WhereConfigOperator::Equal => compare(value, expected) == Some(Ordering::Equal),
+        WhereConfigOperator::NotEqual => compare(value, expected) != Some(Ordering::Equal),
+        WhereConfigOperator::LessThan => compare(value, expected) == Some(Ordering::Less),
+        WhereConfigOperator::GreaterThan => compare(value, expected) == Some(Ordering::Greater),
+        WhereConfigOperator::LessThanOrEqual => matches!(
+            compare(value, expected),
+            Some(Ordering::Less | Ordering::Equal)
+        ),
+        WhereConfigOperator::GreaterThanOrEqual => matches!(
+            compare(value, expected),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        WhereConfigOperator::ILike => like(&text.to_lowercase(), &expected.to_lowercase()),
+        WhereConfigOperator::Contains => text.contains(expected),
+        WhereConfigOperator::BeginsWith => text.starts_with(expected),
+        WhereConfigOperator::EndsWith => text.ends_with(expected),
+        WhereConfigOperator::DoesNotContain => !text.contains(expected),
+        WhereConfigOperator::DoesNotBeginWith => !text.starts_with(expected),
+        WhereConfigOperator::DoesNotEndWith => !text.ends_with(expected),
+    }
+}
+
+/// Numbers are compared as numbers, anything else as text
+fn compare(value: &Value, expected: &str) -> Option<Ordering> {
+    match value {
+        Value::Number(number) => number
+            .as_f64()?
+            .partial_cmp(&expected.trim().parse::<f64>().ok()?),
+        value => Some(text_of(value).as_str().cmp(expected)),
+    }
+}
+
+/// SQL `LIKE` matching, `%` matches any text and `_` any character
+fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    // position of the last `%` in the pattern, and of the text it was matched against
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '_' || pattern[p] == text[t]) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '%' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '%')
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError {
+    #[error("Fields of pipeline processors should be named")]
+    EmptyField,
+    #[error("Conditions of pipeline processors should have at least one condition")]
+    EmptyConditions,
+    #[error("Pipeline can't route events to the stream {0} it is set on")]
+    RouteToItself(String),
+    #[error("Pipeline routes events to stream {0} which does not exist")]
+    UnknownRoute(String),
+    #[error("Pipeline can't route events to internal stream {0}")]
+    InternalRoute(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn pipeline(processors: Value) -> Pipeline {
+        serde_json::from_value(json!({ "processors": processors })).unwrap()
+    }
+
+    #[test]
+    fn transforms_fields() {
+        let pipeline = pipeline(json!([
+            {"type": "drop", "fields": ["password", "request.headers"]},
+            {"type": "rename", "fields": {"msg": "message", "request.path": "path"}},
+            {"type": "regex", "field": "message", "pattern": r"took (?P<duration>\d+)ms"},
+            {"type": "cast", "fields": {"duration": "int", "status": "int", "ok": "boolean"}},
+            {"type": "enrich", "fields": {"env": "prod"}},
+        ]));
+
+        let output = pipeline.run(json!({
+            "msg": "request took 42ms",
+            "password": "hunter2",
+            "status": "200",
+            "ok": "true",
+            "request": {"path": "/api", "headers": {"cookie": "x"}}
+        }));
+
+        assert_eq!(output.dropped, 0);
+        assert_eq!(
+            output.events,
+            vec![json!({
+                "message": "request took 42ms",
+                "duration": 42,
+                "status": 200,
+                "ok": true,
+                "env": "prod",
+                "path": "/api",
+                "request": {}
+            })]
+        );
+    }
+
+    #[test]
+    fn extracts_grok_patterns() {
+        let pipeline = pipeline(json!([
+            {
+                "type": "grok",
+                "field": "log",
+                "pattern": "%{IP:client} %{WORD:method} %{URIPATHPARAM:path} %{NUMBER:bytes:int}"
+            },
+            {"type": "keep", "fields": ["client", "method", "path", "bytes"]},
+        ]));
+
+        let output = pipeline.run(json!([
+            {"log": "10.0.0.1 GET /index.html?page=2 1532", "host": "web-1"},
+            {"log": "not an access log"},
+        ]));
+
+        assert_eq!(
+            output.events,
+            vec![
+                json!({
+                    "client": "10.0.0.1",
+                    "method": "GET",
+                    "path": "/index.html?page=2",
+                    "bytes": 1532
+                }),
+                json!({}),
+            ]
+        );
+        assert!(Pattern::grok("%{NOPE:field}").is_err());
+        assert!(Pattern::regex("no groups").is_err());
+    }
+
+    #[test]
+    fn drops_and_routes_on_conditions() {
+        let pipeline = pipeline(json!([
+            {
+                "type": "dropWhere",
+                "conditions": {
+                    "conditionConfig": [{"column": "level", "operator": "=", "value": "debug"}]
+                }
+            },
+            {
+                "type": "route",
+                "stream": "audit",
+                "conditions": {
+                    "operator": "or",
+                    "conditionConfig": [
+                        {"column": "kind", "operator": "ilike", "value": "AUDIT%"},
+                        {"column": "status", "operator": ">=", "value": "500"}
+                    ]
+                }
+            },
+        ]));
+
+        let output = pipeline.run(json!([
+            {"level": "debug", "kind": "audit_login"},
+            {"level": "info", "kind": "audit_login"},
+            {"level": "info", "kind": "request", "status": 503},
+            {"level": "info", "kind": "request", "status": 200},
+        ]));
+
+        assert_eq!(output.dropped, 1);
+        assert_eq!(
+            output.events,
+            vec![json!({"level": "info", "kind": "request", "status": 200})]
+        );
+        assert_eq!(output.routed["audit"].len(), 2);
+
+        assert!(pipeline.validate("app").is_ok());
+        assert!(matches!(
+            pipeline.validate("audit"),
+            Err(PipelineError::RouteToItself(_))
+        ));
+    }
+}
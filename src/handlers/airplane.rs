@@ -18,26 +18,42 @@
 
 use arrow_array::RecordBatch;
 use arrow_flight::PollInfo;
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::error::FlightError;
 use arrow_flight::flight_service_server::FlightServiceServer;
-use arrow_schema::ArrowError;
+use arrow_schema::{ArrowError, SchemaRef};
 use serde_json::json;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Instant;
 use tonic::codec::CompressionEncoding;
 use tracing::{error, info};
 
-use futures_util::{Future, TryFutureExt};
+use futures_util::{Future, StreamExt, TryFutureExt, TryStreamExt, future};
 
 use tonic::transport::{Identity, Server, ServerTlsConfig};
 use tonic_web::GrpcWebLayer;
 
+use crate::audit::{self, AuditQuery, AuditRequest, Outcome};
+use crate::event::format::LogSourceEntry;
+use crate::handlers::flight_sql;
 use crate::handlers::http::cluster::get_node_info;
+use crate::handlers::http::ingest::PostError;
+use crate::handlers::http::modal::utils::ingest_utils::{
+    get_custom_fields_from_metadata, push_record_batch, validate_stream_for_ingestion,
+};
 use crate::handlers::http::modal::{NodeMetadata, NodeType};
-use crate::handlers::http::query::into_query;
+use crate::handlers::http::query::{
+    Query as QueryJson, create_streams_for_distributed, into_query,
+};
 use crate::handlers::livetail::cross_origin_config;
+use crate::handlers::{AUTHORIZATION_KEY, TelemetryType};
 use crate::metrics::QUERY_EXECUTE_TIME;
+use crate::option::Mode;
 use crate::parseable::PARSEABLE;
 use crate::query::{QUERY_SESSION, execute, resolve_stream_names};
+use crate::storage::StreamType;
 use crate::utils::arrow::flight::{
     append_temporary_events, get_query_from_ticket, into_flight_data, run_do_get_rpc,
     send_to_ingester,
@@ -56,6 +72,8 @@ use tonic::{Request, Response, Status, Streaming};
 use crate::handlers::livetail::extract_session_key;
 use crate::rbac;
 use crate::rbac::Users;
+use crate::rbac::apply_access_policies;
+use crate::rbac::map::SessionKey;
 
 #[derive(Clone, Debug)]
 pub struct AirServiceImpl {}
@@ -70,13 +88,27 @@ impl FlightService for AirServiceImpl {
     type ListActionsStream = stream::BoxStream<'static, Result<ActionType, Status>>;
     type DoExchangeStream = stream::BoxStream<'static, Result<FlightData, Status>>;
 
+    /// Exchanges the credentials of the client for a bearer token, used by Flight SQL clients
+    /// which authenticate once per connection. Other clients can keep sending their credentials
+    /// with every request.
     async fn handshake(
         &self,
-        _request: Request<Streaming<HandshakeRequest>>,
+        request: Request<Streaming<HandshakeRequest>>,
     ) -> Result<Response<Self::HandshakeStream>, Status> {
-        Err(Status::unimplemented(
-            "handshake is disabled in favour of direct authentication and authorization",
-        ))
+        let token = flight_sql::handshake(request.metadata())?;
+        let authorization = format!("Bearer {token}")
+            .parse()
+            .map_err(|_| Status::internal("Failed to create bearer token"))?;
+
+        let output = stream::iter([Ok(HandshakeResponse {
+            protocol_version: 0,
+            payload: token.into(),
+        })]);
+        let mut response = Response::new(Box::pin(output) as Self::HandshakeStream);
+        response
+            .metadata_mut()
+            .insert(AUTHORIZATION_KEY, authorization);
+        Ok(response)
     }
 
     /// list_flights is an operation that allows a client
@@ -84,9 +116,23 @@ impl FlightService for AirServiceImpl {
     /// about available datasets or "flights" that the server can provide.
     async fn list_flights(
         &self,
-        _request: Request<Criteria>,
+        request: Request<Criteria>,
     ) -> Result<Response<Self::ListFlightsStream>, Status> {
-        Err(Status::unimplemented("Implement list_flights"))
+        let key = extract_session_key(request.metadata())
+            .map_err(|e| Status::unauthenticated(e.to_string()))?;
+
+        let flights = flight_sql::list_streams(&key)
+            .await?
+            .into_iter()
+            .map(|(stream_name, schema)| {
+                FlightInfo::new()
+                    .try_with_schema(&schema)
+                    .map(|info| info.with_descriptor(FlightDescriptor::new_path(vec![stream_name])))
+                    .map_err(|err| Status::internal(err.to_string()))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Response::new(Box::pin(stream::iter(flights))))
     }
 
     async fn poll_flight_info(
@@ -98,15 +144,29 @@ impl FlightService for AirServiceImpl {
 
     async fn get_flight_info(
         &self,
-        _request: Request<FlightDescriptor>,
+        request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
-        Err(Status::unimplemented("Implement get_flight_info"))
+        let Some(command) = flight_sql::decode_command(&request.get_ref().cmd) else {
+            return Err(Status::unimplemented(
+                "get_flight_info is only supported for Flight SQL commands",
+            ));
+        };
+        flight_sql::get_flight_info(command, request).await
     }
 
     async fn get_schema(
         &self,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<SchemaResult>, Status> {
+        if let Some(command) = flight_sql::decode_command(&request.get_ref().cmd) {
+            let (schema, _) = flight_sql::plan(command, request.metadata()).await?;
+            let options = IpcWriteOptions::default();
+            let schema_result = SchemaAsIpc::new(&schema, &options)
+                .try_into()
+                .map_err(|err: ArrowError| Status::internal(err.to_string()))?;
+            return Ok(Response::new(schema_result));
+        }
+
         let table_name = request.into_inner().path;
         let table_name = table_name[0].clone();
 
@@ -124,67 +184,60 @@ impl FlightService for AirServiceImpl {
     }
 
     async fn do_get(&self, req: Request<Ticket>) -> Result<Response<Self::DoGetStream>, Status> {
+        if let Some(command) = flight_sql::decode_command(&req.get_ref().ticket) {
+            return flight_sql::do_get(command, req).await;
+        }
+
         let key = extract_session_key(req.metadata())
             .map_err(|e| Status::unauthenticated(e.to_string()))?;
 
         let ticket =
             get_query_from_ticket(&req).map_err(|e| Status::invalid_argument(e.to_string()))?;
-        let streams = resolve_stream_names(&ticket.query).map_err(|e| {
-            error!("Failed to extract table names from SQL: {}", e);
-            Status::invalid_argument("Invalid SQL query syntax")
-        })?;
         info!("query requested to airplane: {:?}", ticket);
 
-        // get the query session_state
-        let session_state = QUERY_SESSION.state();
-
-        let time_range = TimeRange::parse_human_time(&ticket.start_time, &ticket.end_time)
-            .map_err(|e| Status::internal(e.to_string()))?;
-        // create a visitor to extract the table name
-
-        let stream_name = streams
-            .first()
-            .ok_or_else(|| Status::aborted("Malformed SQL Provided, Table Name Not Found"))?
-            .to_owned();
+        let (records, _) = run_query(key, &ticket).await?;
 
-        // map payload to query
-        let query = into_query(&ticket, &session_state, time_range)
-            .await
-            .map_err(|_| Status::internal("Failed to parse query"))?;
-
-        let event = if send_to_ingester(
-            query.time_range.start.timestamp_millis(),
-            query.time_range.end.timestamp_millis(),
-        ) {
-            let sql = format!("select * from \"{}\"", &stream_name);
-            let start_time = ticket.start_time.clone();
-            let end_time = ticket.end_time.clone();
-            let out_ticket = json!({
-                "query": sql,
-                "startTime": start_time,
-                "endTime": end_time
-            })
-            .to_string();
+        /*
+        * INFO: No returning the schema with the data.
+        * kept it in case it needs to be sent in the future.
 
-            let ingester_metadatas: Vec<NodeMetadata> = get_node_info(NodeType::Ingestor)
-                .await
-                .map_err(|err| Status::failed_precondition(err.to_string()))?;
-            let mut minute_result: Vec<RecordBatch> = vec![];
+        let schemas = results
+            .iter()
+            .map(|batch| batch.schema())
+            .map(|s| s.as_ref().clone())
+            .collect::<Vec<_>>();
+        let schema = Schema::try_merge(schemas).map_err(|err| Status::internal(err.to_string()))?;
+         */
+        // Taxi out airplane
+        // Airplane takes off 🛫
+        into_flight_data(records).map_err(|e| *e)
+    }
 
-            for im in ingester_metadatas {
-                if let Ok(mut batches) = run_do_get_rpc(im, out_ticket.clone()).await {
-                    minute_result.append(&mut batches);
-                }
-            }
-            let mr = minute_result.iter().collect::<Vec<_>>();
-            let event = append_temporary_events(&stream_name, mr).await?;
-            Some(event)
-        } else {
-            None
-        };
+    /// Ingests record batches into the stream named in the path of the flight descriptor,
+    /// the batches are pushed as is, without any conversion to JSON
+    async fn do_put(
+        &self,
+        req: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        let key = extract_session_key(req.metadata())
+            .map_err(|e| Status::unauthenticated(e.to_string()))?;
+        let p_custom_fields = get_custom_fields_from_metadata(&req);
+
+        let mut flight_data = req.into_inner();
+        let first = flight_data
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("Empty flight data stream"))?;
+        let stream_name = first
+            .flight_descriptor
+            .as_ref()
+            .and_then(|descriptor| descriptor.path.first())
+            .cloned()
+            .ok_or_else(|| {
+                Status::invalid_argument("Stream name is missing from the flight descriptor path")
+            })?;
 
-        // try authorize
-        match Users.authorize(key.clone(), rbac::role::Action::Query, None, None) {
+        match Users.authorize(key, rbac::role::Action::Ingest, Some(&stream_name), None) {
             rbac::Response::Authorized => (),
             rbac::Response::UnAuthorized => {
                 return Err(Status::permission_denied(
@@ -196,81 +249,68 @@ impl FlightService for AirServiceImpl {
             }
         }
 
-        let permissions = Users.get_permissions(&key);
-
-        user_auth_for_datasets(&permissions, &streams)
-            .await
-            .map_err(|_| {
-                Status::permission_denied("User Does not have permission to access this")
-            })?;
-        let time = Instant::now();
+        if matches!(PARSEABLE.options.mode, Mode::Query | Mode::Prism) {
+            return Err(Status::failed_precondition(
+                "ingestion is not allowed on this server",
+            ));
+        }
+        if PARSEABLE
+            .streams
+            .list_internal_streams()
+            .contains(&stream_name)
+        {
+            return Err(Status::invalid_argument(
+                PostError::InternalStream(stream_name).to_string(),
+            ));
+        }
 
-        let (records, _) = execute(query, false)
+        PARSEABLE
+            .create_stream_if_not_exists(
+                &stream_name,
+                StreamType::UserDefined,
+                None,
+                vec![LogSourceEntry::default()],
+                TelemetryType::Logs,
+            )
             .await
             .map_err(|err| Status::internal(err.to_string()))?;
-
-        let records = match records {
-            actix_web::Either::Left(rbs) => rbs,
-            actix_web::Either::Right(_) => {
-                return Err(Status::failed_precondition(
-                    "Expected batch results, got stream",
-                ));
-            }
-        };
-
-        /*
-        * INFO: No returning the schema with the data.
-        * kept it in case it needs to be sent in the future.
-
-        let schemas = results
-            .iter()
-            .map(|batch| batch.schema())
-            .map(|s| s.as_ref().clone())
-            .collect::<Vec<_>>();
-        let schema = Schema::try_merge(schemas).map_err(|err| Status::internal(err.to_string()))?;
-         */
-        // Taxi out airplane
-        let out = into_flight_data(records);
-
-        if event.is_some() {
-            // Clear staging of stream once airplane has taxied
-            PARSEABLE.get_or_create_stream(&stream_name).clear();
+        validate_stream_for_ingestion(&stream_name)
+            .map_err(|err| Status::failed_precondition(err.to_string()))?;
+
+        let mut batches = FlightRecordBatchStream::new_from_flight_data(
+            stream::once(future::ready(Ok(first)))
+                .chain(flight_data)
+                .map_err(FlightError::from),
+        );
+        let mut results = vec![];
+        while let Some(rb) = batches
+            .try_next()
+            .await
+            .map_err(|err| Status::invalid_argument(err.to_string()))?
+        {
+            let origin_size = rb.get_array_memory_size() as u64;
+            push_record_batch(&stream_name, rb, origin_size, &p_custom_fields)
+                .await
+                .map_err(|err| Status::invalid_argument(err.to_string()))?;
+            results.push(Ok(PutResult::default()));
         }
 
-        let time = time.elapsed().as_secs_f64();
-        QUERY_EXECUTE_TIME
-            .with_label_values(&[&format!("flight-query-{stream_name}")])
-            .observe(time);
-
-        // Airplane takes off 🛫
-        out.map_err(|e| *e)
-    }
-
-    async fn do_put(
-        &self,
-        _request: Request<Streaming<FlightData>>,
-    ) -> Result<Response<Self::DoPutStream>, Status> {
-        Err(Status::unimplemented(
-            "do_put not implemented because we are only using flight for querying",
-        ))
+        Ok(Response::new(Box::pin(stream::iter(results))))
     }
 
     async fn do_action(
         &self,
-        _request: Request<Action>,
+        request: Request<Action>,
     ) -> Result<Response<Self::DoActionStream>, Status> {
-        Err(Status::unimplemented(
-            "do_action not implemented because we are only using flight for querying",
-        ))
+        flight_sql::do_action(request).await
     }
 
     async fn list_actions(
         &self,
         _request: Request<Empty>,
     ) -> Result<Response<Self::ListActionsStream>, Status> {
-        Err(Status::unimplemented(
-            "list_actions not implemented because we are only using flight for querying",
-        ))
+        let actions = flight_sql::list_actions().into_iter().map(Ok);
+        Ok(Response::new(Box::pin(stream::iter(actions))))
     }
 
     async fn do_exchange(
@@ -283,6 +323,155 @@ impl FlightService for AirServiceImpl {
     }
 }
 
+/// Runs the query of the ticket, along with the records still in the staging of the ingestors,
+/// returns the records and the schema of the query
+pub(crate) async fn run_query(
+    key: SessionKey,
+    ticket: &QueryJson,
+) -> Result<(Vec<RecordBatch>, SchemaRef), Status> {
+    let audit = audit::is_audited(rbac::role::Action::Query).then(|| {
+        let stream = resolve_stream_names(&ticket.query)
+            .ok()
+            .and_then(|streams| streams.into_iter().next());
+        AuditRequest::new(
+            None,
+            rbac::role::Action::Query,
+            stream.as_deref(),
+            "POST",
+            "/arrow.flight.protocol.FlightService/DoGet",
+        )
+    });
+    let audit_query = AuditQuery {
+        sql: ticket.query.clone(),
+        bytes_scanned: Default::default(),
+        streaming: false,
+    };
+
+    let result = execute_query(key.clone(), ticket, &audit_query).await;
+    if let Some(audit) = audit {
+        let outcome = match &result {
+            Ok(_) => Outcome::Success,
+            Err(status) if status.code() == tonic::Code::PermissionDenied => Outcome::Denied,
+            Err(_) => Outcome::Failure,
+        };
+        let user_id = Users.get_userid_from_session(&key);
+        audit.record(user_id, None, outcome, Some(&audit_query));
+    }
+
+    result
+}
+
+async fn execute_query(
+    key: SessionKey,
+    ticket: &QueryJson,
+    audit_query: &AuditQuery,
+) -> Result<(Vec<RecordBatch>, SchemaRef), Status> {
+    let streams = resolve_stream_names(&ticket.query).map_err(|e| {
+        error!("Failed to extract table names from SQL: {}", e);
+        Status::invalid_argument("Invalid SQL query syntax")
+    })?;
+    create_streams_for_distributed(streams.clone())
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    // get the query session_state
+    let session_state = QUERY_SESSION.state();
+
+    let time_range = TimeRange::parse_human_time(&ticket.start_time, &ticket.end_time)
+        .map_err(|e| Status::internal(e.to_string()))?;
+    // create a visitor to extract the table name
+
+    let stream_name = streams
+        .first()
+        .ok_or_else(|| Status::aborted("Malformed SQL Provided, Table Name Not Found"))?
+        .to_owned();
+
+    // map payload to query
+    let mut query = into_query(ticket, &session_state, time_range, &key)
+        .await
+        .map_err(|_| Status::internal("Failed to parse query"))?;
+
+    let event = if send_to_ingester(
+        query.time_range.start.timestamp_millis(),
+        query.time_range.end.timestamp_millis(),
+    ) {
+        let sql = format!("select * from \"{}\"", &stream_name);
+        let start_time = ticket.start_time.clone();
+        let end_time = ticket.end_time.clone();
+        let out_ticket = json!({
+            "query": sql,
+            "startTime": start_time,
+            "endTime": end_time
+        })
+        .to_string();
+
+        let ingester_metadatas: Vec<NodeMetadata> = get_node_info(NodeType::Ingestor)
+            .await
+            .map_err(|err| Status::failed_precondition(err.to_string()))?;
+        let mut minute_result: Vec<RecordBatch> = vec![];
+
+        for im in ingester_metadatas {
+            if let Ok(mut batches) = run_do_get_rpc(im, out_ticket.clone()).await {
+                minute_result.append(&mut batches);
+            }
+        }
+        let mr = minute_result.iter().collect::<Vec<_>>();
+        let event = append_temporary_events(&stream_name, mr).await?;
+        Some(event)
+    } else {
+        None
+    };
+
+    // try authorize
+    match Users.authorize(key.clone(), rbac::role::Action::Query, None, None) {
+        rbac::Response::Authorized => (),
+        rbac::Response::UnAuthorized => {
+            return Err(Status::permission_denied(
+                "user is not authorized to access this resource",
+            ));
+        }
+        rbac::Response::ReloadRequired => {
+            return Err(Status::unauthenticated("reload required"));
+        }
+    }
+
+    let permissions = Users.get_permissions(&key);
+
+    user_auth_for_datasets(&permissions, &streams)
+        .await
+        .map_err(|_| Status::permission_denied("User Does not have permission to access this"))?;
+    query.raw_logical_plan = apply_access_policies(query.raw_logical_plan, &permissions)
+        .map_err(|err| Status::internal(err.to_string()))?;
+    query.bytes_scanned = audit_query.bytes_scanned.clone();
+    let time = Instant::now();
+
+    let schema = Arc::new(query.raw_logical_plan.schema().as_arrow().clone());
+    let (records, _) = execute(query, false)
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    let records = match records {
+        actix_web::Either::Left(rbs) => rbs,
+        actix_web::Either::Right(_) => {
+            return Err(Status::failed_precondition(
+                "Expected batch results, got stream",
+            ));
+        }
+    };
+
+    if event.is_some() {
+        // Clear staging of stream once airplane has taxied
+        PARSEABLE.get_or_create_stream(&stream_name).clear();
+    }
+
+    let time = time.elapsed().as_secs_f64();
+    QUERY_EXECUTE_TIME
+        .with_label_values(&[&format!("flight-query-{stream_name}")])
+        .observe(time);
+
+    Ok((records, schema))
+}
+
 pub fn server() -> impl Future<Output = Result<(), Box<dyn std::error::Error + Send>>> + Send {
     let mut addr: SocketAddr = PARSEABLE
         .options
@@ -0,0 +1,493 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Flight SQL command set served by the airplane service, so that standard clients
+//! (ADBC, JDBC Flight SQL driver, DBeaver...) can query Parseable directly.
+//!
+//! Statements are stateless, the handle of a statement or of a prepared statement
+//! is the JSON query ticket also accepted by `do_get`.
+
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::sql::metadata::{SqlInfoData, SqlInfoDataBuilder};
+use arrow_flight::sql::{
+    ActionClosePreparedStatementRequest, ActionCreatePreparedStatementRequest,
+    ActionCreatePreparedStatementResult, Any, Command, CommandPreparedStatementQuery,
+    CommandStatementQuery, ProstMessageExt, SqlInfo, TicketStatementQuery,
+};
+use arrow_flight::{
+    Action, ActionType, FlightDescriptor, FlightEndpoint, FlightInfo, IpcMessage, SchemaAsIpc,
+    Ticket,
+};
+use arrow_ipc::writer::IpcWriteOptions;
+use arrow_schema::{ArrowError, SchemaRef};
+use bytes::Bytes;
+use chrono::Utc;
+use futures::{TryStreamExt, stream};
+use once_cell::sync::Lazy;
+use prost::Message;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status};
+use ulid::Ulid;
+
+use crate::about;
+use crate::handlers::airplane::run_query;
+use crate::handlers::http::query::{Query as QueryJson, create_streams_for_distributed};
+use crate::handlers::livetail::extract_session_key;
+use crate::parseable::PARSEABLE;
+use crate::query::{QUERY_SESSION, resolve_stream_names};
+use crate::rbac::column_mask::{column_masks, mask_schema};
+use crate::rbac::map::SessionKey;
+use crate::rbac::role::Action as RbacAction;
+use crate::rbac::{self, EXPIRY_DURATION, Users, apply_access_policies};
+use crate::utils::arrow::flight::DoGetStream;
+use crate::utils::user_auth_for_datasets;
+
+pub const CREATE_PREPARED_STATEMENT: &str = "CreatePreparedStatement";
+pub const CLOSE_PREPARED_STATEMENT: &str = "ClosePreparedStatement";
+
+/// Flight SQL has no notion of a time range, clients may set it with these headers
+pub const START_TIME_KEY: &str = "x-p-start-time";
+pub const END_TIME_KEY: &str = "x-p-end-time";
+/// Statements without a time range query the last hour of the streams rather than all of it
+const DEFAULT_TIME_RANGE: &str = "1h";
+
+const TABLE_TYPE: &str = "TABLE";
+
+static SQL_INFO: Lazy<SqlInfoData> = Lazy::new(|| {
+    let mut builder = SqlInfoDataBuilder::new();
+    builder.append(SqlInfo::FlightSqlServerName, "Parseable");
+    builder.append(
+        SqlInfo::FlightSqlServerVersion,
+        about::current().released_version.to_string(),
+    );
+    builder.append(SqlInfo::FlightSqlServerReadOnly, true);
+    builder.append(SqlInfo::FlightSqlServerSql, true);
+    builder.append(SqlInfo::FlightSqlServerSubstrait, false);
+    builder.append(SqlInfo::SqlIdentifierQuoteChar, "\"");
+    builder.build().expect("sql info is valid")
+});
+
+/// Decodes a Flight SQL command, `None` when the bytes hold anything else, e.g. a JSON ticket
+pub fn decode_command(bytes: &[u8]) -> Option<Command> {
+    let any = Any::decode(bytes).ok()?;
+    match Command::try_from(any).ok()? {
+        Command::Unknown(_) => None,
+        command => Some(command),
+    }
+}
+
+/// Authenticates the credentials of the handshake and returns a session token,
+/// accepted as a bearer token by `extract_session_key`
+pub fn handshake(metadata: &MetadataMap) -> Result<String, Status> {
+    let key = extract_session_key(metadata).map_err(|e| Status::unauthenticated(e.to_string()))?;
+    authorize(key.clone(), RbacAction::Login)?;
+
+    let user = Users
+        .get_userid_from_session(&key)
+        .and_then(|userid| Users.get_user(&userid))
+        .ok_or_else(|| Status::unauthenticated("user not found"))?;
+    let session = Ulid::new();
+    Users.new_session(&user, SessionKey::SessionId(session), EXPIRY_DURATION);
+
+    Ok(session.to_string())
+}
+
+/// Schema of the result of the command, along with the ticket to fetch the result with
+pub async fn plan(command: Command, metadata: &MetadataMap) -> Result<(SchemaRef, Bytes), Status> {
+    let key = extract_session_key(metadata).map_err(|e| Status::unauthenticated(e.to_string()))?;
+    authorize(key.clone(), RbacAction::Query)?;
+
+    match command {
+        Command::CommandStatementQuery(CommandStatementQuery { query, .. }) => {
+            let query = statement_query(query, metadata)?;
+            let schema = statement_schema(&key, &query).await?;
+            let ticket = TicketStatementQuery {
+                statement_handle: statement_handle(&query)?,
+            };
+            Ok((schema, encode(&ticket)))
+        }
+        Command::CommandPreparedStatementQuery(CommandPreparedStatementQuery {
+            prepared_statement_handle,
+        }) => {
+            let query = from_handle(&prepared_statement_handle)?;
+            let schema = statement_schema(&key, &query).await?;
+            let ticket = TicketStatementQuery {
+                statement_handle: prepared_statement_handle,
+            };
+            Ok((schema, encode(&ticket)))
+        }
+        Command::CommandGetCatalogs(command) => {
+            let ticket = encode(&command);
+            Ok((command.into_builder().schema(), ticket))
+        }
+        Command::CommandGetDbSchemas(command) => {
+            let ticket = encode(&command);
+            Ok((command.into_builder().schema(), ticket))
+        }
+        Command::CommandGetTables(command) => {
+            let ticket = encode(&command);
+            Ok((command.into_builder().schema(), ticket))
+        }
+        Command::CommandGetSqlInfo(command) => {
+            let ticket = encode(&command);
+            Ok((command.into_builder(&SQL_INFO).schema(), ticket))
+        }
+        command => Err(Status::unimplemented(format!(
+            "Flight SQL command {} is not supported",
+            command.type_url()
+        ))),
+    }
+}
+
+pub async fn get_flight_info(
+    command: Command,
+    request: Request<FlightDescriptor>,
+) -> Result<Response<FlightInfo>, Status> {
+    let (schema, ticket) = plan(command, request.metadata()).await?;
+    let info = FlightInfo::new()
+        .try_with_schema(&schema)
+        .map_err(arrow_error)?
+        .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(ticket)))
+        .with_descriptor(request.into_inner());
+
+    Ok(Response::new(info))
+}
+
+pub async fn do_get(
+    command: Command,
+    request: Request<Ticket>,
+) -> Result<Response<DoGetStream>, Status> {
+    let key = extract_session_key(request.metadata())
+        .map_err(|e| Status::unauthenticated(e.to_string()))?;
+    authorize(key.clone(), RbacAction::Query)?;
+    let (catalog_name, schema_name) = catalog_and_schema();
+
+    let (schema, records) = match command {
+        Command::TicketStatementQuery(TicketStatementQuery { statement_handle }) => {
+            let query = from_handle(&statement_handle)?;
+            let (records, schema) = run_query(key, &query).await?;
+            (schema, records)
+        }
+        Command::CommandGetCatalogs(command) => {
+            let mut builder = command.into_builder();
+            builder.append(catalog_name);
+            let rb = builder.build().map_err(arrow_error)?;
+            (rb.schema(), vec![rb])
+        }
+        Command::CommandGetDbSchemas(command) => {
+            let mut builder = command.into_builder();
+            builder.append(catalog_name, schema_name);
+            let rb = builder.build().map_err(arrow_error)?;
+            (rb.schema(), vec![rb])
+        }
+        Command::CommandGetTables(command) => {
+            let mut builder = command.into_builder();
+            for (stream_name, schema) in list_streams(&key).await? {
+                builder
+                    .append(
+                        &catalog_name,
+                        &schema_name,
+                        stream_name,
+                        TABLE_TYPE,
+                        &schema,
+                    )
+                    .map_err(arrow_error)?;
+            }
+            let rb = builder.build().map_err(arrow_error)?;
+            (rb.schema(), vec![rb])
+        }
+        Command::CommandGetSqlInfo(command) => {
+            let rb = command
+                .into_builder(&SQL_INFO)
+                .build()
+                .map_err(arrow_error)?;
+            (rb.schema(), vec![rb])
+        }
+        command => {
+            return Err(Status::unimplemented(format!(
+                "Flight SQL command {} is not supported",
+                command.type_url()
+            )));
+        }
+    };
+
+    // the schema of the batches may be more precise than the one of the plan
+    let schema = records.first().map_or(schema, RecordBatch::schema);
+    let output = FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(stream::iter(records.into_iter().map(Ok)))
+        .map_err(|err| Status::internal(err.to_string()));
+
+    Ok(Response::new(Box::pin(output) as DoGetStream))
+}
+
+pub async fn do_action(
+    request: Request<Action>,
+) -> Result<Response<stream::BoxStream<'static, Result<arrow_flight::Result, Status>>>, Status> {
+    let key = extract_session_key(request.metadata())
+        .map_err(|e| Status::unauthenticated(e.to_string()))?;
+    authorize(key.clone(), RbacAction::Query)?;
+
+    let action = request.get_ref();
+    match action.r#type.as_str() {
+        CREATE_PREPARED_STATEMENT => {
+            let ActionCreatePreparedStatementRequest { query, .. } = unpack(&action.body)?;
+            let query = statement_query(query, request.metadata())?;
+            let schema = statement_schema(&key, &query).await?;
+            let IpcMessage(dataset_schema) = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+                .try_into()
+                .map_err(arrow_error)?;
+
+            let result = ActionCreatePreparedStatementResult {
+                prepared_statement_handle: statement_handle(&query)?,
+                dataset_schema,
+                parameter_schema: Bytes::new(),
+            };
+            let output = stream::iter([Ok(arrow_flight::Result {
+                body: encode(&result),
+            })]);
+            Ok(Response::new(Box::pin(output)))
+        }
+        CLOSE_PREPARED_STATEMENT => {
+            // handles are stateless, there is nothing to release
+            let _: ActionClosePreparedStatementRequest = unpack(&action.body)?;
+            Ok(Response::new(Box::pin(stream::empty())))
+        }
+        action => Err(Status::unimplemented(format!(
+            "action {action} is not supported"
+        ))),
+    }
+}
+
+pub fn list_actions() -> Vec<ActionType> {
+    vec![
+        ActionType {
+            r#type: CREATE_PREPARED_STATEMENT.to_owned(),
+            description: "Creates a reusable prepared statement resource on the server.\n\
+                Request Message: ActionCreatePreparedStatementRequest\n\
+                Response Message: ActionCreatePreparedStatementResult"
+                .to_owned(),
+        },
+        ActionType {
+            r#type: CLOSE_PREPARED_STATEMENT.to_owned(),
+            description: "Closes a reusable prepared statement resource on the server.\n\
+                Request Message: ActionClosePreparedStatementRequest\n\
+                Response Message: N/A"
+                .to_owned(),
+        },
+    ]
+}
+
+/// Streams the user is allowed to list, along with their schema
+pub async fn list_streams(key: &SessionKey) -> Result<Vec<(String, SchemaRef)>, Status> {
+    let stream_names = PARSEABLE
+        .metastore
+        .list_streams()
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    let permissions = Users.get_permissions(key);
+    let mut streams = Vec::new();
+    for stream_name in stream_names {
+        if Users.authorize(
+            key.clone(),
+            RbacAction::ListStream,
+            Some(&stream_name),
+            None,
+        ) != rbac::Response::Authorized
+            || !PARSEABLE.check_or_load_stream(&stream_name).await
+        {
+            continue;
+        }
+        if let Ok(stream) = PARSEABLE.get_stream(&stream_name) {
+            let masks = column_masks(&permissions, &stream_name);
+            let schema = Arc::new(mask_schema(&stream.get_schema(), &masks));
+            streams.push((stream_name, schema));
+        }
+    }
+    streams.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(streams)
+}
+
+fn authorize(key: SessionKey, action: RbacAction) -> Result<(), Status> {
+    match Users.authorize(key, action, None, None) {
+        rbac::Response::Authorized => Ok(()),
+        rbac::Response::UnAuthorized => Err(Status::permission_denied(
+            "user is not authorized to access this resource",
+        )),
+        rbac::Response::ReloadRequired => Err(Status::unauthenticated("reload required")),
+    }
+}
+
+/// Query of a statement, over the time range set in the request headers
+fn statement_query(query: String, metadata: &MetadataMap) -> Result<QueryJson, Status> {
+    let header = |key| {
+        metadata
+            .get(key)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+
+    let (start_time, end_time) = match (header(START_TIME_KEY), header(END_TIME_KEY)) {
+        (Some(start_time), Some(end_time)) => (start_time, end_time),
+        // relative start times, e.g. `1h`, go back from now
+        (Some(start_time), None) if humantime::parse_duration(&start_time).is_ok() => {
+            (start_time, "now".to_owned())
+        }
+        (Some(start_time), None) => (start_time, Utc::now().to_rfc3339()),
+        (None, None) => (DEFAULT_TIME_RANGE.to_owned(), "now".to_owned()),
+        (None, Some(_)) => {
+            return Err(Status::invalid_argument(format!(
+                "{START_TIME_KEY} has to be set along with {END_TIME_KEY}"
+            )));
+        }
+    };
+
+    Ok(QueryJson {
+        query,
+        start_time,
+        end_time,
+        send_null: false,
+        fields: false,
+        streaming: false,
+        filter_tags: None,
+        filter_id: None,
+    })
+}
+
+/// Plans the statement to get the schema of its result, after checking that
+/// the user can query all the streams of the statement
+async fn statement_schema(key: &SessionKey, query: &QueryJson) -> Result<SchemaRef, Status> {
+    let streams = resolve_stream_names(&query.query)
+        .map_err(|_| Status::invalid_argument("Invalid SQL query syntax"))?;
+    create_streams_for_distributed(streams.clone())
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?;
+    let permissions = Users.get_permissions(key);
+    user_auth_for_datasets(&permissions, &streams)
+        .await
+        .map_err(|_| Status::permission_denied("User Does not have permission to access this"))?;
+
+    let plan = QUERY_SESSION
+        .state()
+        .create_logical_plan(&query.query)
+        .await
+        .map_err(|err| Status::invalid_argument(err.to_string()))?;
+    let plan = apply_access_policies(plan, &permissions)
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    Ok(Arc::new(plan.schema().as_arrow().clone()))
+}
+
+fn statement_handle(query: &QueryJson) -> Result<Bytes, Status> {
+    serde_json::to_vec(query)
+        .map(Bytes::from)
+        .map_err(|err| Status::internal(err.to_string()))
+}
+
+fn from_handle(handle: &[u8]) -> Result<QueryJson, Status> {
+    serde_json::from_slice(handle).map_err(|_| Status::invalid_argument("Invalid statement handle"))
+}
+
+fn catalog_and_schema() -> (String, String) {
+    let state = QUERY_SESSION.state();
+    let catalog = &state.config_options().catalog;
+    (
+        catalog.default_catalog.clone(),
+        catalog.default_schema.clone(),
+    )
+}
+
+fn encode<M: ProstMessageExt>(message: &M) -> Bytes {
+    message.as_any().encode_to_vec().into()
+}
+
+fn unpack<M: ProstMessageExt>(body: &[u8]) -> Result<M, Status> {
+    Any::decode(body)
+        .map_err(|err| Status::invalid_argument(err.to_string()))?
+        .unpack()
+        .map_err(arrow_error)?
+        .ok_or_else(|| Status::invalid_argument(format!("Expected {}", M::type_url())))
+}
+
+fn arrow_error(err: ArrowError) -> Status {
+    Status::internal(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_flight::sql::CommandGetTables;
+
+    use super::*;
+
+    #[test]
+    fn decodes_flight_sql_commands_only() {
+        let command = CommandStatementQuery {
+            query: "select * from app".to_owned(),
+            transaction_id: None,
+        };
+        assert!(matches!(
+            decode_command(&encode(&command)),
+            Some(Command::CommandStatementQuery(_))
+        ));
+
+        let command = CommandGetTables::default();
+        assert!(matches!(
+            decode_command(&encode(&command)),
+            Some(Command::CommandGetTables(_))
+        ));
+
+        let ticket = br#"{"query":"select * from app","startTime":"1h","endTime":"now"}"#;
+        assert!(decode_command(ticket).is_none());
+        assert!(decode_command(&[]).is_none());
+    }
+
+    #[test]
+    fn statement_handle_roundtrip() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(START_TIME_KEY, "1h".parse().unwrap());
+        metadata.insert(END_TIME_KEY, "now".parse().unwrap());
+
+        let query = statement_query("select * from app".to_owned(), &metadata).unwrap();
+        let query = from_handle(&statement_handle(&query).unwrap()).unwrap();
+        assert_eq!(query.query, "select * from app");
+        assert_eq!(query.start_time, "1h");
+        assert_eq!(query.end_time, "now");
+    }
+
+    #[test]
+    fn bounds_statements_without_time_range() {
+        let query = statement_query("select * from app".to_owned(), &MetadataMap::new()).unwrap();
+        assert_eq!(query.start_time, DEFAULT_TIME_RANGE);
+        assert_eq!(query.end_time, "now");
+
+        let mut metadata = MetadataMap::new();
+        metadata.insert(START_TIME_KEY, "30m".parse().unwrap());
+        let query = statement_query("select * from app".to_owned(), &metadata).unwrap();
+        assert_eq!(query.start_time, "30m");
+        assert_eq!(query.end_time, "now");
+
+        let mut metadata = MetadataMap::new();
+        metadata.insert(END_TIME_KEY, "2024-01-01T00:00:00Z".parse().unwrap());
+        assert!(statement_query("select * from app".to_owned(), &metadata).is_err());
+    }
+}
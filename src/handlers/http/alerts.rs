@@ -24,11 +24,12 @@ use crate::{
         alert_enums::{AlertType, NotificationState},
         alert_structs::{AlertConfig, AlertRequest, AlertStateEntry, NotificationStateRequest},
         alert_traits::AlertTrait,
-        alert_types::ThresholdAlert,
+        alert_types::{AnomalyAlert, ForecastAlert, ThresholdAlert},
         target::Retry,
     },
     metastore::metastore_traits::MetastoreObject,
     parseable::PARSEABLE,
+    rbac::Users,
     utils::{actix::extract_session_key_from_req, user_auth_for_query},
 };
 use actix_web::{
@@ -248,7 +249,10 @@ pub async fn post(
     req: HttpRequest,
     Json(alert): Json<AlertRequest>,
 ) -> Result<impl Responder, AlertError> {
+    let session_key = extract_session_key_from_req(&req)?;
     let mut alert: AlertConfig = alert.into().await?;
+    // evaluations only read the rows the creator of the alert can query
+    alert.created_by = Users.get_userid_from_session(&session_key);
 
     if alert.notification_config.interval > alert.get_eval_frequency() {
         return Err(AlertError::ValidationFailure(
@@ -279,16 +283,20 @@ pub async fn post(
     alert.notification_config.times = Retry::Finite(times);
 
     let threshold_alert;
+    let anomaly_alert;
+    let forecast_alert;
     let alert: &dyn AlertTrait = match &alert.alert_type {
         AlertType::Threshold => {
             threshold_alert = ThresholdAlert::from(alert);
             &threshold_alert
         }
         AlertType::Anomaly(_) => {
-            return Err(AlertError::NotPresentInOSS("anomaly"));
+            anomaly_alert = AnomalyAlert::try_from(alert)?;
+            &anomaly_alert
         }
         AlertType::Forecast(_) => {
-            return Err(AlertError::NotPresentInOSS("forecast"));
+            forecast_alert = ForecastAlert::try_from(alert)?;
+            &forecast_alert
         }
     };
 
@@ -301,8 +309,6 @@ pub async fn post(
 
     // validate the incoming alert query
     // does the user have access to these tables or not?
-    let session_key = extract_session_key_from_req(&req)?;
-
     alert.validate(&session_key).await?;
 
     // update persistent storage first
@@ -534,7 +540,9 @@ pub async fn modify_alert(
     user_auth_for_query(&session_key, alert.get_query()).await?;
 
     let mut new_config = alert_request.into().await?;
-    if &new_config.alert_type != alert.get_alert_type() {
+    if std::mem::discriminant(&new_config.alert_type)
+        != std::mem::discriminant(alert.get_alert_type())
+    {
         return Err(AlertError::InvalidAlertModifyRequest);
     }
 
@@ -552,6 +560,7 @@ pub async fn modify_alert(
 
     // Prepare the updated config
     let mut old_config = alert.to_alert_config();
+    old_config.alert_type = new_config.alert_type.clone();
     old_config.threshold_config = new_config.threshold_config;
     old_config.datasets = new_config.datasets;
     old_config.eval_config = new_config.eval_config;
@@ -561,14 +570,15 @@ pub async fn modify_alert(
     old_config.tags = new_config.tags;
     old_config.targets = new_config.targets;
     old_config.title = new_config.title;
+    old_config.created_by = Users.get_userid_from_session(&session_key);
 
     let new_alert: Box<dyn AlertTrait> = match &new_config.alert_type {
         AlertType::Threshold => Box::new(ThresholdAlert::from(old_config)) as Box<dyn AlertTrait>,
         AlertType::Anomaly(_) => {
-            return Err(AlertError::NotPresentInOSS("anomaly"));
+            Box::new(AnomalyAlert::try_from(old_config)?) as Box<dyn AlertTrait>
         }
         AlertType::Forecast(_) => {
-            return Err(AlertError::NotPresentInOSS("forecast"));
+            Box::new(ForecastAlert::try_from(old_config)?) as Box<dyn AlertTrait>
         }
     };
 
@@ -0,0 +1,210 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashSet;
+
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, ResponseError,
+    http::{StatusCode, header::ContentType},
+    web::{self, Json, Path},
+};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use ulid::Ulid;
+
+use crate::{
+    handlers::http::{cluster::sync_api_key_with_ingestors, rbac::RBACError},
+    metastore::MetastoreError,
+    option::Mode,
+    parseable::PARSEABLE,
+    rbac::{
+        Users,
+        api_key::{API_KEYS, ApiKey, ApiKeyInfo},
+        map::roles,
+    },
+    utils::get_user_from_request,
+};
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKey {
+    pub name: String,
+    pub roles: HashSet<String>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Created key along with its token, which can't be retrieved afterwards
+#[derive(Debug, serde::Serialize)]
+struct CreatedApiKey {
+    #[serde(flatten)]
+    key: ApiKeyInfo,
+    token: String,
+}
+
+// POST /apikeys
+pub async fn create(
+    req: HttpRequest,
+    Json(request): Json<CreateApiKey>,
+) -> Result<impl Responder, ApiKeyError> {
+    let created_by = get_user_from_request(&req)?;
+    let CreateApiKey {
+        name,
+        roles: key_roles,
+        expires_at,
+    } = request;
+
+    let name = name.trim().to_owned();
+    if name.is_empty() {
+        return Err(ApiKeyError::Invalid(
+            "API key name can't be empty".to_owned(),
+        ));
+    }
+    if key_roles.is_empty() {
+        return Err(ApiKeyError::Invalid(
+            "API key should be bound to at least one role".to_owned(),
+        ));
+    }
+    let non_existent_roles = key_roles
+        .iter()
+        .filter(|role| !roles().contains_key(*role))
+        .cloned()
+        .collect_vec();
+    if !non_existent_roles.is_empty() {
+        return Err(RBACError::RolesDoNotExist(non_existent_roles).into());
+    }
+    if expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(ApiKeyError::Invalid(
+            "API key expiry should be in the future".to_owned(),
+        ));
+    }
+    if API_KEYS.name_in_use(&name) {
+        return Err(ApiKeyError::Exists(name));
+    }
+
+    let (key, token) = ApiKey::new(name, key_roles, expires_at, created_by);
+    PARSEABLE.metastore.put_api_key(&key).await?;
+    Users.put_api_key(key.clone());
+
+    if PARSEABLE.options.mode == Mode::Query {
+        sync_api_key_with_ingestors(&key).await?;
+    }
+
+    Ok((
+        web::Json(CreatedApiKey {
+            key: ApiKeyInfo::from(&key),
+            token,
+        }),
+        StatusCode::CREATED,
+    ))
+}
+
+// GET /apikeys
+pub async fn list() -> Result<impl Responder, ApiKeyError> {
+    let keys = API_KEYS
+        .list()
+        .iter()
+        .sorted_by_key(|key| key.created_at)
+        .map(ApiKeyInfo::from)
+        .collect_vec();
+
+    Ok(web::Json(keys))
+}
+
+// GET /apikeys/{key_id}
+pub async fn get(key_id: Path<Ulid>) -> Result<impl Responder, ApiKeyError> {
+    let key_id = key_id.into_inner();
+    let key = API_KEYS.get(&key_id).ok_or(ApiKeyError::NotFound(key_id))?;
+
+    Ok(web::Json(ApiKeyInfo::from(&key)))
+}
+
+// DELETE /apikeys/{key_id}
+// Revokes the key, which is kept so that it still shows up when listing
+pub async fn revoke(key_id: Path<Ulid>) -> Result<impl Responder, ApiKeyError> {
+    let key_id = key_id.into_inner();
+    let mut key = API_KEYS.get(&key_id).ok_or(ApiKeyError::NotFound(key_id))?;
+
+    if key.revoked_at.is_none() {
+        key.revoked_at = Some(Utc::now());
+        PARSEABLE.metastore.put_api_key(&key).await?;
+        Users.put_api_key(key.clone());
+
+        if PARSEABLE.options.mode == Mode::Query {
+            sync_api_key_with_ingestors(&key).await?;
+        }
+    }
+
+    Ok(web::Json(ApiKeyInfo::from(&key)))
+}
+
+// PUT /apikeys/{key_id}/sync
+// Puts a key created or revoked on the querier in memory of the ingestor
+pub async fn sync(
+    key_id: Path<Ulid>,
+    Json(key): Json<ApiKey>,
+) -> Result<HttpResponse, ApiKeyError> {
+    if key.id != key_id.into_inner() {
+        return Err(ApiKeyError::Invalid(
+            "API key id doesn't match the path".to_owned(),
+        ));
+    }
+    Users.put_api_key(key);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiKeyError {
+    #[error("API key {0} does not exist")]
+    NotFound(Ulid),
+    #[error("An active API key named {0} already exists")]
+    Exists(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0}")]
+    Rbac(#[from] RBACError),
+    #[error(transparent)]
+    MetastoreError(#[from] MetastoreError),
+}
+
+impl ResponseError for ApiKeyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Exists(_) => StatusCode::CONFLICT,
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::Rbac(e) => e.status_code(),
+            Self::MetastoreError(e) => e.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            Self::Rbac(e) => e.error_response(),
+            Self::MetastoreError(metastore_error) => {
+                actix_web::HttpResponse::build(self.status_code())
+                    .insert_header(ContentType::json())
+                    .json(metastore_error.to_detail())
+            }
+            _ => actix_web::HttpResponse::build(self.status_code())
+                .insert_header(ContentType::plaintext())
+                .body(self.to_string()),
+        }
+    }
+}
@@ -41,13 +41,16 @@ use url::Url;
 use utils::{IngestionStats, QueriedStats, StorageStats, check_liveness, to_url_string};
 
 use crate::INTRA_CLUSTER_CLIENT;
+use crate::event::pipeline::Pipeline;
 use crate::handlers::http::query::{Query, QueryError, TIME_ELAPSED_HEADER};
 use crate::metrics::prom_utils::Metrics;
 use crate::option::Mode;
 use crate::parseable::PARSEABLE;
+use crate::rbac::api_key::ApiKey;
 use crate::rbac::role::model::DefaultPrivilege;
 use crate::rbac::user::User;
 use crate::stats::Stats;
+use crate::storage::bloom_filter::BloomFilterConfig;
 use crate::storage::{ObjectStorageError, ObjectStoreFormat};
 
 use super::base_path_without_preceding_slash;
@@ -422,6 +425,94 @@ pub async fn sync_streams_with_ingestors(
     ).await
 }
 
+// forward the bloom filter configuration of a stream to all ingestors, they write the parquet files
+pub async fn sync_bloom_filter_with_ingestors(
+    stream_name: &str,
+    bloom_filter: &BloomFilterConfig,
+) -> Result<(), StreamError> {
+    let stream_name = stream_name.to_string();
+    let bloom_filter = bloom_filter.clone();
+
+    for_each_live_ingestor(move |ingestor| {
+        let url = format!(
+            "{}{}/logstream/{}/bloomfilter",
+            ingestor.domain_name,
+            base_path_without_preceding_slash(),
+            stream_name
+        );
+        let bloom_filter = bloom_filter.clone();
+        async move {
+            let res = INTRA_CLUSTER_CLIENT
+                .put(url)
+                .header(header::AUTHORIZATION, &ingestor.token)
+                .json(&bloom_filter)
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(
+                        "Fatal: failed to forward bloom filter configuration to ingestor: {}\n Error: {:?}",
+                        ingestor.domain_name, err
+                    );
+                    StreamError::Network(err)
+                })?;
+
+            if !res.status().is_success() {
+                error!(
+                    "failed to forward bloom filter configuration to ingestor: {}\nResponse Returned: {:?}",
+                    ingestor.domain_name,
+                    res.text().await
+                );
+            }
+            Ok(())
+        }
+    })
+    .await
+}
+
+// forward the ingest pipeline of a stream to all ingestors, they run it over the events they receive
+pub async fn sync_pipeline_with_ingestors(
+    stream_name: &str,
+    pipeline: &Pipeline,
+) -> Result<(), StreamError> {
+    let stream_name = stream_name.to_string();
+    let pipeline = pipeline.clone();
+
+    for_each_live_ingestor(move |ingestor| {
+        let url = format!(
+            "{}{}/logstream/{}/pipeline",
+            ingestor.domain_name,
+            base_path_without_preceding_slash(),
+            stream_name
+        );
+        let pipeline = pipeline.clone();
+        async move {
+            let res = INTRA_CLUSTER_CLIENT
+                .put(url)
+                .header(header::AUTHORIZATION, &ingestor.token)
+                .json(&pipeline)
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(
+                        "Fatal: failed to forward ingest pipeline to ingestor: {}\n Error: {:?}",
+                        ingestor.domain_name, err
+                    );
+                    StreamError::Network(err)
+                })?;
+
+            if !res.status().is_success() {
+                error!(
+                    "failed to forward ingest pipeline to ingestor: {}\nResponse Returned: {:?}",
+                    ingestor.domain_name,
+                    res.text().await
+                );
+            }
+            Ok(())
+        }
+    })
+    .await
+}
+
 // forward the demo data request to one of the live ingestor
 pub async fn get_demo_data_from_ingestor(action: &str) -> Result<(), PostError> {
     let ingestor_infos: Vec<NodeMetadata> =
@@ -638,6 +729,54 @@ pub async fn sync_user_creation_with_ingestors(
     .await
 }
 
+// forward a created or revoked API key to all ingestors to keep them in sync
+pub async fn sync_api_key_with_ingestors(key: &ApiKey) -> Result<(), RBACError> {
+    let key_data = to_vec(key).map_err(|err| {
+        error!("Fatal: failed to serialize API key: {:?}", err);
+        RBACError::SerdeError(err)
+    })?;
+    let key_id = key.id;
+
+    for_each_live_ingestor(move |ingestor| {
+        let url = format!(
+            "{}{}/apikeys/{}/sync",
+            ingestor.domain_name,
+            base_path_without_preceding_slash(),
+            key_id
+        );
+
+        let key_data = key_data.clone();
+
+        async move {
+            let res = INTRA_CLUSTER_CLIENT
+                .put(url)
+                .header(header::AUTHORIZATION, &ingestor.token)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(key_data)
+                .send()
+                .await
+                .map_err(|err| {
+                    error!(
+                        "Fatal: failed to forward request to ingestor: {}\n Error: {:?}",
+                        ingestor.domain_name, err
+                    );
+                    RBACError::Network(err)
+                })?;
+
+            if !res.status().is_success() {
+                error!(
+                    "failed to forward request to ingestor: {}\nResponse Returned: {:?}",
+                    ingestor.domain_name,
+                    res.text().await
+                );
+            }
+
+            Ok(())
+        }
+    })
+    .await
+}
+
 // forward the password reset request to all ingestors to keep them in sync
 pub async fn sync_password_reset_with_ingestors(username: &str) -> Result<(), RBACError> {
     let username = username.to_owned();
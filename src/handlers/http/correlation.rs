@@ -20,6 +20,7 @@ use actix_web::web::{Json, Path};
 use actix_web::{HttpRequest, HttpResponse, Responder, web};
 use anyhow::Error;
 use itertools::Itertools;
+use serde_json::json;
 
 use crate::rbac::Users;
 use crate::utils::actix::extract_session_key_from_req;
@@ -59,6 +60,34 @@ pub async fn get(
     Ok(web::Json(correlation))
 }
 
+/// SQL query of the correlation, to run with its time range
+pub async fn query(
+    req: HttpRequest,
+    correlation_id: Path<String>,
+) -> Result<impl Responder, CorrelationError> {
+    let correlation_id = correlation_id.into_inner();
+    let session_key = extract_session_key_from_req(&req)
+        .map_err(|err| CorrelationError::AnyhowError(Error::msg(err.to_string())))?;
+
+    let correlation = CORRELATIONS.get_correlation(&correlation_id).await?;
+
+    let permissions = Users.get_permissions(&session_key);
+
+    let tables = &correlation
+        .table_configs
+        .iter()
+        .map(|t| t.table_name.clone())
+        .collect_vec();
+
+    user_auth_for_datasets(&permissions, tables).await?;
+
+    Ok(web::Json(json!({
+        "query": correlation.query()?,
+        "startTime": correlation.start_time,
+        "endTime": correlation.end_time,
+    })))
+}
+
 pub async fn post(
     req: HttpRequest,
     Json(mut correlation): Json<CorrelationConfig>,
@@ -181,6 +181,27 @@ pub async fn setup_otel_stream(
     };
 
     let log_source = LogSource::from(log_source.to_str().unwrap());
+    let stream_name = stream_name.to_str().unwrap().to_owned();
+
+    prepare_otel_stream(
+        stream_name,
+        log_source,
+        expected_log_source,
+        known_fields,
+        telemetry_type,
+    )
+    .await
+}
+
+/// Creates the stream of OTEL data if needed and validates that it holds the same kind of data,
+/// shared by the HTTP endpoints and the OTLP gRPC receiver
+pub async fn prepare_otel_stream(
+    stream_name: String,
+    log_source: LogSource,
+    expected_log_source: LogSource,
+    known_fields: &[&str],
+    telemetry_type: TelemetryType,
+) -> Result<(String, LogSource, LogSourceEntry, Option<String>), PostError> {
     if log_source != expected_log_source {
         return Err(PostError::IncorrectLogSource(
             expected_log_source,
@@ -188,8 +209,6 @@ pub async fn setup_otel_stream(
         ));
     }
 
-    let stream_name = stream_name.to_str().unwrap().to_owned();
-
     let log_source_entry = LogSourceEntry::new(
         log_source.clone(),
         known_fields.iter().map(|&s| s.to_string()).collect(),
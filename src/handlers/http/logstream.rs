@@ -18,15 +18,20 @@
 
 use self::error::StreamError;
 use super::cluster::utils::{IngestionStats, QueriedStats, StorageStats};
+use super::cluster::{sync_bloom_filter_with_ingestors, sync_pipeline_with_ingestors};
 use super::query::update_schema_when_distributed;
 use crate::event::format::override_data_type;
+use crate::event::pipeline::{Pipeline, PipelineError};
 use crate::hottier::{CURRENT_HOT_TIER_VERSION, HotTierManager, StreamHotTier};
 use crate::metadata::SchemaVersion;
 use crate::metrics::{EVENTS_INGESTED_DATE, EVENTS_INGESTED_SIZE_DATE, EVENTS_STORAGE_SIZE_DATE};
+use crate::option::Mode;
 use crate::parseable::{PARSEABLE, StreamNotFound};
 use crate::rbac::Users;
+use crate::rbac::column_mask::{column_masks, mask_schema};
 use crate::rbac::role::Action;
 use crate::stats::{Stats, event_labels_date, storage_size_labels_date};
+use crate::storage::bloom_filter::BloomFilterConfig;
 use crate::storage::retention::Retention;
 use crate::storage::{ObjectStoreFormat, StreamInfo, StreamType};
 use crate::utils::actix::extract_session_key_from_req;
@@ -159,8 +164,13 @@ pub async fn detect_schema(Json(json): Json<Value>) -> Result<impl Responder, St
     }
 }
 
-pub async fn get_schema(stream_name: Path<String>) -> Result<impl Responder, StreamError> {
+pub async fn get_schema(
+    req: HttpRequest,
+    stream_name: Path<String>,
+) -> Result<impl Responder, StreamError> {
     let stream_name = stream_name.into_inner();
+    let key = extract_session_key_from_req(&req)
+        .map_err(|err| StreamError::Anyhow(anyhow::Error::msg(err.to_string())))?;
 
     // Ensure parseable is aware of stream in distributed mode
     if !PARSEABLE.check_or_load_stream(&stream_name).await {
@@ -170,7 +180,9 @@ pub async fn get_schema(stream_name: Path<String>) -> Result<impl Responder, Str
     let stream = PARSEABLE.get_stream(&stream_name)?;
     match update_schema_when_distributed(&vec![stream_name.clone()]).await {
         Ok(_) => {
-            let schema = stream.get_schema();
+            // restricted users don't get to see the columns hidden from them
+            let masks = column_masks(&Users.get_permissions(&key), &stream_name);
+            let schema = mask_schema(&stream.get_schema(), &masks);
             Ok((web::Json(schema), StatusCode::OK))
         }
         Err(err) => Err(StreamError::Custom {
@@ -236,6 +248,140 @@ pub async fn put_retention(
     ))
 }
 
+pub async fn get_bloom_filter(stream_name: Path<String>) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name.clone()).into());
+    }
+
+    let bloom_filter = PARSEABLE
+        .get_stream(&stream_name)?
+        .get_bloom_filter()
+        .unwrap_or_default();
+    Ok((web::Json(bloom_filter), StatusCode::OK))
+}
+
+/// Sets the columns of the stream to write bloom filters for, an empty list of columns
+/// disables them. Applies to parquet files written from now on.
+pub async fn put_bloom_filter(
+    stream_name: Path<String>,
+    Json(bloom_filter): Json<BloomFilterConfig>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name).into());
+    }
+
+    let config = (!bloom_filter.columns.is_empty()).then(|| bloom_filter.clone());
+    PARSEABLE
+        .storage
+        .get_object_store()
+        .put_bloom_filter(&stream_name, config.as_ref())
+        .await?;
+    PARSEABLE.get_stream(&stream_name)?.set_bloom_filter(config);
+
+    if PARSEABLE.options.mode == Mode::Query {
+        sync_bloom_filter_with_ingestors(&stream_name, &bloom_filter).await?;
+    }
+
+    Ok((
+        format!("set bloom filter configuration for log stream {stream_name}"),
+        StatusCode::OK,
+    ))
+}
+
+pub async fn get_pipeline(stream_name: Path<String>) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name.clone()).into());
+    }
+
+    let pipeline = PARSEABLE
+        .get_stream(&stream_name)?
+        .get_pipeline()
+        .unwrap_or_default();
+    Ok((web::Json(pipeline), StatusCode::OK))
+}
+
+/// Sets the ingest pipeline of the stream, a pipeline without processors removes it.
+/// Applies to events ingested from now on.
+pub async fn put_pipeline(
+    stream_name: Path<String>,
+    Json(pipeline): Json<Pipeline>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name).into());
+    }
+    validate_pipeline(&stream_name, &pipeline).await?;
+
+    let config = (!pipeline.is_empty()).then(|| pipeline.clone());
+    PARSEABLE
+        .storage
+        .get_object_store()
+        .put_pipeline(&stream_name, config.as_ref())
+        .await?;
+    PARSEABLE.get_stream(&stream_name)?.set_pipeline(config);
+
+    if PARSEABLE.options.mode == Mode::Query {
+        sync_pipeline_with_ingestors(&stream_name, &pipeline).await?;
+    }
+
+    Ok((
+        format!("set ingest pipeline for log stream {stream_name}"),
+        StatusCode::OK,
+    ))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PipelineDryRun {
+    /// Pipeline to run, the one set on the stream if not given
+    #[serde(default)]
+    pub pipeline: Option<Pipeline>,
+    /// Sample event, or array of events
+    pub events: Value,
+}
+
+/// Runs a pipeline over sample events without ingesting them, and returns the events
+/// which would be pushed into the stream, routed to other streams and dropped
+pub async fn dry_run_pipeline(
+    stream_name: Path<String>,
+    Json(dry_run): Json<PipelineDryRun>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    if !PARSEABLE.check_or_load_stream(&stream_name).await {
+        return Err(StreamNotFound(stream_name).into());
+    }
+
+    let pipeline = match dry_run.pipeline {
+        Some(pipeline) => {
+            validate_pipeline(&stream_name, &pipeline).await?;
+            pipeline
+        }
+        None => PARSEABLE
+            .get_stream(&stream_name)?
+            .get_pipeline()
+            .unwrap_or_default(),
+    };
+
+    Ok((web::Json(pipeline.run(dry_run.events)), StatusCode::OK))
+}
+
+/// Checks the processors of the pipeline, and that the streams it routes to exist
+async fn validate_pipeline(stream_name: &str, pipeline: &Pipeline) -> Result<(), StreamError> {
+    pipeline.validate(stream_name)?;
+    for route in pipeline.routes() {
+        if !PARSEABLE.check_or_load_stream(route).await {
+            return Err(PipelineError::UnknownRoute(route.to_owned()).into());
+        }
+        if PARSEABLE.get_stream(route)?.get_stream_type() == StreamType::Internal {
+            return Err(PipelineError::InternalRoute(route.to_owned()).into());
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn get_stats_date(stream_name: &str, date: &str) -> Result<Stats, StreamError> {
     let event_labels = event_labels_date(stream_name, "json", date);
     let storage_size_labels = storage_size_labels_date(stream_name, date);
@@ -498,6 +644,7 @@ pub mod error {
     use actix_web::http::header::ContentType;
 
     use crate::{
+        event::pipeline::PipelineError,
         hottier::HotTierError,
         metastore::MetastoreError,
         parseable::StreamNotFound,
@@ -574,6 +721,8 @@ pub mod error {
         InvalidQueryParameter(String),
         #[error(transparent)]
         MetastoreError(#[from] MetastoreError),
+        #[error("{0}")]
+        Pipeline(#[from] PipelineError),
     }
 
     impl actix_web::ResponseError for StreamError {
@@ -612,6 +761,7 @@ pub mod error {
                 StreamError::HotTierError(_) => StatusCode::INTERNAL_SERVER_ERROR,
                 StreamError::InvalidQueryParameter(_) => StatusCode::BAD_REQUEST,
                 StreamError::MetastoreError(e) => e.status_code(),
+                StreamError::Pipeline(_) => StatusCode::BAD_REQUEST,
             }
         }
 
@@ -23,12 +23,16 @@ use actix_web::{
     Error, HttpMessage, Route,
     dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
     error::{ErrorBadRequest, ErrorForbidden, ErrorUnauthorized},
-    http::header::{self, HeaderName},
+    http::{
+        StatusCode,
+        header::{self, HeaderName},
+    },
 };
 use chrono::{Duration, Utc};
 use futures_util::future::LocalBoxFuture;
 
 use crate::{
+    audit::{self, AuditQuery, AuditRequest, Outcome, REQUEST_ID_HEADER},
     handlers::{
         AUTHORIZATION_KEY, KINESIS_COMMON_ATTRIBUTES_KEY, LOG_SOURCE_KEY, LOG_SOURCE_KINESIS,
         STREAM_NAME_HEADER_KEY,
@@ -76,6 +80,7 @@ impl RouteExt for Route {
         self.wrap(Auth {
             action,
             method: auth_no_context,
+            audited: audit::is_audited,
         })
     }
 
@@ -83,6 +88,7 @@ impl RouteExt for Route {
         self.wrap(Auth {
             action,
             method: auth_resource_context,
+            audited: audit::is_audited,
         })
     }
 
@@ -90,6 +96,7 @@ impl RouteExt for Route {
         self.wrap(Auth {
             action,
             method: auth_user_context,
+            audited: audit::is_audited,
         })
     }
 }
@@ -98,6 +105,8 @@ impl RouteExt for Route {
 pub struct Auth {
     pub action: Action,
     pub method: fn(&mut ServiceRequest, Action) -> Result<rbac::Response, Error>,
+    /// whether requests for the action are recorded in the audit log
+    pub audited: fn(Action) -> bool,
 }
 
 impl<S, B> Transform<S, ServiceRequest> for Auth
@@ -117,6 +126,7 @@ where
             action: self.action,
             service,
             auth_method: self.method,
+            audited: self.audited,
         }))
     }
 }
@@ -124,6 +134,7 @@ where
 pub struct AuthMiddleware<S> {
     action: Action,
     auth_method: fn(&mut ServiceRequest, Action) -> Result<rbac::Response, Error>,
+    audited: fn(Action) -> bool,
     service: S,
 }
 
@@ -172,6 +183,10 @@ where
         let key: Result<SessionKey, Error> = extract_session_key(&mut req);
         let userid: Result<String, RBACError> = get_user_from_request(&http_req);
 
+        let audit = (self.audited)(self.action)
+            .then(|| AuditRequest::from_service_request(&req, self.action));
+        let audit_user = userid.as_ref().ok().cloned();
+
         let fut = self.service.call(req);
         Box::pin(async move {
             let Ok(key) = key else {
@@ -264,6 +279,10 @@ where
 
             match auth_result? {
                 rbac::Response::UnAuthorized => {
+                    if let Some(audit) = audit {
+                        let status = StatusCode::FORBIDDEN;
+                        audit.record(audit_user, Some(status), Outcome::Denied, None);
+                    }
                     return Err(ErrorForbidden(
                         "You don't have permission to access this resource. Please contact your administrator for assistance.",
                     ));
@@ -276,7 +295,31 @@ where
                 _ => {}
             }
 
-            fut.await
+            let mut res = fut.await;
+            if let Some(audit) = audit {
+                let status = match &res {
+                    Ok(res) => res.status(),
+                    Err(err) => err.as_response_error().status_code(),
+                };
+                let query = res
+                    .as_ref()
+                    .ok()
+                    .and_then(|res| res.request().extensions().get::<AuditQuery>().cloned());
+                if let Ok(res) = &mut res
+                    && let Ok(request_id) = header::HeaderValue::from_str(&audit.request_id)
+                {
+                    res.headers_mut()
+                        .insert(HeaderName::from_static(REQUEST_ID_HEADER), request_id);
+                }
+                audit.record(
+                    audit_user,
+                    Some(status),
+                    Outcome::from(status),
+                    query.as_ref(),
+                );
+            }
+
+            res
         })
     }
 }
@@ -30,6 +30,7 @@ use self::query::Query;
 
 pub mod about;
 pub mod alerts;
+pub mod api_keys;
 pub mod cluster;
 pub mod correlation;
 pub mod demo_data;
@@ -27,10 +27,12 @@ use bytes::Bytes;
 use tracing::warn;
 
 use crate::{
-    catalog::remove_manifest_from_snapshot,
+    catalog::{remove_manifest_from_snapshot, remove_partitions_from_snapshot},
+    event::pipeline::Pipeline,
     handlers::http::logstream::error::StreamError,
     parseable::{PARSEABLE, StreamNotFound},
     stats,
+    storage::bloom_filter::BloomFilterConfig,
 };
 
 pub async fn retention_cleanup(
@@ -51,8 +53,15 @@ pub async fn retention_cleanup(
         return Err(StreamNotFound(stream_name.clone()).into());
     }
 
-    if let Err(err) = remove_manifest_from_snapshot(storage.clone(), &stream_name, date_list).await
-    {
+    // whole dates are sent as `date=..`, expired hours and minutes as `date=../hour=..[/minute=..]`
+    let (partitions, dates): (Vec<_>, Vec<_>) =
+        date_list.into_iter().partition(|date| date.contains('/'));
+
+    let result = match remove_manifest_from_snapshot(storage.clone(), &stream_name, dates).await {
+        Ok(()) => remove_partitions_from_snapshot(storage, &stream_name, partitions).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
         return Err(StreamError::Custom {
             msg: format!(
                 "failed to update snapshot during retention cleanup for stream {}: {}",
@@ -65,6 +74,60 @@ pub async fn retention_cleanup(
     Ok(actix_web::HttpResponse::NoContent().finish())
 }
 
+/// Bloom filter configuration of a stream, synced from the querier
+pub async fn put_bloom_filter(
+    stream_name: Path<String>,
+    Json(bloom_filter): Json<BloomFilterConfig>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    if !PARSEABLE.streams.contains(&stream_name)
+        && !PARSEABLE
+            .create_stream_and_schema_from_storage(&stream_name)
+            .await
+            .unwrap_or(false)
+    {
+        return Err(StreamNotFound(stream_name.clone()).into());
+    }
+
+    let bloom_filter = (!bloom_filter.columns.is_empty()).then_some(bloom_filter);
+    PARSEABLE
+        .storage()
+        .get_object_store()
+        .put_bloom_filter(&stream_name, bloom_filter.as_ref())
+        .await?;
+    PARSEABLE
+        .get_stream(&stream_name)?
+        .set_bloom_filter(bloom_filter);
+
+    Ok(actix_web::HttpResponse::NoContent().finish())
+}
+
+/// Ingest pipeline of a stream, synced from the querier
+pub async fn put_pipeline(
+    stream_name: Path<String>,
+    Json(pipeline): Json<Pipeline>,
+) -> Result<impl Responder, StreamError> {
+    let stream_name = stream_name.into_inner();
+    if !PARSEABLE.streams.contains(&stream_name)
+        && !PARSEABLE
+            .create_stream_and_schema_from_storage(&stream_name)
+            .await
+            .unwrap_or(false)
+    {
+        return Err(StreamNotFound(stream_name.clone()).into());
+    }
+
+    let pipeline = (!pipeline.is_empty()).then_some(pipeline);
+    PARSEABLE
+        .storage()
+        .get_object_store()
+        .put_pipeline(&stream_name, pipeline.as_ref())
+        .await?;
+    PARSEABLE.get_stream(&stream_name)?.set_pipeline(pipeline);
+
+    Ok(actix_web::HttpResponse::NoContent().finish())
+}
+
 pub async fn delete(stream_name: Path<String>) -> Result<impl Responder, StreamError> {
     let stream_name = stream_name.into_inner();
 
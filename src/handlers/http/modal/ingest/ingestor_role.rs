@@ -26,6 +26,7 @@ use actix_web::{
 use crate::{
     handlers::http::{modal::utils::rbac_utils::get_metadata, role::RoleError},
     rbac::{
+        api_key::API_KEYS,
         map::{mut_roles, mut_sessions, read_user_groups, users},
         role::model::DefaultPrivilege,
     },
@@ -61,6 +62,13 @@ pub async fn put(
         }
     }
 
+    // and over the API keys bound to this role
+    for key in API_KEYS.list() {
+        if key.roles.contains(&name) {
+            session_refresh_users.insert(key.userid());
+        }
+    }
+
     for userid in session_refresh_users {
         mut_sessions().remove_user(&userid);
     }
@@ -37,10 +37,11 @@ use crate::{
     handlers::{
         airplane,
         http::{
-            base_path, ingest, logstream,
+            api_keys, base_path, ingest, logstream,
             middleware::{DisAllowRootUser, RouteExt},
             resource_check, role,
         },
+        otlp,
     },
     migration,
     parseable::PARSEABLE,
@@ -76,6 +77,7 @@ impl ParseableServer for IngestServer {
                     .service(Server::get_liveness_factory())
                     .service(Self::get_user_webscope())
                     .service(Self::get_user_role_webscope())
+                    .service(Self::get_api_keys_webscope())
                     .service(Server::get_metrics_webscope())
                     .service(Server::get_readiness_factory())
                     .service(Server::get_demo_data_webscope()),
@@ -129,7 +131,10 @@ impl ParseableServer for IngestServer {
         let (cancel_tx, cancel_rx) = oneshot::channel();
         thread::spawn(|| sync::handler(cancel_rx));
 
+        audit::init_scheduler();
+
         tokio::spawn(airplane::server());
+        tokio::spawn(otlp::server());
 
         // Ingestors shouldn't have to deal with OpenId auth flow
         let result = self.start(shutdown_rx, prometheus.clone(), None).await;
@@ -176,6 +181,14 @@ impl IngestServer {
                     .route(web::put().to(ingestor_role::put).authorize(Action::PutRole)),
             )
     }
+    // get the API keys webscope
+    pub fn get_api_keys_webscope() -> Scope {
+        web::scope("/apikeys").service(
+            web::resource("/{key_id}/sync")
+                // PUT /apikeys/{key_id}/sync => Sync creation or revocation of an API key
+                .route(web::put().to(api_keys::sync).authorize(Action::PutApiKey)),
+        )
+    }
     // get the user webscope
     pub fn get_user_webscope() -> Scope {
         web::scope("/user")
@@ -272,6 +285,22 @@ impl IngestServer {
                             .authorize_for_resource(Action::GetStats),
                     ),
                 )
+                .service(
+                    // PUT "/logstream/{logstream}/bloomfilter" ==> Sync bloom filter configuration of a log stream
+                    web::resource("/bloomfilter").route(
+                        web::put()
+                            .to(ingestor_logstream::put_bloom_filter)
+                            .authorize_for_resource(Action::PutBloomFilter),
+                    ),
+                )
+                .service(
+                    // PUT "/logstream/{logstream}/pipeline" ==> Sync ingest pipeline of a log stream
+                    web::resource("/pipeline").route(
+                        web::put()
+                            .to(ingestor_logstream::put_pipeline)
+                            .authorize_for_resource(Action::PutPipeline),
+                    ),
+                )
                 .service(
                     web::scope("/retention").service(
                         web::resource("/cleanup").route(
@@ -30,8 +30,11 @@ use crate::{
         role::RoleError,
     },
     rbac::{
+        api_key::API_KEYS,
+        column_mask,
         map::{mut_roles, mut_sessions, read_user_groups, users},
         role::model::DefaultPrivilege,
+        row_filter,
     },
     validator,
 };
@@ -45,6 +48,8 @@ pub async fn put(
     let name = name.into_inner();
     // validate the role name
     validator::user_role_name(&name).map_err(RoleError::ValidationError)?;
+    row_filter::validate(&privileges)?;
+    column_mask::validate(&privileges)?;
     let mut metadata = get_metadata().await?;
     metadata.roles.insert(name.clone(), privileges.clone());
 
@@ -67,6 +72,13 @@ pub async fn put(
         }
     }
 
+    // and over the API keys bound to this role
+    for key in API_KEYS.list() {
+        if key.roles.contains(&name) {
+            session_refresh_users.insert(key.userid());
+        }
+    }
+
     for userid in session_refresh_users {
         mut_sessions().remove_user(&userid);
     }
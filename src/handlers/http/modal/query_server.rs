@@ -19,6 +19,7 @@
 use std::sync::Arc;
 use std::thread;
 
+use crate::audit;
 use crate::handlers::airplane;
 use crate::handlers::http::cluster;
 use crate::handlers::http::logstream;
@@ -30,7 +31,7 @@ use crate::handlers::http::{rbac, role};
 use crate::hottier::HotTierManager;
 use crate::rbac::role::Action;
 use crate::sync::sync_start;
-use crate::{analytics, migration, storage, sync};
+use crate::{analytics, migration, storage, sync, users};
 use actix_web::middleware::from_fn;
 use actix_web::web::{ServiceConfig, resource};
 use actix_web::{Scope, web};
@@ -66,6 +67,7 @@ impl ParseableServer for QueryServer {
                     .service(Server::get_users_webscope())
                     .service(Server::get_dashboards_webscope())
                     .service(Server::get_filters_webscope())
+                    .service(Server::get_bundles_webscope())
                     .service(Server::get_llm_webscope())
                     .service(Server::get_oauth_webscope())
                     .service(Self::get_user_role_webscope())
@@ -76,6 +78,7 @@ impl ParseableServer for QueryServer {
                     .service(Server::get_metrics_webscope())
                     .service(Server::get_alerts_webscope())
                     .service(Server::get_targets_webscope())
+                    .service(Server::get_api_keys_webscope())
                     .service(Self::get_cluster_web_scope())
                     .service(Server::get_demo_data_webscope())
                     .service(Server::get_dataset_stats_webscope()),
@@ -124,6 +127,9 @@ impl ParseableServer for QueryServer {
         load_on_init().await?;
         // track all parquet files already in the data directory
         storage::retention::load_retention_from_global();
+        storage::compaction::init_scheduler();
+        users::reports::init_scheduler();
+        audit::init_scheduler();
 
         // all internal data structures populated now.
         // start the analytics scheduler if enabled
@@ -330,6 +336,44 @@ impl QueryServer {
                                     .authorize_for_resource(Action::GetRetention),
                             ),
                     )
+                    .service(
+                        web::resource("/bloomfilter")
+                            // PUT "/logstream/{logstream}/bloomfilter" ==> Set bloom filter columns for given logstream
+                            .route(
+                                web::put()
+                                    .to(logstream::put_bloom_filter)
+                                    .authorize_for_resource(Action::PutBloomFilter),
+                            )
+                            // GET "/logstream/{logstream}/bloomfilter" ==> Get bloom filter columns for given logstream
+                            .route(
+                                web::get()
+                                    .to(logstream::get_bloom_filter)
+                                    .authorize_for_resource(Action::GetBloomFilter),
+                            ),
+                    )
+                    .service(
+                        web::resource("/pipeline")
+                            // PUT "/logstream/{logstream}/pipeline" ==> Set ingest pipeline for given logstream
+                            .route(
+                                web::put()
+                                    .to(logstream::put_pipeline)
+                                    .authorize_for_resource(Action::PutPipeline),
+                            )
+                            // GET "/logstream/{logstream}/pipeline" ==> Get ingest pipeline for given logstream
+                            .route(
+                                web::get()
+                                    .to(logstream::get_pipeline)
+                                    .authorize_for_resource(Action::GetPipeline),
+                            ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/pipeline/dryrun" ==> Run an ingest pipeline over sample events
+                        web::resource("/pipeline/dryrun").route(
+                            web::post()
+                                .to(logstream::dry_run_pipeline)
+                                .authorize_for_resource(Action::GetPipeline),
+                        ),
+                    )
                     .service(
                         web::resource("/hottier")
                             // PUT "/logstream/{logstream}/hottier" ==> Set hottier for given logstream
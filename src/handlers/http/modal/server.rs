@@ -19,9 +19,11 @@
 use std::thread;
 
 use crate::analytics;
+use crate::audit;
 use crate::handlers;
 use crate::handlers::http::about;
 use crate::handlers::http::alerts;
+use crate::handlers::http::api_keys;
 use crate::handlers::http::base_path;
 use crate::handlers::http::demo_data::get_demo_data;
 use crate::handlers::http::health_check;
@@ -31,6 +33,7 @@ use crate::handlers::http::prism_base_path;
 use crate::handlers::http::query;
 use crate::handlers::http::resource_check;
 use crate::handlers::http::targets;
+use crate::handlers::http::users::bundles;
 use crate::handlers::http::users::dashboards;
 use crate::handlers::http::users::filters;
 use crate::hottier::HotTierManager;
@@ -40,6 +43,7 @@ use crate::storage;
 use crate::storage::field_stats::get_dataset_stats;
 use crate::sync;
 use crate::sync::sync_start;
+use crate::users;
 
 use actix_web::Resource;
 use actix_web::Scope;
@@ -91,6 +95,7 @@ impl ParseableServer for Server {
                     .service(Self::get_users_webscope())
                     .service(Self::get_dashboards_webscope())
                     .service(Self::get_filters_webscope())
+                    .service(Self::get_bundles_webscope())
                     .service(Self::get_llm_webscope())
                     .service(Self::get_oauth_webscope())
                     .service(Self::get_user_role_webscope())
@@ -100,6 +105,7 @@ impl ParseableServer for Server {
                     )))
                     .service(Self::get_alerts_webscope())
                     .service(Self::get_targets_webscope())
+                    .service(Self::get_api_keys_webscope())
                     .service(Self::get_metrics_webscope())
                     .service(Self::get_demo_data_webscope())
                     .service(Self::get_dataset_stats_webscope()),
@@ -137,6 +143,9 @@ impl ParseableServer for Server {
         load_on_init().await?;
 
         storage::retention::load_retention_from_global();
+        storage::compaction::init_scheduler();
+        users::reports::init_scheduler();
+        audit::init_scheduler();
 
         // local sync on init
         let startup_sync_handle = tokio::spawn(async {
@@ -164,6 +173,7 @@ impl ParseableServer for Server {
 
         tokio::spawn(handlers::livetail::server());
         tokio::spawn(handlers::airplane::server());
+        tokio::spawn(handlers::otlp::server());
 
         let result = self
             .start(shutdown_rx, prometheus.clone(), PARSEABLE.options.openid())
@@ -252,6 +262,13 @@ impl Server {
                             .authorize(Action::DeleteCorrelation),
                     ),
             )
+            .service(
+                web::resource("/{correlation_id}/query").route(
+                    web::get()
+                        .to(http::correlation::query)
+                        .authorize(Action::GetCorrelation),
+                ),
+            )
     }
 
     pub fn get_alerts_webscope() -> Scope {
@@ -331,6 +348,29 @@ impl Server {
             )
     }
 
+    // get the API keys web scope
+    pub fn get_api_keys_webscope() -> Scope {
+        web::scope("/apikeys")
+            .service(
+                web::resource("")
+                    .route(web::get().to(api_keys::list).authorize(Action::ListApiKey))
+                    .route(
+                        web::post()
+                            .to(api_keys::create)
+                            .authorize(Action::PutApiKey),
+                    ),
+            )
+            .service(
+                web::resource("/{key_id}")
+                    .route(web::get().to(api_keys::get).authorize(Action::GetApiKey))
+                    .route(
+                        web::delete()
+                            .to(api_keys::revoke)
+                            .authorize(Action::DeleteApiKey),
+                    ),
+            )
+    }
+
     // get the dashboards web scope
     pub fn get_dashboards_webscope() -> Scope {
         web::scope("/dashboards")
@@ -380,10 +420,63 @@ impl Server {
                                 .to(dashboards::add_tile)
                                 .authorize(Action::CreateDashboard),
                         ),
+                    )
+                    .service(
+                        web::resource("/tiles/{tile_id}/query").route(
+                            web::post()
+                                .to(dashboards::resolve_tile_query)
+                                .authorize(Action::GetDashboard),
+                        ),
+                    )
+                    .service(
+                        web::resource("/variables/{name}/options").route(
+                            web::get()
+                                .to(dashboards::variable_options)
+                                .authorize(Action::GetDashboard),
+                        ),
+                    )
+                    .service(
+                        web::resource("/runs")
+                            .route(
+                                web::get()
+                                    .to(dashboards::list_report_runs)
+                                    .authorize(Action::GetDashboard),
+                            )
+                            .route(
+                                web::post()
+                                    .to(dashboards::run_report)
+                                    .authorize(Action::CreateDashboard),
+                            ),
                     ),
             )
     }
 
+    // get the bundles web scope, to move dashboards, filters, alerts,
+    // targets and correlations between environments
+    pub fn get_bundles_webscope() -> Scope {
+        web::scope("/bundles")
+            .service(
+                web::resource("/export")
+                    .route(
+                        web::get()
+                            .to(bundles::export_all)
+                            .authorize(Action::ExportBundle),
+                    )
+                    .route(
+                        web::post()
+                            .to(bundles::export)
+                            .authorize(Action::ExportBundle),
+                    ),
+            )
+            .service(
+                web::resource("/import").route(
+                    web::post()
+                        .to(bundles::import)
+                        .authorize(Action::ImportBundle),
+                ),
+            )
+    }
+
     // get the filters web scope
     pub fn get_filters_webscope() -> Scope {
         web::scope("/filters")
@@ -410,6 +503,10 @@ impl Server {
                             .authorize(Action::CreateFilter),
                     ),
             )
+            .service(
+                web::resource("/{filter_id}/compile")
+                    .route(web::get().to(filters::compile).authorize(Action::GetFilter)),
+            )
     }
     pub fn get_counts_webscope() -> Resource {
         web::resource("/counts").route(web::post().to(query::get_counts).authorize(Action::Query))
@@ -506,6 +603,44 @@ impl Server {
                                     .authorize_for_resource(Action::GetRetention),
                             ),
                     )
+                    .service(
+                        web::resource("/bloomfilter")
+                            // PUT "/logstream/{logstream}/bloomfilter" ==> Set bloom filter columns for given logstream
+                            .route(
+                                web::put()
+                                    .to(logstream::put_bloom_filter)
+                                    .authorize_for_resource(Action::PutBloomFilter),
+                            )
+                            // GET "/logstream/{logstream}/bloomfilter" ==> Get bloom filter columns for given logstream
+                            .route(
+                                web::get()
+                                    .to(logstream::get_bloom_filter)
+                                    .authorize_for_resource(Action::GetBloomFilter),
+                            ),
+                    )
+                    .service(
+                        web::resource("/pipeline")
+                            // PUT "/logstream/{logstream}/pipeline" ==> Set ingest pipeline for given logstream
+                            .route(
+                                web::put()
+                                    .to(logstream::put_pipeline)
+                                    .authorize_for_resource(Action::PutPipeline),
+                            )
+                            // GET "/logstream/{logstream}/pipeline" ==> Get ingest pipeline for given logstream
+                            .route(
+                                web::get()
+                                    .to(logstream::get_pipeline)
+                                    .authorize_for_resource(Action::GetPipeline),
+                            ),
+                    )
+                    .service(
+                        // POST "/logstream/{logstream}/pipeline/dryrun" ==> Run an ingest pipeline over sample events
+                        web::resource("/pipeline/dryrun").route(
+                            web::post()
+                                .to(logstream::dry_run_pipeline)
+                                .authorize_for_resource(Action::GetPipeline),
+                        ),
+                    )
                     .service(
                         web::resource("/hottier")
                             // PUT "/logstream/{logstream}/hottier" ==> Set hottier for given logstream
@@ -18,18 +18,21 @@
 
 use actix_web::HttpRequest;
 use actix_web::http::header::USER_AGENT;
+use arrow_array::RecordBatch;
 use chrono::Utc;
 use opentelemetry_proto::tonic::{
     logs::v1::LogsData, metrics::v1::MetricsData, trace::v1::TracesData,
 };
 use serde_json::Value;
 use std::collections::HashMap;
+use tonic::Request;
 use tracing::warn;
 
 use crate::{
     event::{
-        FORMAT_KEY, SOURCE_IP_KEY, USER_AGENT_KEY,
-        format::{EventFormat, LogSource, json},
+        Event, FORMAT_KEY, SOURCE_IP_KEY, USER_AGENT_KEY,
+        format::{EventFormat, LogSource, arrow, json},
+        pipeline::PipelineOutput,
     },
     handlers::{
         EXTRACT_LOG_KEY, LOG_SOURCE_KEY, STREAM_NAME_HEADER_KEY, TelemetryType,
@@ -39,7 +42,7 @@ use crate::{
         },
     },
     otel::{logs::flatten_otel_logs, metrics::flatten_otel_metrics, traces::flatten_otel_traces},
-    parseable::PARSEABLE,
+    parseable::{PARSEABLE, StreamNotFound},
     storage::StreamType,
     utils::json::{convert_array_to_object, flatten::convert_to_array},
 };
@@ -59,78 +62,119 @@ pub async fn flatten_and_push_logs(
     // Verify the dataset fields count
     verify_dataset_fields_count(stream_name)?;
 
-    match log_source {
+    let records = flatten_logs(json, log_source).await?;
+    for record in records {
+        process_and_push_logs(
+            stream_name,
+            record,
+            log_source,
+            p_custom_fields,
+            time_partition.clone(),
+            telemetry_type,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Splits the JSON sent by the log source into records, e.g. one per OTel log record
+async fn flatten_logs(json: Value, log_source: &LogSource) -> Result<Vec<Value>, PostError> {
+    let records = match log_source {
         LogSource::Kinesis => {
             //custom flattening required for Amazon Kinesis
             let message: Message = serde_json::from_value(json)?;
             let flattened_kinesis_data = flatten_kinesis_logs(message).await?;
-            let record = convert_to_array(flattened_kinesis_data)?;
-            push_logs(
-                stream_name,
-                record,
-                log_source,
-                p_custom_fields,
-                time_partition,
-                telemetry_type,
-            )
-            .await?;
+            vec![convert_to_array(flattened_kinesis_data)?]
         }
         LogSource::OtelLogs => {
             //custom flattening required for otel logs
             let logs: LogsData = serde_json::from_value(json)?;
-            for record in flatten_otel_logs(&logs) {
-                push_logs(
-                    stream_name,
-                    record,
-                    log_source,
-                    p_custom_fields,
-                    time_partition.clone(),
-                    telemetry_type,
-                )
-                .await?;
-            }
+            flatten_otel_logs(&logs)
         }
         LogSource::OtelTraces => {
             //custom flattening required for otel traces
             let traces: TracesData = serde_json::from_value(json)?;
-            for record in flatten_otel_traces(&traces) {
-                push_logs(
-                    stream_name,
-                    record,
-                    log_source,
-                    p_custom_fields,
-                    time_partition.clone(),
-                    telemetry_type,
-                )
-                .await?;
-            }
+            flatten_otel_traces(&traces)
         }
         LogSource::OtelMetrics => {
             //custom flattening required for otel metrics
             let metrics: MetricsData = serde_json::from_value(json)?;
-            for record in flatten_otel_metrics(metrics) {
-                push_logs(
-                    stream_name,
-                    record,
-                    log_source,
-                    p_custom_fields,
-                    time_partition.clone(),
-                    telemetry_type,
-                )
-                .await?;
-            }
+            flatten_otel_metrics(metrics)
         }
-        _ => {
-            push_logs(
-                stream_name,
-                json,
-                log_source,
-                p_custom_fields,
-                time_partition,
-                telemetry_type,
-            )
-            .await?
+        _ => vec![json],
+    };
+
+    Ok(records)
+}
+
+/// Runs the ingest pipeline of the stream, if any, over the events before pushing them.
+/// Events routed to other streams are pushed into them as they are, once checked to fit
+/// them so that a request isn't rejected after pushing part of its events.
+async fn process_and_push_logs(
+    stream_name: &str,
+    json: Value,
+    log_source: &LogSource,
+    p_custom_fields: &HashMap<String, String>,
+    time_partition: Option<String>,
+    telemetry_type: TelemetryType,
+) -> Result<(), PostError> {
+    let Some(pipeline) = PARSEABLE.get_stream(stream_name)?.get_pipeline() else {
+        return push_logs(
+            stream_name,
+            json,
+            log_source,
+            p_custom_fields,
+            time_partition,
+            telemetry_type,
+        )
+        .await;
+    };
+
+    let PipelineOutput { events, routed, .. } = pipeline.run(json);
+    for (stream_name, events) in &routed {
+        // routes are checked when setting the pipeline, the stream may not be loaded yet on ingestors
+        if !PARSEABLE.streams.contains(stream_name)
+            && !PARSEABLE
+                .create_stream_and_schema_from_storage(stream_name)
+                .await?
+        {
+            return Err(StreamNotFound(stream_name.clone()).into());
         }
+        let time_partition = PARSEABLE.get_stream(stream_name)?.get_time_partition();
+        for_each_event(
+            stream_name,
+            Value::Array(events.clone()),
+            log_source,
+            p_custom_fields,
+            time_partition,
+            telemetry_type,
+            |_| Ok(()),
+        )?;
+    }
+
+    if !events.is_empty() {
+        push_logs(
+            stream_name,
+            Value::Array(events),
+            log_source,
+            p_custom_fields,
+            time_partition,
+            telemetry_type,
+        )
+        .await?;
+    }
+    for (stream_name, events) in routed {
+        let time_partition = PARSEABLE.get_stream(&stream_name)?.get_time_partition();
+        push_logs(
+            &stream_name,
+            Value::Array(events),
+            log_source,
+            p_custom_fields,
+            time_partition,
+            telemetry_type,
+        )
+        .await?;
     }
 
     Ok(())
@@ -143,6 +187,28 @@ pub async fn push_logs(
     p_custom_fields: &HashMap<String, String>,
     time_partition: Option<String>,
     telemetry_type: TelemetryType,
+) -> Result<(), PostError> {
+    for_each_event(
+        stream_name,
+        json,
+        log_source,
+        p_custom_fields,
+        time_partition,
+        telemetry_type,
+        |event| Ok(event.process()?),
+    )
+}
+
+/// Turns the JSON into the events of the stream, handed one at a time to `f` so that
+/// each is checked against the schema as left by the previous one
+fn for_each_event(
+    stream_name: &str,
+    json: Value,
+    log_source: &LogSource,
+    p_custom_fields: &HashMap<String, String>,
+    time_partition: Option<String>,
+    telemetry_type: TelemetryType,
+    mut f: impl FnMut(Event) -> Result<(), PostError>,
 ) -> Result<(), PostError> {
     let stream = PARSEABLE.get_stream(stream_name)?;
     let time_partition_limit = PARSEABLE
@@ -165,7 +231,89 @@ pub async fn push_logs(
     for json in data {
         let origin_size = serde_json::to_vec(&json).unwrap().len() as u64; // string length need not be the same as byte length
         let schema = PARSEABLE.get_stream(stream_name)?.get_schema_raw();
-        json::Event { json, p_timestamp }
+        let event = json::Event { json, p_timestamp }.into_event(
+            stream_name.to_owned(),
+            origin_size,
+            &schema,
+            static_schema_flag,
+            custom_partition.as_ref(),
+            time_partition.as_ref(),
+            schema_version,
+            StreamType::UserDefined,
+            p_custom_fields,
+            telemetry_type,
+        )?;
+        f(event)?;
+    }
+    Ok(())
+}
+
+/// Pushes the records flattened from an OTLP request received over gRPC
+pub async fn push_otel_records(
+    records: Vec<Value>,
+    stream_name: &str,
+    log_source: &LogSource,
+    p_custom_fields: &HashMap<String, String>,
+    telemetry_type: TelemetryType,
+) -> Result<(), PostError> {
+    verify_dataset_fields_count(stream_name)?;
+
+    for record in records {
+        process_and_push_logs(
+            stream_name,
+            record,
+            log_source,
+            p_custom_fields,
+            None,
+            telemetry_type,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Pushes a record batch into the stream as is, without going through JSON, split by
+/// the custom partition and the minute of the time partition of the stream
+pub async fn push_record_batch(
+    stream_name: &str,
+    rb: RecordBatch,
+    origin_size: u64,
+    p_custom_fields: &HashMap<String, String>,
+) -> Result<(), PostError> {
+    verify_dataset_fields_count(stream_name)?;
+
+    let stream = PARSEABLE.get_stream(stream_name)?;
+    let static_schema_flag = stream.get_static_schema_flag();
+    let custom_partition = stream.get_custom_partition();
+    let time_partition = stream.get_time_partition();
+    let time_partition_limit = stream
+        .get_time_partition_limit()
+        .map_or(30, |days| days.get() as i64);
+    let schema_version = stream.get_schema_version();
+    let p_timestamp = Utc::now();
+
+    let mut batches = match custom_partition.as_ref() {
+        Some(custom_partition) => arrow::split_by_custom_partition(rb, custom_partition)?,
+        None => vec![rb],
+    };
+    if let Some(time_partition) = time_partition.as_ref() {
+        let mut split = Vec::with_capacity(batches.len());
+        for rb in batches {
+            split.extend(arrow::split_by_time_partition(
+                rb,
+                time_partition,
+                time_partition_limit,
+            )?);
+        }
+        batches = split;
+    }
+    let total_rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+
+    for rb in batches {
+        // attribute the size of the payload to every batch in proportion of its rows
+        let origin_size = origin_size * rb.num_rows() as u64 / total_rows.max(1) as u64;
+        let schema = PARSEABLE.get_stream(stream_name)?.get_schema_raw();
+        arrow::Event::new(rb, p_timestamp)
             .into_event(
                 stream_name.to_owned(),
                 origin_size,
@@ -176,7 +324,7 @@ pub async fn push_logs(
                 schema_version,
                 StreamType::UserDefined,
                 p_custom_fields,
-                telemetry_type,
+                TelemetryType::Logs,
             )?
             .process()?;
     }
@@ -244,6 +392,24 @@ pub fn get_custom_fields_from_header(req: &HttpRequest) -> HashMap<String, Strin
     p_custom_fields
 }
 
+/// Same as the custom fields of HTTP ingestion, user agent and source IP of the client
+pub fn get_custom_fields_from_metadata<T>(req: &Request<T>) -> HashMap<String, String> {
+    let user_agent = req
+        .metadata()
+        .get(USER_AGENT.as_str())
+        .and_then(|a| a.to_str().ok())
+        .unwrap_or_default();
+    let source_ip = req
+        .remote_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default();
+
+    HashMap::from([
+        (USER_AGENT_KEY.to_string(), user_agent.to_string()),
+        (SOURCE_IP_KEY.to_string(), source_ip),
+    ])
+}
+
 fn verify_dataset_fields_count(stream_name: &str) -> Result<(), PostError> {
     let fields_count = PARSEABLE
         .get_stream(stream_name)?
@@ -302,7 +468,14 @@ pub fn validate_stream_for_ingestion(stream_name: &str) -> Result<(), PostError>
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::event::pipeline::Pipeline;
     use actix_web::test::TestRequest;
+    use opentelemetry_proto::tonic::{
+        common::v1::{AnyValue, KeyValue, any_value},
+        logs::v1::{LogRecord, ResourceLogs, ScopeLogs, SeverityNumber},
+        resource::v1::Resource,
+    };
+    use serde_json::json;
 
     #[test]
     fn test_get_custom_fields_from_header_with_custom_fields() {
@@ -355,4 +528,59 @@ mod tests {
         assert_eq!(custom_fields.get(USER_AGENT_KEY).unwrap(), "");
         assert_eq!(custom_fields.get(SOURCE_IP_KEY).unwrap(), "");
     }
+
+    #[tokio::test]
+    async fn routes_flattened_records() {
+        let record = |severity: SeverityNumber| LogRecord {
+            severity_number: severity as i32,
+            ..Default::default()
+        };
+        let logs = LogsData {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource {
+                    attributes: vec![KeyValue {
+                        key: "service.name".to_owned(),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::StringValue("checkout".to_owned())),
+                        }),
+                    }],
+                    ..Default::default()
+                }),
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![record(SeverityNumber::Info), record(SeverityNumber::Error)],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let pipeline: Pipeline = serde_json::from_value(json!({
+            "processors": [{
+                "type": "route",
+                "stream": "errors",
+                "conditions": {
+                    "conditionConfig": [{"column": "severity_text", "operator": "=", "value": "ERROR"}]
+                }
+            }]
+        }))
+        .unwrap();
+
+        let records = flatten_logs(serde_json::to_value(&logs).unwrap(), &LogSource::OtelLogs)
+            .await
+            .unwrap();
+        let outputs: Vec<_> = records
+            .into_iter()
+            .map(|record| pipeline.run(record))
+            .collect();
+
+        let kept: Vec<_> = outputs.iter().flat_map(|output| &output.events).collect();
+        let routed: Vec<_> = outputs
+            .iter()
+            .flat_map(|output| output.routed.get("errors").into_iter().flatten())
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0]["severity_text"], "INFO");
+        assert_eq!(routed.len(), 1);
+        assert_eq!(routed[0]["severity_text"], "ERROR");
+        assert_eq!(routed[0]["service.name"], "checkout");
+    }
 }
@@ -142,6 +142,10 @@ pub async fn login(
             };
             Ok(resp)
         }
+        // API keys authorize requests on their own, there is no session to exchange them for
+        SessionKey::ApiKey(_) => Err(OIDCError::BadRequest(
+            "API keys can't be used to log in".to_string(),
+        )),
     }
 }
 
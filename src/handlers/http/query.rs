@@ -16,6 +16,7 @@
  *
  */
 
+use crate::audit::AuditQuery;
 use crate::event::error::EventError;
 use crate::handlers::http::fetch_schema;
 use crate::metastore::MetastoreError;
@@ -25,12 +26,14 @@ use crate::utils::arrow::record_batches_to_json;
 use actix_web::http::StatusCode;
 use actix_web::http::header::ContentType;
 use actix_web::web::{self, Json};
-use actix_web::{Either, FromRequest, HttpRequest, HttpResponse, Responder};
+use actix_web::{Either, FromRequest, HttpMessage, HttpRequest, HttpResponse, Responder};
 use arrow_array::RecordBatch;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
+use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
 use datafusion::error::DataFusionError;
 use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{Filter, LogicalPlan};
 use datafusion::sql::sqlparser::parser::ParserError;
 use futures::stream::once;
 use futures::{Stream, StreamExt, future};
@@ -38,7 +41,7 @@ use futures_util::Future;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Instant;
@@ -49,14 +52,17 @@ use crate::event::{DEFAULT_TIMESTAMP_KEY, commit_schema};
 use crate::metrics::{QUERY_EXECUTE_TIME, increment_query_calls_by_date};
 use crate::parseable::{PARSEABLE, StreamNotFound};
 use crate::query::error::ExecuteError;
-use crate::query::{CountsRequest, Query as LogicalQuery, execute};
+use crate::query::{CountConditions, CountsRequest, Query as LogicalQuery, execute};
 use crate::query::{QUERY_SESSION, resolve_stream_names};
 use crate::rbac::Users;
+use crate::rbac::apply_access_policies;
+use crate::rbac::row_filter::row_filters;
 use crate::response::QueryResponse;
 use crate::storage::ObjectStorageError;
+use crate::users::filters::{FILTERS, FilterCompileError};
 use crate::utils::actix::extract_session_key_from_req;
 use crate::utils::time::{TimeParseError, TimeRange};
-use crate::utils::user_auth_for_datasets;
+use crate::utils::{get_hash, is_admin_session, user_auth_for_datasets};
 
 pub const TIME_ELAPSED_HEADER: &str = "p-time-elapsed";
 /// Query Request through http endpoint.
@@ -74,6 +80,9 @@ pub struct Query {
     pub streaming: bool,
     #[serde(skip)]
     pub filter_tags: Option<Vec<String>>,
+    /// ID of a saved filter, applied to the stream it was saved for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_id: Option<String>,
 }
 
 /// A function to execute the query and fetch QueryResponse
@@ -90,11 +99,13 @@ pub async fn get_records_and_fields(
     //check or load streams in memory
     create_streams_for_distributed(tables.clone()).await?;
 
-    let query: LogicalQuery = into_query(query_request, &session_state, time_range).await?;
+    let mut query: LogicalQuery =
+        into_query(query_request, &session_state, time_range, creds).await?;
 
     let permissions = Users.get_permissions(creds);
 
     user_auth_for_datasets(&permissions, &tables).await?;
+    query.raw_logical_plan = apply_access_policies(query.raw_logical_plan, &permissions)?;
 
     let (records, fields) = execute(query, false).await?;
 
@@ -109,6 +120,14 @@ pub async fn get_records_and_fields(
 }
 
 pub async fn query(req: HttpRequest, query_request: Query) -> Result<HttpResponse, QueryError> {
+    // recorded in the audit log along with the request, including when the query fails
+    let audit_query = AuditQuery {
+        sql: query_request.query.clone(),
+        bytes_scanned: Default::default(),
+        streaming: query_request.streaming,
+    };
+    req.extensions_mut().insert(audit_query.clone());
+
     let session_state = QUERY_SESSION.state();
     let time_range =
         TimeRange::parse_human_time(&query_request.start_time, &query_request.end_time)?;
@@ -116,11 +135,15 @@ pub async fn query(req: HttpRequest, query_request: Query) -> Result<HttpRespons
     //check or load streams in memory
     create_streams_for_distributed(tables.clone()).await?;
 
-    let query: LogicalQuery = into_query(&query_request, &session_state, time_range).await?;
     let creds = extract_session_key_from_req(&req)?;
+    let mut query: LogicalQuery =
+        into_query(&query_request, &session_state, time_range, &creds).await?;
+    query.bytes_scanned = audit_query.bytes_scanned;
     let permissions = Users.get_permissions(&creds);
 
     user_auth_for_datasets(&permissions, &tables).await?;
+    // applied before the shortcut for counts below, which a filtered plan doesn't take
+    query.raw_logical_plan = apply_access_policies(query.raw_logical_plan, &permissions)?;
     let time = Instant::now();
 
     // Track billing metrics for query calls
@@ -348,10 +371,17 @@ pub async fn get_counts(
     let creds = extract_session_key_from_req(&req)?;
     let permissions = Users.get_permissions(&creds);
 
-    let body = counts_request.into_inner();
+    let mut body = counts_request.into_inner();
 
     // does user have access to table?
     user_auth_for_datasets(&permissions, std::slice::from_ref(&body.stream)).await?;
+    // counts from the manifests include rows outside of the row filters, so count with a query
+    if body.conditions.is_none() && row_filters(&permissions, &body.stream).is_some() {
+        body.conditions = Some(CountConditions {
+            conditions: None,
+            group_by: None,
+        });
+    }
     // Track billing metrics for query calls
     let current_date = chrono::Utc::now().date_naive().to_string();
     increment_query_calls_by_date(&current_date);
@@ -373,6 +403,7 @@ pub async fn get_counts(
             fields: true,
             streaming: false,
             filter_tags: None,
+            filter_id: None,
         };
 
         let creds = extract_session_key_from_req(&req)?;
@@ -484,6 +515,7 @@ pub async fn into_query(
     query: &Query,
     session_state: &SessionState,
     time_range: TimeRange,
+    key: &SessionKey,
 ) -> Result<LogicalQuery, QueryError> {
     if query.query.is_empty() {
         return Err(QueryError::EmptyQuery);
@@ -496,15 +528,61 @@ pub async fn into_query(
     if query.end_time.is_empty() {
         return Err(QueryError::EmptyEndTime);
     }
-    let raw_logical_plan = session_state.create_logical_plan(&query.query).await?;
+    let mut raw_logical_plan = session_state.create_logical_plan(&query.query).await?;
+    if let Some(filter_id) = &query.filter_id {
+        raw_logical_plan = apply_saved_filter(raw_logical_plan, filter_id, key).await?;
+    }
 
     Ok(crate::query::Query {
         raw_logical_plan,
         time_range,
         filter_tag: query.filter_tags.clone(),
+        bytes_scanned: Default::default(),
     })
 }
 
+/// Filters the scans of a query with a saved filter of the user, the query should
+/// read only the stream the filter was saved for
+async fn apply_saved_filter(
+    plan: LogicalPlan,
+    filter_id: &str,
+    key: &SessionKey,
+) -> Result<LogicalPlan, QueryError> {
+    let user_id = Users
+        .get_userid_from_session(key)
+        .ok_or(QueryError::MalformedQuery("Saved filter does not exist"))?;
+    let filter = FILTERS
+        .get_filter(filter_id, &get_hash(&user_id), is_admin_session(key))
+        .await
+        .ok_or(QueryError::MalformedQuery("Saved filter does not exist"))?;
+
+    let mut streams = HashSet::new();
+    plan.apply_with_subqueries(|node| {
+        if let LogicalPlan::TableScan(scan) = node {
+            streams.insert(scan.table_name.table().to_owned());
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    if streams.len() != 1 || !streams.contains(&filter.stream_name) {
+        return Err(QueryError::MalformedQuery(
+            "Query should read only the stream of the saved filter",
+        ));
+    }
+
+    let predicate = filter.to_expr().await?;
+    let plan = plan
+        .transform_up_with_subqueries(|node| {
+            if let LogicalPlan::TableScan(_) = &node {
+                let scan_filter = Filter::try_new(predicate.clone(), Arc::new(node))?;
+                return Ok(Transformed::yes(LogicalPlan::Filter(scan_filter)));
+            }
+            Ok(Transformed::no(node))
+        })?
+        .data;
+
+    Ok(plan)
+}
+
 /// unused for now, might need it in the future
 #[allow(unused)]
 fn transform_query_for_ingestor(query: &Query) -> Option<Query> {
@@ -534,6 +612,7 @@ fn transform_query_for_ingestor(query: &Query) -> Option<Query> {
         query: query.query.clone(),
         fields: false,
         filter_tags: query.filter_tags.clone(),
+        filter_id: query.filter_id.clone(),
         send_null: query.send_null,
         start_time: start_time.to_rfc3339(),
         end_time: end_time.to_rfc3339(),
@@ -587,6 +666,8 @@ Description: {0}"#
     ParserError(#[from] ParserError),
     #[error(transparent)]
     MetastoreError(#[from] MetastoreError),
+    #[error("Saved filter error: {0}")]
+    SavedFilter(#[from] FilterCompileError),
 }
 
 impl actix_web::ResponseError for QueryError {
@@ -28,8 +28,11 @@ use actix_web::{
 use crate::{
     parseable::PARSEABLE,
     rbac::{
+        api_key::API_KEYS,
+        column_mask::{self, ColumnMaskError},
         map::{DEFAULT_ROLE, mut_roles, mut_sessions, read_user_groups, users},
         role::model::DefaultPrivilege,
+        row_filter::{self, RowFilterError},
     },
     storage::{self, ObjectStorageError, StorageMetadata},
     validator::{self, error::UsernameValidationError},
@@ -44,6 +47,8 @@ pub async fn put(
     let name = name.into_inner();
     // validate the role name
     validator::user_role_name(&name).map_err(RoleError::ValidationError)?;
+    row_filter::validate(&privileges)?;
+    column_mask::validate(&privileges)?;
     let mut metadata = get_metadata().await?;
     metadata.roles.insert(name.clone(), privileges.clone());
 
@@ -66,6 +71,13 @@ pub async fn put(
         }
     }
 
+    // and over the API keys bound to this role
+    for key in API_KEYS.list() {
+        if key.roles.contains(&name) {
+            session_refresh_users.insert(key.userid());
+        }
+    }
+
     for userid in session_refresh_users {
         mut_sessions().remove_user(&userid);
     }
@@ -173,6 +185,10 @@ pub enum RoleError {
     Network(#[from] reqwest::Error),
     #[error("Validation Error: {0}")]
     ValidationError(#[from] UsernameValidationError),
+    #[error("{0}")]
+    RowFilter(#[from] RowFilterError),
+    #[error("{0}")]
+    ColumnMask(#[from] ColumnMaskError),
 }
 
 impl actix_web::ResponseError for RoleError {
@@ -184,6 +200,8 @@ impl actix_web::ResponseError for RoleError {
             Self::SerdeError(_) => StatusCode::BAD_REQUEST,
             Self::Network(_) => StatusCode::BAD_GATEWAY,
             Self::ValidationError(_) => StatusCode::BAD_REQUEST,
+            Self::RowFilter(_) => StatusCode::BAD_REQUEST,
+            Self::ColumnMask(_) => StatusCode::BAD_REQUEST,
         }
     }
 
@@ -0,0 +1,145 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::{
+    alerts::AlertError,
+    correlation::CorrelationError,
+    handlers::http::rbac::RBACError,
+    metastore::MetastoreError,
+    users::bundles::{self, Bundle, BundleUser, ConflictPolicy, ExportRequest},
+    utils::{actix::extract_session_key_from_req, get_user_from_request, is_admin},
+};
+use actix_web::http::StatusCode;
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, ResponseError,
+    http::header::ContentType,
+    web::{self, Json, Query},
+};
+use serde::Deserialize;
+
+use super::dashboards::DashboardError;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportParams {
+    #[serde(default)]
+    policy: ConflictPolicy,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+fn bundle_user(req: &HttpRequest) -> Result<BundleUser, BundleError> {
+    let is_admin = is_admin(req).map_err(|e| BundleError::Custom(e.to_string()))?;
+    Ok(BundleUser::new(get_user_from_request(req)?, is_admin))
+}
+
+/// Export all the dashboards, filters, alerts and correlations of the user
+pub async fn export_all(req: HttpRequest) -> Result<impl Responder, BundleError> {
+    let session_key =
+        extract_session_key_from_req(&req).map_err(|e| BundleError::Custom(e.to_string()))?;
+    let user = bundle_user(&req)?;
+
+    let bundle = bundles::export(&ExportRequest::default(), &session_key, &user).await?;
+    Ok((web::Json(bundle), StatusCode::OK))
+}
+
+/// Export the selected dashboards, filters, alerts and correlations
+pub async fn export(
+    req: HttpRequest,
+    Json(request): Json<ExportRequest>,
+) -> Result<impl Responder, BundleError> {
+    let session_key =
+        extract_session_key_from_req(&req).map_err(|e| BundleError::Custom(e.to_string()))?;
+    let user = bundle_user(&req)?;
+
+    let bundle = bundles::export(&request, &session_key, &user).await?;
+    Ok((web::Json(bundle), StatusCode::OK))
+}
+
+/// Import a bundle, nothing is saved on a dry run or if any object of the bundle is invalid
+pub async fn import(
+    req: HttpRequest,
+    params: Query<ImportParams>,
+    Json(bundle): Json<Bundle>,
+) -> Result<impl Responder, BundleError> {
+    let session_key =
+        extract_session_key_from_req(&req).map_err(|e| BundleError::Custom(e.to_string()))?;
+    let user = bundle_user(&req)?;
+
+    let report =
+        bundles::import(bundle, params.policy, params.dry_run, &session_key, &user).await?;
+
+    let status = if report.is_valid() {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    Ok((web::Json(report), status))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BundleError {
+    #[error("Unsupported bundle version: {0}")]
+    UnsupportedVersion(String),
+    #[error("{0} does not exist")]
+    NotFound(String),
+    #[error("User is not authorized to export {0}")]
+    Unauthorized(String),
+    #[error("User does not exist")]
+    UserDoesNotExist(#[from] RBACError),
+    #[error("{0}")]
+    Alert(#[from] AlertError),
+    #[error("{0}")]
+    Dashboard(#[from] DashboardError),
+    #[error("{0}")]
+    Correlation(#[from] CorrelationError),
+    #[error("Failed to save {0}, the objects of the bundle saved before it were kept: {1}")]
+    PartiallyImported(String, String),
+    #[error("Error: {0}")]
+    Custom(String),
+    #[error(transparent)]
+    MetastoreError(#[from] MetastoreError),
+}
+
+impl ResponseError for BundleError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnsupportedVersion(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Unauthorized(_) => StatusCode::FORBIDDEN,
+            Self::UserDoesNotExist(_) => StatusCode::NOT_FOUND,
+            Self::Alert(e) => e.status_code(),
+            Self::Dashboard(e) => e.status_code(),
+            Self::Correlation(e) => e.status_code(),
+            Self::PartiallyImported(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Custom(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::MetastoreError(e) => e.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            BundleError::MetastoreError(e) => {
+                HttpResponse::build(self.status_code()).json(e.to_detail())
+            }
+            _ => HttpResponse::build(self.status_code())
+                .insert_header(ContentType::plaintext())
+                .body(self.to_string()),
+        }
+    }
+}
@@ -21,9 +21,17 @@ use std::collections::HashMap;
 use crate::{
     handlers::http::rbac::RBACError,
     metastore::MetastoreError,
+    rbac::Users,
     storage::ObjectStorageError,
-    users::dashboards::{DASHBOARDS, Dashboard, Tile, validate_dashboard_id},
-    utils::{get_hash, get_user_from_request, is_admin},
+    users::{
+        dashboards::{DASHBOARDS, Dashboard, Tile, validate_dashboard_id},
+        reports,
+        variables::{self, VariableError, VariableValue},
+    },
+    utils::{
+        actix::extract_session_key_from_req, get_hash, get_user_from_request, is_admin,
+        time::TimeRange, user_auth_for_query,
+    },
 };
 use actix_web::http::StatusCode;
 use actix_web::{
@@ -31,7 +39,9 @@ use actix_web::{
     http::header::ContentType,
     web::{self, Json, Path},
 };
-use serde_json::Error as SerdeError;
+use serde::Deserialize;
+use serde_json::{Error as SerdeError, json};
+use ulid::Ulid;
 
 pub async fn list_dashboards(req: HttpRequest) -> Result<impl Responder, DashboardError> {
     let query_map = web::Query::<HashMap<String, String>>::from_query(req.query_string())
@@ -92,6 +102,8 @@ pub async fn create_dashboard(
     }
 
     let user_id = get_hash(&get_user_from_request(&req)?);
+    variables::validate(&dashboard.variables)?;
+    validate_schedule(&req, &dashboard).await?;
 
     DASHBOARDS.create(&user_id, &mut dashboard).await?;
     Ok((web::Json(dashboard), StatusCode::OK))
@@ -178,6 +190,8 @@ pub async fn update_dashboard(
 
         dashboard
     };
+    variables::validate(&final_dashboard.variables)?;
+    validate_schedule(&req, &final_dashboard).await?;
 
     DASHBOARDS
         .update(&user_id, dashboard_id, &mut final_dashboard)
@@ -240,6 +254,133 @@ pub async fn list_tags() -> Result<impl Responder, DashboardError> {
     Ok((web::Json(tags), StatusCode::OK))
 }
 
+/// List the runs of a scheduled report, latest first
+pub async fn list_report_runs(
+    req: HttpRequest,
+    dashboard_id: Path<String>,
+) -> Result<impl Responder, DashboardError> {
+    let user_id = get_hash(&get_user_from_request(&req)?);
+    let dashboard_id = validate_dashboard_id(dashboard_id.into_inner())?;
+    let is_admin = is_admin(&req).map_err(|e| DashboardError::Custom(e.to_string()))?;
+    DASHBOARDS
+        .get_dashboard_by_user(dashboard_id, &user_id, is_admin)
+        .await
+        .ok_or(DashboardError::Unauthorized)?;
+
+    let runs = reports::list_runs(dashboard_id).await?;
+    Ok((web::Json(runs), StatusCode::OK))
+}
+
+/// Run a scheduled report now, independent of its schedule
+pub async fn run_report(
+    req: HttpRequest,
+    dashboard_id: Path<String>,
+) -> Result<impl Responder, DashboardError> {
+    let user_id = get_hash(&get_user_from_request(&req)?);
+    let dashboard_id = validate_dashboard_id(dashboard_id.into_inner())?;
+    let is_admin = is_admin(&req).map_err(|e| DashboardError::Custom(e.to_string()))?;
+
+    let dashboard = DASHBOARDS
+        .get_dashboard_by_user(dashboard_id, &user_id, is_admin)
+        .await
+        .ok_or(DashboardError::Unauthorized)?;
+
+    let run = reports::run_report(&dashboard).await?;
+    Ok((web::Json(run), StatusCode::OK))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ResolveQueryRequest {
+    /// selected values of the dashboard variables, defaults are used for the rest
+    #[serde(default)]
+    variables: HashMap<String, VariableValue>,
+}
+
+/// Resolve the query of a tile with the selected values of the dashboard variables
+pub async fn resolve_tile_query(
+    path: Path<(String, Ulid)>,
+    request: Option<Json<ResolveQueryRequest>>,
+) -> Result<impl Responder, DashboardError> {
+    let (dashboard_id, tile_id) = path.into_inner();
+    let dashboard_id = validate_dashboard_id(dashboard_id)?;
+    let dashboard = DASHBOARDS
+        .get_dashboard(dashboard_id)
+        .await
+        .ok_or_else(|| DashboardError::Metadata("Dashboard does not exist"))?;
+    let tile = dashboard
+        .tiles
+        .iter()
+        .flatten()
+        .find(|tile| tile.tile_id == tile_id)
+        .ok_or(DashboardError::Metadata("Tile does not exist"))?;
+
+    let selected = request
+        .map(|Json(request)| request.variables)
+        .unwrap_or_default();
+    let query = dashboard
+        .tile_query(tile, &selected)
+        .ok_or(DashboardError::Metadata("Tile has no query"))??;
+
+    Ok((
+        web::Json(json!({ "tileId": tile_id, "query": query })),
+        StatusCode::OK,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariableOptionsParams {
+    start_time: Option<String>,
+    end_time: Option<String>,
+}
+
+/// List the options of a dashboard variable, running its query over the time range
+pub async fn variable_options(
+    req: HttpRequest,
+    path: Path<(String, String)>,
+    params: web::Query<VariableOptionsParams>,
+) -> Result<impl Responder, DashboardError> {
+    let (dashboard_id, name) = path.into_inner();
+    let dashboard_id = validate_dashboard_id(dashboard_id)?;
+    let dashboard = DASHBOARDS
+        .get_dashboard(dashboard_id)
+        .await
+        .ok_or_else(|| DashboardError::Metadata("Dashboard does not exist"))?;
+    let variable = dashboard
+        .variables
+        .iter()
+        .find(|variable| variable.name == name)
+        .ok_or(DashboardError::Metadata("Variable does not exist"))?;
+
+    let session_key =
+        extract_session_key_from_req(&req).map_err(|e| DashboardError::Custom(e.to_string()))?;
+    if let Some(query) = &variable.query {
+        user_auth_for_query(&session_key, query)
+            .await
+            .map_err(|_| DashboardError::Unauthorized)?;
+    }
+    let time_range = TimeRange::parse_human_time(
+        params.start_time.as_deref().unwrap_or("1h"),
+        params.end_time.as_deref().unwrap_or("now"),
+    )
+    .map_err(|_| DashboardError::InvalidQueryParameter)?;
+
+    let options = variable
+        .options(&time_range, &Users.get_permissions(&session_key))
+        .await?;
+    Ok((web::Json(options), StatusCode::OK))
+}
+
+async fn validate_schedule(req: &HttpRequest, dashboard: &Dashboard) -> Result<(), DashboardError> {
+    let Some(schedule) = &dashboard.schedule else {
+        return Ok(());
+    };
+    let session_key =
+        extract_session_key_from_req(req).map_err(|e| DashboardError::Custom(e.to_string()))?;
+
+    schedule.validate(dashboard, &session_key).await
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum DashboardError {
     #[error("Failed to connect to storage: {0}")]
@@ -256,6 +397,8 @@ pub enum DashboardError {
     Unauthorized,
     #[error("Invalid query parameter")]
     InvalidQueryParameter,
+    #[error("{0}")]
+    Variable(#[from] VariableError),
     #[error(transparent)]
     MetastoreError(#[from] MetastoreError),
 }
@@ -270,6 +413,8 @@ impl actix_web::ResponseError for DashboardError {
             Self::Custom(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
             Self::InvalidQueryParameter => StatusCode::BAD_REQUEST,
+            Self::Variable(VariableError::Query(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Variable(_) => StatusCode::BAD_REQUEST,
             Self::MetastoreError(e) => e.status_code(),
         }
     }
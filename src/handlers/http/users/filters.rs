@@ -17,11 +17,11 @@
  */
 
 use crate::{
-    handlers::http::rbac::RBACError,
+    handlers::http::{query::create_streams_for_distributed, rbac::RBACError},
     metastore::MetastoreError,
     parseable::PARSEABLE,
     storage::ObjectStorageError,
-    users::filters::{CURRENT_FILTER_VERSION, FILTERS, Filter},
+    users::filters::{CURRENT_FILTER_VERSION, FILTERS, Filter, FilterCompileError},
     utils::{actix::extract_session_key_from_req, get_hash, get_user_from_request, is_admin},
 };
 use actix_web::http::StatusCode;
@@ -30,7 +30,7 @@ use actix_web::{
     http::header::ContentType,
     web::{self, Json, Path},
 };
-use serde_json::Error as SerdeError;
+use serde_json::{Error as SerdeError, json};
 use ulid::Ulid;
 
 pub async fn list(req: HttpRequest) -> Result<impl Responder, FiltersError> {
@@ -126,6 +126,37 @@ pub async fn delete(
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Compile a saved filter into the predicate it applies to its stream,
+/// the same predicate a query referencing the filter by ID is filtered with
+pub async fn compile(
+    req: HttpRequest,
+    filter_id: Path<String>,
+) -> Result<impl Responder, FiltersError> {
+    let user_id = get_user_from_request(&req)?;
+    let filter_id = filter_id.into_inner();
+    let is_admin = is_admin(&req).map_err(|e| FiltersError::Custom(e.to_string()))?;
+    let filter = FILTERS
+        .get_filter(&filter_id, &get_hash(&user_id), is_admin)
+        .await
+        .ok_or(FiltersError::Metadata(
+            "Filter does not exist or user is not authorized",
+        ))?;
+
+    create_streams_for_distributed(vec![filter.stream_name.clone()])
+        .await
+        .map_err(|e| FiltersError::Custom(e.to_string()))?;
+    let expr = filter.to_expr().await?;
+
+    Ok((
+        web::Json(json!({
+            "filterId": filter_id,
+            "streamName": filter.stream_name,
+            "expr": expr.to_string(),
+        })),
+        StatusCode::OK,
+    ))
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum FiltersError {
     #[error("Failed to connect to storage: {0}")]
@@ -140,6 +171,8 @@ pub enum FiltersError {
     Custom(String),
     #[error(transparent)]
     MetastoreError(#[from] MetastoreError),
+    #[error("{0}")]
+    Compile(#[from] FilterCompileError),
 }
 
 impl actix_web::ResponseError for FiltersError {
@@ -151,6 +184,7 @@ impl actix_web::ResponseError for FiltersError {
             Self::UserDoesNotExist(_) => StatusCode::NOT_FOUND,
             Self::Custom(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::MetastoreError(e) => e.status_code(),
+            Self::Compile(_) => StatusCode::BAD_REQUEST,
         }
     }
 
@@ -16,6 +16,7 @@
  *
  */
 
+pub mod bundles;
 pub mod dashboards;
 pub mod filters;
 
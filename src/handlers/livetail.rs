@@ -26,6 +26,7 @@ use futures::stream::BoxStream;
 use futures_util::{Future, StreamExt, TryFutureExt, TryStreamExt};
 use http_auth_basic::Credentials;
 use rand::distributions::{Alphanumeric, DistString};
+use serde::Deserialize;
 use tonic::metadata::MetadataMap;
 use tonic::transport::{Identity, Server, ServerTlsConfig};
 use tonic::{Request, Response, Status, Streaming};
@@ -39,14 +40,30 @@ use tonic_web::GrpcWebLayer;
 use tower_http::cors::CorsLayer;
 use tracing::{info, warn};
 
-use crate::livetail::{LIVETAIL, Message};
+use crate::audit::{self, AuditQuery, AuditRequest, Outcome};
+use crate::livetail::{LIVETAIL, LiveTailFilter, Message};
 use crate::parseable::PARSEABLE;
+use crate::rbac::api_key::API_KEY_PREFIX;
+use crate::rbac::column_mask::column_masks;
 use crate::rbac::map::SessionKey;
+use crate::rbac::row_filter::row_filter_expr;
 use crate::rbac::{self, Users};
 use crate::utils;
 
 use super::SESSION_COOKIE_NAME;
 
+/// Optional keys of the livetail ticket, next to `stream`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveTailOptions {
+    /// SQL expression as found in a WHERE clause, only matching rows are delivered
+    filter: Option<String>,
+    /// Columns to deliver, all the columns of the stream by default
+    columns: Option<Vec<String>>,
+    /// Fraction of the rows to deliver, between 0 and 1
+    sample_rate: Option<f64>,
+}
+
 #[derive(Clone)]
 pub struct FlightServiceImpl {}
 
@@ -102,44 +119,36 @@ impl FlightService for FlightServiceImpl {
         let ticket: serde_json::Value = serde_json::from_slice(&req.into_inner().ticket)
             .map_err(|err| Status::internal(err.to_string()))?;
         let stream = extract_stream(&ticket).map_err(|e| *e)?;
+        let options: LiveTailOptions = serde_json::from_value(ticket.clone())
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
         info!("livetail requested for stream {}", stream);
-        match Users.authorize(key, rbac::role::Action::Query, Some(stream), None) {
-            rbac::Response::Authorized => (),
-            rbac::Response::UnAuthorized => {
-                return Err(Status::permission_denied(
-                    "user is not authenticated to access this resource",
-                ));
-            }
-            rbac::Response::ReloadRequired => {
-                return Err(Status::unauthenticated("reload required"));
-            }
-        }
-
-        let schema = PARSEABLE
-            .get_stream(stream)
-            .map_err(|err| Status::failed_precondition(err.to_string()))?
-            .get_schema();
-
-        let rx = LIVETAIL.new_pipe(
-            Alphanumeric.sample_string(&mut rand::thread_rng(), 32),
-            stream.to_string(),
-        );
-
-        let adapter_schema = schema.clone();
-        let rx = rx.map(move |x| match x {
-            Message::Record(t) => Ok(utils::arrow::adapt_batch(&adapter_schema, &t)),
-            Message::Skipped(_) => {
-                warn!("livetail channel capacity is full.");
-                Ok(RecordBatch::new_empty(adapter_schema.clone()))
-            }
+        let audit = audit::is_audited(rbac::role::Action::Query).then(|| {
+            AuditRequest::new(
+                None,
+                rbac::role::Action::Query,
+                Some(stream),
+                "POST",
+                "/arrow.flight.protocol.FlightService/DoGet",
+            )
+        });
+        let audit_query = options.filter.clone().map(|sql| AuditQuery {
+            sql,
+            bytes_scanned: Default::default(),
+            streaming: true,
         });
 
-        let rb_stream = FlightDataEncoderBuilder::new()
-            .with_schema(schema)
-            .build(rx);
+        let result = tail(&key, stream, options);
+        if let Some(audit) = audit {
+            let outcome = match &result {
+                Ok(_) => Outcome::Success,
+                Err(status) if status.code() == tonic::Code::PermissionDenied => Outcome::Denied,
+                Err(_) => Outcome::Failure,
+            };
+            let user_id = Users.get_userid_from_session(&key);
+            audit.record(user_id, None, outcome, audit_query.as_ref());
+        }
 
-        let rb_stream = rb_stream.map_err(|err| Status::unknown(err.to_string()));
-        Ok(Response::new(Box::pin(rb_stream)))
+        result.map(Response::new)
     }
 
     async fn do_put(
@@ -171,6 +180,69 @@ impl FlightService for FlightServiceImpl {
     }
 }
 
+/// Opens a pipe of the events ingested into the stream, as allowed by the permissions of the session
+fn tail(
+    key: &SessionKey,
+    stream: &str,
+    options: LiveTailOptions,
+) -> Result<<FlightServiceImpl as FlightService>::DoGetStream, Status> {
+    match Users.authorize(key.clone(), rbac::role::Action::Query, Some(stream), None) {
+        rbac::Response::Authorized => (),
+        rbac::Response::UnAuthorized => {
+            return Err(Status::permission_denied(
+                "user is not authenticated to access this resource",
+            ));
+        }
+        rbac::Response::ReloadRequired => {
+            return Err(Status::unauthenticated("reload required"));
+        }
+    }
+
+    let schema = PARSEABLE
+        .get_stream(stream)
+        .map_err(|err| Status::failed_precondition(err.to_string()))?
+        .get_schema();
+
+    let permissions = Users.get_permissions(key);
+    let filter = LiveTailFilter::new(
+        schema,
+        &column_masks(&permissions, stream),
+        options.filter.as_deref(),
+        options.columns.as_deref(),
+        options.sample_rate,
+    )
+    .map_err(|err| Status::invalid_argument(err.to_string()))?;
+    let filter = match row_filter_expr(&permissions, stream) {
+        Some(row_filter) => filter
+            .with_row_filter(row_filter)
+            .map_err(|err| Status::internal(err.to_string()))?,
+        None => filter,
+    };
+    let schema = filter.output_schema();
+
+    let rx = LIVETAIL.new_pipe(
+        Alphanumeric.sample_string(&mut rand::thread_rng(), 32),
+        stream.to_string(),
+        filter,
+    );
+
+    let adapter_schema = schema.clone();
+    let rx = rx.map(move |x| match x {
+        Message::Record(t) => Ok(utils::arrow::adapt_batch(&adapter_schema, &t)),
+        Message::Skipped(_) => {
+            warn!("livetail channel capacity is full.");
+            Ok(RecordBatch::new_empty(adapter_schema.clone()))
+        }
+    });
+
+    let rb_stream = FlightDataEncoderBuilder::new()
+        .with_schema(schema)
+        .build(rx);
+
+    let rb_stream = rb_stream.map_err(|err| Status::unknown(err.to_string()));
+    Ok(Box::pin(rb_stream))
+}
+
 pub fn server() -> impl Future<Output = Result<(), Box<dyn std::error::Error + Send>>> + Send {
     let mut addr: SocketAddr = PARSEABLE
         .options
@@ -252,6 +324,16 @@ pub fn extract_session_key(headers: &MetadataMap) -> Result<SessionKey, Box<Stat
         return Ok(basic);
     }
 
+    // API key, or the session handed out by the flight handshake
+    if let Some(token) = extract_bearer_token(headers) {
+        if token.starts_with(API_KEY_PREFIX) {
+            return Ok(SessionKey::ApiKey(token.to_owned()));
+        }
+        let session = ulid::Ulid::from_string(token)
+            .map_err(|_| Status::invalid_argument("Bearer token is invalid"))?;
+        return Ok(SessionKey::SessionId(session));
+    }
+
     let session = extract_cookie(headers)
         .map(|cookie| ulid::Ulid::from_string(cookie.value()))
         .transpose()
@@ -273,6 +355,14 @@ fn extract_basic_auth(header: &MetadataMap) -> Option<Credentials> {
         .and_then(|value| Credentials::from_header(value.to_string()).ok())
 }
 
+fn extract_bearer_token(header: &MetadataMap) -> Option<&str> {
+    header
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
 fn extract_cookie(header: &MetadataMap) -> Option<Cookie<'_>> {
     // extract the cookie from the request
     let cookies = header.get_all("cookie");
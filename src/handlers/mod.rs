@@ -21,8 +21,10 @@ use std::fmt::Display;
 use serde::{Deserialize, Serialize};
 
 pub mod airplane;
+pub mod flight_sql;
 pub mod http;
 pub mod livetail;
+pub mod otlp;
 
 pub const STREAM_NAME_HEADER_KEY: &str = "x-p-stream";
 pub const LOG_SOURCE_KEY: &str = "x-p-log-source";
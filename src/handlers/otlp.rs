@@ -0,0 +1,308 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! OTLP gRPC receiver, so that collectors using the default gRPC exporter can ship
+//! logs, metrics and traces directly. Requests carry the same `x-p-stream` and
+//! `x-p-log-source` metadata as the OTLP/HTTP endpoints.
+
+use std::net::SocketAddr;
+
+use actix_web::{ResponseError, http::StatusCode};
+use futures_util::{Future, TryFutureExt};
+use opentelemetry_proto::tonic::collector::{
+    logs::v1::{
+        ExportLogsServiceRequest, ExportLogsServiceResponse,
+        logs_service_server::{LogsService, LogsServiceServer},
+    },
+    metrics::v1::{
+        ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+        metrics_service_server::{MetricsService, MetricsServiceServer},
+    },
+    trace::v1::{
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+        trace_service_server::{TraceService, TraceServiceServer},
+    },
+};
+use tonic::codec::CompressionEncoding;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+
+use crate::event::FORMAT_KEY;
+use crate::event::format::LogSource;
+use crate::handlers::http::ingest::{PostError, prepare_otel_stream};
+use crate::handlers::http::max_event_payload_size;
+use crate::handlers::http::modal::utils::ingest_utils::{
+    get_custom_fields_from_metadata, push_otel_records,
+};
+use crate::handlers::livetail::extract_session_key;
+use crate::handlers::{LOG_SOURCE_KEY, STREAM_NAME_HEADER_KEY, TelemetryType};
+use crate::otel::logs::{OTEL_LOG_KNOWN_FIELD_LIST, flatten_otel_protobuf};
+use crate::otel::metrics::{OTEL_METRICS_KNOWN_FIELD_LIST, flatten_otel_metrics_protobuf};
+use crate::otel::traces::{OTEL_TRACES_KNOWN_FIELD_LIST, flatten_otel_traces_protobuf};
+use crate::parseable::PARSEABLE;
+use crate::rbac::{self, Users};
+use crate::utils::header_parsing::ParseHeaderError;
+
+#[derive(Clone, Debug, Default)]
+pub struct OtlpReceiver;
+
+#[tonic::async_trait]
+impl LogsService for OtlpReceiver {
+    async fn export(
+        &self,
+        req: Request<ExportLogsServiceRequest>,
+    ) -> Result<Response<ExportLogsServiceResponse>, Status> {
+        let (stream_name, log_source) = setup_stream(
+            &req,
+            LogSource::OtelLogs,
+            &OTEL_LOG_KNOWN_FIELD_LIST,
+            TelemetryType::Logs,
+        )
+        .await?;
+        let records = flatten_otel_protobuf(req.get_ref());
+        push(
+            &req,
+            records,
+            &stream_name,
+            &log_source,
+            TelemetryType::Logs,
+        )
+        .await?;
+
+        Ok(Response::new(ExportLogsServiceResponse {
+            partial_success: None,
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl MetricsService for OtlpReceiver {
+    async fn export(
+        &self,
+        req: Request<ExportMetricsServiceRequest>,
+    ) -> Result<Response<ExportMetricsServiceResponse>, Status> {
+        let (stream_name, log_source) = setup_stream(
+            &req,
+            LogSource::OtelMetrics,
+            &OTEL_METRICS_KNOWN_FIELD_LIST,
+            TelemetryType::Metrics,
+        )
+        .await?;
+        let records = flatten_otel_metrics_protobuf(req.get_ref());
+        push(
+            &req,
+            records,
+            &stream_name,
+            &log_source,
+            TelemetryType::Metrics,
+        )
+        .await?;
+
+        Ok(Response::new(ExportMetricsServiceResponse {
+            partial_success: None,
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl TraceService for OtlpReceiver {
+    async fn export(
+        &self,
+        req: Request<ExportTraceServiceRequest>,
+    ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+        let (stream_name, log_source) = setup_stream(
+            &req,
+            LogSource::OtelTraces,
+            &OTEL_TRACES_KNOWN_FIELD_LIST,
+            TelemetryType::Traces,
+        )
+        .await?;
+        let records = flatten_otel_traces_protobuf(req.get_ref());
+        push(
+            &req,
+            records,
+            &stream_name,
+            &log_source,
+            TelemetryType::Traces,
+        )
+        .await?;
+
+        Ok(Response::new(ExportTraceServiceResponse {
+            partial_success: None,
+        }))
+    }
+}
+
+/// Authorizes the request to ingest into the stream named in its metadata, then creates the
+/// stream if needed. The log source defaults to the one expected by the service when missing.
+async fn setup_stream<T>(
+    req: &Request<T>,
+    expected_log_source: LogSource,
+    known_fields: &[&str],
+    telemetry_type: TelemetryType,
+) -> Result<(String, LogSource), Status> {
+    let key =
+        extract_session_key(req.metadata()).map_err(|e| Status::unauthenticated(e.to_string()))?;
+
+    let stream_name = req
+        .metadata()
+        .get(STREAM_NAME_HEADER_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| Status::invalid_argument(ParseHeaderError::MissingStreamName.to_string()))?
+        .to_owned();
+    let log_source = req
+        .metadata()
+        .get(LOG_SOURCE_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(LogSource::from)
+        .unwrap_or_else(|| expected_log_source.clone());
+
+    match Users.authorize(key, rbac::role::Action::Ingest, Some(&stream_name), None) {
+        rbac::Response::Authorized => (),
+        rbac::Response::UnAuthorized => {
+            return Err(Status::permission_denied(
+                "user is not authorized to access this resource",
+            ));
+        }
+        rbac::Response::ReloadRequired => {
+            return Err(Status::unauthenticated("reload required"));
+        }
+    }
+
+    if PARSEABLE
+        .streams
+        .list_internal_streams()
+        .contains(&stream_name)
+    {
+        return Err(into_status(PostError::InternalStream(stream_name)));
+    }
+
+    let (stream_name, log_source, ..) = prepare_otel_stream(
+        stream_name,
+        log_source,
+        expected_log_source,
+        known_fields,
+        telemetry_type,
+    )
+    .await
+    .map_err(into_status)?;
+
+    Ok((stream_name, log_source))
+}
+
+async fn push<T>(
+    req: &Request<T>,
+    records: Vec<serde_json::Value>,
+    stream_name: &str,
+    log_source: &LogSource,
+    telemetry_type: TelemetryType,
+) -> Result<(), Status> {
+    let mut p_custom_fields = get_custom_fields_from_metadata(req);
+    p_custom_fields.insert(FORMAT_KEY.to_string(), log_source.to_string());
+
+    push_otel_records(
+        records,
+        stream_name,
+        log_source,
+        &p_custom_fields,
+        telemetry_type,
+    )
+    .await
+    .map_err(into_status)
+}
+
+/// Maps the HTTP status of an ingestion error onto the closest gRPC status
+fn into_status(err: PostError) -> Status {
+    let message = err.to_string();
+    match err.status_code() {
+        StatusCode::BAD_REQUEST => Status::invalid_argument(message),
+        StatusCode::UNAUTHORIZED => Status::unauthenticated(message),
+        StatusCode::FORBIDDEN => Status::permission_denied(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        _ => Status::internal(message),
+    }
+}
+
+pub fn server() -> impl Future<Output = Result<(), Box<dyn std::error::Error + Send>>> + Send {
+    let mut addr: SocketAddr = PARSEABLE
+        .options
+        .address
+        .parse()
+        .unwrap_or_else(|err| panic!("{}, failed to parse `{}` as a socket address. Please set the environment variable `P_ADDR` to `<ip address>:<port>` without the scheme (e.g., 192.168.1.1:8000). Please refer to the documentation: https://logg.ing/env for more details.", PARSEABLE.options.address, err));
+    addr.set_port(PARSEABLE.options.otlp_grpc_port);
+
+    // decoded requests are held in memory, the same as the payloads of HTTP OTLP
+    let max_decoding_message_size = max_event_payload_size();
+    let logs = LogsServiceServer::new(OtlpReceiver)
+        .max_decoding_message_size(max_decoding_message_size)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd);
+    let metrics = MetricsServiceServer::new(OtlpReceiver)
+        .max_decoding_message_size(max_decoding_message_size)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd);
+    let traces = TraceServiceServer::new(OtlpReceiver)
+        .max_decoding_message_size(max_decoding_message_size)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd);
+
+    let identity = match (
+        &PARSEABLE.options.tls_cert_path,
+        &PARSEABLE.options.tls_key_path,
+    ) {
+        (Some(cert), Some(key)) => {
+            match (std::fs::read_to_string(cert), std::fs::read_to_string(key)) {
+                (Ok(cert_file), Ok(key_file)) => Some(Identity::from_pem(cert_file, key_file)),
+                _ => None,
+            }
+        }
+        (_, _) => None,
+    };
+
+    let mut server = Server::builder();
+    if let Some(identity) = identity {
+        server = match Server::builder().tls_config(ServerTlsConfig::new().identity(identity)) {
+            Ok(server) => server,
+            Err(_) => Server::builder(),
+        };
+    }
+
+    server
+        .add_service(logs)
+        .add_service(metrics)
+        .add_service(traces)
+        .serve(addr)
+        .map_err(|err| Box::new(err) as Box<dyn std::error::Error + Send>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parseable::StreamNotFound;
+
+    #[test]
+    fn maps_ingestion_errors_to_grpc_codes() {
+        let status = into_status(StreamNotFound("app".to_owned()).into());
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        let status = into_status(PostError::Header(ParseHeaderError::MissingStreamName));
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -17,18 +17,29 @@
  */
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     sync::{Arc, RwLock, Weak},
     task::Poll,
 };
 
+use arrow::array::AsArray;
+use arrow_array::{BooleanArray, RecordBatch, RecordBatchOptions};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use arrow_select::filter::filter_record_batch;
+use datafusion::common::{Column, DFSchema};
+use datafusion::error::DataFusionError;
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::prelude::{Expr, SessionContext};
 use futures_util::Stream;
+use once_cell::sync::Lazy;
+use rand::Rng;
 use tokio::sync::mpsc::{
     self, Receiver, Sender, UnboundedReceiver, UnboundedSender, error::TrySendError,
 };
+use tracing::warn;
 
-use arrow_array::RecordBatch;
-use once_cell::sync::Lazy;
+use crate::rbac::column_mask::ColumnMask;
+use crate::utils::arrow::adapt_batch;
 
 pub static LIVETAIL: Lazy<LiveTail> = Lazy::new(LiveTail::default);
 
@@ -39,8 +50,8 @@ pub struct LiveTail {
 }
 
 impl LiveTail {
-    pub fn new_pipe(&self, id: String, stream: String) -> ReceiverPipe {
-        let (sender, revc) = channel(id, stream.clone(), Arc::downgrade(&self.pipes));
+    pub fn new_pipe(&self, id: String, stream: String, filter: LiveTailFilter) -> ReceiverPipe {
+        let (sender, revc) = channel(id, stream.clone(), filter, Arc::downgrade(&self.pipes));
         self.pipes
             .write()
             .unwrap()
@@ -56,7 +67,7 @@ impl LiveTail {
             return;
         };
         for pipe in pipes {
-            pipe.send(rb.clone())
+            pipe.send(rb)
         }
     }
 }
@@ -69,6 +80,217 @@ impl Default for LiveTail {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum LiveTailError {
+    #[error("Invalid filter: {0}")]
+    Filter(#[from] DataFusionError),
+    #[error("Filter must be a boolean expression, got {0}")]
+    NotBoolean(DataType),
+    #[error("Column {0} is not part of the stream")]
+    UnknownColumn(String),
+    #[error("Sample rate must be greater than 0 and at most 1, got {0}")]
+    SampleRate(f64),
+}
+
+/// Rows and columns of the stream a pipe is interested in, applied before a batch
+/// is sent down the pipe so that the channel only fills up with what gets delivered
+pub struct LiveTailFilter {
+    /// Schema of the stream when the pipe was opened
+    schema: SchemaRef,
+    /// row filters of the user, the rows outside of which are never delivered
+    row_filter: Option<Arc<dyn PhysicalExpr>>,
+    /// values of the columns the user can see, once masked
+    masks: Option<Vec<Arc<dyn PhysicalExpr>>>,
+    /// Schema of the stream as seen by the user, the predicate is evaluated against it
+    visible_schema: SchemaRef,
+    predicate: Option<Arc<dyn PhysicalExpr>>,
+    projection: Option<Vec<usize>>,
+    sample_rate: Option<f64>,
+}
+
+impl LiveTailFilter {
+    /// `masks` are the column masks of the user, `filter` is a SQL expression as found in a
+    /// WHERE clause, e.g. `status >= 500`, over the masked columns
+    pub fn new(
+        schema: SchemaRef,
+        masks: &BTreeMap<String, ColumnMask>,
+        filter: Option<&str>,
+        columns: Option<&[String]>,
+        sample_rate: Option<f64>,
+    ) -> Result<Self, LiveTailError> {
+        let (masks, visible_schema) = if masks.is_empty() {
+            (None, schema.clone())
+        } else {
+            let (masks, visible_schema) = mask_columns(&schema, masks)?;
+            (Some(masks), visible_schema)
+        };
+
+        let predicate = match filter.map(str::trim).filter(|filter| !filter.is_empty()) {
+            Some(filter) => {
+                let df_schema = DFSchema::try_from(visible_schema.as_ref().clone())?;
+                let ctx = SessionContext::new();
+                let expr = ctx.parse_sql_expr(filter, &df_schema)?;
+                let predicate = ctx.create_physical_expr(expr, &df_schema)?;
+                match predicate.data_type(&visible_schema)? {
+                    DataType::Boolean => Some(predicate),
+                    data_type => return Err(LiveTailError::NotBoolean(data_type)),
+                }
+            }
+            None => None,
+        };
+
+        let projection = columns
+            .filter(|columns| !columns.is_empty())
+            .map(|columns| {
+                columns
+                    .iter()
+                    .map(|column| {
+                        visible_schema
+                            .index_of(column)
+                            .map_err(|_| LiveTailError::UnknownColumn(column.to_owned()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+
+        if let Some(rate) = sample_rate
+            && !(rate > 0.0 && rate <= 1.0)
+        {
+            return Err(LiveTailError::SampleRate(rate));
+        }
+
+        Ok(Self {
+            schema,
+            row_filter: None,
+            masks,
+            visible_schema,
+            predicate,
+            projection,
+            sample_rate: sample_rate.filter(|rate| *rate < 1.0),
+        })
+    }
+
+    /// Only delivers the rows matching the row filter, a predicate over the fields of the stream
+    pub fn with_row_filter(mut self, row_filter: Expr) -> Result<Self, LiveTailError> {
+        let df_schema = DFSchema::try_from(self.schema.as_ref().clone())?;
+        self.row_filter = Some(SessionContext::new().create_physical_expr(row_filter, &df_schema)?);
+        Ok(self)
+    }
+
+    /// Schema of the batches coming out of the filter
+    pub fn output_schema(&self) -> SchemaRef {
+        match &self.projection {
+            Some(projection) => Arc::new(
+                self.visible_schema
+                    .project(projection)
+                    .expect("projection is validated against the schema"),
+            ),
+            None => self.visible_schema.clone(),
+        }
+    }
+
+    /// Returns the rows and columns of the batch to deliver, if any
+    pub fn apply(&self, rb: &RecordBatch) -> Option<RecordBatch> {
+        if self.predicate.is_none()
+            && self.row_filter.is_none()
+            && self.masks.is_none()
+            && self.projection.is_none()
+            && self.sample_rate.is_none()
+        {
+            return Some(rb.clone());
+        }
+
+        let mut rb = adapt_batch(&self.schema, rb);
+        if let Some(row_filter) = &self.row_filter {
+            rb = evaluate(row_filter, &rb)
+                .inspect_err(|err| warn!("livetail row filter failed to evaluate: {err}"))
+                .ok()?;
+        }
+        if let Some(masks) = &self.masks {
+            rb = mask(masks, &self.visible_schema, &rb)
+                .inspect_err(|err| warn!("livetail column masks failed to evaluate: {err}"))
+                .ok()?;
+        }
+        if let Some(predicate) = &self.predicate {
+            rb = evaluate(predicate, &rb)
+                .inspect_err(|err| warn!("livetail filter failed to evaluate: {err}"))
+                .ok()?;
+        }
+
+        if let Some(rate) = self.sample_rate {
+            let mut rng = rand::thread_rng();
+            let mask: BooleanArray = (0..rb.num_rows())
+                .map(|_| Some(rng.gen_bool(rate)))
+                .collect();
+            rb = filter_record_batch(&rb, &mask).expect("mask has as many rows as the batch");
+        }
+
+        if let Some(projection) = &self.projection {
+            rb = rb
+                .project(projection)
+                .expect("projection is validated against the schema");
+        }
+
+        (rb.num_rows() > 0).then_some(rb)
+    }
+}
+
+/// Expressions turning batches of the stream into batches of the columns the user
+/// can see, along with the schema of the latter
+fn mask_columns(
+    schema: &SchemaRef,
+    masks: &BTreeMap<String, ColumnMask>,
+) -> Result<(Vec<Arc<dyn PhysicalExpr>>, SchemaRef), DataFusionError> {
+    let df_schema = DFSchema::try_from(schema.as_ref().clone())?;
+    let ctx = SessionContext::new();
+    let mut exprs = vec![];
+    let mut fields = vec![];
+    for field in schema.fields() {
+        let column = Expr::Column(Column::from_name(field.name()));
+        match masks.get(field.name()) {
+            Some(ColumnMask::Hide) => {}
+            Some(mask) => {
+                let expr = mask.apply(column, field.data_type())?;
+                let expr = ctx.create_physical_expr(expr, &df_schema)?;
+                fields.push(Field::new(field.name(), expr.data_type(schema)?, true));
+                exprs.push(expr);
+            }
+            None => {
+                fields.push(field.as_ref().clone());
+                exprs.push(ctx.create_physical_expr(column, &df_schema)?);
+            }
+        }
+    }
+
+    Ok((exprs, Arc::new(Schema::new(fields))))
+}
+
+fn mask(
+    masks: &[Arc<dyn PhysicalExpr>],
+    schema: &SchemaRef,
+    rb: &RecordBatch,
+) -> Result<RecordBatch, DataFusionError> {
+    let columns = masks
+        .iter()
+        .map(|mask| mask.evaluate(rb)?.into_array(rb.num_rows()))
+        .collect::<Result<Vec<_>, _>>()?;
+    // all of the columns may be hidden
+    let options = RecordBatchOptions::new().with_row_count(Some(rb.num_rows()));
+    Ok(RecordBatch::try_new_with_options(
+        schema.clone(),
+        columns,
+        &options,
+    )?)
+}
+
+fn evaluate(
+    predicate: &Arc<dyn PhysicalExpr>,
+    rb: &RecordBatch,
+) -> Result<RecordBatch, DataFusionError> {
+    let mask = predicate.evaluate(rb)?.into_array(rb.num_rows())?;
+    Ok(filter_record_batch(rb, mask.as_boolean())?)
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Message {
     Record(RecordBatch),
@@ -85,10 +307,14 @@ pub struct SenderPipe {
     pub id: Id,
     inner: Sender<RecordBatch>,
     command: UnboundedSender<Command>,
+    filter: LiveTailFilter,
 }
 
 impl SenderPipe {
-    pub fn send(&self, rb: RecordBatch) {
+    pub fn send(&self, rb: &RecordBatch) {
+        let Some(rb) = self.filter.apply(rb) else {
+            return;
+        };
         if let Err(TrySendError::Full(rb)) = self.inner.try_send(rb) {
             self.command
                 .send(Command::Skipping(rb.num_rows()))
@@ -108,6 +334,7 @@ pub struct ReceiverPipe {
 fn channel(
     id: String,
     stream: String,
+    filter: LiveTailFilter,
     weak_ptr: Weak<LiveTailRegistry>,
 ) -> (SenderPipe, ReceiverPipe) {
     let (command_tx, command_rx) = mpsc::unbounded_channel::<Command>();
@@ -118,6 +345,7 @@ fn channel(
             id: id.clone(),
             inner: rb_tx,
             command: command_tx,
+            filter,
         },
         ReceiverPipe {
             id,
@@ -168,3 +396,122 @@ impl Drop for ReceiverPipe {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int64Array, StringArray};
+    use arrow_schema::{Field, Schema};
+    use datafusion::prelude::{col, lit};
+
+    use super::*;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("status", DataType::Int64, true),
+            Field::new("msg", DataType::Utf8, true),
+            Field::new("host", DataType::Utf8, true),
+        ]))
+    }
+
+    fn no_masks() -> BTreeMap<String, ColumnMask> {
+        BTreeMap::new()
+    }
+
+    fn batch() -> RecordBatch {
+        // events need not hold all the fields of the stream
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("msg", DataType::Utf8, true),
+            Field::new("status", DataType::Int64, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["ok", "boom", "fail"])),
+                Arc::new(Int64Array::from(vec![200, 500, 503])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn filters_and_projects() {
+        let columns = ["msg".to_owned()];
+        let filter = LiveTailFilter::new(
+            schema(),
+            &no_masks(),
+            Some("status >= 500"),
+            Some(&columns),
+            None,
+        )
+        .unwrap();
+        assert_eq!(filter.output_schema().fields().len(), 1);
+
+        let rb = filter.apply(&batch()).unwrap();
+        assert_eq!(rb.schema(), filter.output_schema());
+        assert_eq!(
+            rb.column(0).as_string::<i32>().iter().collect::<Vec<_>>(),
+            vec![Some("boom"), Some("fail")]
+        );
+
+        let filter =
+            LiveTailFilter::new(schema(), &no_masks(), Some("status = 404"), None, None).unwrap();
+        assert!(filter.apply(&batch()).is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert!(LiveTailFilter::new(schema(), &no_masks(), Some("status +"), None, None).is_err());
+        assert!(
+            LiveTailFilter::new(schema(), &no_masks(), Some("status + 1"), None, None).is_err()
+        );
+        assert!(
+            LiveTailFilter::new(
+                schema(),
+                &no_masks(),
+                None,
+                Some(&["missing".to_owned()]),
+                None
+            )
+            .is_err()
+        );
+        assert!(LiveTailFilter::new(schema(), &no_masks(), None, None, Some(0.0)).is_err());
+        assert!(LiveTailFilter::new(schema(), &no_masks(), None, None, Some(1.5)).is_err());
+    }
+
+    #[test]
+    fn applies_row_filter() {
+        let filter = LiveTailFilter::new(schema(), &no_masks(), Some("status >= 500"), None, None)
+            .unwrap()
+            .with_row_filter(col("msg").eq(lit("fail")))
+            .unwrap();
+
+        let rb = filter.apply(&batch()).unwrap();
+        assert_eq!(rb.num_rows(), 1);
+        assert_eq!(rb.column(1).as_string::<i32>().value(0), "fail");
+    }
+
+    #[test]
+    fn masks_columns() {
+        let masks = BTreeMap::from([
+            ("host".to_owned(), ColumnMask::Hide),
+            ("msg".to_owned(), ColumnMask::Partial { keep_last: 2 }),
+        ]);
+        let filter =
+            LiveTailFilter::new(schema(), &masks, Some("msg = '**il'"), None, None).unwrap();
+        assert!(filter.output_schema().field_with_name("host").is_err());
+
+        let rb = filter.apply(&batch()).unwrap();
+        assert_eq!(rb.num_rows(), 1);
+        assert_eq!(rb.column(1).as_string::<i32>().value(0), "**il");
+
+        let columns = ["host".to_owned()];
+        assert!(LiveTailFilter::new(schema(), &masks, None, Some(&columns), None).is_err());
+        assert!(LiveTailFilter::new(schema(), &masks, Some("host = 'a'"), None, None).is_err());
+    }
+
+    #[test]
+    fn passes_everything_through_by_default() {
+        let filter = LiveTailFilter::new(schema(), &no_masks(), None, None, Some(1.0)).unwrap();
+        assert_eq!(filter.apply(&batch()).unwrap(), batch());
+    }
+}
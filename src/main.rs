@@ -66,6 +66,9 @@ async fn main() -> anyhow::Result<()> {
     banner::print(&PARSEABLE, &metadata).await;
     // initialize the rbac map
     rbac::map::init(&metadata);
+    if let Err(e) = rbac::api_key::API_KEYS.load().await {
+        warn!("Failed to load API keys: {:?}", e);
+    }
     // keep metadata info in mem
     metadata.set_global();
 
@@ -25,6 +25,7 @@ use std::sync::Arc;
 
 use crate::catalog::snapshot::ManifestItem;
 use crate::event::format::LogSourceEntry;
+use crate::event::pipeline::Pipeline;
 use crate::handlers::TelemetryType;
 use crate::hottier::StreamHotTier;
 use crate::metrics::{
@@ -32,6 +33,7 @@ use crate::metrics::{
     EVENTS_STORAGE_SIZE_DATE, LIFETIME_EVENTS_INGESTED, LIFETIME_EVENTS_INGESTED_SIZE,
 };
 use crate::storage::StreamType;
+use crate::storage::bloom_filter::BloomFilterConfig;
 use crate::storage::retention::Retention;
 
 pub fn update_stats(
@@ -92,6 +94,8 @@ pub struct LogStreamMetadata {
     pub stream_type: StreamType,
     pub log_source: Vec<LogSourceEntry>,
     pub telemetry_type: TelemetryType,
+    pub bloom_filter: Option<BloomFilterConfig>,
+    pub pipeline: Option<Pipeline>,
 }
 
 impl LogStreamMetadata {
@@ -23,6 +23,7 @@ use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use erased_serde::Serialize as ErasedSerialize;
+use object_store::UpdateVersion;
 use tonic::async_trait;
 use ulid::Ulid;
 
@@ -35,6 +36,7 @@ use crate::{
     handlers::http::modal::NodeType,
     metastore::MetastoreError,
     option::Mode,
+    rbac::api_key::ApiKey,
     users::filters::Filter,
 };
 
@@ -94,11 +96,28 @@ pub trait Metastore: std::fmt::Debug + Send + Sync {
     async fn put_target(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError>;
     async fn delete_target(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError>;
 
+    /// api keys
+    async fn get_api_keys(&self) -> Result<Vec<ApiKey>, MetastoreError>;
+    async fn get_api_key(&self, key_id: &Ulid) -> Result<Option<ApiKey>, MetastoreError>;
+    async fn put_api_key(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError>;
+
     /// dashboards
     async fn get_dashboards(&self) -> Result<Vec<Bytes>, MetastoreError>;
     async fn put_dashboard(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError>;
     async fn delete_dashboard(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError>;
 
+    /// report runs
+    async fn get_report_runs(&self, dashboard_id: &Ulid) -> Result<Vec<Bytes>, MetastoreError>;
+    async fn put_report_run(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError>;
+    async fn get_report_lease(&self, dashboard_id: &Ulid) -> Result<Option<Bytes>, MetastoreError>;
+    async fn put_report_lease(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError>;
+
+    /// files replaced by compaction or retention, deleted after a grace period
+    async fn get_deferred_deletes(&self) -> Result<Vec<Bytes>, MetastoreError>;
+    async fn put_deferred_delete(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError>;
+    async fn delete_deferred_delete(&self, obj: &dyn MetastoreObject)
+    -> Result<(), MetastoreError>;
+
     /// chats
     async fn get_chats(&self) -> Result<DashMap<String, Vec<Bytes>>, MetastoreError>;
     async fn put_chat(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError>;
@@ -159,6 +178,18 @@ pub trait Metastore: std::fmt::Debug + Send + Sync {
         lower_bound: DateTime<Utc>,
         upper_bound: DateTime<Utc>,
     ) -> Result<(), MetastoreError>;
+    /// Fetch a manifest along with its version, for `put_manifest_if_unchanged`
+    async fn get_manifest_versioned(
+        &self,
+        manifest_url: &str,
+    ) -> Result<Option<(Manifest, UpdateVersion)>, MetastoreError>;
+    /// Put a manifest only if no other writer updated it since it was read at `version`
+    async fn put_manifest_if_unchanged(
+        &self,
+        obj: &dyn MetastoreObject,
+        manifest_url: &str,
+        version: UpdateVersion,
+    ) -> Result<(), MetastoreError>;
     async fn delete_manifest(
         &self,
         stream_name: &str,
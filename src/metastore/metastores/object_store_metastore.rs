@@ -26,6 +26,7 @@ use arrow_schema::Schema;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
+use object_store::UpdateVersion;
 use relative_path::RelativePathBuf;
 use tonic::async_trait;
 use tracing::warn;
@@ -47,13 +48,16 @@ use crate::{
     },
     option::Mode,
     parseable::PARSEABLE,
+    rbac::api_key::ApiKey,
     storage::{
-        ALERTS_ROOT_DIRECTORY, ObjectStorage, ObjectStorageError, PARSEABLE_ROOT_DIRECTORY,
+        ALERTS_ROOT_DIRECTORY, API_KEYS_ROOT_DIRECTORY, DEFERRED_DELETES_ROOT_DIRECTORY,
+        ObjectStorage, ObjectStorageError, PARSEABLE_ROOT_DIRECTORY, REPORTS_ROOT_DIRECTORY,
         SETTINGS_ROOT_DIRECTORY, STREAM_METADATA_FILE_NAME, STREAM_ROOT_DIRECTORY,
         TARGETS_ROOT_DIRECTORY,
         object_storage::{
-            alert_json_path, alert_state_json_path, filter_path, manifest_path, mttr_json_path,
-            parseable_json_path, schema_path, stream_json_path, to_bytes,
+            alert_json_path, alert_state_json_path, api_key_json_path, filter_path, manifest_path,
+            mttr_json_path, parseable_json_path, report_lease_json_path, schema_path,
+            stream_json_path, to_bytes,
         },
     },
     users::filters::{Filter, migrate_v1_v2},
@@ -404,6 +408,77 @@ impl Metastore for ObjectStoreMetastore {
             .await?)
     }
 
+    /// Fetch all runs of a scheduled report
+    async fn get_report_runs(&self, dashboard_id: &Ulid) -> Result<Vec<Bytes>, MetastoreError> {
+        let runs_path = RelativePathBuf::from_iter([
+            SETTINGS_ROOT_DIRECTORY,
+            REPORTS_ROOT_DIRECTORY,
+            &dashboard_id.to_string(),
+        ]);
+        let runs = self
+            .storage
+            .get_objects(
+                Some(&runs_path),
+                Box::new(|file_name| file_name.ends_with(".json")),
+            )
+            .await?;
+
+        Ok(runs)
+    }
+
+    /// Save the run of a scheduled report
+    async fn put_report_run(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError> {
+        let path = RelativePathBuf::from(obj.get_object_path());
+        Ok(self.storage.put_object(&path, to_bytes(obj)).await?)
+    }
+
+    /// Fetch the lease on the runs of a scheduled report
+    async fn get_report_lease(&self, dashboard_id: &Ulid) -> Result<Option<Bytes>, MetastoreError> {
+        match self
+            .storage
+            .get_object(&report_lease_json_path(dashboard_id))
+            .await
+        {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(ObjectStorageError::NoSuchKey(_)) => Ok(None),
+            Err(e) => Err(MetastoreError::ObjectStorageError(e)),
+        }
+    }
+
+    /// Save the lease on the runs of a scheduled report
+    async fn put_report_lease(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError> {
+        let path = RelativePathBuf::from(obj.get_object_path());
+        Ok(self.storage.put_object(&path, to_bytes(obj)).await?)
+    }
+
+    /// Fetch all the files waiting to be deleted
+    async fn get_deferred_deletes(&self) -> Result<Vec<Bytes>, MetastoreError> {
+        let path =
+            RelativePathBuf::from_iter([PARSEABLE_ROOT_DIRECTORY, DEFERRED_DELETES_ROOT_DIRECTORY]);
+        Ok(self
+            .storage
+            .get_objects(
+                Some(&path),
+                Box::new(|file_name| file_name.ends_with(".json")),
+            )
+            .await?)
+    }
+
+    /// Save files to be deleted after a grace period
+    async fn put_deferred_delete(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError> {
+        let path = RelativePathBuf::from(obj.get_object_path());
+        Ok(self.storage.put_object(&path, to_bytes(obj)).await?)
+    }
+
+    /// Forget files once they were deleted
+    async fn delete_deferred_delete(
+        &self,
+        obj: &dyn MetastoreObject,
+    ) -> Result<(), MetastoreError> {
+        let path = RelativePathBuf::from(obj.get_object_path());
+        Ok(self.storage.delete_object(&path).await?)
+    }
+
     /// Fetch all chats
     async fn get_chats(&self) -> Result<DashMap<String, Vec<Bytes>>, MetastoreError> {
         let all_user_chats = DashMap::new();
@@ -763,6 +838,35 @@ impl Metastore for ObjectStoreMetastore {
         Ok(self.storage.put_object(&path, to_bytes(obj)).await?)
     }
 
+    /// Fetch a `Manifest` file along with its version
+    async fn get_manifest_versioned(
+        &self,
+        manifest_url: &str,
+    ) -> Result<Option<(Manifest, UpdateVersion)>, MetastoreError> {
+        match self
+            .storage
+            .get_object_versioned(&RelativePathBuf::from(manifest_url))
+            .await
+        {
+            Ok((bytes, version)) => Ok(Some((serde_json::from_slice(&bytes)?, version))),
+            Err(ObjectStorageError::NoSuchKey(_)) => Ok(None),
+            Err(err) => Err(MetastoreError::ObjectStorageError(err)),
+        }
+    }
+
+    /// Put a `Manifest` file unless it was updated since it was read at `version`
+    async fn put_manifest_if_unchanged(
+        &self,
+        obj: &dyn MetastoreObject,
+        manifest_url: &str,
+        version: UpdateVersion,
+    ) -> Result<(), MetastoreError> {
+        Ok(self
+            .storage
+            .put_object_if_unchanged(&RelativePathBuf::from(manifest_url), to_bytes(obj), version)
+            .await?)
+    }
+
     async fn delete_manifest(
         &self,
         stream_name: &str,
@@ -816,6 +920,42 @@ impl Metastore for ObjectStoreMetastore {
             .await?)
     }
 
+    async fn get_api_keys(&self) -> Result<Vec<ApiKey>, MetastoreError> {
+        let keys_path =
+            RelativePathBuf::from_iter([SETTINGS_ROOT_DIRECTORY, API_KEYS_ROOT_DIRECTORY]);
+        let keys = self
+            .storage
+            .get_objects(
+                Some(&keys_path),
+                Box::new(|file_name| file_name.ends_with(".json")),
+            )
+            .await?
+            .iter()
+            .filter_map(|bytes| {
+                serde_json::from_slice(bytes)
+                    .inspect_err(|err| warn!("Expected compatible json, error = {err}"))
+                    .ok()
+            })
+            .collect();
+
+        Ok(keys)
+    }
+
+    async fn get_api_key(&self, key_id: &Ulid) -> Result<Option<ApiKey>, MetastoreError> {
+        match self.storage.get_object(&api_key_json_path(key_id)).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)
+                .inspect_err(|err| warn!("Expected compatible json, error = {err}"))
+                .ok()),
+            Err(ObjectStorageError::NoSuchKey(_)) => Ok(None),
+            Err(e) => Err(MetastoreError::ObjectStorageError(e)),
+        }
+    }
+
+    async fn put_api_key(&self, obj: &dyn MetastoreObject) -> Result<(), MetastoreError> {
+        let path = RelativePathBuf::from(obj.get_object_path());
+        Ok(self.storage.put_object(&path, to_bytes(obj)).await?)
+    }
+
     async fn get_all_schemas(&self, stream_name: &str) -> Result<Vec<Schema>, MetastoreError> {
         let path_prefix =
             relative_path::RelativePathBuf::from(format!("{stream_name}/{STREAM_ROOT_DIRECTORY}"));
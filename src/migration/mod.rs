@@ -371,6 +371,8 @@ async fn setup_logstream_metadata(
         stream_type,
         log_source,
         telemetry_type,
+        bloom_filter,
+        pipeline,
         ..
     } = serde_json::from_value(stream_metadata_value).unwrap_or_default();
 
@@ -407,6 +409,8 @@ async fn setup_logstream_metadata(
         stream_type,
         log_source,
         telemetry_type,
+        bloom_filter,
+        pipeline,
     };
 
     Ok(metadata)
@@ -381,6 +381,8 @@ impl Parseable {
         // Set hot tier fields from the stored metadata
         metadata.hot_tier_enabled = hot_tier_enabled;
         metadata.hot_tier.clone_from(&hot_tier);
+        metadata.bloom_filter = stream_metadata.bloom_filter;
+        metadata.pipeline = stream_metadata.pipeline;
 
         let ingestor_id = INGESTOR_META
             .get()
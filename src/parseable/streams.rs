@@ -51,12 +51,15 @@ use crate::{
     event::{
         DEFAULT_TIMESTAMP_KEY,
         format::{LogSource, LogSourceEntry},
+        pipeline::Pipeline,
     },
     hottier::StreamHotTier,
     metadata::{LogStreamMetadata, SchemaVersion},
     metrics,
     option::Mode,
-    storage::{StreamType, object_storage::to_bytes, retention::Retention},
+    storage::{
+        StreamType, bloom_filter::BloomFilterConfig, object_storage::to_bytes, retention::Retention,
+    },
     utils::time::{Minute, TimeRange},
 };
 
@@ -531,7 +534,7 @@ impl Stream {
         writer.disk.retain(|_, w| !forced && w.is_current());
     }
 
-    fn parquet_writer_props(
+    pub fn parquet_writer_props(
         &self,
         merged_schema: &Schema,
         time_partition: Option<&String>,
@@ -574,6 +577,11 @@ impl Stream {
             }
         }
 
+        // Bloom filters and page statistics for point lookups on high-cardinality columns
+        if let Some(bloom_filter) = self.get_bloom_filter() {
+            props = bloom_filter.apply(props, merged_schema);
+        }
+
         // Set sorting columns
         props.set_sorting_columns(Some(sorting_column_vec)).build()
     }
@@ -874,6 +882,26 @@ impl Stream {
         self.metadata.write().expect(LOCK_EXPECT).retention = Some(retention);
     }
 
+    pub fn get_bloom_filter(&self) -> Option<BloomFilterConfig> {
+        self.metadata
+            .read()
+            .expect(LOCK_EXPECT)
+            .bloom_filter
+            .clone()
+    }
+
+    pub fn set_bloom_filter(&self, bloom_filter: Option<BloomFilterConfig>) {
+        self.metadata.write().expect(LOCK_EXPECT).bloom_filter = bloom_filter;
+    }
+
+    pub fn get_pipeline(&self) -> Option<Pipeline> {
+        self.metadata.read().expect(LOCK_EXPECT).pipeline.clone()
+    }
+
+    pub fn set_pipeline(&self, pipeline: Option<Pipeline>) {
+        self.metadata.write().expect(LOCK_EXPECT).pipeline = pipeline;
+    }
+
     pub fn set_first_event_at(&self, first_event_at: &str) {
         self.metadata.write().expect(LOCK_EXPECT).first_event_at = Some(first_event_at.to_owned());
     }
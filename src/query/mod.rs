@@ -51,7 +51,7 @@ use serde_json::{Value, json};
 use std::ops::Bound;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::task::{Context, Poll};
 use sysinfo::System;
 use tokio::runtime::Runtime;
@@ -127,6 +127,8 @@ pub struct Query {
     pub raw_logical_plan: LogicalPlan,
     pub time_range: TimeRange,
     pub filter_tag: Option<Vec<String>>,
+    /// Bytes read by the execution of the query, set once the execution is done
+    pub bytes_scanned: Arc<AtomicU64>,
 }
 
 impl Query {
@@ -267,6 +269,7 @@ impl Query {
                 .collect();
 
             let actual_io_bytes = get_total_bytes_scanned(&plan);
+            self.bytes_scanned.store(actual_io_bytes, Ordering::Relaxed);
 
             // Track billing metrics for query scan
             let current_date = chrono::Utc::now().date_naive().to_string();
@@ -281,6 +284,7 @@ impl Query {
             let monitor_state = Arc::new(MonitorState {
                 plan: plan.clone(),
                 active_streams: AtomicUsize::new(output_partitions),
+                bytes_scanned: self.bytes_scanned.clone(),
             });
 
             let streams = execute_stream_partitioned(plan.clone(), task_ctx.clone())?
@@ -855,6 +859,7 @@ pub mod error {
 struct MonitorState {
     plan: Arc<dyn ExecutionPlan>,
     active_streams: AtomicUsize,
+    bytes_scanned: Arc<AtomicU64>,
 }
 
 /// A wrapper that monitors the ExecutionPlan and logs metrics when the stream finishes.
@@ -921,6 +926,7 @@ impl PartitionedMetricMonitor {
 
         if prev_count == 1 {
             let bytes = get_total_bytes_scanned(&self.state.plan);
+            self.state.bytes_scanned.store(bytes, Ordering::Relaxed);
             let current_date = chrono::Utc::now().date_naive().to_string();
             increment_bytes_scanned_in_query_by_date(bytes, &current_date);
         }
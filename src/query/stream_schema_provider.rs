@@ -145,7 +145,8 @@ impl StandardTableProvider {
         // create file groups from vec file partitions
         let file_groups = partitions.into_iter().map(FileGroup::new).collect_vec();
 
-        // parquet file source, default table parquet options
+        // parquet file source, default table parquet options.
+        // Bloom filters, written for the configured columns of the stream, are read by default.
         let file_source = if let Some(phyiscal_expr) = filters {
             ParquetSource::default().with_predicate(phyiscal_expr)
         } else {
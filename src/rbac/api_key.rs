@@ -0,0 +1,318 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Long-lived API keys for agents and automation, sent as `Authorization: Bearer <token>`.
+//!
+//! A key is bound to a set of roles and authorizes requests as its own identity, never
+//! with the permissions of the user who created it. Only the hash of the secret is stored.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use ulid::Ulid;
+
+use crate::{
+    metastore::{MetastoreError, metastore_traits::MetastoreObject},
+    parseable::PARSEABLE,
+    storage::object_storage::api_key_json_path,
+};
+
+use super::user::{self, Basic, PassCode};
+
+/// Prefix of the tokens, sets them apart from the session ids accepted as bearer tokens
+pub const API_KEY_PREFIX: &str = "pb_";
+
+/// Prefix of the identity requests authorized by an API key are made as
+pub const API_KEY_USER_PREFIX: &str = "apikey:";
+
+/// Last use of a key is only persisted once this much time has passed since
+/// the last persisted use, the in memory timestamp is always accurate
+const LAST_USED_PERSIST_INTERVAL: Duration = Duration::minutes(5);
+
+pub static API_KEYS: Lazy<ApiKeys> = Lazy::new(ApiKeys::default);
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: Ulid,
+    pub name: String,
+    pub roles: HashSet<String>,
+    /// argon2 hash of the secret part of the token
+    pub key_hash: String,
+    /// userid of the user who created the key
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    /// Creates a key and returns it along with its token, which is only ever shown once
+    pub fn new(
+        name: String,
+        roles: HashSet<String>,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: String,
+    ) -> (Self, String) {
+        let id = Ulid::new();
+        let PassCode { password, hash } = Basic::gen_new_password();
+        let key = Self {
+            id,
+            name,
+            roles,
+            key_hash: hash,
+            created_by,
+            created_at: Utc::now(),
+            expires_at,
+            last_used_at: None,
+            revoked_at: None,
+        };
+
+        (key, format!("{API_KEY_PREFIX}{id}_{password}"))
+    }
+
+    /// Identity of the requests authorized by the key
+    pub fn userid(&self) -> String {
+        format!("{API_KEY_USER_PREFIX}{}", self.id)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none()
+            && self
+                .expires_at
+                .is_none_or(|expires_at| expires_at > Utc::now())
+    }
+
+    /// Expiry of the sessions of the key
+    pub fn session_expiry(&self) -> DateTime<Utc> {
+        self.expires_at.unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    pub fn verify_secret(&self, secret: &str) -> bool {
+        user::verify(&self.key_hash, secret)
+    }
+
+    /// Keeps the latest of the two uses
+    fn record_last_use(&mut self, last_used_at: Option<DateTime<Utc>>) {
+        self.last_used_at = self.last_used_at.max(last_used_at);
+    }
+}
+
+impl MetastoreObject for ApiKey {
+    fn get_object_path(&self) -> String {
+        api_key_json_path(&self.id).to_string()
+    }
+
+    fn get_object_id(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+/// Splits a token into the id of its key and its secret
+pub fn parse_token(token: &str) -> Option<(Ulid, &str)> {
+    let (id, secret) = token.strip_prefix(API_KEY_PREFIX)?.split_once('_')?;
+    let id = Ulid::from_string(id).ok()?;
+    (!secret.is_empty()).then_some((id, secret))
+}
+
+/// Key as listed, without the hash of its secret
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyInfo {
+    pub id: Ulid,
+    pub name: String,
+    pub roles: HashSet<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub active: bool,
+}
+
+impl From<&ApiKey> for ApiKeyInfo {
+    fn from(key: &ApiKey) -> Self {
+        Self {
+            id: key.id,
+            name: key.name.clone(),
+            roles: key.roles.clone(),
+            created_by: key.created_by.clone(),
+            created_at: key.created_at,
+            expires_at: key.expires_at,
+            last_used_at: key.last_used_at,
+            revoked_at: key.revoked_at,
+            active: key.is_active(),
+        }
+    }
+}
+
+/// In memory map of the keys, a std lock as authorization is synchronous
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    keys: RwLock<HashMap<Ulid, ApiKey>>,
+    /// last use of each key that was persisted
+    persisted_uses: RwLock<HashMap<Ulid, DateTime<Utc>>>,
+}
+
+impl ApiKeys {
+    pub async fn load(&self) -> anyhow::Result<()> {
+        let keys = PARSEABLE.metastore.get_api_keys().await?;
+        let mut map = self.keys.write().expect("not poisoned");
+        for key in keys {
+            map.insert(key.id, key);
+        }
+
+        Ok(())
+    }
+
+    pub fn insert(&self, key: ApiKey) {
+        self.keys.write().expect("not poisoned").insert(key.id, key);
+    }
+
+    pub fn get(&self, id: &Ulid) -> Option<ApiKey> {
+        self.keys.read().expect("not poisoned").get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<ApiKey> {
+        self.keys
+            .read()
+            .expect("not poisoned")
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Whether an active key with this name exists
+    pub fn name_in_use(&self, name: &str) -> bool {
+        self.keys
+            .read()
+            .expect("not poisoned")
+            .values()
+            .any(|key| key.is_active() && key.name == name)
+    }
+
+    /// Records the use of a key, returns whether its last use should be persisted
+    pub fn record_use(&self, id: &Ulid) -> bool {
+        let now = Utc::now();
+        let previous_use = {
+            let mut keys = self.keys.write().expect("not poisoned");
+            let Some(key) = keys.get_mut(id) else {
+                return false;
+            };
+            key.last_used_at.replace(now)
+        };
+
+        let mut persisted_uses = self.persisted_uses.write().expect("not poisoned");
+        let last_persisted = persisted_uses.get(id).copied().or(previous_use);
+        if last_persisted.is_some_and(|last_use| now - last_use < LAST_USED_PERSIST_INTERVAL) {
+            return false;
+        }
+        persisted_uses.insert(*id, now);
+
+        true
+    }
+
+    /// Persists the last use of a key onto the key as stored, so that a revocation or a
+    /// change of expiry saved since the key was loaded isn't overwritten by the stale copy
+    pub async fn persist_last_use(&self, id: &Ulid) -> Result<(), MetastoreError> {
+        let Some(last_used_at) = self.get(id).and_then(|key| key.last_used_at) else {
+            return Ok(());
+        };
+        let Some(mut stored) = PARSEABLE.metastore.get_api_key(id).await? else {
+            return Ok(());
+        };
+        stored.record_last_use(Some(last_used_at));
+        PARSEABLE.metastore.put_api_key(&stored).await?;
+        self.refresh(stored);
+
+        Ok(())
+    }
+
+    /// Replaces the key in memory with the key as stored, keeping its latest use
+    fn refresh(&self, mut stored: ApiKey) {
+        let mut keys = self.keys.write().expect("not poisoned");
+        if let Some(key) = keys.get(&stored.id) {
+            stored.record_last_use(key.last_used_at);
+        }
+        keys.insert(stored.id, stored);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tokens() {
+        let (key, token) = ApiKey::new(
+            "ci".to_owned(),
+            HashSet::from(["writer".to_owned()]),
+            None,
+            "admin".to_owned(),
+        );
+
+        let (id, secret) = parse_token(&token).unwrap();
+        assert_eq!(id, key.id);
+        assert!(key.verify_secret(secret));
+        assert!(!key.verify_secret("guess"));
+
+        assert!(parse_token(&key.id.to_string()).is_none());
+        assert!(parse_token(&format!("{API_KEY_PREFIX}{}_", key.id)).is_none());
+        assert!(parse_token(&format!("{API_KEY_PREFIX}nope_secret")).is_none());
+    }
+
+    #[test]
+    fn expired_and_revoked_keys_are_inactive() {
+        let (mut key, _) = ApiKey::new("ci".to_owned(), HashSet::new(), None, "admin".to_owned());
+        assert!(key.is_active());
+
+        key.expires_at = Some(Utc::now() - Duration::minutes(1));
+        assert!(!key.is_active());
+
+        key.expires_at = Some(Utc::now() + Duration::days(1));
+        key.revoked_at = Some(Utc::now());
+        assert!(!key.is_active());
+    }
+
+    #[test]
+    fn revoked_keys_stay_revoked_after_a_stale_use() {
+        let keys = ApiKeys::default();
+        let (key, _) = ApiKey::new("ci".to_owned(), HashSet::new(), None, "admin".to_owned());
+        keys.insert(key.clone());
+
+        // revoked on another node while this one still has the active key in memory
+        let mut stored = key.clone();
+        stored.revoked_at = Some(Utc::now());
+
+        assert!(keys.record_use(&key.id));
+        let last_used_at = keys.get(&key.id).unwrap().last_used_at;
+        stored.record_last_use(last_used_at);
+        keys.refresh(stored.clone());
+
+        assert!(stored.revoked_at.is_some());
+        assert_eq!(stored.last_used_at, last_used_at);
+        let key = keys.get(&key.id).unwrap();
+        assert!(!key.is_active());
+        assert_eq!(key.last_used_at, last_used_at);
+    }
+}
@@ -0,0 +1,509 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Column-level masking, reader and writer privileges on a stream can hide columns of
+//! the stream, or replace their values with a hash or a partially masked text.
+//!
+//! Hashes are keyed with a secret of the deployment, `P_COLUMN_MASK_SECRET`, so that the
+//! hash of a guessable value, e.g. an SSN, can't be computed outside of the server to
+//! find the rows holding it. Hashes of the same value stay equal within a deployment.
+//!
+//! Masks are applied as a projection over every scan of the stream in the logical plan,
+//! so filters, joins and aggregations only ever see the masked values. A column is only
+//! masked if every privilege of the user querying the stream masks it, with the least
+//! restrictive of their masks.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow_schema::{DataType, Field, Schema};
+use datafusion::{
+    common::{
+        Column, ScalarValue,
+        tree_node::{Transformed, TreeNode},
+    },
+    error::DataFusionError,
+    functions::{
+        crypto::expr_fn::sha256,
+        encoding::expr_fn::encode,
+        string::expr_fn::{concat, repeat},
+        unicode::expr_fn::{character_length, right},
+    },
+    logical_expr::{LogicalPlan, Operator, Projection, binary_expr, when},
+    prelude::{Expr, cast, lit},
+};
+
+use once_cell::sync::Lazy;
+
+use crate::{parseable::PARSEABLE, utils::get_hash};
+
+use super::role::{Action, ParseableResourceType, Permission, model::DefaultPrivilege};
+
+/// Secret hashed values are prefixed with, the admin credentials are a secret
+/// every node of the deployment shares when no secret is set
+static HASH_SECRET: Lazy<String> = Lazy::new(|| {
+    let options = &PARSEABLE.options;
+    options
+        .column_mask_secret
+        .clone()
+        .unwrap_or_else(|| get_hash(&format!("{}:{}", options.username, options.password)))
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ColumnMask {
+    /// The column is left out of schemas and results, its values read as nulls
+    Hide,
+    /// Values are replaced with the hex encoded SHA-256 of their text
+    /// prefixed with the column mask secret of the deployment
+    Hash,
+    /// All but the last characters of the text of values are replaced with `*`
+    Partial {
+        #[serde(rename = "keepLast")]
+        keep_last: usize,
+    },
+}
+
+impl ColumnMask {
+    /// How much of the values gets through the mask, to pick the least restrictive mask
+    fn exposure(&self) -> usize {
+        match self {
+            Self::Hide => 0,
+            Self::Hash => 1,
+            Self::Partial { keep_last } => keep_last.saturating_add(2),
+        }
+    }
+
+    /// Values of the column once masked
+    pub fn apply(&self, column: Expr, data_type: &DataType) -> Result<Expr, DataFusionError> {
+        let text = cast(column, DataType::Utf8);
+        match self {
+            Self::Hide => Ok(lit(ScalarValue::try_from(data_type)?)),
+            Self::Hash => Ok(encode(
+                sha256(binary_expr(
+                    lit(HASH_SECRET.as_str()),
+                    Operator::StringConcat,
+                    text,
+                )),
+                lit("hex"),
+            )),
+            Self::Partial { keep_last } => {
+                let keep_last = lit(*keep_last as i64);
+                let length = character_length(text.clone());
+                when(
+                    length.clone().gt(keep_last.clone()),
+                    concat(vec![
+                        repeat(lit("*"), length.clone() - keep_last.clone()),
+                        right(text, keep_last),
+                    ]),
+                )
+                .otherwise(repeat(lit("*"), length))
+            }
+        }
+    }
+
+    /// Type of the values of a column of `data_type` once masked
+    fn data_type(&self, data_type: &DataType) -> DataType {
+        match self {
+            Self::Hide => data_type.clone(),
+            Self::Hash | Self::Partial { .. } => DataType::Utf8,
+        }
+    }
+}
+
+/// Masks of the columns of the stream for the permissions, by column name
+pub fn column_masks(permissions: &[Permission], stream: &str) -> BTreeMap<String, ColumnMask> {
+    let mut grants = vec![];
+    for permission in permissions {
+        match permission {
+            Permission::Resource(Action::All, _)
+            | Permission::Resource(_, ParseableResourceType::All) => return BTreeMap::new(),
+            Permission::Resource(Action::Query, ParseableResourceType::Stream(resource))
+                if resource == stream || resource == "*" =>
+            {
+                return BTreeMap::new();
+            }
+            Permission::RestrictedQuery {
+                stream: resource,
+                masks,
+                ..
+            } if resource == stream || resource == "*" => grants.push(masks),
+            _ => {}
+        }
+    }
+
+    let Some((first, rest)) = grants.split_first() else {
+        return BTreeMap::new();
+    };
+    first
+        .iter()
+        .filter_map(|(column, mask)| {
+            rest.iter()
+                .try_fold(*mask, |mask, masks| {
+                    let other = masks.get(column)?;
+                    Some(std::cmp::max_by_key(mask, *other, ColumnMask::exposure))
+                })
+                .map(|mask| (column.clone(), mask))
+        })
+        .collect()
+}
+
+/// Schema of the stream as seen through the masks, without the hidden columns
+pub fn mask_schema(schema: &Schema, masks: &BTreeMap<String, ColumnMask>) -> Schema {
+    let fields = schema
+        .fields()
+        .iter()
+        .filter_map(|field| match masks.get(field.name()) {
+            Some(ColumnMask::Hide) => None,
+            Some(mask) => Some(Arc::new(Field::new(
+                field.name(),
+                mask.data_type(field.data_type()),
+                true,
+            ))),
+            None => Some(field.clone()),
+        })
+        .collect::<Vec<_>>();
+
+    Schema::new_with_metadata(fields, schema.metadata().clone())
+}
+
+/// Masks the columns of every scan of a stream in the plan, and leaves the hidden
+/// columns out of the output of the plan
+pub fn apply_column_masks(
+    plan: LogicalPlan,
+    permissions: &[Permission],
+) -> Result<LogicalPlan, DataFusionError> {
+    let mut masks_by_stream: HashMap<String, BTreeMap<String, ColumnMask>> = HashMap::new();
+    let mut hidden = HashSet::new();
+    let mut masked = false;
+    let plan = plan
+        .transform_up_with_subqueries(|node| {
+            let LogicalPlan::TableScan(scan) = &node else {
+                // masked values may not be of the type of the column they replace
+                if masked {
+                    return node.recompute_schema().map(Transformed::yes);
+                }
+                return Ok(Transformed::no(node));
+            };
+            let stream = scan.table_name.table().to_owned();
+            let masks = masks_by_stream
+                .entry(stream.clone())
+                .or_insert_with_key(|stream| column_masks(permissions, stream));
+            if masks.is_empty() {
+                return Ok(Transformed::no(node));
+            }
+
+            let exprs = scan
+                .projected_schema
+                .iter()
+                .map(|(qualifier, field)| {
+                    let column = Expr::Column(Column::new(qualifier.cloned(), field.name()));
+                    match masks.get(field.name()) {
+                        Some(mask) => {
+                            if *mask == ColumnMask::Hide {
+                                hidden.insert((stream.clone(), field.name().clone()));
+                            }
+                            Ok(mask
+                                .apply(column, field.data_type())?
+                                .alias_qualified(qualifier.cloned(), field.name()))
+                        }
+                        None => Ok(column),
+                    }
+                })
+                .collect::<Result<Vec<_>, DataFusionError>>()?;
+            masked = true;
+
+            let projection = Projection::try_new(exprs, Arc::new(node))?;
+            Ok(Transformed::yes(LogicalPlan::Projection(projection)))
+        })?
+        .data;
+
+    let hidden = hidden_fields(&plan, &hidden);
+    if hidden.is_empty() {
+        return Ok(plan);
+    }
+    let exprs = plan
+        .schema()
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !hidden.contains(i))
+        .map(|(_, (qualifier, field))| Expr::Column(Column::new(qualifier.cloned(), field.name())))
+        .collect();
+
+    Ok(LogicalPlan::Projection(Projection::try_new(
+        exprs,
+        Arc::new(plan),
+    )?))
+}
+
+/// Positions of the fields of the output of the plan holding hidden columns, as output by
+/// the mask projections and passed on through projections, aliases and the other nodes
+fn hidden_fields(plan: &LogicalPlan, hidden: &HashSet<(String, String)>) -> HashSet<usize> {
+    match plan {
+        LogicalPlan::Projection(projection) => {
+            let input = projection.input.as_ref();
+            let input_hidden = hidden_fields(input, hidden);
+            projection
+                .expr
+                .iter()
+                .enumerate()
+                .filter(|(i, expr)| {
+                    let mut expr = *expr;
+                    while let Expr::Alias(alias) = expr {
+                        expr = &alias.expr;
+                    }
+                    match (expr, input) {
+                        (Expr::Column(column), _) => input
+                            .schema()
+                            .index_of_column(column)
+                            .is_ok_and(|index| input_hidden.contains(&index)),
+                        // the mask projection over the scan of a stream
+                        (Expr::Literal(..), LogicalPlan::TableScan(scan)) => hidden.contains(&(
+                            scan.table_name.table().to_owned(),
+                            projection.schema.field(*i).name().clone(),
+                        )),
+                        _ => false,
+                    }
+                })
+                .map(|(i, _)| i)
+                .collect()
+        }
+        LogicalPlan::SubqueryAlias(alias) => hidden_fields(&alias.input, hidden),
+        _ => {
+            let mut input_hidden = HashSet::new();
+            for input in plan.inputs() {
+                let positions = hidden_fields(input, hidden);
+                input_hidden.extend(
+                    input
+                        .schema()
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| positions.contains(i))
+                        .map(|(_, (qualifier, field))| (qualifier.cloned(), field.name().clone())),
+                );
+            }
+            if input_hidden.is_empty() {
+                return HashSet::new();
+            }
+            plan.schema()
+                .iter()
+                .enumerate()
+                .filter(|(_, (qualifier, field))| {
+                    input_hidden.contains(&(qualifier.cloned(), field.name().clone()))
+                })
+                .map(|(i, _)| i)
+                .collect()
+        }
+    }
+}
+
+/// Checks that masks are only set on privileges on a stream
+pub fn validate(privileges: &[DefaultPrivilege]) -> Result<(), ColumnMaskError> {
+    for privilege in privileges {
+        let (DefaultPrivilege::Reader {
+            resource, masks, ..
+        }
+        | DefaultPrivilege::Writer {
+            resource, masks, ..
+        }) = privilege
+        else {
+            continue;
+        };
+        if masks.is_empty() {
+            continue;
+        }
+
+        if !matches!(resource, ParseableResourceType::Stream(_)) {
+            return Err(ColumnMaskError::NotStream);
+        }
+        if masks.keys().any(|column| column.trim().is_empty()) {
+            return Err(ColumnMaskError::EmptyColumn);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ColumnMaskError {
+    #[error("Column masks can only be set on privileges on a stream")]
+    NotStream,
+    #[error("Column masks should name a column")]
+    EmptyColumn,
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        datasource::{empty::EmptyTable, provider_as_source},
+        logical_expr::LogicalPlanBuilder,
+        prelude::col,
+    };
+
+    use super::*;
+    use crate::rbac::role::RoleBuilder;
+
+    fn permissions(privileges: &[DefaultPrivilege]) -> Vec<Permission> {
+        privileges
+            .iter()
+            .flat_map(|privilege| RoleBuilder::from(privilege).build())
+            .collect()
+    }
+
+    fn reader(stream: &str, masks: &[(&str, ColumnMask)]) -> DefaultPrivilege {
+        DefaultPrivilege::Reader {
+            resource: ParseableResourceType::Stream(stream.to_owned()),
+            filter: None,
+            masks: masks
+                .iter()
+                .map(|(column, mask)| (column.to_string(), *mask))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn combines_masks_of_roles() {
+        let masked = permissions(&[
+            reader(
+                "payments",
+                &[
+                    ("ssn", ColumnMask::Hide),
+                    ("card", ColumnMask::Hash),
+                    ("email", ColumnMask::Hide),
+                ],
+            ),
+            reader(
+                "payments",
+                &[
+                    ("ssn", ColumnMask::Hide),
+                    ("card", ColumnMask::Partial { keep_last: 4 }),
+                ],
+            ),
+        ]);
+
+        assert_eq!(
+            column_masks(&masked, "payments"),
+            BTreeMap::from([
+                ("card".to_owned(), ColumnMask::Partial { keep_last: 4 }),
+                ("ssn".to_owned(), ColumnMask::Hide),
+            ])
+        );
+        assert!(column_masks(&masked, "other").is_empty());
+
+        let mut privileges = vec![reader("payments", &[("ssn", ColumnMask::Hide)])];
+        privileges.push(reader("payments", &[]));
+        assert!(column_masks(&permissions(&privileges), "payments").is_empty());
+    }
+
+    #[test]
+    fn masks_schema() {
+        let schema = Schema::new(vec![
+            Field::new("ssn", DataType::Utf8, false),
+            Field::new("card", DataType::Int64, false),
+            Field::new("status", DataType::Int64, false),
+        ]);
+        let masks = BTreeMap::from([
+            ("ssn".to_owned(), ColumnMask::Hide),
+            ("card".to_owned(), ColumnMask::Partial { keep_last: 4 }),
+        ]);
+
+        assert_eq!(
+            mask_schema(&schema, &masks),
+            Schema::new(vec![
+                Field::new("card", DataType::Utf8, true),
+                Field::new("status", DataType::Int64, false),
+            ])
+        );
+    }
+
+    fn payments() -> LogicalPlanBuilder {
+        let schema = Schema::new(vec![
+            Field::new("ssn", DataType::Utf8, true),
+            Field::new("status", DataType::Int64, true),
+        ]);
+        let table = provider_as_source(Arc::new(EmptyTable::new(Arc::new(schema))));
+        LogicalPlanBuilder::scan("payments", table, None).unwrap()
+    }
+
+    fn output_fields(plan: LogicalPlan) -> Vec<String> {
+        let masked = permissions(&[reader("payments", &[("ssn", ColumnMask::Hide)])]);
+        let plan = apply_column_masks(plan, &masked).unwrap();
+        plan.schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect()
+    }
+
+    #[test]
+    fn drops_hidden_columns_of_aliased_streams() {
+        let plan = payments()
+            .alias("p")
+            .unwrap()
+            .project(vec![
+                col("p.ssn"),
+                col("p.ssn").alias("id"),
+                col("p.status"),
+            ])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(output_fields(plan), vec!["status"]);
+    }
+
+    #[test]
+    fn drops_hidden_columns_of_subqueries() {
+        let plan = payments()
+            .project(vec![col("ssn"), col("status")])
+            .unwrap()
+            .alias("sub")
+            .unwrap()
+            .project(vec![col("sub.ssn"), col("sub.status")])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(output_fields(plan), vec!["status"]);
+    }
+
+    #[test]
+    fn deserializes_masks() {
+        let privilege: DefaultPrivilege = serde_json::from_value(serde_json::json!({
+            "privilege": "reader",
+            "resource": {"stream": "payments"},
+            "masks": {
+                "ssn": {"type": "hide"},
+                "card": {"type": "partial", "keepLast": 4}
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            privilege,
+            reader(
+                "payments",
+                &[
+                    ("ssn", ColumnMask::Hide),
+                    ("card", ColumnMask::Partial { keep_last: 4 }),
+                ],
+            )
+        );
+        assert!(validate(&[privilege]).is_ok());
+    }
+}
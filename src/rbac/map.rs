@@ -154,8 +154,13 @@ pub fn init(metadata: &StorageMetadata) {
 // cleanup of unused session is done when a new session is added
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum SessionKey {
-    BasicAuth { username: String, password: String },
+    BasicAuth {
+        username: String,
+        password: String,
+    },
     SessionId(ulid::Ulid),
+    /// token of an API key, sent as a bearer token
+    ApiKey(String),
 }
 
 #[derive(Debug, Default)]
@@ -259,33 +264,20 @@ impl Sessions {
                 match *user_perm {
                     // if any action is ALL then we we authorize
                     Permission::Unit(action) => action == required_action || action == Action::All,
-                    Permission::Resource(action, ref resource_type) => {
-                        match resource_type {
-                            ParseableResourceType::Stream(resource_id)
-                            | ParseableResourceType::Llm(resource_id) => {
-                                let ok_resource =
-                                    if let Some(context_resource_id) = context_resource {
-                                        let is_internal = PARSEABLE
-                                            .get_stream(context_resource_id)
-                                            .is_ok_and(|stream| {
-                                                stream
-                                                    .get_stream_type()
-                                                    .eq(&crate::storage::StreamType::Internal)
-                                            });
-                                        resource_id == context_resource_id
-                                            || resource_id == "*"
-                                            || is_internal
-                                    } else {
-                                        // if no resource to match then resource check is not needed
-                                        // WHEN IS THIS VALID??
-                                        true
-                                    };
-                                (action == required_action || action == Action::All) && ok_resource
-                            }
-                            ParseableResourceType::All => {
-                                action == required_action || action == Action::All
-                            }
+                    Permission::Resource(action, ref resource_type) => match resource_type {
+                        ParseableResourceType::Stream(resource_id)
+                        | ParseableResourceType::Llm(resource_id) => {
+                            (action == required_action || action == Action::All)
+                                && resource_matches(resource_id, context_resource)
                         }
+                        ParseableResourceType::All => {
+                            action == required_action || action == Action::All
+                        }
+                    },
+                    // the rows and columns that can be read are restricted when the query is planned
+                    Permission::RestrictedQuery { ref stream, .. } => {
+                        required_action == Action::Query
+                            && resource_matches(stream, context_resource)
                     }
                     Permission::SelfUser if required_action == Action::GetUserRoles => {
                         context_user.map(|x| x == username).unwrap_or_default()
@@ -305,6 +297,23 @@ impl Sessions {
     }
 }
 
+fn resource_matches(resource_id: &str, context_resource: Option<&str>) -> bool {
+    if let Some(context_resource_id) = context_resource {
+        let is_internal = PARSEABLE
+            .get_stream(context_resource_id)
+            .is_ok_and(|stream| {
+                stream
+                    .get_stream_type()
+                    .eq(&crate::storage::StreamType::Internal)
+            });
+        resource_id == context_resource_id || resource_id == "*" || is_internal
+    } else {
+        // if no resource to match then resource check is not needed
+        // WHEN IS THIS VALID??
+        true
+    }
+}
+
 // UserMap is a map of [username --> User]
 // This map is populated at startup with the list of users from parseable.json file
 #[derive(Debug, Default, Clone, derive_more::Deref, derive_more::DerefMut)]
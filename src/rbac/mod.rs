@@ -16,22 +16,32 @@
  *
  */
 
+pub mod api_key;
+pub mod column_mask;
 pub mod map;
 pub mod role;
+pub mod row_filter;
 pub mod user;
 pub mod utils;
 
 use std::collections::{HashMap, HashSet};
 
 use chrono::{DateTime, Duration, TimeDelta, Utc};
+use datafusion::{error::DataFusionError, logical_expr::LogicalPlan};
 use itertools::Itertools;
 use role::model::DefaultPrivilege;
 use serde::Serialize;
+use tracing::warn;
+use ulid::Ulid;
 use url::Url;
 
+use crate::option::Mode;
+use crate::parseable::PARSEABLE;
+use crate::rbac::api_key::{API_KEY_USER_PREFIX, API_KEYS, ApiKey};
 use crate::rbac::map::{mut_sessions, mut_users, read_user_groups, roles, sessions, users};
 use crate::rbac::role::Action;
 use crate::rbac::user::User;
+use crate::utils::get_hash;
 
 use self::map::SessionKey;
 use self::role::{Permission, RoleBuilder};
@@ -141,6 +151,31 @@ impl Users {
         permissions.into_iter().collect_vec()
     }
 
+    /// Permissions of a user or an API key from their roles, for acting on their behalf
+    /// outside of a session, e.g. when evaluating the alerts they created
+    pub fn get_user_permissions(&self, userid: &str) -> Vec<Permission> {
+        if let Some(id) = userid.strip_prefix(API_KEY_USER_PREFIX) {
+            return Ulid::from_string(id)
+                .ok()
+                .and_then(|id| API_KEYS.get(&id))
+                .filter(ApiKey::is_active)
+                .map(|key| roles_to_permission(key.roles.into_iter().collect()))
+                .unwrap_or_default();
+        }
+
+        let Some(user) = users().get(userid).cloned() else {
+            return vec![];
+        };
+        let mut user_roles = user.roles();
+        for group in &user.user_groups {
+            if let Some(group) = read_user_groups().get(group) {
+                user_roles.extend(group.roles.iter().cloned());
+            }
+        }
+
+        roles_to_permission(user_roles)
+    }
+
     pub fn session_exists(&self, session: &SessionKey) -> bool {
         sessions().get(session).is_some()
     }
@@ -149,6 +184,12 @@ impl Users {
         mut_sessions().remove_session(session)
     }
 
+    /// Puts the key in memory and drops its sessions, so that a revoked key stops working
+    pub fn put_api_key(&self, key: ApiKey) {
+        mut_sessions().remove_user(&key.userid());
+        API_KEYS.insert(key);
+    }
+
     pub fn new_session(&self, user: &User, session: SessionKey, expires_in: TimeDelta) {
         mut_sessions().track_new(
             user.userid().to_owned(),
@@ -165,6 +206,11 @@ impl Users {
         context_stream: Option<&str>,
         context_user: Option<&str>,
     ) -> Response {
+        // keys are checked on every request as they can be revoked or expire at any time
+        if let SessionKey::ApiKey(token) = &key {
+            return self.authorize_api_key(&key, token, action, context_stream, context_user);
+        }
+
         // try fetch from auth map for faster auth flow
         if let Some(res) = sessions().check_auth(&key, action, context_stream, context_user) {
             return res;
@@ -201,9 +247,67 @@ impl Users {
         Response::UnAuthorized
     }
 
+    fn authorize_api_key(
+        &self,
+        key: &SessionKey,
+        token: &str,
+        action: Action,
+        context_stream: Option<&str>,
+        context_user: Option<&str>,
+    ) -> Response {
+        let Some((id, secret)) = api_key::parse_token(token) else {
+            return Response::UnAuthorized;
+        };
+        let Some(api_key) = API_KEYS.get(&id).filter(ApiKey::is_active) else {
+            mut_sessions().remove_session(key);
+            return Response::UnAuthorized;
+        };
+
+        let response =
+            if let Some(res) = sessions().check_auth(key, action, context_stream, context_user) {
+                res
+            } else if api_key.verify_secret(secret) {
+                let mut sessions = mut_sessions();
+                sessions.track_new(
+                    api_key.userid(),
+                    key.clone(),
+                    api_key.session_expiry(),
+                    roles_to_permission(api_key.roles.into_iter().collect()),
+                );
+                sessions
+                    .check_auth(key, action, context_stream, context_user)
+                    .expect("entry for this key just added")
+            } else {
+                return Response::UnAuthorized;
+            };
+
+        // keys are managed on the nodes serving the API, ingestors only track uses in memory
+        if API_KEYS.record_use(&id) && matches!(PARSEABLE.options.mode, Mode::All | Mode::Query) {
+            tokio::spawn(async move {
+                if let Err(err) = API_KEYS.persist_last_use(&id).await {
+                    warn!("Failed to save last use of API key {id}: {err}");
+                }
+            });
+        }
+
+        response
+    }
+
     pub fn get_userid_from_session(&self, session: &SessionKey) -> Option<String> {
         sessions().get_userid(session).cloned()
     }
+
+    /// Userid of a user or an API key from its hash, as recorded as the author of dashboards
+    pub fn get_userid_from_hash(&self, hash: &str) -> Option<String> {
+        if let Some(userid) = users().keys().find(|userid| get_hash(userid) == hash) {
+            return Some(userid.clone());
+        }
+        API_KEYS
+            .list()
+            .iter()
+            .map(ApiKey::userid)
+            .find(|userid| get_hash(userid) == hash)
+    }
 }
 
 /// This struct represents a user along with their roles, email, etc
@@ -230,6 +334,23 @@ pub struct UsersPrism {
     pub user_groups: HashSet<String>,
 }
 
+/// Restricts the plan to the rows and columns of the streams the permissions can query
+pub fn apply_access_policies(
+    plan: LogicalPlan,
+    permissions: &[Permission],
+) -> Result<LogicalPlan, DataFusionError> {
+    // row filters end up under the masks, they are over the values of the columns
+    let plan = column_mask::apply_column_masks(plan, permissions)?;
+    row_filter::apply_row_filters(plan, permissions)
+}
+
+/// Whether the permissions can query all of the rows and columns of the stream, neither
+/// row filters nor column masks apply to it
+pub fn reads_unrestricted(permissions: &[Permission], stream: &str) -> bool {
+    row_filter::row_filters(permissions, stream).is_none()
+        && column_mask::column_masks(permissions, stream).is_empty()
+}
+
 pub fn roles_to_permission(roles: Vec<String>) -> Vec<Permission> {
     let mut perms = HashSet::new();
     for role in &roles {
@@ -243,3 +364,43 @@ pub fn roles_to_permission(roles: Vec<String>) -> Vec<Permission> {
     }
     perms.into_iter().collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbac::{
+        column_mask::ColumnMask,
+        role::{ParseableResourceType, model::DefaultPrivilege},
+    };
+
+    fn reader(stream: &str, filter: Option<&str>, hidden: Option<&str>) -> Vec<Permission> {
+        let privilege = DefaultPrivilege::Reader {
+            resource: ParseableResourceType::Stream(stream.to_owned()),
+            filter: filter.map(str::to_owned),
+            masks: hidden
+                .map(|column| (column.to_owned(), ColumnMask::Hide))
+                .into_iter()
+                .collect(),
+        };
+        RoleBuilder::from(&privilege).build()
+    }
+
+    #[test]
+    fn filters_and_masks_restrict_reads() {
+        assert!(reads_unrestricted(&reader("app", None, None), "app"));
+        assert!(!reads_unrestricted(
+            &reader("app", Some("namespace = 'payments'"), None),
+            "app"
+        ));
+        assert!(!reads_unrestricted(
+            &reader("app", None, Some("email")),
+            "app"
+        ));
+        // no access at all
+        assert!(!reads_unrestricted(&reader("app", None, None), "other"));
+
+        let mut permissions = reader("app", None, Some("email"));
+        permissions.extend(RoleBuilder::from(&DefaultPrivilege::Admin).build());
+        assert!(reads_unrestricted(&permissions, "app"));
+    }
+}
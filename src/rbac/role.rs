@@ -17,6 +17,10 @@
 *
 */
 
+use std::collections::BTreeMap;
+
+use super::column_mask::ColumnMask;
+
 // Represents actions that corresponds to an api
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Action {
@@ -35,6 +39,10 @@ pub enum Action {
     DeleteStream,
     GetRetention,
     PutRetention,
+    GetBloomFilter,
+    PutBloomFilter,
+    GetPipeline,
+    PutPipeline,
     PutHotTierEnabled,
     GetHotTierEnabled,
     DeleteHotTierEnabled,
@@ -75,6 +83,12 @@ pub enum Action {
     CreateCorrelation,
     DeleteCorrelation,
     PutCorrelation,
+    ExportBundle,
+    ImportBundle,
+    PutApiKey,
+    ListApiKey,
+    GetApiKey,
+    DeleteApiKey,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
@@ -91,6 +105,13 @@ pub enum ParseableResourceType {
 pub enum Permission {
     Unit(Action),
     Resource(Action, ParseableResourceType),
+    /// query access to a stream restricted to the rows matching the filter, a SQL
+    /// expression, with the values of some of its columns masked
+    RestrictedQuery {
+        stream: String,
+        filter: Option<String>,
+        masks: BTreeMap<String, ColumnMask>,
+    },
     SelfUser,
 }
 
@@ -99,6 +120,8 @@ pub enum Permission {
 pub struct RoleBuilder {
     actions: Vec<Action>,
     resource_type: Option<ParseableResourceType>,
+    row_filter: Option<String>,
+    column_masks: BTreeMap<String, ColumnMask>,
 }
 
 // R x P
@@ -108,9 +131,33 @@ impl RoleBuilder {
         self
     }
 
+    pub fn with_row_filter(mut self, row_filter: Option<String>) -> Self {
+        self.row_filter = row_filter;
+        self
+    }
+
+    pub fn with_column_masks(mut self, column_masks: BTreeMap<String, ColumnMask>) -> Self {
+        self.column_masks = column_masks;
+        self
+    }
+
     pub fn build(self) -> Vec<Permission> {
         let mut perms = Vec::new();
         for action in self.actions {
+            // queries of a restricted stream only get to read the rows matching the filter,
+            // with the masked columns
+            if action == Action::Query
+                && (self.row_filter.is_some() || !self.column_masks.is_empty())
+                && let Some(ParseableResourceType::Stream(stream)) = &self.resource_type
+            {
+                perms.push(Permission::RestrictedQuery {
+                    stream: stream.clone(),
+                    filter: self.row_filter.clone(),
+                    masks: self.column_masks.clone(),
+                });
+                continue;
+            }
+
             let perm = match action {
                 Action::Login
                 | Action::Metrics
@@ -152,7 +199,13 @@ impl RoleBuilder {
                 | Action::GetUserGroup
                 | Action::DeleteUserGroup
                 | Action::ModifyUserGroup
-                | Action::GetAnalytics => Permission::Unit(action),
+                | Action::GetAnalytics
+                | Action::ExportBundle
+                | Action::ImportBundle
+                | Action::PutApiKey
+                | Action::ListApiKey
+                | Action::GetApiKey
+                | Action::DeleteApiKey => Permission::Unit(action),
                 Action::Query
                 | Action::QueryLLM
                 | Action::AddLLM
@@ -166,6 +219,10 @@ impl RoleBuilder {
                 | Action::GetStats
                 | Action::GetRetention
                 | Action::PutRetention
+                | Action::GetBloomFilter
+                | Action::PutBloomFilter
+                | Action::GetPipeline
+                | Action::PutPipeline
                 | Action::All => Permission::Resource(action, self.resource_type.clone().unwrap()),
             };
             perms.push(perm);
@@ -179,7 +236,9 @@ impl RoleBuilder {
 // we can put same model in the backend
 // user -> Vec<DefaultRoles>
 pub mod model {
-    use crate::rbac::role::ParseableResourceType;
+    use std::collections::BTreeMap;
+
+    use crate::rbac::{column_mask::ColumnMask, role::ParseableResourceType};
 
     use super::{Action, RoleBuilder};
 
@@ -188,9 +247,27 @@ pub mod model {
     pub enum DefaultPrivilege {
         Admin,
         Editor,
-        Writer { resource: ParseableResourceType },
-        Ingestor { resource: ParseableResourceType },
-        Reader { resource: ParseableResourceType },
+        Writer {
+            resource: ParseableResourceType,
+            /// SQL expression restricting the rows of the stream that can be queried
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            filter: Option<String>,
+            /// masks of the columns of the stream, by column name
+            #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+            masks: BTreeMap<String, ColumnMask>,
+        },
+        Ingestor {
+            resource: ParseableResourceType,
+        },
+        Reader {
+            resource: ParseableResourceType,
+            /// SQL expression restricting the rows of the stream that can be queried
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            filter: Option<String>,
+            /// masks of the columns of the stream, by column name
+            #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+            masks: BTreeMap<String, ColumnMask>,
+        },
     }
 
     impl From<&DefaultPrivilege> for RoleBuilder {
@@ -198,12 +275,22 @@ pub mod model {
             match value {
                 DefaultPrivilege::Admin => admin_perm_builder(),
                 DefaultPrivilege::Editor => editor_perm_builder(),
-                DefaultPrivilege::Writer { resource } => {
-                    writer_perm_builder().with_resource(resource.to_owned())
-                }
-                DefaultPrivilege::Reader { resource } => {
-                    reader_perm_builder().with_resource(resource.to_owned())
-                }
+                DefaultPrivilege::Writer {
+                    resource,
+                    filter,
+                    masks,
+                } => writer_perm_builder()
+                    .with_resource(resource.to_owned())
+                    .with_row_filter(filter.to_owned())
+                    .with_column_masks(masks.to_owned()),
+                DefaultPrivilege::Reader {
+                    resource,
+                    filter,
+                    masks,
+                } => reader_perm_builder()
+                    .with_resource(resource.to_owned())
+                    .with_row_filter(filter.to_owned())
+                    .with_column_masks(masks.to_owned()),
                 DefaultPrivilege::Ingestor { resource } => {
                     ingest_perm_builder().with_resource(resource.to_owned())
                 }
@@ -215,6 +302,8 @@ pub mod model {
         RoleBuilder {
             actions: vec![Action::All],
             resource_type: Some(ParseableResourceType::All),
+            row_filter: None,
+            column_masks: BTreeMap::new(),
         }
     }
 
@@ -239,6 +328,10 @@ pub mod model {
                 Action::GetStats,
                 Action::GetRetention,
                 Action::PutRetention,
+                Action::GetBloomFilter,
+                Action::PutBloomFilter,
+                Action::GetPipeline,
+                Action::PutPipeline,
                 Action::PutHotTierEnabled,
                 Action::GetHotTierEnabled,
                 Action::DeleteHotTierEnabled,
@@ -259,8 +352,12 @@ pub mod model {
                 Action::CreateDashboard,
                 Action::DeleteDashboard,
                 Action::GetUserRoles,
+                Action::ExportBundle,
+                Action::ImportBundle,
             ],
             resource_type: Some(ParseableResourceType::All),
+            row_filter: None,
+            column_masks: BTreeMap::new(),
         }
     }
 
@@ -278,6 +375,10 @@ pub mod model {
                 Action::GetAlert,
                 Action::DeleteAlert,
                 Action::GetRetention,
+                Action::PutBloomFilter,
+                Action::GetBloomFilter,
+                Action::PutPipeline,
+                Action::GetPipeline,
                 Action::PutHotTierEnabled,
                 Action::GetHotTierEnabled,
                 Action::DeleteHotTierEnabled,
@@ -301,6 +402,8 @@ pub mod model {
                 Action::GetUserRoles,
             ],
             resource_type: None,
+            row_filter: None,
+            column_masks: BTreeMap::new(),
         }
     }
 
@@ -329,11 +432,15 @@ pub mod model {
                 Action::CreateDashboard,
                 Action::DeleteDashboard,
                 Action::GetRetention,
+                Action::GetBloomFilter,
+                Action::GetPipeline,
                 Action::GetStreamInfo,
                 Action::GetUserRoles,
                 Action::GetAlert,
             ],
             resource_type: None,
+            row_filter: None,
+            column_masks: BTreeMap::new(),
         }
     }
 
@@ -341,6 +448,8 @@ pub mod model {
         RoleBuilder {
             actions: vec![Action::Ingest],
             resource_type: None,
+            row_filter: None,
+            column_masks: BTreeMap::new(),
         }
     }
 }
@@ -0,0 +1,361 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Row-level security, reader and writer privileges on a stream can carry a filter
+//! restricting the rows of the stream that can be queried, e.g. `namespace = 'payments'`.
+//!
+//! Filters are applied to every scan of the stream in the logical plan, so that whatever
+//! SQL is written, rows outside of the filter are never read. Filters of the roles of a
+//! user are combined with OR, and a role reading the stream unfiltered lifts them.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use arrow_schema::DataType;
+use datafusion::{
+    common::{
+        DFSchema,
+        tree_node::{Transformed, TreeNode},
+    },
+    error::DataFusionError,
+    logical_expr::{ExprSchemable, Filter, LogicalPlan, utils::disjunction},
+    prelude::{Expr, col, lit},
+    sql::sqlparser::{dialect::PostgreSqlDialect, parser::Parser, tokenizer::Token},
+};
+use tracing::warn;
+
+use crate::{
+    parseable::PARSEABLE,
+    query::QUERY_SESSION,
+    storage::{
+        StreamType,
+        field_stats::{
+            DATASET_STATS_CUSTOM_PARTITION, DATASET_STATS_FIELD_NAME, DATASET_STATS_STREAM_NAME,
+        },
+    },
+};
+
+use super::{
+    column_mask::{ColumnMask, column_masks},
+    role::{Action, ParseableResourceType, Permission, model::DefaultPrivilege},
+};
+
+/// Row filters restricting what the permissions can query of the stream, `None` if all of
+/// it can be queried. An empty list means that no row can be queried.
+pub fn row_filters(permissions: &[Permission], stream: &str) -> Option<Vec<String>> {
+    let mut filters = vec![];
+    for permission in permissions {
+        match permission {
+            Permission::Resource(Action::All, _)
+            | Permission::Resource(_, ParseableResourceType::All) => return None,
+            Permission::Resource(Action::Query, ParseableResourceType::Stream(resource))
+                if resource == stream || resource == "*" =>
+            {
+                return None;
+            }
+            Permission::RestrictedQuery {
+                stream: resource,
+                filter,
+                ..
+            } if resource == stream || resource == "*" => match filter {
+                Some(filter) => filters.push(filter.clone()),
+                // masked columns only, all the rows can be queried
+                None => return None,
+            },
+            _ => {}
+        }
+    }
+
+    Some(filters)
+}
+
+/// Predicate over the fields of the stream that rows have to match to be queried,
+/// `None` if all of the stream can be queried
+pub fn row_filter_expr(permissions: &[Permission], stream: &str) -> Option<Expr> {
+    if stream == DATASET_STATS_STREAM_NAME {
+        return dataset_stats_filter(permissions);
+    }
+    let stream_schema = match PARSEABLE.get_stream(stream) {
+        // other internal streams can be queried by anyone
+        Ok(stream) if stream.get_stream_type() == StreamType::Internal => return None,
+        Ok(stream) => Some(stream.get_schema()),
+        Err(_) => None,
+    };
+    let filters = row_filters(permissions, stream)?;
+
+    let Some(df_schema) =
+        stream_schema.and_then(|schema| DFSchema::try_from(schema.as_ref().clone()).ok())
+    else {
+        return Some(lit(false));
+    };
+
+    // a filter that doesn't compile, e.g. as the stream doesn't have its fields yet, matches nothing
+    let exprs = filters.iter().map(|filter| {
+        compile(filter, &df_schema).unwrap_or_else(|err| {
+            warn!("Row filter {filter:?} of stream {stream} does not apply: {err}");
+            lit(false)
+        })
+    });
+
+    Some(disjunction(exprs).unwrap_or_else(|| lit(false)))
+}
+
+/// Predicate over the rows of the dataset stats stream, which hold the top values of every
+/// field of a dataset whatever the row filters and column masks on it. Only the stats of the
+/// datasets the permissions read without a row filter can be queried, less the stats of their
+/// masked columns. `None` if all the stats can be queried.
+pub fn dataset_stats_filter(permissions: &[Permission]) -> Option<Expr> {
+    let unmasked_fields = |masks: BTreeMap<String, ColumnMask>| {
+        col(DATASET_STATS_FIELD_NAME).in_list(masks.into_keys().map(lit).collect(), true)
+    };
+
+    if row_filters(permissions, "*").is_none() {
+        let masks = column_masks(permissions, "*");
+        return (!masks.is_empty()).then(|| unmasked_fields(masks));
+    }
+
+    let datasets: BTreeSet<&str> = permissions
+        .iter()
+        .filter_map(|permission| match permission {
+            Permission::Resource(_, ParseableResourceType::Stream(stream))
+            | Permission::RestrictedQuery { stream, .. } => Some(stream.as_str()),
+            _ => None,
+        })
+        .filter(|dataset| row_filters(permissions, dataset).is_none())
+        .collect();
+
+    let mut unmasked = vec![];
+    let mut predicates = vec![];
+    for dataset in datasets {
+        let masks = column_masks(permissions, dataset);
+        if masks.is_empty() {
+            unmasked.push(lit(dataset));
+        } else {
+            predicates.push(
+                col(DATASET_STATS_CUSTOM_PARTITION)
+                    .eq(lit(dataset))
+                    .and(unmasked_fields(masks)),
+            );
+        }
+    }
+    if !unmasked.is_empty() {
+        predicates.insert(
+            0,
+            col(DATASET_STATS_CUSTOM_PARTITION).in_list(unmasked, false),
+        );
+    }
+
+    Some(disjunction(predicates).unwrap_or_else(|| lit(false)))
+}
+
+/// Filters every scan of a stream in the plan with the row filters of the permissions
+pub fn apply_row_filters(
+    plan: LogicalPlan,
+    permissions: &[Permission],
+) -> Result<LogicalPlan, DataFusionError> {
+    let mut predicates: HashMap<String, Option<Expr>> = HashMap::new();
+    let plan = plan
+        .transform_up_with_subqueries(|node| {
+            let LogicalPlan::TableScan(scan) = &node else {
+                return Ok(Transformed::no(node));
+            };
+            let stream = scan.table_name.table().to_owned();
+            let predicate = predicates
+                .entry(stream)
+                .or_insert_with_key(|stream| row_filter_expr(permissions, stream));
+
+            match predicate {
+                Some(predicate) => {
+                    let filter = Filter::try_new(predicate.clone(), Arc::new(node))?;
+                    Ok(Transformed::yes(LogicalPlan::Filter(filter)))
+                }
+                None => Ok(Transformed::no(node)),
+            }
+        })?
+        .data;
+
+    Ok(plan)
+}
+
+fn compile(filter: &str, df_schema: &DFSchema) -> Result<Expr, DataFusionError> {
+    let expr = QUERY_SESSION.parse_sql_expr(filter, df_schema)?;
+    match expr.get_type(df_schema)? {
+        DataType::Boolean => Ok(expr),
+        data_type => Err(DataFusionError::Plan(format!(
+            "Row filter should evaluate to a boolean, got {data_type}"
+        ))),
+    }
+}
+
+/// Checks that filters are only set on privileges on a stream, and that they are SQL expressions
+pub fn validate(privileges: &[DefaultPrivilege]) -> Result<(), RowFilterError> {
+    for privilege in privileges {
+        let (DefaultPrivilege::Reader {
+            resource, filter, ..
+        }
+        | DefaultPrivilege::Writer {
+            resource, filter, ..
+        }) = privilege
+        else {
+            continue;
+        };
+        let Some(filter) = filter else {
+            continue;
+        };
+
+        if !matches!(resource, ParseableResourceType::Stream(_)) {
+            return Err(RowFilterError::NotStream);
+        }
+        let mut parser = Parser::new(&PostgreSqlDialect {})
+            .try_with_sql(filter)
+            .map_err(|err| RowFilterError::Invalid(err.to_string()))?;
+        parser
+            .parse_expr()
+            .map_err(|err| RowFilterError::Invalid(err.to_string()))?;
+        if parser.peek_token().token != Token::EOF {
+            return Err(RowFilterError::Invalid(format!(
+                "unexpected {} after the expression",
+                parser.peek_token().token
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RowFilterError {
+    #[error("Row filters can only be set on privileges on a stream")]
+    NotStream,
+    #[error("Row filter should be a SQL expression: {0}")]
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbac::role::RoleBuilder;
+
+    fn permissions(privileges: &[DefaultPrivilege]) -> Vec<Permission> {
+        privileges
+            .iter()
+            .flat_map(|privilege| RoleBuilder::from(privilege).build())
+            .collect()
+    }
+
+    fn reader(stream: &str, filter: Option<&str>) -> DefaultPrivilege {
+        DefaultPrivilege::Reader {
+            resource: ParseableResourceType::Stream(stream.to_owned()),
+            filter: filter.map(str::to_owned),
+            masks: Default::default(),
+        }
+    }
+
+    #[test]
+    fn combines_filters_of_roles() {
+        let filtered = permissions(&[
+            reader("k8s-logs", Some("namespace = 'payments'")),
+            reader("k8s-logs", Some("namespace = 'checkout'")),
+            reader("app", None),
+        ]);
+
+        let mut filters = row_filters(&filtered, "k8s-logs").unwrap();
+        filters.sort();
+        assert_eq!(
+            filters,
+            vec!["namespace = 'checkout'", "namespace = 'payments'"]
+        );
+        assert_eq!(row_filters(&filtered, "app"), None);
+        assert_eq!(row_filters(&filtered, "other"), Some(vec![]));
+    }
+
+    #[test]
+    fn unfiltered_access_lifts_filters() {
+        let mut privileges = vec![reader("k8s-logs", Some("namespace = 'payments'"))];
+        privileges.push(reader("k8s-logs", None));
+        assert_eq!(row_filters(&permissions(&privileges), "k8s-logs"), None);
+
+        let privileges = vec![
+            reader("k8s-logs", Some("namespace = 'payments'")),
+            DefaultPrivilege::Editor,
+        ];
+        assert_eq!(row_filters(&permissions(&privileges), "k8s-logs"), None);
+    }
+
+    #[test]
+    fn restricts_dataset_stats_to_unfiltered_datasets() {
+        let filtered = permissions(&[
+            reader("k8s-logs", Some("namespace = 'payments'")),
+            reader("app", None),
+        ]);
+        assert_eq!(
+            dataset_stats_filter(&filtered),
+            Some(col(DATASET_STATS_CUSTOM_PARTITION).in_list(vec![lit("app")], false))
+        );
+
+        let filtered = permissions(&[reader("k8s-logs", Some("namespace = 'payments'"))]);
+        assert_eq!(dataset_stats_filter(&filtered), Some(lit(false)));
+
+        assert_eq!(
+            dataset_stats_filter(&permissions(&[DefaultPrivilege::Editor])),
+            None
+        );
+    }
+
+    #[test]
+    fn leaves_masked_fields_out_of_dataset_stats() {
+        let masked = permissions(&[
+            DefaultPrivilege::Reader {
+                resource: ParseableResourceType::Stream("k8s-logs".to_owned()),
+                filter: None,
+                masks: [
+                    ("email".to_owned(), ColumnMask::Hide),
+                    ("ssn".to_owned(), ColumnMask::Hash),
+                ]
+                .into(),
+            },
+            reader("app", None),
+        ]);
+
+        let dataset = col(DATASET_STATS_CUSTOM_PARTITION);
+        let field = col(DATASET_STATS_FIELD_NAME);
+        assert_eq!(
+            dataset_stats_filter(&masked),
+            Some(
+                dataset.clone().in_list(vec![lit("app")], false).or(dataset
+                    .eq(lit("k8s-logs"))
+                    .and(field.in_list(vec![lit("email"), lit("ssn")], true)))
+            )
+        );
+    }
+
+    #[test]
+    fn validates_filters() {
+        assert!(validate(&[reader("k8s-logs", Some("namespace = 'payments'"))]).is_ok());
+        assert!(validate(&[reader("k8s-logs", Some("namespace = 'payments' OR"))]).is_err());
+        assert!(validate(&[reader("k8s-logs", Some("1 = 1; DROP TABLE x"))]).is_err());
+        assert!(
+            validate(&[DefaultPrivilege::Reader {
+                resource: ParseableResourceType::All,
+                filter: Some("namespace = 'payments'".to_owned()),
+                masks: Default::default(),
+            }])
+            .is_err()
+        );
+    }
+}
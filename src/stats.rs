@@ -142,6 +142,21 @@ pub async fn update_deleted_stats(
             storage_size += manifest.storage_size as i64;
         }
     }
+    update_deleted_totals(storage, stream_name, num_row, ingestion_size, storage_size).await
+}
+
+/// Moves the given amounts from the ingested to the deleted stats of the stream
+/// and persists the updated stats.
+///
+/// Used as is when only some of the partitions of a date are removed,
+/// as the per date counters can't be decremented.
+pub async fn update_deleted_totals(
+    storage: Arc<dyn ObjectStorage>,
+    stream_name: &str,
+    num_row: i64,
+    ingestion_size: i64,
+    storage_size: i64,
+) -> Result<(), ObjectStorageError> {
     EVENTS_DELETED
         .with_label_values(&[stream_name, "json"])
         .add(num_row);
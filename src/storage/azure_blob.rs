@@ -38,7 +38,8 @@ use datafusion::{
 };
 use futures::{StreamExt, TryStreamExt, stream::FuturesUnordered};
 use object_store::{
-    BackoffConfig, ClientOptions, ListResult, ObjectMeta, ObjectStore, PutPayload, RetryConfig,
+    BackoffConfig, ClientOptions, ListResult, ObjectMeta, ObjectStore, PutMode, PutPayload,
+    RetryConfig, UpdateVersion,
     azure::{MicrosoftAzure, MicrosoftAzureBuilder},
     buffered::BufReader,
     limit::LimitStore,
@@ -571,6 +572,57 @@ impl ObjectStorage for BlobStore {
         Ok(())
     }
 
+    async fn get_object_versioned(
+        &self,
+        path: &RelativePath,
+    ) -> Result<(Bytes, UpdateVersion), ObjectStorageError> {
+        let resp = self.client.get(&to_object_store_path(path)).await;
+        increment_object_store_calls_by_date("GET", &Utc::now().date_naive().to_string());
+        let resp = resp?;
+        let version = UpdateVersion {
+            e_tag: resp.meta.e_tag.clone(),
+            version: resp.meta.version.clone(),
+        };
+        let body = resp.bytes().await?;
+        increment_files_scanned_in_object_store_calls_by_date(
+            "GET",
+            1,
+            &Utc::now().date_naive().to_string(),
+        );
+        increment_bytes_scanned_in_object_store_calls_by_date(
+            "GET",
+            body.len() as u64,
+            &Utc::now().date_naive().to_string(),
+        );
+
+        Ok((body, version))
+    }
+
+    async fn put_object_if_unchanged(
+        &self,
+        path: &RelativePath,
+        resource: Bytes,
+        version: UpdateVersion,
+    ) -> Result<(), ObjectStorageError> {
+        let resp = self
+            .client
+            .put_opts(
+                &to_object_store_path(path),
+                resource.into(),
+                PutMode::Update(version).into(),
+            )
+            .await;
+        increment_object_store_calls_by_date("PUT", &Utc::now().date_naive().to_string());
+        resp?;
+        increment_files_scanned_in_object_store_calls_by_date(
+            "PUT",
+            1,
+            &Utc::now().date_naive().to_string(),
+        );
+
+        Ok(())
+    }
+
     async fn delete_prefix(&self, path: &RelativePath) -> Result<(), ObjectStorageError> {
         self._delete_prefix(path.as_ref()).await?;
 
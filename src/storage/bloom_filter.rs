@@ -0,0 +1,159 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use arrow_schema::Schema;
+use parquet::{file::properties::WriterPropertiesBuilder, schema::types::ColumnPath};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_FPP: f64 = 0.01;
+
+/// Columns of a stream for which parquet bloom filters are written, e.g. `trace_id`
+/// or `request_id`, so that point lookups can skip row groups which don't hold the value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", try_from = "BloomFilterView")]
+pub struct BloomFilterConfig {
+    pub columns: Vec<String>,
+    /// False positive probability of the filters
+    pub fpp: f64,
+    /// Number of distinct values expected in a row group, sizes the filters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ndv: Option<u64>,
+}
+
+impl Default for BloomFilterConfig {
+    fn default() -> Self {
+        Self {
+            columns: vec![],
+            fpp: DEFAULT_FPP,
+            ndv: None,
+        }
+    }
+}
+
+impl BloomFilterConfig {
+    /// Enables the bloom filters of the configured columns
+    /// which are present in the schema of the parquet file
+    pub fn apply(
+        &self,
+        mut props: WriterPropertiesBuilder,
+        schema: &Schema,
+    ) -> WriterPropertiesBuilder {
+        for column in &self.columns {
+            if schema.index_of(column).is_err() {
+                continue;
+            }
+            let path = ColumnPath::new(vec![column.to_owned()]);
+            props = props
+                .set_column_bloom_filter_enabled(path.clone(), true)
+                .set_column_bloom_filter_fpp(path.clone(), self.fpp);
+            if let Some(ndv) = self.ndv {
+                props = props.set_column_bloom_filter_ndv(path, ndv);
+            }
+        }
+
+        props
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BloomFilterView {
+    columns: Vec<String>,
+    #[serde(default)]
+    fpp: Option<f64>,
+    #[serde(default)]
+    ndv: Option<u64>,
+}
+
+impl TryFrom<BloomFilterView> for BloomFilterConfig {
+    type Error = String;
+
+    fn try_from(view: BloomFilterView) -> Result<Self, Self::Error> {
+        let mut columns: Vec<String> = Vec::with_capacity(view.columns.len());
+        for column in view.columns {
+            let column = column.trim();
+            if column.is_empty() {
+                return Err("Column name of a bloom filter can't be empty".to_string());
+            }
+            if !columns.iter().any(|c| c == column) {
+                columns.push(column.to_owned());
+            }
+        }
+
+        let fpp = view.fpp.unwrap_or(DEFAULT_FPP);
+        if !(fpp > 0.0 && fpp < 1.0) {
+            return Err(format!(
+                "False positive probability of bloom filters should be between 0 and 1 exclusive, got {fpp}"
+            ));
+        }
+        if view.ndv == Some(0) {
+            return Err(
+                "Number of distinct values of bloom filters should be positive".to_string(),
+            );
+        }
+
+        Ok(Self {
+            columns,
+            fpp,
+            ndv: view.ndv,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::{DataType, Field};
+    use parquet::file::properties::WriterProperties;
+
+    use super::*;
+
+    #[test]
+    fn validates_config() {
+        let config: BloomFilterConfig =
+            serde_json::from_str(r#"{"columns": ["trace_id", " trace_id", "user_id"]}"#).unwrap();
+        assert_eq!(config.columns, vec!["trace_id", "user_id"]);
+        assert_eq!(config.fpp, DEFAULT_FPP);
+
+        assert!(serde_json::from_str::<BloomFilterConfig>(r#"{"columns": [""]}"#).is_err());
+        assert!(
+            serde_json::from_str::<BloomFilterConfig>(r#"{"columns": ["a"], "fpp": 1.0}"#).is_err()
+        );
+        assert!(
+            serde_json::from_str::<BloomFilterConfig>(r#"{"columns": ["a"], "ndv": 0}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn enables_filters_of_present_columns() {
+        let config = BloomFilterConfig {
+            columns: vec!["trace_id".to_string(), "missing".to_string()],
+            fpp: 0.05,
+            ndv: Some(1000),
+        };
+        let schema = Schema::new(vec![Field::new("trace_id", DataType::Utf8, true)]);
+        let props = config.apply(WriterProperties::builder(), &schema).build();
+
+        let trace_id = ColumnPath::new(vec!["trace_id".to_string()]);
+        let filter = props.bloom_filter_properties(&trace_id).unwrap();
+        assert_eq!(filter.fpp, 0.05);
+        assert_eq!(filter.ndv, 1000);
+
+        let missing = ColumnPath::new(vec!["missing".to_string()]);
+        assert!(props.bloom_filter_properties(&missing).is_none());
+    }
+}
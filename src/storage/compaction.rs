@@ -0,0 +1,659 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Background compaction of the small parquet files uploaded every minute, per node and
+//! partition. Files of an hour (and custom partition) are merged into files of about
+//! `P_COMPACTION_TARGET_FILE_SIZE`, the manifest is updated in a single conditional write,
+//! and the original files are deleted after a grace period once the new manifest is committed.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use arrow::compute::SortOptions;
+use arrow::row::{Row, RowConverter, Rows, SortField};
+use arrow_array::RecordBatch;
+use arrow_schema::{Field, Schema, SchemaRef};
+use arrow_select::interleave::interleave_record_batch;
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use clokwerk::{AsyncScheduler, TimeUnits};
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use relative_path::{RelativePath, RelativePathBuf};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use ulid::Ulid;
+
+use crate::catalog::manifest::{File, Manifest, create_from_parquet_bytes};
+use crate::catalog::{list_manifests, relative_data_path, update_manifest};
+use crate::event::DEFAULT_TIMESTAMP_KEY;
+use crate::parseable::PARSEABLE;
+use crate::utils::arrow::adapt_batch;
+
+use super::deferred_delete::{delete_due_files, delete_later};
+use super::{ObjectStorage, ObjectStorageError, ObjectStoreFormat};
+
+type SchedulerHandle = JoinHandle<()>;
+
+static SCHEDULER_HANDLER: Lazy<Mutex<Option<SchedulerHandle>>> = Lazy::new(|| Mutex::new(None));
+
+/// Set while compaction runs, so that a run taking longer than the interval isn't started twice
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Days which ended less than this long ago are not compacted,
+/// ingestors may still be uploading files and updating their manifests
+const COMPACTION_GRACE: TimeDelta = TimeDelta::hours(1);
+
+/// Files merged at once add up to at most this many bytes, whatever the target size,
+/// as they are held in memory while merged
+const MAX_MERGE_INPUT_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Rows read from every file, and written, at a time while merging
+const MERGE_BATCH_SIZE: usize = 8192;
+
+pub fn init_scheduler() {
+    if !PARSEABLE.options.compaction {
+        return;
+    }
+
+    info!("Setting up compaction scheduler");
+    let mut scheduler = AsyncScheduler::new();
+    scheduler.every(1.hour()).run(compact_all_streams);
+
+    let scheduler_handler = tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            scheduler.run_pending().await;
+        }
+    });
+
+    *SCHEDULER_HANDLER.lock().unwrap() = Some(scheduler_handler);
+    info!("Compaction scheduler is initialized")
+}
+
+async fn compact_all_streams() {
+    if RUNNING.swap(true, Ordering::AcqRel) {
+        warn!("Skipping compaction, the previous run is still in progress");
+        return;
+    }
+
+    delete_due_files().await;
+
+    let compact_until = Utc::now() - COMPACTION_GRACE;
+    for stream_name in PARSEABLE.streams.list() {
+        if let Err(err) = compact_stream(&stream_name, compact_until).await {
+            error!("Failed to compact stream={stream_name}: {err}");
+        }
+    }
+
+    RUNNING.store(false, Ordering::Release);
+}
+
+/// Compacts the files of the days of the stream which ended before `compact_until`
+pub async fn compact_stream(
+    stream_name: &str,
+    compact_until: DateTime<Utc>,
+) -> Result<(), ObjectStorageError> {
+    info!("running compaction for stream={stream_name}");
+    let store = PARSEABLE.storage.get_object_store();
+    let target_size = PARSEABLE
+        .options
+        .compaction_target_file_size
+        .saturating_mul(1024 * 1024)
+        .min(MAX_MERGE_INPUT_SIZE);
+
+    let dates = store.list_dates(stream_name).await?;
+    for date in dates.into_iter().filter(|date| {
+        date.strip_prefix("date=")
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+            .is_some_and(|date| date < compact_until.date_naive())
+    }) {
+        for (manifest_path, manifest) in list_manifests(&store, stream_name, &date).await? {
+            compact_manifest(&store, stream_name, &manifest_path, manifest, target_size).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Merges the planned files of a manifest and commits them in place of the originals,
+/// unless some of the originals were removed from the manifest in the meantime,
+/// in which case the merged files are discarded
+async fn compact_manifest(
+    store: &Arc<dyn ObjectStorage>,
+    stream_name: &str,
+    manifest_path: &RelativePath,
+    manifest: Manifest,
+    target_size: u64,
+) -> Result<(), ObjectStorageError> {
+    let batches = plan(stream_name, &manifest.files, target_size);
+    if batches.is_empty() {
+        return Ok(());
+    }
+
+    let stream = PARSEABLE.get_stream(stream_name)?;
+    let time_partition = stream.get_time_partition();
+    let custom_partition = stream.get_custom_partition();
+    let time_column = time_partition.as_deref().unwrap_or(DEFAULT_TIMESTAMP_KEY);
+
+    let mut compacted: Vec<(File, Vec<usize>)> = Vec::with_capacity(batches.len());
+    let mut uploaded = Vec::with_capacity(batches.len());
+    for batch in batches {
+        let paths = batch
+            .iter()
+            .filter_map(|&index| relative_data_path(stream_name, &manifest.files[index].file_path))
+            .collect_vec();
+        let Some(directory) = paths.last().and_then(|path| path.parent()) else {
+            continue;
+        };
+        let path = directory.join(format!("compacted.{}.parquet", Ulid::new()));
+
+        let result = async {
+            let mut sources = Vec::with_capacity(paths.len());
+            for source in &paths {
+                sources.push(store.get_object(source).await?);
+            }
+            let bytes = match merge(sources, time_column, |schema| {
+                stream.parquet_writer_props(
+                    schema,
+                    time_partition.as_ref(),
+                    custom_partition.as_ref(),
+                )
+            }) {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    warn!(
+                        "Skipping compaction of {} files into {path}: {err}",
+                        paths.len()
+                    );
+                    return Ok(None);
+                }
+            };
+            store.put_object(&path, bytes.clone()).await?;
+            let file = create_from_parquet_bytes(store.absolute_url(&path).to_string(), bytes)?;
+            Ok::<_, ObjectStorageError>(Some(file))
+        }
+        .await;
+
+        match result {
+            Ok(Some(mut file)) => {
+                // ingestion size refers to the data as it was ingested
+                file.ingestion_size = batch
+                    .iter()
+                    .map(|&index| manifest.files[index].ingestion_size)
+                    .sum();
+                uploaded.push(path);
+                compacted.push((file, batch));
+            }
+            Ok(None) => {}
+            Err(err) => {
+                discard(store, &uploaded).await;
+                return Err(err);
+            }
+        }
+    }
+    if compacted.is_empty() {
+        return Ok(());
+    }
+
+    // the merged files replace the originals as long as the manifest still lists all of them,
+    // files added in the meantime are kept
+    let replaced: HashSet<&str> = compacted
+        .iter()
+        .flat_map(|(_, batch)| batch.iter())
+        .map(|&index| manifest.files[index].file_path.as_str())
+        .collect();
+    let merged = compacted.iter().map(|(file, _)| file.clone()).collect_vec();
+    let committed = update_manifest(manifest_path.as_str(), |current| {
+        let listed: HashSet<&str> = current
+            .files
+            .iter()
+            .map(|file| file.file_path.as_str())
+            .collect();
+        if !replaced.is_subset(&listed) {
+            return false;
+        }
+        current
+            .files
+            .retain(|file| !replaced.contains(file.file_path.as_str()));
+        current.files.extend(merged.iter().cloned());
+        true
+    })
+    .await;
+    let manifest = match committed {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => {
+            warn!("Files of {manifest_path} changed during compaction, discarding merged files");
+            discard(store, &uploaded).await;
+            return Ok(());
+        }
+        Err(err @ ObjectStorageError::Conflict(_)) => {
+            discard(store, &uploaded).await;
+            return Err(err);
+        }
+        Err(err) => {
+            // the manifest may have been written, the merged files are left in place
+            return Err(err);
+        }
+    };
+    let originals = replaced
+        .iter()
+        .filter_map(|file_path| relative_data_path(stream_name, file_path))
+        .collect_vec();
+    info!(
+        "Compacted {} files of {manifest_path} into {}",
+        originals.len(),
+        uploaded.len()
+    );
+
+    let storage_size = manifest.files.iter().map(|file| file.file_size).sum();
+    if let Err(err) = update_snapshot_size(store, stream_name, manifest_path, storage_size).await {
+        error!("Failed to update snapshot after compaction of {manifest_path}: {err}");
+    }
+
+    // queries planned with the previous manifest may still read the original files
+    if let Err(err) = delete_later(stream_name, manifest_path.as_str(), originals).await {
+        error!("Failed to schedule the deletion of the files compacted in {manifest_path}: {err}");
+    }
+
+    Ok(())
+}
+
+/// Groups the files of a manifest smaller than `target_size` by their compaction prefix,
+/// then splits every group, in path order, into batches of about `target_size` bytes.
+/// Returns the indices of the files of every batch holding more than one file.
+fn plan(stream_name: &str, files: &[File], target_size: u64) -> Vec<Vec<usize>> {
+    let mut prefixes: BTreeMap<String, Vec<(RelativePathBuf, usize)>> = BTreeMap::new();
+    for (index, file) in files.iter().enumerate() {
+        if file.file_size >= target_size {
+            continue;
+        }
+        let Some(path) = relative_data_path(stream_name, &file.file_path) else {
+            continue;
+        };
+        if let Some(prefix) = compaction_prefix(&path) {
+            prefixes.entry(prefix).or_default().push((path, index));
+        }
+    }
+
+    let mut batches = Vec::new();
+    for mut group in prefixes.into_values() {
+        group.sort();
+        let mut batch = Vec::new();
+        let mut size = 0;
+        for (_, index) in group {
+            if !batch.is_empty() && size + files[index].file_size > target_size {
+                if batch.len() > 1 {
+                    batches.push(std::mem::take(&mut batch));
+                } else {
+                    batch.clear();
+                }
+                size = 0;
+            }
+            batch.push(index);
+            size += files[index].file_size;
+        }
+        if batch.len() > 1 {
+            batches.push(batch);
+        }
+    }
+
+    batches
+}
+
+/// Files under the same prefix can be merged, it is the path of the file without the
+/// `minute=` partition and the file name, e.g. `app/date=2024-01-01/hour=05/region=eu`
+/// for `app/date=2024-01-01/hour=05/minute=10/region=eu/host.data.parquet`
+fn compaction_prefix(path: &RelativePath) -> Option<String> {
+    let mut components = path.as_str().split('/').collect_vec();
+    components.pop()?;
+    let minute = components
+        .iter()
+        .position(|component| component.starts_with("minute="))?;
+    components.remove(minute);
+
+    Some(components.join("/"))
+}
+
+/// Merges parquet files into a single one, adding the columns missing from some of the files
+/// and merging the rows on the time column, latest first, the way staging writes them.
+/// Rows are streamed from the files into the writer, a batch of every file at a time.
+fn merge(
+    files: Vec<Bytes>,
+    time_column: &str,
+    props: impl FnOnce(&Schema) -> WriterProperties,
+) -> Result<Bytes, ParquetError> {
+    let mut schemas = Vec::with_capacity(files.len());
+    let mut readers = Vec::with_capacity(files.len());
+    for bytes in files {
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(bytes)?.with_batch_size(MERGE_BATCH_SIZE);
+        schemas.push(builder.schema().clone());
+        readers.push(builder.build()?);
+    }
+
+    let merged = Schema::try_merge(schemas.iter().map(|schema| schema.as_ref().clone()))?;
+    // columns missing from some of the files are filled with nulls
+    let fields = merged
+        .fields()
+        .iter()
+        .map(|field| {
+            let everywhere = schemas
+                .iter()
+                .all(|schema| schema.field_with_name(field.name()).is_ok());
+            Arc::new(Field::clone(field).with_nullable(field.is_nullable() || !everywhere))
+        })
+        .collect_vec();
+    let schema = Arc::new(Schema::new_with_metadata(fields, merged.metadata().clone()));
+
+    let mut buffer = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buffer, schema.clone(), Some(props(&schema)))?;
+    match schema.field_with_name(time_column) {
+        Ok(field) => {
+            let options = SortOptions {
+                descending: true,
+                nulls_first: true,
+            };
+            let converter = RowConverter::new(vec![SortField::new_with_options(
+                field.data_type().clone(),
+                options,
+            )])?;
+            let mut cursors = Vec::with_capacity(readers.len());
+            for reader in readers {
+                cursors.extend(Cursor::new(reader, &schema, time_column, &converter)?);
+            }
+            merge_sorted(cursors, time_column, &converter, &mut writer)?;
+        }
+        Err(_) => {
+            for reader in readers {
+                for batch in reader {
+                    writer.write(&adapt_batch(&schema, &batch?))?;
+                }
+            }
+        }
+    }
+    writer.close()?;
+
+    Ok(Bytes::from(buffer))
+}
+
+/// Writes the rows of the cursors in the order of the time column, each file being sorted on
+/// it already. Rows taken from a batch are written before the cursor moves to the next one.
+fn merge_sorted<W: Write + Send>(
+    mut cursors: Vec<Cursor>,
+    time_column: &str,
+    converter: &RowConverter,
+    writer: &mut ArrowWriter<W>,
+) -> Result<(), ParquetError> {
+    let mut indices = Vec::with_capacity(MERGE_BATCH_SIZE);
+    while let Some(next) = cursors
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.current().cmp(&b.current()))
+        .map(|(index, _)| index)
+    {
+        indices.push((next, cursors[next].row));
+        cursors[next].row += 1;
+
+        let exhausted = cursors[next].row == cursors[next].batch.num_rows();
+        if exhausted || indices.len() == MERGE_BATCH_SIZE {
+            let batches = cursors.iter().map(|cursor| &cursor.batch).collect_vec();
+            writer.write(&interleave_record_batch(&batches, &indices)?)?;
+            indices.clear();
+        }
+        if exhausted && !cursors[next].advance(time_column, converter)? {
+            cursors.swap_remove(next);
+        }
+    }
+
+    Ok(())
+}
+
+/// Current batch of a file being merged, along with the rows of its time column
+struct Cursor {
+    reader: ParquetRecordBatchReader,
+    schema: SchemaRef,
+    batch: RecordBatch,
+    rows: Rows,
+    row: usize,
+}
+
+impl Cursor {
+    /// Cursor at the first row of the file, none if the file holds no rows
+    fn new(
+        mut reader: ParquetRecordBatchReader,
+        schema: &SchemaRef,
+        time_column: &str,
+        converter: &RowConverter,
+    ) -> Result<Option<Self>, ParquetError> {
+        let Some((batch, rows)) = next_batch(&mut reader, schema, time_column, converter)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            reader,
+            schema: schema.clone(),
+            batch,
+            rows,
+            row: 0,
+        }))
+    }
+
+    fn current(&self) -> Row<'_> {
+        self.rows.row(self.row)
+    }
+
+    /// Moves to the next batch of the file, false once the file is read through
+    fn advance(
+        &mut self,
+        time_column: &str,
+        converter: &RowConverter,
+    ) -> Result<bool, ParquetError> {
+        let Some((batch, rows)) =
+            next_batch(&mut self.reader, &self.schema, time_column, converter)?
+        else {
+            return Ok(false);
+        };
+        self.batch = batch;
+        self.rows = rows;
+        self.row = 0;
+
+        Ok(true)
+    }
+}
+
+/// Next batch of the file holding rows, adapted to the merged schema
+fn next_batch(
+    reader: &mut ParquetRecordBatchReader,
+    schema: &Schema,
+    time_column: &str,
+    converter: &RowConverter,
+) -> Result<Option<(RecordBatch, Rows)>, ParquetError> {
+    for batch in reader {
+        let batch = adapt_batch(schema, &batch?);
+        if batch.num_rows() == 0 {
+            continue;
+        }
+        let time = batch
+            .column_by_name(time_column)
+            .expect("time column is in the merged schema");
+        let rows = converter.convert_columns(&[time.clone()])?;
+        return Ok(Some((batch, rows)));
+    }
+
+    Ok(None)
+}
+
+/// Deletes the files written for a manifest update which wasn't committed
+pub(super) async fn discard(store: &Arc<dyn ObjectStorage>, paths: &[RelativePathBuf]) {
+    for path in paths {
+        if let Err(err) = store.delete_object(path).await {
+            warn!("Failed to delete discarded file {path}: {err}");
+        }
+    }
+}
+
+/// Reflects the size of the compacted manifest in the snapshot of the stream
+async fn update_snapshot_size(
+    store: &Arc<dyn ObjectStorage>,
+    stream_name: &str,
+    manifest_path: &RelativePath,
+    storage_size: u64,
+) -> Result<(), ObjectStorageError> {
+    let mut meta: ObjectStoreFormat = serde_json::from_slice(
+        &PARSEABLE
+            .metastore
+            .get_stream_json(stream_name, false)
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?,
+    )?;
+
+    let Some(item) = meta
+        .snapshot
+        .manifest_list
+        .iter_mut()
+        .find(|item| item.manifest_path.ends_with(manifest_path.as_str()))
+    else {
+        return Ok(());
+    };
+    item.storage_size = storage_size;
+
+    store.put_snapshot(stream_name, meta.snapshot).await
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{
+        Array, RecordBatch, StringArray, TimestampMillisecondArray, cast::AsArray,
+        types::TimestampMillisecondType,
+    };
+    use arrow_schema::{DataType, TimeUnit};
+
+    use super::*;
+
+    fn file(path: &str, file_size: u64) -> File {
+        File {
+            file_path: format!("/data/{path}"),
+            file_size,
+            ..File::default()
+        }
+    }
+
+    fn parquet(timestamps: Vec<i64>, column: &str) -> Bytes {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                DEFAULT_TIMESTAMP_KEY,
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new(column, DataType::Utf8, true),
+        ]));
+        let values = timestamps.iter().map(|t| Some(t.to_string())).collect_vec();
+        let rb = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(TimestampMillisecondArray::from(timestamps)),
+                Arc::new(StringArray::from(values)),
+            ],
+        )
+        .unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, schema, None).unwrap();
+        writer.write(&rb).unwrap();
+        writer.close().unwrap();
+        Bytes::from(buffer)
+    }
+
+    #[test]
+    fn prefix_drops_minute_and_file_name() {
+        let path = RelativePath::new("app/date=2024-01-01/hour=05/minute=10/region=eu/a.parquet");
+        assert_eq!(
+            compaction_prefix(path).unwrap(),
+            "app/date=2024-01-01/hour=05/region=eu"
+        );
+        assert!(compaction_prefix(RelativePath::new("app/date=2024-01-01/a.parquet")).is_none());
+    }
+
+    #[test]
+    fn plans_batches_per_hour() {
+        let files = vec![
+            file("app/date=2024-01-01/hour=05/minute=01/a.parquet", 10),
+            file("app/date=2024-01-01/hour=05/minute=00/b.parquet", 10),
+            file("app/date=2024-01-01/hour=06/minute=00/c.parquet", 10),
+            file("app/date=2024-01-01/hour=05/minute=02/d.parquet", 10),
+            file("app/date=2024-01-01/hour=05/minute=03/e.parquet", 100),
+            file("app/date=2024-01-01/hour=05/minute=04/f.parquet", 10),
+        ];
+        // the file of hour 06 is alone and e is already big enough
+        assert_eq!(plan("app", &files, 25), vec![vec![1, 0], vec![3, 5]]);
+    }
+
+    #[test]
+    fn merges_and_sorts_files() {
+        let files = vec![parquet(vec![3, 1], "host"), parquet(vec![4, 2], "region")];
+        let props = |_: &Schema| WriterProperties::builder().build();
+        let bytes = merge(files, DEFAULT_TIMESTAMP_KEY, props).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(bytes)
+            .unwrap()
+            .build()
+            .unwrap();
+        let rb = reader.map(Result::unwrap).next().unwrap();
+        assert_eq!(rb.num_columns(), 3);
+        let timestamps = rb
+            .column_by_name(DEFAULT_TIMESTAMP_KEY)
+            .unwrap()
+            .as_primitive::<TimestampMillisecondType>();
+        assert_eq!(timestamps.values().to_vec(), vec![4, 3, 2, 1]);
+        assert_eq!(rb.column_by_name("region").unwrap().null_count(), 2);
+    }
+
+    #[test]
+    fn merges_files_of_many_batches() {
+        // interleaved timestamps, each file sorted latest first and spanning several batches
+        let rows = (MERGE_BATCH_SIZE * 2) as i64;
+        let files = (0..3)
+            .map(|file| parquet((0..rows).rev().map(|t| t * 3 + file).collect(), "host"))
+            .collect_vec();
+        let props = |_: &Schema| WriterProperties::builder().build();
+        let bytes = merge(files, DEFAULT_TIMESTAMP_KEY, props).unwrap();
+
+        let timestamps = ParquetRecordBatchReaderBuilder::try_new(bytes)
+            .unwrap()
+            .build()
+            .unwrap()
+            .flat_map(|rb| {
+                let rb = rb.unwrap();
+                rb.column_by_name(DEFAULT_TIMESTAMP_KEY)
+                    .unwrap()
+                    .as_primitive::<TimestampMillisecondType>()
+                    .values()
+                    .to_vec()
+            })
+            .collect_vec();
+        assert_eq!(timestamps, (0..rows * 3).rev().collect_vec());
+    }
+}
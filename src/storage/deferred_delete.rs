@@ -0,0 +1,161 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Parquet files replaced in a manifest, by compaction or retention, may still be read by
+//! the queries planned with the previous manifest. They are recorded in the metastore and
+//! deleted once past a grace period, unless the manifest refers to them again.
+
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use relative_path::RelativePathBuf;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use ulid::Ulid;
+
+use crate::catalog::relative_data_path;
+use crate::metastore::metastore_traits::MetastoreObject;
+use crate::parseable::PARSEABLE;
+
+use super::ObjectStorageError;
+use super::object_storage::deferred_delete_json_path;
+
+/// Time left to running queries to read the replaced files
+const DELETE_GRACE: TimeDelta = TimeDelta::minutes(30);
+
+/// Set while due files are deleted, both compaction and retention trigger it
+static DELETING: AtomicBool = AtomicBool::new(false);
+
+/// Files no longer referred to by a manifest, to be deleted after `delete_after`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeferredDelete {
+    id: Ulid,
+    stream_name: String,
+    manifest_url: String,
+    delete_after: DateTime<Utc>,
+    files: Vec<RelativePathBuf>,
+}
+
+impl MetastoreObject for DeferredDelete {
+    fn get_object_path(&self) -> String {
+        deferred_delete_json_path(&self.id).to_string()
+    }
+
+    fn get_object_id(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+/// Records the files, just removed from the manifest at `manifest_url`, to be deleted
+/// once past the grace period
+pub async fn delete_later(
+    stream_name: &str,
+    manifest_url: &str,
+    files: Vec<RelativePathBuf>,
+) -> Result<(), ObjectStorageError> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let deferred = DeferredDelete {
+        id: Ulid::new(),
+        stream_name: stream_name.to_owned(),
+        manifest_url: manifest_url.to_owned(),
+        delete_after: Utc::now() + DELETE_GRACE,
+        files,
+    };
+    PARSEABLE
+        .metastore
+        .put_deferred_delete(&deferred)
+        .await
+        .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))
+}
+
+/// Deletes the recorded files past their grace period
+pub async fn delete_due_files() {
+    if DELETING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    match PARSEABLE.metastore.get_deferred_deletes().await {
+        Ok(deferred) => {
+            let now = Utc::now();
+            for bytes in deferred {
+                match serde_json::from_slice::<DeferredDelete>(&bytes) {
+                    Ok(deferred) if deferred.delete_after <= now => delete(deferred).await,
+                    Ok(_) => {}
+                    Err(err) => warn!("Skipping invalid deferred delete: {err}"),
+                }
+            }
+        }
+        Err(err) => error!("Failed to fetch the files to delete: {err}"),
+    }
+
+    DELETING.store(false, Ordering::Release);
+}
+
+async fn delete(deferred: DeferredDelete) {
+    // files the manifest refers to again are kept, whatever happened to it since
+    let referenced: HashSet<RelativePathBuf> = match PARSEABLE
+        .metastore
+        .get_manifest_versioned(&deferred.manifest_url)
+        .await
+    {
+        Ok(Some((manifest, _))) => manifest
+            .files
+            .iter()
+            .filter_map(|file| relative_data_path(&deferred.stream_name, &file.file_path))
+            .collect(),
+        Ok(None) => HashSet::new(),
+        Err(err) => {
+            error!(
+                "Failed to fetch manifest {} before deleting replaced files: {err}",
+                deferred.manifest_url
+            );
+            return;
+        }
+    };
+
+    let store = PARSEABLE.storage.get_object_store();
+    for path in deferred
+        .files
+        .iter()
+        .filter(|path| !referenced.contains(*path))
+    {
+        match store.delete_object(path).await {
+            Ok(()) | Err(ObjectStorageError::NoSuchKey(_)) => {}
+            Err(ObjectStorageError::IoError(err)) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => {
+                // kept for the next run
+                warn!("Failed to delete replaced file {path}: {err}");
+                return;
+            }
+        }
+    }
+
+    match PARSEABLE.metastore.delete_deferred_delete(&deferred).await {
+        Ok(()) => info!(
+            "Deleted {} files replaced in {}",
+            deferred.files.len(),
+            deferred.manifest_url
+        ),
+        Err(err) => error!("Failed to remove deferred delete {}: {err}", deferred.id),
+    }
+}
@@ -69,7 +69,9 @@ use tracing::warn;
 use ulid::Ulid;
 
 pub const DATASET_STATS_STREAM_NAME: &str = "pstats";
-const DATASET_STATS_CUSTOM_PARTITION: &str = "dataset_name";
+pub const DATASET_STATS_CUSTOM_PARTITION: &str = "dataset_name";
+/// Column of the field names of the stats, once flattened
+pub const DATASET_STATS_FIELD_NAME: &str = "field_stats_field_name";
 const MAX_CONCURRENT_FIELD_STATS: usize = 10;
 
 #[derive(Serialize, Debug)]
@@ -529,6 +531,7 @@ pub async fn get_dataset_stats(
         start_time: dataset_stats_request.start_time.clone(),
         end_time: dataset_stats_request.end_time.clone(),
         filter_tags: None,
+        filter_id: None,
         fields: false,
         streaming: false,
         send_null: false,
@@ -28,13 +28,15 @@ use chrono::Utc;
 use datafusion::{datasource::listing::ListingTableUrl, execution::runtime_env::RuntimeEnvBuilder};
 use fs_extra::file::CopyOptions;
 use futures::{TryStreamExt, stream::FuturesUnordered};
-use object_store::{ListResult, ObjectMeta, buffered::BufReader};
+use object_store::{ListResult, ObjectMeta, UpdateVersion, buffered::BufReader};
 use relative_path::{RelativePath, RelativePathBuf};
 use tokio::{
     fs::{self, DirEntry, OpenOptions},
     io::AsyncReadExt,
+    sync::Mutex,
 };
 use tokio_stream::wrappers::ReadDirStream;
+use xxhash_rust::xxh3::xxh3_64;
 
 use crate::{
     handlers::http::users::USERS_ROOT_DIR,
@@ -51,6 +53,18 @@ use super::{
     PARSEABLE_ROOT_DIRECTORY, STREAM_METADATA_FILE_NAME, STREAM_ROOT_DIRECTORY,
 };
 
+/// The filesystem has no conditional writes, `put_object_if_unchanged` compares
+/// and writes while holding this lock
+static CONDITIONAL_PUT: Mutex<()> = Mutex::const_new(());
+
+/// Version of an object on the filesystem, the hash of its content
+fn content_version(bytes: &[u8]) -> UpdateVersion {
+    UpdateVersion {
+        e_tag: Some(format!("{:016x}", xxh3_64(bytes))),
+        version: None,
+    }
+}
+
 #[derive(Debug, Clone, clap::Args)]
 #[command(
     name = "Local filesystem config",
@@ -102,6 +116,35 @@ impl LocalFS {
     pub fn path_in_root(&self, path: &RelativePath) -> PathBuf {
         path.to_path(&self.root)
     }
+
+    /// Path of an object on the filesystem, relative to the root or absolute as
+    /// stored in the snapshot for the manifests
+    fn object_path(&self, path: &RelativePath) -> PathBuf {
+        let file_path;
+
+        // this is for the `get_manifest()` function because inside a snapshot, we store the absolute path (without `/`) on linux based OS
+        // `home/user/.../manifest.json`
+        // on windows, the path is stored with the drive letter
+        // `D:\\parseable\\data..\\manifest.json`
+        // thus, we need to check if the root of localfs is already present in the path
+        #[cfg(windows)]
+        {
+            // in windows the absolute path (self.root) doesn't matter because we store the complete path
+            file_path = path.to_path("");
+        }
+        #[cfg(not(windows))]
+        {
+            // absolute path (self.root) will always start with `/`
+            let root_str = self.root.to_str().unwrap();
+            file_path = if path.to_string().contains(&root_str[1..]) && root_str.len() > 1 {
+                path.to_path("/")
+            } else {
+                self.path_in_root(path)
+            };
+        }
+
+        file_path
+    }
 }
 
 #[async_trait]
@@ -154,29 +197,7 @@ impl ObjectStorage for LocalFS {
         }
     }
     async fn get_object(&self, path: &RelativePath) -> Result<Bytes, ObjectStorageError> {
-        let file_path;
-
-        // this is for the `get_manifest()` function because inside a snapshot, we store the absolute path (without `/`) on linux based OS
-        // `home/user/.../manifest.json`
-        // on windows, the path is stored with the drive letter
-        // `D:\\parseable\\data..\\manifest.json`
-        // thus, we need to check if the root of localfs is already present in the path
-        #[cfg(windows)]
-        {
-            // in windows the absolute path (self.root) doesn't matter because we store the complete path
-            file_path = path.to_path("");
-        }
-        #[cfg(not(windows))]
-        {
-            // absolute path (self.root) will always start with `/`
-            let root_str = self.root.to_str().unwrap();
-            file_path = if path.to_string().contains(&root_str[1..]) && root_str.len() > 1 {
-                path.to_path("/")
-            } else {
-                self.path_in_root(path)
-            };
-        }
-
+        let file_path = self.object_path(path);
         let file_result = fs::read(file_path).await;
         let res: Result<Bytes, ObjectStorageError> = match file_result {
             Ok(x) => {
@@ -338,6 +359,42 @@ impl ObjectStorage for LocalFS {
         res.map_err(Into::into)
     }
 
+    async fn get_object_versioned(
+        &self,
+        path: &RelativePath,
+    ) -> Result<(Bytes, UpdateVersion), ObjectStorageError> {
+        let bytes = self.get_object(path).await?;
+        let version = content_version(&bytes);
+        Ok((bytes, version))
+    }
+
+    async fn put_object_if_unchanged(
+        &self,
+        path: &RelativePath,
+        resource: Bytes,
+        version: UpdateVersion,
+    ) -> Result<(), ObjectStorageError> {
+        let _guard = CONDITIONAL_PUT.lock().await;
+        let current = match self.get_object(path).await {
+            Ok(bytes) => Some(content_version(&bytes)),
+            Err(ObjectStorageError::NoSuchKey(_)) => None,
+            Err(err) => return Err(err),
+        };
+        if current.is_none_or(|current| current.e_tag != version.e_tag) {
+            return Err(ObjectStorageError::Conflict(path.to_string()));
+        }
+
+        fs::write(self.object_path(path), resource).await?;
+        increment_files_scanned_in_object_store_calls_by_date(
+            "PUT",
+            1,
+            &Utc::now().date_naive().to_string(),
+        );
+        increment_object_store_calls_by_date("PUT", &Utc::now().date_naive().to_string());
+
+        Ok(())
+    }
+
     async fn delete_prefix(&self, path: &RelativePath) -> Result<(), ObjectStorageError> {
         let path = self.path_in_root(path);
 
@@ -745,3 +802,38 @@ impl From<fs_extra::error::Error> for ObjectStorageError {
         ObjectStorageError::UnhandledError(Box::new(e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn conditional_put_fails_after_concurrent_write() {
+        let dir = TempDir::new().unwrap();
+        let store = LocalFS::new(dir.path().to_path_buf());
+        let path = RelativePath::new("app/date=2024-01-01/host.manifest.json");
+        store.put_object(path, Bytes::from("v1")).await.unwrap();
+
+        let (bytes, version) = store.get_object_versioned(path).await.unwrap();
+        assert_eq!(bytes, Bytes::from("v1"));
+
+        // another writer updates the object after it was read
+        store.put_object(path, Bytes::from("v2")).await.unwrap();
+        assert!(matches!(
+            store
+                .put_object_if_unchanged(path, Bytes::from("v3"), version)
+                .await,
+            Err(ObjectStorageError::Conflict(_))
+        ));
+        assert_eq!(store.get_object(path).await.unwrap(), Bytes::from("v2"));
+
+        let (_, version) = store.get_object_versioned(path).await.unwrap();
+        store
+            .put_object_if_unchanged(path, Bytes::from("v3"), version)
+            .await
+            .unwrap();
+        assert_eq!(store.get_object(path).await.unwrap(), Bytes::from("v3"));
+    }
+}
@@ -23,7 +23,7 @@ use tokio::task::JoinError;
 
 use crate::{
     catalog::snapshot::Snapshot,
-    event::format::LogSourceEntry,
+    event::{format::LogSourceEntry, pipeline::Pipeline},
     handlers::TelemetryType,
     hottier::StreamHotTier,
     metadata::SchemaVersion,
@@ -39,6 +39,9 @@ use chrono::Utc;
 use std::fmt::Debug;
 
 mod azure_blob;
+pub mod bloom_filter;
+pub mod compaction;
+pub mod deferred_delete;
 pub mod field_stats;
 mod gcs;
 mod localfs;
@@ -48,6 +51,7 @@ pub mod retention;
 mod s3;
 pub mod store_metadata;
 
+use self::bloom_filter::BloomFilterConfig;
 use self::retention::Retention;
 pub use azure_blob::AzureBlobConfig;
 pub use gcs::GcsConfig;
@@ -67,6 +71,10 @@ pub const SCHEMA_FILE_NAME: &str = ".schema";
 pub const ALERTS_ROOT_DIRECTORY: &str = ".alerts";
 pub const SETTINGS_ROOT_DIRECTORY: &str = ".settings";
 pub const TARGETS_ROOT_DIRECTORY: &str = ".targets";
+pub const REPORTS_ROOT_DIRECTORY: &str = ".reports";
+pub const REPORT_LEASES_ROOT_DIRECTORY: &str = ".report_leases";
+pub const API_KEYS_ROOT_DIRECTORY: &str = ".apikeys";
+pub const DEFERRED_DELETES_ROOT_DIRECTORY: &str = ".deferred_deletes";
 pub const MANIFEST_FILE: &str = "manifest.json";
 
 // max concurrent request allowed for datafusion object store
@@ -131,6 +139,11 @@ pub struct ObjectStoreFormat {
     pub log_source: Vec<LogSourceEntry>,
     #[serde(default)]
     pub telemetry_type: TelemetryType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bloom_filter: Option<BloomFilterConfig>,
+    /// Processors run over the events sent to the stream before they are pushed into it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<Pipeline>,
 }
 
 impl MetastoreObject for ObjectStoreFormat {
@@ -274,6 +287,8 @@ impl Default for ObjectStoreFormat {
             hot_tier: None,
             log_source: vec![LogSourceEntry::default()],
             telemetry_type: TelemetryType::Logs,
+            bloom_filter: None,
+            pipeline: None,
         }
     }
 }
@@ -283,6 +298,9 @@ pub enum ObjectStorageError {
     // no such key inside the object storage
     #[error("{0} not found")]
     NoSuchKey(String),
+    // object was written since it was read, by a conditional put
+    #[error("{0} was modified concurrently")]
+    Conflict(String),
     #[error("Invalid Request: {0}")]
     Invalid(#[from] anyhow::Error),
 
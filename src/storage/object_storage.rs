@@ -23,6 +23,7 @@ use chrono::{DateTime, Utc};
 use datafusion::{datasource::listing::ListingTableUrl, execution::runtime_env::RuntimeEnvBuilder};
 use object_store::ListResult;
 use object_store::ObjectMeta;
+use object_store::UpdateVersion;
 use object_store::buffered::BufReader;
 use once_cell::sync::OnceCell;
 use rayon::prelude::*;
@@ -45,6 +46,7 @@ use ulid::Ulid;
 use crate::catalog::{self, snapshot::Snapshot};
 use crate::event::format::LogSource;
 use crate::event::format::LogSourceEntry;
+use crate::event::pipeline::Pipeline;
 use crate::handlers::http::fetch_schema;
 use crate::handlers::http::modal::ingest_server::INGESTOR_EXPECT;
 use crate::handlers::http::modal::ingest_server::INGESTOR_META;
@@ -55,15 +57,18 @@ use crate::metrics::{EVENTS_STORAGE_SIZE_DATE, LIFETIME_EVENTS_STORAGE_SIZE, STO
 use crate::option::Mode;
 use crate::parseable::{LogStream, PARSEABLE, Stream};
 use crate::stats::FullStats;
+use crate::storage::API_KEYS_ROOT_DIRECTORY;
 use crate::storage::SETTINGS_ROOT_DIRECTORY;
 use crate::storage::TARGETS_ROOT_DIRECTORY;
 use crate::storage::field_stats::DATASET_STATS_STREAM_NAME;
 use crate::storage::field_stats::calculate_field_stats;
+use crate::storage::{REPORT_LEASES_ROOT_DIRECTORY, REPORTS_ROOT_DIRECTORY};
 
 use super::{
-    ALERTS_ROOT_DIRECTORY, MANIFEST_FILE, ObjectStorageError, ObjectStoreFormat,
-    PARSEABLE_METADATA_FILE_NAME, PARSEABLE_ROOT_DIRECTORY, SCHEMA_FILE_NAME,
-    STREAM_METADATA_FILE_NAME, STREAM_ROOT_DIRECTORY, retention::Retention,
+    ALERTS_ROOT_DIRECTORY, DEFERRED_DELETES_ROOT_DIRECTORY, MANIFEST_FILE, ObjectStorageError,
+    ObjectStoreFormat, PARSEABLE_METADATA_FILE_NAME, PARSEABLE_ROOT_DIRECTORY, SCHEMA_FILE_NAME,
+    STREAM_METADATA_FILE_NAME, STREAM_ROOT_DIRECTORY, bloom_filter::BloomFilterConfig,
+    retention::Retention,
 };
 
 /// Context for upload operations containing stream information
@@ -284,6 +289,19 @@ pub trait ObjectStorage: Debug + Send + Sync + 'static {
         path: &RelativePath,
         resource: Bytes,
     ) -> Result<(), ObjectStorageError>;
+    /// Fetches an object along with its current version, see `put_object_if_unchanged`
+    async fn get_object_versioned(
+        &self,
+        path: &RelativePath,
+    ) -> Result<(Bytes, UpdateVersion), ObjectStorageError>;
+    /// Puts the object only if it is still at `version`,
+    /// fails with `ObjectStorageError::Conflict` if it was written in the meantime
+    async fn put_object_if_unchanged(
+        &self,
+        path: &RelativePath,
+        resource: Bytes,
+        version: UpdateVersion,
+    ) -> Result<(), ObjectStorageError>;
     async fn delete_prefix(&self, path: &RelativePath) -> Result<(), ObjectStorageError>;
     async fn check(&self) -> Result<(), ObjectStorageError>;
     async fn delete_stream(&self, stream_name: &str) -> Result<(), ObjectStorageError>;
@@ -519,6 +537,48 @@ pub trait ObjectStorage: Debug + Send + Sync + 'static {
             .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?)
     }
 
+    async fn put_bloom_filter(
+        &self,
+        stream_name: &str,
+        bloom_filter: Option<&BloomFilterConfig>,
+    ) -> Result<(), ObjectStorageError> {
+        let mut stream_metadata: ObjectStoreFormat = serde_json::from_slice(
+            &PARSEABLE
+                .metastore
+                .get_stream_json(stream_name, false)
+                .await
+                .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?,
+        )?;
+        stream_metadata.bloom_filter = bloom_filter.cloned();
+
+        Ok(PARSEABLE
+            .metastore
+            .put_stream_json(&stream_metadata, stream_name)
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?)
+    }
+
+    async fn put_pipeline(
+        &self,
+        stream_name: &str,
+        pipeline: Option<&Pipeline>,
+    ) -> Result<(), ObjectStorageError> {
+        let mut stream_metadata: ObjectStoreFormat = serde_json::from_slice(
+            &PARSEABLE
+                .metastore
+                .get_stream_json(stream_name, false)
+                .await
+                .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?,
+        )?;
+        stream_metadata.pipeline = pipeline.cloned();
+
+        Ok(PARSEABLE
+            .metastore
+            .put_stream_json(&stream_metadata, stream_name)
+            .await
+            .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?)
+    }
+
     async fn upsert_stream_metadata(
         &self,
         stream_name: &str,
@@ -1151,6 +1211,48 @@ pub fn target_json_path(target_id: &Ulid) -> RelativePathBuf {
     ])
 }
 
+/// Constructs the path of an API key
+/// Format: ".settings/.apikeys/{key_id}.json"
+#[inline(always)]
+pub fn api_key_json_path(key_id: &Ulid) -> RelativePathBuf {
+    RelativePathBuf::from_iter([
+        SETTINGS_ROOT_DIRECTORY,
+        API_KEYS_ROOT_DIRECTORY,
+        &format!("{key_id}.json"),
+    ])
+}
+
+/// Constructs the path of a run of a scheduled report
+/// Format: ".settings/.reports/{dashboard_id}/{run_id}.json"
+#[inline(always)]
+pub fn report_run_json_path(dashboard_id: &Ulid, run_id: &Ulid) -> RelativePathBuf {
+    RelativePathBuf::from_iter([
+        SETTINGS_ROOT_DIRECTORY,
+        REPORTS_ROOT_DIRECTORY,
+        &dashboard_id.to_string(),
+        &format!("{run_id}.json"),
+    ])
+}
+
+/// Constructs the path of the lease on the runs of a scheduled report
+pub fn report_lease_json_path(dashboard_id: &Ulid) -> RelativePathBuf {
+    RelativePathBuf::from_iter([
+        SETTINGS_ROOT_DIRECTORY,
+        REPORT_LEASES_ROOT_DIRECTORY,
+        &format!("{dashboard_id}.json"),
+    ])
+}
+
+/// Constructs the path of files waiting to be deleted
+/// Format: ".parseable/.deferred_deletes/{id}.json"
+pub fn deferred_delete_json_path(id: &Ulid) -> RelativePathBuf {
+    RelativePathBuf::from_iter([
+        PARSEABLE_ROOT_DIRECTORY,
+        DEFERRED_DELETES_ROOT_DIRECTORY,
+        &format!("{id}.json"),
+    ])
+}
+
 /// Constructs the path for storing alert state JSON files
 /// Format: ".alerts/alert_state_{alert_id}.json"
 #[inline(always)]
@@ -16,11 +16,12 @@
  *
  */
 
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
-use std::num::NonZeroU32;
 use std::sync::Mutex;
 use std::time::Duration;
 
+use chrono::{NaiveDate, Utc};
 use clokwerk::AsyncScheduler;
 use clokwerk::Job;
 use clokwerk::TimeUnits;
@@ -29,12 +30,40 @@ use once_cell::sync::Lazy;
 use tokio::task::JoinHandle;
 use tracing::{info, warn};
 
+use crate::event::DEFAULT_TIMESTAMP_KEY;
 use crate::parseable::PARSEABLE;
 
+use super::deferred_delete::delete_due_files;
+
 type SchedulerHandle = JoinHandle<()>;
 
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
 static SCHEDULER_HANDLER: Lazy<Mutex<Option<SchedulerHandle>>> = Lazy::new(|| Mutex::new(None));
 
+/// Streams whose retention is running, a run can outlast the hour till the next one
+static RUNNING: Lazy<Mutex<HashSet<String>>> = Lazy::new(Mutex::default);
+
+static COMPLETED_DAY_TASKS: Lazy<Mutex<CompletedDayTasks>> = Lazy::new(Mutex::default);
+
+/// Day up to which the tasks applying to whole days last completed, by stream and task.
+/// Such tasks have nothing new to do till `retain_until` moves to the next day, so the
+/// hourly schedule runs them once a day, or again after a failed run.
+#[derive(Debug, Default)]
+struct CompletedDayTasks(HashMap<(String, Task), NaiveDate>);
+
+impl CompletedDayTasks {
+    fn is_done(&self, stream_name: &str, task: &Task, day: NaiveDate) -> bool {
+        self.0
+            .get(&(stream_name.to_owned(), task.clone()))
+            .is_some_and(|done| *done >= day)
+    }
+
+    fn complete(&mut self, stream_name: &str, task: Task, day: NaiveDate) {
+        self.0.insert((stream_name.to_owned(), task), day);
+    }
+}
+
 pub fn load_retention_from_global() {
     info!("loading retention for all streams");
     init_scheduler();
@@ -44,21 +73,22 @@ pub fn init_scheduler() {
     info!("Setting up scheduler");
     let mut scheduler = AsyncScheduler::new();
     let func = move || async {
-        //get retention every day at 12 am
+        // files replaced or archived by retention tasks are deleted once past their grace period
+        tokio::spawn(delete_due_files());
+
+        //get retention every hour, durations can be set in hours
         for stream_name in PARSEABLE.streams.list() {
             match PARSEABLE.get_stream(&stream_name) {
                 Ok(stream) => {
                     if let Some(config) = stream.get_retention() {
-                        for Task { action, days, .. } in config.tasks.into_iter() {
-                            match action {
-                                Action::Delete => {
-                                    let stream_name = stream_name.clone();
-                                    tokio::spawn(async move {
-                                        action::delete(stream_name, u32::from(days)).await;
-                                    });
-                                }
-                            };
+                        if !RUNNING.lock().unwrap().insert(stream_name.clone()) {
+                            info!("Retention of {stream_name} is still running, skipping this run");
+                            continue;
                         }
+                        tokio::spawn(async move {
+                            config.run(stream_name.clone()).await;
+                            RUNNING.lock().unwrap().remove(&stream_name);
+                        });
                     }
                 }
                 Err(err) => {
@@ -73,7 +103,7 @@ pub fn init_scheduler() {
         func().await;
     });
 
-    scheduler.every(1.day()).at("00:00").run(func);
+    scheduler.every(1.hour()).run(func);
 
     let scheduler_handler = tokio::spawn(async move {
         loop {
@@ -93,11 +123,93 @@ pub struct Retention {
     tasks: Vec<Task>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+impl Retention {
+    /// Retention deleting the data of the stream once it is older than `duration`
+    pub fn delete_after(description: &str, duration: Duration) -> Self {
+        Self {
+            tasks: vec![Task {
+                description: description.to_owned(),
+                action: Action::Delete,
+                duration,
+                columns: vec![],
+                destination: None,
+            }],
+        }
+    }
+
+    /// Runs the retention tasks of a stream one after the other, longest duration first.
+    /// This way data about to be deleted is not archived and data which is being
+    /// archived is not rewritten by a downsample task.
+    async fn run(self, stream_name: String) {
+        let archive_destination = self
+            .tasks
+            .iter()
+            .find(|task| task.action == Action::Archive)
+            .and_then(|task| task.destination.clone());
+
+        let mut tasks = self.tasks;
+        tasks.sort_by(|a, b| b.duration.cmp(&a.duration));
+
+        for task in tasks {
+            let Some(retain_until) = action::get_retain_until(Utc::now(), task.duration) else {
+                warn!(
+                    "retention duration of task \"{}\" for stream={stream_name} is out of range",
+                    task.description
+                );
+                continue;
+            };
+            // delete applies down to the minute, the other tasks to whole days
+            let day = retain_until.date_naive();
+            let day_task = task.action != Action::Delete;
+            if day_task
+                && COMPLETED_DAY_TASKS
+                    .lock()
+                    .unwrap()
+                    .is_done(&stream_name, &task, day)
+            {
+                continue;
+            }
+            let completed = match task.action {
+                Action::Delete => {
+                    action::delete(
+                        stream_name.clone(),
+                        retain_until,
+                        archive_destination.as_deref(),
+                    )
+                    .await;
+                    true
+                }
+                Action::Archive => match &task.destination {
+                    Some(destination) => {
+                        action::archive(stream_name.clone(), retain_until, destination).await
+                    }
+                    None => false,
+                },
+                Action::Downsample => {
+                    action::downsample(stream_name.clone(), retain_until, &task.columns).await
+                }
+            };
+            if day_task && completed {
+                COMPLETED_DAY_TASKS
+                    .lock()
+                    .unwrap()
+                    .complete(&stream_name, task, day);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct Task {
     description: String,
     action: Action,
-    days: NonZeroU32,
+    duration: Duration,
+    /// Columns dropped from the parquet files by the downsample action
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    columns: Vec<String>,
+    /// Prefix under which the archive action moves the objects of the stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    destination: Option<String>,
 }
 
 #[derive(
@@ -105,7 +217,14 @@ pub struct Task {
 )]
 #[serde(rename_all = "lowercase")]
 enum Action {
+    /// Delete the data, down to the minute
     Delete,
+    /// Drop the given columns from the data, reducing its size. Applies to whole days.
+    Downsample,
+    /// Move the data out of the stream, under the given prefix, where it is no longer queried.
+    /// Bucket lifecycle rules on the prefix can transition it to a cheaper storage class.
+    /// Applies to whole days.
+    Archive,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -113,6 +232,10 @@ struct TaskView {
     description: String,
     action: Action,
     duration: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    columns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    destination: Option<String>,
 }
 
 impl TryFrom<Vec<TaskView>> for Retention {
@@ -123,45 +246,119 @@ impl TryFrom<Vec<TaskView>> for Retention {
         let mut tasks = Vec::new();
 
         for task in task_view {
-            let duration = task.duration;
-            if !duration.ends_with('d') {
-                return Err("missing 'd' suffix for duration value".to_string());
-            }
-            let Ok(days) = duration[0..duration.len() - 1].parse() else {
-                return Err("could not convert duration to an unsigned number".to_string());
+            // accepts humantime durations, such as `30d`, `12h` or `1d 12h`
+            let duration = match humantime::parse_duration(&task.duration) {
+                Ok(duration) if duration.is_zero() => {
+                    return Err("duration should be greater than zero".to_string());
+                }
+                Ok(duration) => duration,
+                Err(err) => {
+                    return Err(format!("could not parse duration {}: {err}", task.duration));
+                }
             };
 
-            if set.contains(&task.action) {
-                return Err(format!(
-                    "Configuration contains two task both of action \"{}\"",
-                    task.action
-                ));
-            } else {
-                set.push(task.action)
+            // a stream can be downsampled in several steps, but deleted or archived only once
+            if task.action != Action::Downsample {
+                if set.contains(&task.action) {
+                    return Err(format!(
+                        "Configuration contains two task both of action \"{}\"",
+                        task.action
+                    ));
+                } else {
+                    set.push(task.action)
+                }
+            }
+
+            match task.action {
+                Action::Delete => {}
+                Action::Downsample => {
+                    if task.columns.is_empty() {
+                        return Err("downsample task requires the columns to drop".to_string());
+                    }
+                    if task
+                        .columns
+                        .iter()
+                        .any(|column| column == DEFAULT_TIMESTAMP_KEY)
+                    {
+                        return Err(format!(
+                            "downsample task can not drop the {DEFAULT_TIMESTAMP_KEY} column"
+                        ));
+                    }
+                }
+                Action::Archive => match &task.destination {
+                    Some(destination) if is_valid_destination(destination) => {}
+                    _ => {
+                        return Err("archive task requires a valid destination prefix".to_string());
+                    }
+                },
             }
 
             tasks.push(Task {
                 description: task.description,
                 action: task.action,
-                days,
+                duration,
+                columns: task.columns,
+                destination: task.destination,
             })
         }
 
+        // tasks running after the data is gone would never have any effect
+        let duration_of = |action| {
+            tasks
+                .iter()
+                .find(|task| task.action == action)
+                .map(|task| task.duration)
+        };
+        if let Some(delete_duration) = duration_of(Action::Delete)
+            && tasks
+                .iter()
+                .any(|task| task.action != Action::Delete && task.duration >= delete_duration)
+        {
+            return Err("delete task should have the longest duration".to_string());
+        }
+        if let Some(archive_duration) = duration_of(Action::Archive)
+            && tasks
+                .iter()
+                .any(|task| task.action == Action::Downsample && task.duration >= archive_duration)
+        {
+            return Err("downsample tasks should have a shorter duration than archive".to_string());
+        }
+
         Ok(Retention { tasks })
     }
 }
 
+/// Archive destination should be a plain relative prefix in the same object store
+fn is_valid_destination(destination: &str) -> bool {
+    !destination.is_empty()
+        && !destination.starts_with('/')
+        && destination
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..")
+        && destination
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+}
+
 impl From<Retention> for Vec<TaskView> {
     fn from(value: Retention) -> Self {
         value
             .tasks
             .into_iter()
             .map(|task| {
-                let duration = format!("{}d", task.days);
+                // whole days keep the `30d` form
+                let secs = task.duration.as_secs();
+                let duration = if secs % SECONDS_PER_DAY == 0 && task.duration.subsec_nanos() == 0 {
+                    format!("{}d", secs / SECONDS_PER_DAY)
+                } else {
+                    humantime::format_duration(task.duration).to_string()
+                };
                 TaskView {
                     description: task.description,
                     action: task.action,
                     duration,
+                    columns: task.columns,
+                    destination: task.destination,
                 }
             })
             .collect()
@@ -169,31 +366,41 @@ impl From<Retention> for Vec<TaskView> {
 }
 
 mod action {
-    use crate::catalog::remove_manifest_from_snapshot;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::catalog::manifest::create_from_parquet_bytes;
+    use crate::catalog::{
+        list_manifests, relative_data_path, remove_manifest_from_snapshot,
+        remove_partitions_from_snapshot, update_manifest,
+    };
     use crate::parseable::PARSEABLE;
-    use chrono::{Days, NaiveDate, Utc};
+    use crate::storage::compaction::discard;
+    use crate::storage::deferred_delete::delete_later;
+    use crate::storage::object_storage::to_bytes;
+    use crate::storage::{ObjectStorage, ObjectStorageError, ObjectStoreFormat};
+    use bytes::Bytes;
+    use chrono::{DateTime, NaiveDate, TimeDelta, Timelike, Utc};
     use futures::{StreamExt, stream::FuturesUnordered};
     use itertools::Itertools;
+    use parquet::arrow::{ArrowWriter, ParquetRecordBatchReaderBuilder, ProjectionMask};
+    use parquet::errors::ParquetError;
+    use parquet::file::metadata::SortingColumn;
+    use parquet::file::properties::WriterProperties;
     use relative_path::RelativePathBuf;
-    use tracing::{error, info};
+    use tracing::{error, info, warn};
+    use ulid::Ulid;
 
-    pub(super) async fn delete(stream_name: String, days: u32) {
+    pub(super) async fn delete(
+        stream_name: String,
+        retain_until: DateTime<Utc>,
+        archive_destination: Option<&str>,
+    ) {
         info!("running retention task - delete for stream={stream_name}");
         let store = PARSEABLE.storage.get_object_store();
 
-        let retain_until = get_retain_until(Utc::now().date_naive(), days as u64);
-
-        let Ok(mut dates) = store.list_dates(&stream_name).await else {
-            return;
-        };
-        dates.retain(|date| date.starts_with("date"));
-        let dates_to_delete = dates
-            .into_iter()
-            .filter(|date| string_to_date(date) < retain_until)
-            .collect_vec();
-        let dates = dates_to_delete.clone();
+        let dates = dates_before(&store, &stream_name, retain_until).await;
         if !dates.is_empty() {
-            let delete_tasks = FuturesUnordered::new();
             if let Err(err) =
                 remove_manifest_from_snapshot(store.clone(), &stream_name, dates.clone()).await
             {
@@ -204,30 +411,467 @@ mod action {
                 return;
             }
 
-            for date in dates_to_delete {
-                let path = RelativePathBuf::from_iter([&stream_name, &date]);
-                delete_tasks.push(async move {
-                    PARSEABLE
-                        .storage
-                        .get_object_store()
-                        .delete_prefix(&path)
-                        .await
-                });
+            let paths = dates
+                .iter()
+                .map(|date| RelativePathBuf::from_iter([&stream_name, date]))
+                .collect_vec();
+            if let Err(err) = delete_prefixes(paths).await {
+                error!("Failed to run delete task {err:?}");
+                return;
+            }
+        }
+
+        // the day retention ends on is pruned hour by hour, and minute by minute in its last hour
+        let partitions = expired_partitions(&store, &stream_name, retain_until).await;
+        if !partitions.is_empty() {
+            if let Err(err) =
+                remove_partitions_from_snapshot(store.clone(), &stream_name, partitions.clone())
+                    .await
+            {
+                error!(
+                    "Failed to update snapshot for retention cleanup (stream={}): {}. Aborting delete.",
+                    stream_name, err
+                );
+                return;
+            }
+
+            let paths = partitions
+                .iter()
+                .map(|partition| RelativePathBuf::from_iter([&stream_name, partition]))
+                .collect_vec();
+            if let Err(err) = delete_prefixes(paths).await {
+                error!("Failed to run delete task {err:?}");
+                return;
+            }
+        }
+
+        // archived data expires along with the data of the stream
+        if let Some(destination) = archive_destination {
+            let archive_prefix = format!("{destination}/{stream_name}");
+            let paths = dates_before(&store, &archive_prefix, retain_until)
+                .await
+                .iter()
+                .map(|date| RelativePathBuf::from_iter([&archive_prefix, date]))
+                .collect_vec();
+            if let Err(err) = delete_prefixes(paths).await {
+                error!("Failed to delete archived data for stream={stream_name}: {err:?}");
+            }
+        }
+    }
+
+    /// Returns whether the days before `retain_until` were all archived
+    pub(super) async fn archive(
+        stream_name: String,
+        retain_until: DateTime<Utc>,
+        destination: &str,
+    ) -> bool {
+        info!("running retention task - archive for stream={stream_name}");
+        let store = PARSEABLE.storage.get_object_store();
+
+        let dates = dates_before(&store, &stream_name, retain_until).await;
+        if dates.is_empty() {
+            return true;
+        }
+
+        // copy everything first, data remains queryable from the stream till the snapshot is updated
+        for date in &dates {
+            if let Err(err) = copy_date(&store, &stream_name, date, destination).await {
+                error!(
+                    "Failed to copy {date} of stream={stream_name} to archive {destination}: {err}. Aborting archive."
+                );
+                return false;
+            }
+        }
+
+        if let Err(err) =
+            remove_manifest_from_snapshot(store.clone(), &stream_name, dates.clone()).await
+        {
+            error!(
+                "Failed to update snapshot for retention archive (stream={}): {}. Aborting archive.",
+                stream_name, err
+            );
+            return false;
+        }
+
+        for date in &dates {
+            if let Err(err) = remove_archived_date(&store, &stream_name, date).await {
+                error!("Failed to delete archived data from stream={stream_name}: {err}");
+                return false;
             }
+        }
+        true
+    }
 
-            let res: Vec<_> = delete_tasks.collect().await;
+    /// Returns whether the days before `retain_until` were all downsampled
+    pub(super) async fn downsample(
+        stream_name: String,
+        retain_until: DateTime<Utc>,
+        columns: &[String],
+    ) -> bool {
+        info!("running retention task - downsample for stream={stream_name}");
+        let store = PARSEABLE.storage.get_object_store();
+
+        // never drop the column the data is partitioned on
+        let time_partition = PARSEABLE
+            .get_stream(&stream_name)
+            .ok()
+            .and_then(|stream| stream.get_time_partition());
+        let columns = columns
+            .iter()
+            .filter(|column| time_partition.as_ref() != Some(*column))
+            .cloned()
+            .collect_vec();
+        if columns.is_empty() {
+            return true;
+        }
 
-            for res in res {
-                if let Err(err) = res {
-                    error!("Failed to run delete task {err:?}");
-                    return;
+        let mut completed = true;
+        let mut rewritten_manifests = Vec::new();
+        for date in dates_before(&store, &stream_name, retain_until).await {
+            match downsample_date(&store, &stream_name, &date, &columns).await {
+                Ok(mut manifests) => rewritten_manifests.append(&mut manifests),
+                Err(err) => {
+                    error!("Failed to downsample {date} of stream={stream_name}: {err}");
+                    completed = false;
+                    break;
                 }
             }
         }
+
+        if let Err(err) = update_snapshot_sizes(&store, &stream_name, rewritten_manifests).await {
+            error!("Failed to update snapshot after downsample (stream={stream_name}): {err}");
+            return false;
+        }
+        completed
     }
 
-    fn get_retain_until(current_date: NaiveDate, days: u64) -> NaiveDate {
-        current_date - Days::new(days)
+    /// Lists the `date=` prefixes under `prefix` of the days which ended before `retain_until`
+    async fn dates_before(
+        store: &Arc<dyn ObjectStorage>,
+        prefix: &str,
+        retain_until: DateTime<Utc>,
+    ) -> Vec<String> {
+        let retain_until = retain_until.date_naive();
+
+        let Ok(mut dates) = store.list_dates(prefix).await else {
+            return vec![];
+        };
+        dates.retain(|date| date.starts_with("date"));
+        dates
+            .into_iter()
+            .filter(|date| string_to_date(date) < retain_until)
+            .collect_vec()
+    }
+
+    /// Lists the `hour=` and `minute=` partitions of the day of `retain_until`
+    /// which ended before it, as `date=../hour=..` and `date=../hour=../minute=..`
+    async fn expired_partitions(
+        store: &Arc<dyn ObjectStorage>,
+        stream_name: &str,
+        retain_until: DateTime<Utc>,
+    ) -> Vec<String> {
+        let date = format!("date={}", retain_until.date_naive());
+        let Ok(hours) = store.list_hours(stream_name, &date).await else {
+            return vec![];
+        };
+
+        let mut partitions = Vec::new();
+        for hour in hours {
+            let Some(value) = partition_value(&hour, "hour=") else {
+                continue;
+            };
+            if value < retain_until.hour() {
+                partitions.push(format!("{date}/{hour}"));
+            } else if value == retain_until.hour() {
+                let Ok(minutes) = store.list_minutes(stream_name, &date, &hour).await else {
+                    continue;
+                };
+                partitions.extend(
+                    minutes
+                        .into_iter()
+                        .filter(|minute| {
+                            partition_value(minute, "minute=")
+                                .is_some_and(|value| value < retain_until.minute())
+                        })
+                        .map(|minute| format!("{date}/{hour}/{minute}")),
+                );
+            }
+        }
+        partitions
+    }
+
+    fn partition_value(partition: &str, key: &str) -> Option<u32> {
+        partition.strip_prefix(key)?.parse().ok()
+    }
+
+    async fn delete_prefixes(paths: Vec<RelativePathBuf>) -> Result<(), ObjectStorageError> {
+        let delete_tasks = FuturesUnordered::new();
+        for path in paths {
+            delete_tasks.push(async move {
+                PARSEABLE
+                    .storage
+                    .get_object_store()
+                    .delete_prefix(&path)
+                    .await
+            });
+        }
+
+        let res: Vec<_> = delete_tasks.collect().await;
+        res.into_iter().collect()
+    }
+
+    async fn copy_date(
+        store: &Arc<dyn ObjectStorage>,
+        stream_name: &str,
+        date: &str,
+        destination: &str,
+    ) -> Result<(), ObjectStorageError> {
+        for (manifest_path, manifest) in list_manifests(store, stream_name, date).await? {
+            for file in &manifest.files {
+                let Some(path) = relative_data_path(stream_name, &file.file_path) else {
+                    warn!("Skipping archive of unknown file {}", file.file_path);
+                    continue;
+                };
+                let bytes = store.get_object(&path).await?;
+                store
+                    .put_object(&RelativePathBuf::from(destination).join(&path), bytes)
+                    .await?;
+            }
+            store
+                .put_object(
+                    &RelativePathBuf::from(destination).join(&manifest_path),
+                    to_bytes(&manifest),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Removes the archived date from the stream. The snapshot no longer lists its manifests,
+    /// but queries planned before may still read its files, so these are deleted later.
+    async fn remove_archived_date(
+        store: &Arc<dyn ObjectStorage>,
+        stream_name: &str,
+        date: &str,
+    ) -> Result<(), ObjectStorageError> {
+        for (manifest_path, manifest) in list_manifests(store, stream_name, date).await? {
+            let files = manifest
+                .files
+                .iter()
+                .filter_map(|file| relative_data_path(stream_name, &file.file_path))
+                .collect_vec();
+            delete_later(stream_name, manifest_path.as_str(), files).await?;
+            store.delete_object(&manifest_path).await?;
+        }
+        Ok(())
+    }
+
+    /// Drops the columns from every parquet file of the date which still has them.
+    /// Files are rewritten next to the originals, which are swapped for them in the manifest
+    /// and deleted once past the grace period of deferred deletes.
+    /// Returns the path of the rewritten manifests along with the number of bytes saved.
+    async fn downsample_date(
+        store: &Arc<dyn ObjectStorage>,
+        stream_name: &str,
+        date: &str,
+        columns: &[String],
+    ) -> Result<Vec<(RelativePathBuf, u64)>, ObjectStorageError> {
+        let mut rewritten_manifests = Vec::new();
+        for (manifest_path, manifest) in list_manifests(store, stream_name, date).await? {
+            let mut replacements = Vec::new();
+            let mut uploaded = Vec::new();
+            for file in &manifest.files {
+                if !file.columns.iter().any(|col| columns.contains(&col.name)) {
+                    continue;
+                }
+                let Some(path) = relative_data_path(stream_name, &file.file_path) else {
+                    warn!("Skipping downsample of unknown file {}", file.file_path);
+                    continue;
+                };
+                let Some(directory) = path.parent() else {
+                    continue;
+                };
+                let new_path = directory.join(format!("downsampled.{}.parquet", Ulid::new()));
+
+                let result = async {
+                    let bytes = store.get_object(&path).await?;
+                    let rewritten = drop_columns(bytes, columns)
+                        .map_err(|err| ObjectStorageError::UnhandledError(Box::new(err)))?;
+                    store.put_object(&new_path, rewritten.clone()).await?;
+                    let new_file = create_from_parquet_bytes(
+                        store.absolute_url(&new_path).to_string(),
+                        rewritten,
+                    )?;
+                    Ok::<_, ObjectStorageError>(new_file)
+                }
+                .await;
+                match result {
+                    Ok(mut new_file) => {
+                        // ingestion size refers to the data as it was ingested
+                        new_file.ingestion_size = file.ingestion_size;
+                        uploaded.push(new_path);
+                        replacements.push((path, file.clone(), new_file));
+                    }
+                    Err(err) => {
+                        discard(store, &uploaded).await;
+                        return Err(err);
+                    }
+                }
+            }
+            if replacements.is_empty() {
+                continue;
+            }
+
+            // files replaced in the meantime, e.g. by compaction, are downsampled on the next run
+            let mut swapped = vec![false; replacements.len()];
+            let committed = update_manifest(manifest_path.as_str(), |current| {
+                for ((_, original, new_file), is_swapped) in replacements.iter().zip(&mut swapped) {
+                    *is_swapped = match current
+                        .files
+                        .iter_mut()
+                        .find(|file| file.file_path == original.file_path)
+                    {
+                        Some(file) => {
+                            *file = new_file.clone();
+                            true
+                        }
+                        None => false,
+                    };
+                }
+                swapped.contains(&true)
+            })
+            .await;
+            match committed {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    discard(store, &uploaded).await;
+                    continue;
+                }
+                Err(err @ ObjectStorageError::Conflict(_)) => {
+                    discard(store, &uploaded).await;
+                    return Err(err);
+                }
+                // the manifest may have been written, the new files are left in place
+                Err(err) => return Err(err),
+            }
+
+            let mut saved_bytes = 0;
+            let mut originals = Vec::new();
+            let mut unused = Vec::new();
+            for ((path, original, new_file), (new_path, swapped)) in replacements
+                .into_iter()
+                .zip(uploaded.into_iter().zip(swapped))
+            {
+                if swapped {
+                    saved_bytes += original.file_size.saturating_sub(new_file.file_size);
+                    originals.push(path);
+                } else {
+                    unused.push(new_path);
+                }
+            }
+            discard(store, &unused).await;
+
+            // queries planned with the previous manifest may still read the original files
+            if let Err(err) = delete_later(stream_name, manifest_path.as_str(), originals).await {
+                error!(
+                    "Failed to schedule the deletion of the files downsampled in {manifest_path}: {err}"
+                );
+            }
+            rewritten_manifests.push((manifest_path, saved_bytes));
+        }
+        Ok(rewritten_manifests)
+    }
+
+    /// Rewrites the parquet file without the given columns,
+    /// preserving the compression and sort order of the original file
+    fn drop_columns(bytes: Bytes, columns: &[String]) -> Result<Bytes, ParquetError> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(bytes)?;
+        let metadata = builder.metadata().clone();
+        let keep = builder
+            .schema()
+            .fields()
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| !columns.contains(field.name()))
+            .map(|(index, _)| index)
+            .collect_vec();
+        let mask = ProjectionMask::roots(builder.parquet_schema(), keep);
+        let reader = builder.with_projection(mask).build()?;
+        let schema = reader.schema();
+
+        let mut props = WriterProperties::builder();
+        if let Some(row_group) = metadata.row_groups().first() {
+            if let Some(column) = row_group.columns().first() {
+                props = props.set_compression(column.compression());
+            }
+            // sorting columns refer to columns by index, which change once columns are dropped.
+            // Parseable flattens nested fields, so leaf columns map to the top level fields.
+            let sorting_columns = row_group.sorting_columns().map(|sorting_columns| {
+                sorting_columns
+                    .iter()
+                    .filter_map(|sorting_column| {
+                        let name = row_group
+                            .column(sorting_column.column_idx as usize)
+                            .column_descr()
+                            .path()
+                            .string();
+                        let column_idx = schema.index_of(&name).ok()? as i32;
+                        Some(SortingColumn {
+                            column_idx,
+                            descending: sorting_column.descending,
+                            nulls_first: sorting_column.nulls_first,
+                        })
+                    })
+                    .collect_vec()
+            });
+            props = props.set_sorting_columns(sorting_columns);
+        }
+
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, schema, Some(props.build()))?;
+        for batch in reader {
+            writer.write(&batch?)?;
+        }
+        writer.close()?;
+
+        Ok(Bytes::from(buffer))
+    }
+
+    /// Reflects the reduced size of the rewritten manifests in the snapshot of the stream
+    async fn update_snapshot_sizes(
+        store: &Arc<dyn ObjectStorage>,
+        stream_name: &str,
+        rewritten_manifests: Vec<(RelativePathBuf, u64)>,
+    ) -> Result<(), ObjectStorageError> {
+        if rewritten_manifests.is_empty() {
+            return Ok(());
+        }
+
+        let mut meta: ObjectStoreFormat = serde_json::from_slice(
+            &PARSEABLE
+                .metastore
+                .get_stream_json(stream_name, false)
+                .await
+                .map_err(|e| ObjectStorageError::MetastoreError(Box::new(e.to_detail())))?,
+        )?;
+
+        for item in meta.snapshot.manifest_list.iter_mut() {
+            for (manifest_path, saved_bytes) in &rewritten_manifests {
+                if item.manifest_path.ends_with(manifest_path.as_str()) {
+                    item.storage_size = item.storage_size.saturating_sub(*saved_bytes);
+                }
+            }
+        }
+
+        store.put_snapshot(stream_name, meta.snapshot).await
+    }
+
+    /// Data older than the returned time is expired, `None` if the duration is out of range
+    pub(super) fn get_retain_until(
+        now: DateTime<Utc>,
+        duration: Duration,
+    ) -> Option<DateTime<Utc>> {
+        now.checked_sub_signed(TimeDelta::from_std(duration).ok()?)
     }
 
     fn string_to_date(date: &str) -> NaiveDate {
@@ -240,10 +884,22 @@ mod action {
 
     #[cfg(test)]
     mod tests {
-        use chrono::{Datelike, NaiveDate};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        use arrow_array::{Int64Array, RecordBatch, StringArray};
+        use arrow_schema::{DataType, Field, Schema};
+        use bytes::Bytes;
+        use chrono::{NaiveDate, TimeZone, Utc};
+        use parquet::arrow::{ArrowWriter, ParquetRecordBatchReaderBuilder};
+        use parquet::basic::{Compression, ZstdLevel};
+        use parquet::file::metadata::SortingColumn;
+        use parquet::file::properties::WriterProperties;
+        use relative_path::RelativePathBuf;
 
-        use super::get_retain_until;
-        use super::string_to_date;
+        use super::{
+            drop_columns, get_retain_until, partition_value, relative_data_path, string_to_date,
+        };
 
         #[test]
         fn test_time_from_string() {
@@ -253,9 +909,187 @@ mod action {
         }
         #[test]
         fn test_retain_day() {
-            let current_date = NaiveDate::from_ymd_opt(2000, 1, 2).unwrap();
-            let date = get_retain_until(current_date, 1);
-            assert_eq!(date.day(), 1)
+            let now = Utc.with_ymd_and_hms(2000, 1, 2, 0, 0, 0).unwrap();
+            let retain_until = get_retain_until(now, Duration::from_secs(24 * 60 * 60)).unwrap();
+            assert_eq!(
+                retain_until,
+                Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap()
+            )
+        }
+
+        #[test]
+        fn test_retain_hours() {
+            let now = Utc.with_ymd_and_hms(2000, 1, 2, 6, 30, 0).unwrap();
+            let retain_until = get_retain_until(now, humantime::parse_duration("1d 12h").unwrap());
+            assert_eq!(
+                retain_until,
+                Some(Utc.with_ymd_and_hms(1999, 12, 31, 18, 30, 0).unwrap())
+            )
+        }
+
+        #[test]
+        fn test_partition_value() {
+            assert_eq!(partition_value("hour=05", "hour="), Some(5));
+            assert_eq!(partition_value("minute=59", "minute="), Some(59));
+            assert_eq!(partition_value("minute=59", "hour="), None);
+        }
+
+        #[test]
+        fn test_relative_data_path() {
+            let path = relative_data_path(
+                "app",
+                "/var/parseable/data/app/date=2000-01-01/hour=00/minute=00/host.data.parquet",
+            );
+            assert_eq!(
+                path,
+                Some(RelativePathBuf::from(
+                    "app/date=2000-01-01/hour=00/minute=00/host.data.parquet"
+                ))
+            );
+            assert_eq!(relative_data_path("app", "other/file.parquet"), None);
+        }
+
+        #[test]
+        fn test_drop_columns() {
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("body", DataType::Utf8, true),
+                Field::new("host", DataType::Utf8, true),
+                Field::new("status", DataType::Int64, true),
+            ]));
+            let rb = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(StringArray::from(vec!["a", "b"])),
+                    Arc::new(StringArray::from(vec!["web-1", "web-2"])),
+                    Arc::new(Int64Array::from(vec![500, 200])),
+                ],
+            )
+            .unwrap();
+            let props = WriterProperties::builder()
+                .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                .set_sorting_columns(Some(vec![SortingColumn {
+                    column_idx: 2,
+                    descending: true,
+                    nulls_first: true,
+                }]))
+                .build();
+            let mut buffer = Vec::new();
+            let mut writer = ArrowWriter::try_new(&mut buffer, schema, Some(props)).unwrap();
+            writer.write(&rb).unwrap();
+            writer.close().unwrap();
+
+            let bytes = drop_columns(Bytes::from(buffer), &["body".to_owned()]).unwrap();
+            let builder = ParquetRecordBatchReaderBuilder::try_new(bytes).unwrap();
+            let fields = builder
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| field.name().as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(fields, ["host", "status"]);
+
+            // compression is kept and the sorting column follows the status column
+            let row_group = builder.metadata().row_group(0);
+            assert!(matches!(
+                row_group.column(0).compression(),
+                Compression::ZSTD(_)
+            ));
+            assert_eq!(
+                row_group.sorting_columns().unwrap(),
+                &[SortingColumn {
+                    column_idx: 1,
+                    descending: true,
+                    nulls_first: true,
+                }]
+            );
+
+            let rb = builder.build().unwrap().next().unwrap().unwrap();
+            assert_eq!(rb.num_rows(), 2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn downsample(columns: &[&str]) -> Task {
+        Task {
+            description: "drop debug columns".to_owned(),
+            action: Action::Downsample,
+            duration: Duration::from_secs(7 * SECONDS_PER_DAY),
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            destination: None,
+        }
+    }
+
+    #[test]
+    fn day_tasks_complete_once_a_day() {
+        let day = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let next_day = day.succ_opt().unwrap();
+        let mut completed = CompletedDayTasks::default();
+        assert!(!completed.is_done("app", &downsample(&["body"]), day));
+
+        completed.complete("app", downsample(&["body"]), day);
+        assert!(completed.is_done("app", &downsample(&["body"]), day));
+        assert!(!completed.is_done("app", &downsample(&["body"]), next_day));
+        // other streams and changed tasks are still to run
+        assert!(!completed.is_done("other", &downsample(&["body"]), day));
+        assert!(!completed.is_done("app", &downsample(&["body", "trace"]), day));
+    }
+
+    fn parse(tasks: serde_json::Value) -> Result<Retention, serde_json::Error> {
+        serde_json::from_value(tasks)
+    }
+
+    #[test]
+    fn accepts_tiered_tasks() {
+        let tasks = serde_json::json!([
+            {"description": "drop verbose", "action": "downsample", "duration": "7d", "columns": ["body"]},
+            {"description": "drop traces", "action": "downsample", "duration": "14d", "columns": ["trace_id"]},
+            {"description": "cold storage", "action": "archive", "duration": "30d", "destination": "archive/app"},
+            {"description": "expire", "action": "delete", "duration": "365d"},
+        ]);
+        let retention = parse(tasks.clone()).unwrap();
+        assert_eq!(retention.tasks.len(), 4);
+        assert_eq!(serde_json::to_value(&retention).unwrap(), tasks);
+    }
+
+    #[test]
+    fn rejects_invalid_downsample_and_archive_tasks() {
+        let invalid = [
+            // downsample without columns, or dropping the timestamp
+            serde_json::json!([{"description": "d", "action": "downsample", "duration": "7d"}]),
+            serde_json::json!([{"description": "d", "action": "downsample", "duration": "7d", "columns": []}]),
+            serde_json::json!([
+                {"description": "d", "action": "downsample", "duration": "7d", "columns": [DEFAULT_TIMESTAMP_KEY]}
+            ]),
+            // archive without a valid destination
+            serde_json::json!([{"description": "a", "action": "archive", "duration": "30d"}]),
+            serde_json::json!([{"description": "a", "action": "archive", "duration": "30d", "destination": ""}]),
+            serde_json::json!([
+                {"description": "a", "action": "archive", "duration": "30d", "destination": "../other"}
+            ]),
+            serde_json::json!([
+                {"description": "a", "action": "archive", "duration": "30d", "destination": "/archive"}
+            ]),
+            // a single archive, after the downsample tasks and before the delete
+            serde_json::json!([
+                {"description": "a", "action": "archive", "duration": "30d", "destination": "archive"},
+                {"description": "b", "action": "archive", "duration": "60d", "destination": "cold"}
+            ]),
+            serde_json::json!([
+                {"description": "d", "action": "downsample", "duration": "30d", "columns": ["body"]},
+                {"description": "a", "action": "archive", "duration": "30d", "destination": "archive"}
+            ]),
+            serde_json::json!([
+                {"description": "a", "action": "archive", "duration": "30d", "destination": "archive"},
+                {"description": "x", "action": "delete", "duration": "7d"}
+            ]),
+        ];
+
+        for tasks in invalid {
+            assert!(parse(tasks.clone()).is_err(), "{tasks} should be rejected");
         }
     }
 }
@@ -40,8 +40,9 @@ use datafusion::{
 };
 use futures::{StreamExt, TryStreamExt, stream::FuturesUnordered};
 use object_store::{
-    BackoffConfig, ClientOptions, ListResult, ObjectMeta, ObjectStore, PutPayload, RetryConfig,
-    aws::{AmazonS3, AmazonS3Builder, AmazonS3ConfigKey, Checksum},
+    BackoffConfig, ClientOptions, ListResult, ObjectMeta, ObjectStore, PutMode, PutPayload,
+    RetryConfig, UpdateVersion,
+    aws::{AmazonS3, AmazonS3Builder, AmazonS3ConfigKey, Checksum, S3ConditionalPut},
     buffered::BufReader,
     limit::LimitStore,
     path::Path as StorePath,
@@ -253,7 +254,9 @@ impl S3Config {
             .with_bucket_name(&self.bucket_name)
             .with_virtual_hosted_style_request(!self.use_path_style)
             .with_allow_http(true)
-            .with_retry(retry_config);
+            .with_retry(retry_config)
+            // manifests are updated with If-Match, see `put_object_if_unchanged`
+            .with_conditional_put(S3ConditionalPut::ETagMatch);
 
         if self.set_checksum {
             builder = builder.with_checksum_algorithm(Checksum::SHA256)
@@ -715,6 +718,57 @@ impl ObjectStorage for S3 {
         Ok(())
     }
 
+    async fn get_object_versioned(
+        &self,
+        path: &RelativePath,
+    ) -> Result<(Bytes, UpdateVersion), ObjectStorageError> {
+        let resp = self.client.get(&to_object_store_path(path)).await;
+        increment_object_store_calls_by_date("GET", &Utc::now().date_naive().to_string());
+        let resp = resp?;
+        let version = UpdateVersion {
+            e_tag: resp.meta.e_tag.clone(),
+            version: resp.meta.version.clone(),
+        };
+        let body = resp.bytes().await?;
+        increment_files_scanned_in_object_store_calls_by_date(
+            "GET",
+            1,
+            &Utc::now().date_naive().to_string(),
+        );
+        increment_bytes_scanned_in_object_store_calls_by_date(
+            "GET",
+            body.len() as u64,
+            &Utc::now().date_naive().to_string(),
+        );
+
+        Ok((body, version))
+    }
+
+    async fn put_object_if_unchanged(
+        &self,
+        path: &RelativePath,
+        resource: Bytes,
+        version: UpdateVersion,
+    ) -> Result<(), ObjectStorageError> {
+        let resp = self
+            .client
+            .put_opts(
+                &to_object_store_path(path),
+                resource.into(),
+                PutMode::Update(version).into(),
+            )
+            .await;
+        increment_object_store_calls_by_date("PUT", &Utc::now().date_naive().to_string());
+        resp?;
+        increment_files_scanned_in_object_store_calls_by_date(
+            "PUT",
+            1,
+            &Utc::now().date_naive().to_string(),
+        );
+
+        Ok(())
+    }
+
     async fn delete_prefix(&self, path: &RelativePath) -> Result<(), ObjectStorageError> {
         self._delete_prefix(path.as_ref()).await?;
 
@@ -996,6 +1050,7 @@ impl From<object_store::Error> for ObjectStorageError {
                 ObjectStorageError::UnhandledError(source)
             }
             object_store::Error::NotFound { path, .. } => ObjectStorageError::NoSuchKey(path),
+            object_store::Error::Precondition { path, .. } => ObjectStorageError::Conflict(path),
             err => ObjectStorageError::UnhandledError(Box::new(err)),
         }
     }
@@ -0,0 +1,916 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Bundles of dashboards, filters, alerts, targets and correlations, used to move
+//! observability config between environments, e.g. from staging to production.
+//!
+//! Users export and overwrite their own objects, admins those of every user.
+//! Targets referenced by the alerts and reports of a bundle are exported with it,
+//! credentials included. Streams can't be exported, so the bundle lists the streams
+//! its objects read from, and an import is rejected if any of them is missing.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{
+    alerts::{
+        AlertError, AlertState,
+        alert_enums::{AlertType, NotificationState},
+        alert_structs::{AlertConfig, AlertStateEntry},
+        alert_traits::{AlertManagerTrait, AlertTrait},
+        alert_types::{AnomalyAlert, ForecastAlert, ThresholdAlert},
+        get_alert_manager,
+        target::{TARGETS, Target},
+    },
+    correlation::{CORRELATIONS, CorrelationConfig},
+    handlers::http::users::bundles::BundleError,
+    metastore::metastore_traits::MetastoreObject,
+    parseable::PARSEABLE,
+    query::resolve_stream_names,
+    rbac::{Users, map::SessionKey},
+    utils::{get_hash, user_auth_for_datasets, user_auth_for_query},
+};
+
+use super::{
+    dashboards::{DASHBOARDS, Dashboard},
+    filters::{CURRENT_FILTER_VERSION, FILTERS, Filter, FilterType},
+    variables::{self, VariableError},
+};
+
+pub const CURRENT_BUNDLE_VERSION: &str = "v1";
+
+/// User exporting or importing a bundle
+#[derive(Debug, Clone)]
+pub struct BundleUser {
+    pub userid: String,
+    /// hash of the user ID, as recorded as the owner of dashboards and filters
+    pub user_id: String,
+    pub is_admin: bool,
+}
+
+impl BundleUser {
+    pub fn new(userid: String, is_admin: bool) -> Self {
+        Self {
+            user_id: get_hash(&userid),
+            userid,
+            is_admin,
+        }
+    }
+
+    /// Whether the user can export or overwrite an object of the owner,
+    /// owners are recorded either by their user ID or by its hash
+    fn owns(&self, owner: Option<&str>) -> bool {
+        self.is_admin || owner.is_some_and(|owner| owner == self.userid || owner == self.user_id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    pub version: String,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub dashboards: Vec<Dashboard>,
+    #[serde(default)]
+    pub filters: Vec<Filter>,
+    #[serde(default)]
+    pub alerts: Vec<AlertConfig>,
+    /// targets referenced by the alerts and the report schedules of the bundle
+    #[serde(default)]
+    pub targets: Vec<Target>,
+    #[serde(default)]
+    pub correlations: Vec<CorrelationConfig>,
+    /// streams read by the objects of the bundle, these have to exist on import
+    #[serde(default)]
+    pub streams: Vec<String>,
+}
+
+/// Objects to export, everything accessible to the user is exported when no object is selected
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportRequest {
+    #[serde(default)]
+    pub dashboards: Vec<Ulid>,
+    #[serde(default)]
+    pub filters: Vec<String>,
+    #[serde(default)]
+    pub alerts: Vec<Ulid>,
+    #[serde(default)]
+    pub correlations: Vec<String>,
+}
+
+impl ExportRequest {
+    fn is_empty(&self) -> bool {
+        self.dashboards.is_empty()
+            && self.filters.is_empty()
+            && self.alerts.is_empty()
+            && self.correlations.is_empty()
+    }
+
+    /// IDs of the objects to export, `None` meaning all of them
+    fn selection<'a, T>(&self, ids: &'a [T]) -> Option<&'a [T]> {
+        if self.is_empty() { None } else { Some(ids) }
+    }
+}
+
+/// What to do with an object of the bundle which already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// keep the existing object
+    #[default]
+    Skip,
+    /// replace the existing object
+    Overwrite,
+    /// import the object with a new ID
+    Rename,
+}
+
+impl ConflictPolicy {
+    fn resolve(self, exists: bool) -> ImportAction {
+        match (exists, self) {
+            (false, _) => ImportAction::Create,
+            (true, Self::Skip) => ImportAction::Skip,
+            (true, Self::Overwrite) => ImportAction::Overwrite,
+            (true, Self::Rename) => ImportAction::Rename,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectKind {
+    Target,
+    Filter,
+    Correlation,
+    Dashboard,
+    Alert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Create,
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+/// Outcome of the import of an object, `error` is set when the object is invalid
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedObject {
+    pub kind: ObjectKind,
+    pub id: String,
+    pub title: String,
+    pub action: ImportAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ImportedObject {
+    fn new(kind: ObjectKind, id: impl ToString, title: &str, action: ImportAction) -> Self {
+        Self {
+            kind,
+            id: id.to_string(),
+            title: title.to_owned(),
+            action,
+            new_id: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub policy: ConflictPolicy,
+    pub missing_streams: Vec<String>,
+    pub objects: Vec<ImportedObject>,
+}
+
+impl ImportReport {
+    /// An import is applied only if all streams exist and all objects are valid
+    pub fn is_valid(&self) -> bool {
+        self.missing_streams.is_empty() && self.objects.iter().all(|o| o.error.is_none())
+    }
+
+    fn push(&mut self, object: ImportedObject, error: Option<String>) {
+        self.objects.push(ImportedObject { error, ..object });
+    }
+}
+
+/// Exports the selected objects of the user along with the targets they notify
+pub async fn export(
+    request: &ExportRequest,
+    session_key: &SessionKey,
+    user: &BundleUser,
+) -> Result<Bundle, BundleError> {
+    let dashboards = export_dashboards(request.selection(&request.dashboards), user).await?;
+    let filters = export_filters(request.selection(&request.filters), session_key, user).await?;
+    let alerts = export_alerts(request.selection(&request.alerts), session_key, user).await?;
+    let correlations =
+        export_correlations(request.selection(&request.correlations), session_key, user).await?;
+
+    let target_ids: BTreeSet<Ulid> = alerts
+        .iter()
+        .flat_map(|alert| alert.targets.iter())
+        .chain(
+            dashboards
+                .iter()
+                .filter_map(|dashboard| dashboard.schedule.as_ref())
+                .flat_map(|schedule| schedule.targets.iter()),
+        )
+        .copied()
+        .collect();
+    let mut targets = Vec::with_capacity(target_ids.len());
+    for target_id in &target_ids {
+        targets.push(TARGETS.get_target_by_id(target_id).await?);
+    }
+
+    let mut streams = BTreeSet::new();
+    for dashboard in &dashboards {
+        let tile_queries = dashboard
+            .tiles
+            .iter()
+            .flatten()
+            .filter_map(|tile| dashboard.tile_query(tile, &HashMap::new()))
+            .flatten();
+        let variable_queries = dashboard
+            .variables
+            .iter()
+            .filter_map(|variable| variable.query.clone());
+        for query in tile_queries.chain(variable_queries) {
+            streams.extend(resolve_stream_names(&query).unwrap_or_default());
+        }
+    }
+    streams.extend(filters.iter().map(|filter| filter.stream_name.clone()));
+    streams.extend(alerts.iter().flat_map(|alert| alert.datasets.clone()));
+    streams.extend(
+        correlations
+            .iter()
+            .flat_map(|correlation| &correlation.table_configs)
+            .map(|table| table.table_name.clone()),
+    );
+
+    Ok(Bundle {
+        version: CURRENT_BUNDLE_VERSION.to_owned(),
+        exported_at: Utc::now(),
+        dashboards,
+        filters,
+        alerts,
+        targets,
+        correlations,
+        streams: streams.into_iter().collect(),
+    })
+}
+
+async fn export_dashboards(
+    ids: Option<&[Ulid]>,
+    user: &BundleUser,
+) -> Result<Vec<Dashboard>, BundleError> {
+    let Some(ids) = ids else {
+        let mut dashboards = DASHBOARDS.list_dashboards(0).await;
+        dashboards.retain(|dashboard| user.owns(dashboard.author.as_deref()));
+        return Ok(dashboards);
+    };
+
+    let mut dashboards = Vec::with_capacity(ids.len());
+    for id in ids {
+        let dashboard = DASHBOARDS
+            .get_dashboard(*id)
+            .await
+            .ok_or_else(|| BundleError::NotFound(format!("dashboard {id}")))?;
+        if !user.owns(dashboard.author.as_deref()) {
+            return Err(BundleError::Unauthorized(format!("dashboard {id}")));
+        }
+        dashboards.push(dashboard);
+    }
+
+    Ok(dashboards)
+}
+
+async fn export_filters(
+    ids: Option<&[String]>,
+    session_key: &SessionKey,
+    user: &BundleUser,
+) -> Result<Vec<Filter>, BundleError> {
+    let mut filters = FILTERS.list_filters(session_key).await;
+    let Some(ids) = ids else {
+        filters.retain(|filter| user.owns(filter.user_id.as_deref()));
+        return Ok(filters);
+    };
+
+    ids.iter()
+        .map(|id| {
+            let filter = filters
+                .iter()
+                .find(|filter| filter.filter_id.as_ref() == Some(id))
+                .ok_or_else(|| BundleError::NotFound(format!("filter {id}")))?;
+            if !user.owns(filter.user_id.as_deref()) {
+                return Err(BundleError::Unauthorized(format!("filter {id}")));
+            }
+            Ok(filter.clone())
+        })
+        .collect()
+}
+
+async fn export_alerts(
+    ids: Option<&[Ulid]>,
+    session_key: &SessionKey,
+    user: &BundleUser,
+) -> Result<Vec<AlertConfig>, BundleError> {
+    let alerts = get_alert_manager().await.get_all_alerts().await;
+
+    let mut configs = vec![];
+    match ids {
+        None => {
+            for alert in alerts.values() {
+                if user.owns(alert.get_created_by())
+                    && user_auth_for_query(session_key, alert.get_query())
+                        .await
+                        .is_ok()
+                {
+                    configs.push(alert.to_alert_config());
+                }
+            }
+            configs.sort_by_key(|config| config.id);
+        }
+        Some(ids) => {
+            for id in ids {
+                let alert = alerts
+                    .get(id)
+                    .ok_or_else(|| BundleError::NotFound(format!("alert {id}")))?;
+                if !user.owns(alert.get_created_by()) {
+                    return Err(BundleError::Unauthorized(format!("alert {id}")));
+                }
+                user_auth_for_query(session_key, alert.get_query())
+                    .await
+                    .map_err(|_| BundleError::Unauthorized(format!("alert {id}")))?;
+                configs.push(alert.to_alert_config());
+            }
+        }
+    }
+
+    Ok(configs)
+}
+
+async fn export_correlations(
+    ids: Option<&[String]>,
+    session_key: &SessionKey,
+    user: &BundleUser,
+) -> Result<Vec<CorrelationConfig>, BundleError> {
+    let mut correlations = CORRELATIONS.list_correlations(session_key).await?;
+    let Some(ids) = ids else {
+        correlations.retain(|correlation| user.owns(Some(&correlation.user_id)));
+        correlations.sort_by(|a, b| a.id.cmp(&b.id));
+        return Ok(correlations);
+    };
+
+    ids.iter()
+        .map(|id| {
+            let correlation = correlations
+                .iter()
+                .find(|correlation| &correlation.id == id)
+                .ok_or_else(|| BundleError::NotFound(format!("correlation {id}")))?;
+            if !user.owns(Some(&correlation.user_id)) {
+                return Err(BundleError::Unauthorized(format!("correlation {id}")));
+            }
+            Ok(correlation.clone())
+        })
+        .collect()
+}
+
+/// Objects of a bundle ready to be saved, along with what to do with each of them
+#[derive(Default)]
+struct ImportPlan {
+    targets: Vec<Target>,
+    filters: Vec<(Filter, Option<Filter>)>,
+    correlations: Vec<CorrelationConfig>,
+    dashboards: Vec<Dashboard>,
+    alerts: Vec<(Box<dyn AlertTrait>, ImportAction)>,
+}
+
+/// Validates the objects of the bundle and resolves their conflicts with the existing
+/// objects following the policy. Unless it's a dry run, the objects are then saved,
+/// given that all of them are valid. New objects are owned by the importing user,
+/// only the objects the user owns can be overwritten, and targets only by admins.
+pub async fn import(
+    mut bundle: Bundle,
+    policy: ConflictPolicy,
+    dry_run: bool,
+    session_key: &SessionKey,
+    user: &BundleUser,
+) -> Result<ImportReport, BundleError> {
+    if bundle.version != CURRENT_BUNDLE_VERSION {
+        return Err(BundleError::UnsupportedVersion(bundle.version));
+    }
+
+    let mut report = ImportReport {
+        dry_run,
+        policy,
+        missing_streams: vec![],
+        objects: vec![],
+    };
+    for stream in &bundle.streams {
+        if !PARSEABLE.check_or_load_stream(stream).await {
+            report.missing_streams.push(stream.clone());
+        }
+    }
+
+    let mut plan = ImportPlan::default();
+    let user_id = user.user_id.clone();
+
+    // targets come first, alerts and reports of the bundle may point to renamed targets
+    let mut targets: HashMap<Ulid, Target> = TARGETS
+        .list()
+        .await?
+        .into_iter()
+        .map(|target| (target.id, target))
+        .collect();
+    let mut target_ids = HashMap::new();
+    for mut target in std::mem::take(&mut bundle.targets) {
+        let action = policy.resolve(targets.contains_key(&target.id));
+        let mut object = ImportedObject::new(ObjectKind::Target, target.id, &target.name, action);
+        if action == ImportAction::Rename {
+            let id = Ulid::new();
+            target_ids.insert(target.id, id);
+            object.new_id = Some(id.to_string());
+            target.id = id;
+        }
+        // targets have no owner, they are shared by the alerts and reports of all users
+        let error = (action == ImportAction::Overwrite && !user.is_admin)
+            .then(|| "Only admins can overwrite targets".to_owned());
+        if action != ImportAction::Skip {
+            targets.insert(target.id, target.clone());
+            plan.targets.push(target);
+        }
+        report.push(object, error);
+    }
+    let remap_targets = |ids: &mut Vec<Ulid>| {
+        for id in ids.iter_mut() {
+            if let Some(new_id) = target_ids.get(id) {
+                *id = *new_id;
+            }
+        }
+    };
+
+    for mut filter in std::mem::take(&mut bundle.filters) {
+        let id = filter.filter_id.clone().unwrap_or_default();
+        let existing = FILTERS.get_filter_by_id(&id).await;
+        let action = policy.resolve(existing.is_some());
+        let mut object = ImportedObject::new(ObjectKind::Filter, &id, &filter.filter_name, action);
+        if action == ImportAction::Skip {
+            report.push(object, None);
+            continue;
+        }
+
+        if action == ImportAction::Rename || id.is_empty() {
+            let new_id = Ulid::new().to_string();
+            object.new_id = Some(new_id.clone());
+            filter.filter_id = Some(new_id);
+        }
+        filter.user_id = match &existing {
+            Some(existing) if action == ImportAction::Overwrite => existing.user_id.clone(),
+            _ => Some(user_id.clone()),
+        };
+        filter.version = Some(CURRENT_FILTER_VERSION.to_string());
+
+        let error = match &existing {
+            Some(existing)
+                if action == ImportAction::Overwrite && !user.owns(existing.user_id.as_deref()) =>
+            {
+                Some(not_owned("filter"))
+            }
+            _ => validate_filter(&filter, session_key).await.err(),
+        };
+        report.push(object, error);
+        let replaced = existing.filter(|_| action == ImportAction::Overwrite);
+        plan.filters.push((filter, replaced));
+    }
+
+    for mut correlation in std::mem::take(&mut bundle.correlations) {
+        let existing = CORRELATIONS.get_correlation(&correlation.id).await.ok();
+        let action = policy.resolve(existing.is_some());
+        let mut object = ImportedObject::new(
+            ObjectKind::Correlation,
+            &correlation.id,
+            &correlation.title,
+            action,
+        );
+        if action == ImportAction::Skip {
+            report.push(object, None);
+            continue;
+        }
+
+        if action == ImportAction::Rename || correlation.id.is_empty() {
+            correlation.id = get_hash(&Ulid::new().to_string());
+            object.new_id = Some(correlation.id.clone());
+        }
+        correlation.user_id = match &existing {
+            Some(existing) if action == ImportAction::Overwrite => existing.user_id.clone(),
+            _ => user.userid.clone(),
+        };
+
+        let error = match &existing {
+            Some(existing)
+                if action == ImportAction::Overwrite && !user.owns(Some(&existing.user_id)) =>
+            {
+                Some(not_owned("correlation"))
+            }
+            _ => correlation
+                .validate(session_key)
+                .await
+                .err()
+                .map(|err| err.to_string()),
+        };
+        report.push(object, error);
+        plan.correlations.push(correlation);
+    }
+
+    let existing_dashboards = DASHBOARDS.list_dashboards(0).await;
+    // titles of dashboards are unique, the dashboard holding each title
+    let mut titles: HashMap<String, Option<Ulid>> = existing_dashboards
+        .iter()
+        .map(|dashboard| (dashboard.title.clone(), dashboard.dashboard_id))
+        .collect();
+    for mut dashboard in std::mem::take(&mut bundle.dashboards) {
+        // a dashboard with the same title is the same dashboard
+        let existing = existing_dashboards
+            .iter()
+            .find(|d| dashboard.dashboard_id.is_some() && d.dashboard_id == dashboard.dashboard_id)
+            .or_else(|| {
+                existing_dashboards
+                    .iter()
+                    .find(|d| d.title == dashboard.title)
+            });
+        let action = policy.resolve(existing.is_some());
+        let id = dashboard
+            .dashboard_id
+            .map(|id| id.to_string())
+            .unwrap_or_default();
+        let mut object = ImportedObject::new(ObjectKind::Dashboard, id, &dashboard.title, action);
+        if action == ImportAction::Skip {
+            report.push(object, None);
+            continue;
+        }
+
+        match existing {
+            Some(existing) if action == ImportAction::Overwrite => {
+                dashboard.created = existing.created;
+                let author = existing.author.clone().unwrap_or_else(|| user_id.clone());
+                dashboard.set_metadata(&author, existing.dashboard_id);
+            }
+            _ => {
+                if action == ImportAction::Rename {
+                    dashboard.title = unique_title(&dashboard.title, &titles);
+                }
+                dashboard.created = dashboard.created.or_else(|| Some(Utc::now()));
+                let dashboard_id = dashboard
+                    .dashboard_id
+                    .filter(|_| action == ImportAction::Create);
+                dashboard.set_metadata(&user_id, dashboard_id);
+            }
+        }
+        if action == ImportAction::Rename {
+            object.new_id = dashboard.dashboard_id.map(|id| id.to_string());
+        }
+        if let Some(schedule) = dashboard.schedule.as_mut() {
+            remap_targets(&mut schedule.targets);
+        }
+
+        let owned = existing.is_none_or(|existing| user.owns(existing.author.as_deref()));
+        let error = match titles.insert(dashboard.title.clone(), dashboard.dashboard_id) {
+            _ if action == ImportAction::Overwrite && !owned => Some(not_owned("dashboard")),
+            Some(holder) if holder != dashboard.dashboard_id => {
+                Some("Dashboard title must be unique".to_owned())
+            }
+            _ => validate_dashboard(&dashboard, &targets, session_key)
+                .await
+                .err(),
+        };
+        report.push(object, error);
+        plan.dashboards.push(dashboard);
+    }
+
+    let existing_alerts = get_alert_manager().await.get_all_alerts().await;
+    for mut config in std::mem::take(&mut bundle.alerts) {
+        let existing = existing_alerts.get(&config.id);
+        let action = policy.resolve(existing.is_some());
+        let mut object = ImportedObject::new(ObjectKind::Alert, config.id, &config.title, action);
+        if action == ImportAction::Skip {
+            report.push(object, None);
+            continue;
+        }
+        if action == ImportAction::Overwrite
+            && existing.is_some_and(|existing| !user.owns(existing.get_created_by()))
+        {
+            report.push(object, Some(not_owned("alert")));
+            continue;
+        }
+
+        if action == ImportAction::Rename {
+            config.id = Ulid::new();
+            object.new_id = Some(config.id.to_string());
+        }
+        remap_targets(&mut config.targets);
+        if config.state != AlertState::Disabled {
+            config.state = AlertState::NotTriggered;
+        }
+        config.notification_state = NotificationState::Notify;
+        config.last_triggered_at = None;
+        config.sanitize_other_fields();
+        // imported alerts run with the access of the importing user, whoever created them
+        config.created_by = Some(user.userid.clone());
+
+        match validate_alert(config, &targets, session_key).await {
+            Ok(alert) => {
+                report.push(object, None);
+                plan.alerts.push((alert, action));
+            }
+            Err(err) => report.push(object, Some(err)),
+        }
+    }
+
+    if !dry_run && report.is_valid() {
+        apply(plan, session_key).await?;
+    }
+
+    Ok(report)
+}
+
+/// Saves the objects of a validated import one after the other, targets first so that
+/// alerts and reports never notify a missing target. Saving isn't atomic, when an object
+/// fails to save the objects saved before it are kept, and the error names the object.
+/// Importing the bundle again with the overwrite policy completes the import.
+async fn apply(plan: ImportPlan, session_key: &SessionKey) -> Result<(), BundleError> {
+    for target in plan.targets {
+        let id = target.id;
+        TARGETS
+            .update(target)
+            .await
+            .map_err(|err| failed_to_save("target", id, err))?;
+    }
+
+    for (filter, replaced) in plan.filters {
+        let id = filter.filter_id.clone().unwrap_or_default();
+        if let Some(replaced) = replaced
+            && replaced.get_object_path() != filter.get_object_path()
+        {
+            PARSEABLE
+                .metastore
+                .delete_filter(&replaced)
+                .await
+                .map_err(|err| failed_to_save("filter", &id, err))?;
+        }
+        PARSEABLE
+            .metastore
+            .put_filter(&filter)
+            .await
+            .map_err(|err| failed_to_save("filter", &id, err))?;
+        FILTERS.update(&filter).await;
+    }
+
+    for correlation in plan.correlations {
+        let id = correlation.id.clone();
+        CORRELATIONS
+            .import(correlation, session_key)
+            .await
+            .map_err(|err| failed_to_save("correlation", id, err))?;
+    }
+
+    for dashboard in plan.dashboards {
+        DASHBOARDS
+            .import(&dashboard)
+            .await
+            .map_err(|err| failed_to_save("dashboard", &dashboard.title, err))?;
+    }
+
+    let alerts = get_alert_manager().await;
+    for (alert, action) in plan.alerts {
+        let id = *alert.get_id();
+        save_alert(&*alert, action, &*alerts)
+            .await
+            .map_err(|err| failed_to_save("alert", id, err))?;
+    }
+
+    Ok(())
+}
+
+async fn save_alert(
+    alert: &dyn AlertTrait,
+    action: ImportAction,
+    alerts: &dyn AlertManagerTrait,
+) -> Result<(), AlertError> {
+    let id = *alert.get_id();
+    PARSEABLE
+        .metastore
+        .put_alert(&alert.to_alert_config())
+        .await?;
+    let state_entry = AlertStateEntry::new(id, *alert.get_state());
+    PARSEABLE
+        .metastore
+        .put_alert_state(&state_entry as &dyn MetastoreObject)
+        .await?;
+
+    if action == ImportAction::Overwrite {
+        alerts.delete_task(id).await?;
+        alerts.delete(id).await?;
+    }
+    alerts.update(alert).await;
+    if alert.get_state() != &AlertState::Disabled {
+        alerts.start_task(alert.clone_box()).await?;
+    }
+
+    Ok(())
+}
+
+fn failed_to_save(kind: &str, id: impl Display, err: impl Display) -> BundleError {
+    BundleError::PartiallyImported(format!("{kind} {id}"), err.to_string())
+}
+
+fn not_owned(kind: &str) -> String {
+    format!("User is not authorized to overwrite this {kind}, it belongs to another user")
+}
+
+async fn validate_filter(filter: &Filter, session_key: &SessionKey) -> Result<(), String> {
+    if filter.query.filter_type == FilterType::SQL {
+        let query = filter.query.filter_query.as_deref().unwrap_or_default();
+        return user_auth_for_query(session_key, query)
+            .await
+            .map_err(|err| err.to_string());
+    }
+
+    if !PARSEABLE.check_or_load_stream(&filter.stream_name).await {
+        return Err(format!("Stream {} does not exist", filter.stream_name));
+    }
+    let permissions = Users.get_permissions(session_key);
+    user_auth_for_datasets(&permissions, &[filter.stream_name.clone()])
+        .await
+        .map_err(|err| err.to_string())
+}
+
+async fn validate_dashboard(
+    dashboard: &Dashboard,
+    targets: &HashMap<Ulid, Target>,
+    session_key: &SessionKey,
+) -> Result<(), String> {
+    if let Some(schedule) = &dashboard.schedule {
+        if !dashboard.is_report() {
+            return Err("Only report dashboards can be scheduled".to_owned());
+        }
+        schedule.validate_config().map_err(|err| err.to_string())?;
+        for target_id in &schedule.targets {
+            match targets.get(target_id) {
+                None => return Err(format!("Target {target_id} does not exist")),
+                Some(target) if !target.target.supports_reports() => {
+                    return Err(
+                        "Reports can only be delivered to webhook, slack, teams and email targets"
+                            .to_owned(),
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    variables::validate(&dashboard.variables).map_err(|err| err.to_string())?;
+    for tile in dashboard.tiles.iter().flatten() {
+        // queries of tiles referencing variables without a default can't be checked
+        let query = match dashboard.tile_query(tile, &HashMap::new()) {
+            Some(Ok(query)) => query,
+            Some(Err(VariableError::Missing(_))) | None => continue,
+            Some(Err(err)) => return Err(err.to_string()),
+        };
+        user_auth_for_query(session_key, &query)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+async fn validate_alert(
+    config: AlertConfig,
+    targets: &HashMap<Ulid, Target>,
+    session_key: &SessionKey,
+) -> Result<Box<dyn AlertTrait>, String> {
+    if let Some(target_id) = config.targets.iter().find(|id| !targets.contains_key(id)) {
+        return Err(format!("Target {target_id} does not exist"));
+    }
+
+    let alert: Box<dyn AlertTrait> = match &config.alert_type {
+        AlertType::Threshold => Box::new(ThresholdAlert::from(config)) as Box<dyn AlertTrait>,
+        AlertType::Anomaly(_) => {
+            Box::new(AnomalyAlert::try_from(config).map_err(|err: AlertError| err.to_string())?)
+                as Box<dyn AlertTrait>
+        }
+        AlertType::Forecast(_) => {
+            Box::new(ForecastAlert::try_from(config).map_err(|err: AlertError| err.to_string())?)
+                as Box<dyn AlertTrait>
+        }
+    };
+    alert
+        .validate(session_key)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(alert)
+}
+
+/// Title suffixed so that it doesn't collide with any of the given titles
+fn unique_title(title: &str, titles: &HashMap<String, Option<Ulid>>) -> String {
+    let mut candidate = format!("{title} (imported)");
+    let mut n = 2;
+    while titles.contains_key(&candidate) {
+        candidate = format!("{title} (imported {n})");
+        n += 1;
+    }
+
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_conflicts() {
+        assert_eq!(ConflictPolicy::Skip.resolve(false), ImportAction::Create);
+        assert_eq!(ConflictPolicy::Skip.resolve(true), ImportAction::Skip);
+        assert_eq!(
+            ConflictPolicy::Overwrite.resolve(true),
+            ImportAction::Overwrite
+        );
+        assert_eq!(ConflictPolicy::Rename.resolve(true), ImportAction::Rename);
+    }
+
+    #[test]
+    fn renames_to_unique_title() {
+        let mut titles = HashMap::from([("logs".to_owned(), Some(Ulid::new()))]);
+        assert_eq!(unique_title("logs", &titles), "logs (imported)");
+
+        titles.insert("logs (imported)".to_owned(), None);
+        titles.insert("logs (imported 2)".to_owned(), None);
+        assert_eq!(unique_title("logs", &titles), "logs (imported 3)");
+    }
+
+    #[test]
+    fn selects_everything_when_nothing_is_selected() {
+        let request = ExportRequest::default();
+        assert!(request.selection(&request.alerts).is_none());
+
+        let request = ExportRequest {
+            filters: vec!["filter".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(request.selection(&request.alerts), Some(&[][..]));
+        assert_eq!(
+            request.selection(&request.filters),
+            Some(&["filter".to_owned()][..])
+        );
+    }
+
+    #[test]
+    fn only_owners_and_admins_own_objects() {
+        let user = BundleUser::new("alice".to_owned(), false);
+        assert!(user.owns(Some("alice")));
+        assert!(user.owns(Some(&get_hash("alice"))));
+        assert!(!user.owns(Some("bob")));
+        assert!(!user.owns(None));
+
+        let admin = BundleUser::new("admin".to_owned(), true);
+        assert!(admin.owns(Some("bob")));
+        assert!(admin.owns(None));
+    }
+
+    #[test]
+    fn parses_bundle_without_objects() {
+        let bundle: Bundle =
+            serde_json::from_str(r#"{"version": "v1", "exportedAt": "2025-01-01T00:00:00Z"}"#)
+                .unwrap();
+        assert!(bundle.dashboards.is_empty() && bundle.streams.is_empty());
+    }
+}
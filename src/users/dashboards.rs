@@ -16,6 +16,8 @@
  *
  */
 
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
 use relative_path::RelativePathBuf;
@@ -30,6 +32,11 @@ use crate::{
     parseable::PARSEABLE,
 };
 
+use super::{
+    reports::ReportSchedule,
+    variables::{self, Variable, VariableError, VariableValue},
+};
+
 pub static DASHBOARDS: Lazy<Dashboards> = Lazy::new(Dashboards::default);
 pub const CURRENT_DASHBOARD_VERSION: &str = "v1";
 
@@ -52,6 +59,18 @@ pub struct Tile {
     #[serde(flatten)]
     pub other_fields: Option<serde_json::Map<String, Value>>,
 }
+
+impl Tile {
+    /// SQL query of the tile, if it has one
+    pub fn query(&self) -> Option<&str> {
+        self.other_fields
+            .as_ref()?
+            .get("query")?
+            .as_str()
+            .filter(|query| !query.trim().is_empty())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Dashboard {
@@ -65,6 +84,12 @@ pub struct Dashboard {
     pub is_favorite: Option<bool>, // whether the dashboard is marked as favorite, default is false
     dashboard_type: Option<DashboardType>,
     pub tiles: Option<Vec<Tile>>,
+    /// delivery schedule of a report dashboard
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ReportSchedule>,
+    /// variables referenced in the queries of the tiles
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<Variable>,
 }
 
 impl MetastoreObject for Dashboard {
@@ -103,6 +128,22 @@ impl Dashboard {
         self.is_favorite = self.is_favorite.or(Some(false));
     }
 
+    /// whether the dashboard is a report
+    pub fn is_report(&self) -> bool {
+        self.dashboard_type == Some(DashboardType::Report)
+    }
+
+    /// query of the tile with the variables replaced by the selected values,
+    /// or by their defaults if not selected
+    pub fn tile_query(
+        &self,
+        tile: &Tile,
+        selected: &HashMap<String, VariableValue>,
+    ) -> Option<Result<String, VariableError>> {
+        tile.query()
+            .map(|query| variables::substitute(query, &self.variables, selected))
+    }
+
     /// create a summary of the dashboard
     /// used for listing dashboards
     pub fn to_summary(&self) -> serde_json::Map<String, serde_json::Value> {
@@ -288,6 +329,27 @@ impl Dashboards {
         Ok(())
     }
 
+    /// Save a dashboard imported from a bundle with its own ID
+    /// replace the dashboard with the same ID, if any
+    pub async fn import(&self, dashboard: &Dashboard) -> Result<(), DashboardError> {
+        let mut dashboards = self.0.write().await;
+
+        let has_duplicate = dashboards
+            .iter()
+            .any(|d| d.title == dashboard.title && d.dashboard_id != dashboard.dashboard_id);
+
+        if has_duplicate {
+            return Err(DashboardError::Metadata("Dashboard title must be unique"));
+        }
+
+        self.save_dashboard(dashboard).await?;
+
+        dashboards.retain(|d| d.dashboard_id != dashboard.dashboard_id);
+        dashboards.push(dashboard.clone());
+
+        Ok(())
+    }
+
     /// Delete a dashboard
     /// This function is called when deleting a dashboard
     /// delete dashboard in memory and from the object store
@@ -16,6 +16,16 @@
  *
  */
 
+use arrow_schema::{DataType, Schema};
+use datafusion::{
+    common::{
+        Column, DFSchema, ScalarValue,
+        tree_node::{Transformed, TreeNode, TreeNodeRecursion},
+    },
+    error::DataFusionError,
+    logical_expr::{ExprSchemable, Like, LogicalPlan, cast},
+    prelude::{Expr, ident, lit},
+};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -24,7 +34,8 @@ use tokio::sync::RwLock;
 use super::TimeFilter;
 use crate::{
     metastore::metastore_traits::MetastoreObject,
-    parseable::PARSEABLE,
+    parseable::{PARSEABLE, StreamNotFound},
+    query::QUERY_SESSION,
     rbac::{Users, map::SessionKey},
     storage::object_storage::filter_path,
     utils::{get_hash, user_auth_for_datasets, user_auth_for_query},
@@ -61,6 +72,119 @@ impl MetastoreObject for Filter {
     }
 }
 
+impl Filter {
+    /// Compiles the filter into a predicate over the fields of its stream,
+    /// validated against the schema of the stream
+    pub async fn to_expr(&self) -> Result<Expr, FilterCompileError> {
+        let schema = PARSEABLE.get_stream(&self.stream_name)?.get_schema();
+
+        let expr = match self.query.filter_type {
+            FilterType::Filter => self
+                .query
+                .filter_builder
+                .as_ref()
+                .ok_or(FilterCompileError::MissingQuery)?
+                .to_expr(&schema)?,
+            FilterType::SQL => {
+                let query = self
+                    .query
+                    .filter_query
+                    .as_deref()
+                    .ok_or(FilterCompileError::MissingQuery)?;
+                sql_predicate(query).await?
+            }
+            FilterType::Search => return Err(FilterCompileError::Unsupported("search")),
+        };
+
+        let df_schema = DFSchema::try_from(schema.as_ref().clone())?;
+        match expr.get_type(&df_schema)? {
+            DataType::Boolean => Ok(expr),
+            data_type => Err(FilterCompileError::NotBoolean(data_type)),
+        }
+    }
+}
+
+/// Predicate of the WHERE clause of a saved SQL query, with columns unqualified
+/// so that it applies to the stream whatever alias the query gave it
+async fn sql_predicate(query: &str) -> Result<Expr, FilterCompileError> {
+    let plan = QUERY_SESSION.state().create_logical_plan(query).await?;
+    where_predicate(&plan)
+}
+
+/// Predicate filtering the scan of a plan reading a single table, predicates of
+/// HAVING clauses and of the queries a join or a subquery would bring are left out
+fn where_predicate(plan: &LogicalPlan) -> Result<Expr, FilterCompileError> {
+    let mut scans = 0;
+    plan.apply_with_subqueries(|node| {
+        if let LogicalPlan::TableScan(_) = node {
+            scans += 1;
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    if scans != 1 {
+        return Err(FilterCompileError::NotSingleTable);
+    }
+
+    let mut predicate = None;
+    plan.apply(|node| {
+        if let LogicalPlan::Filter(filter) = node
+            && reads_scan(&filter.input)
+        {
+            predicate = Some(filter.predicate.clone());
+            return Ok(TreeNodeRecursion::Stop);
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+
+    let predicate = predicate.unwrap_or_else(|| lit(true));
+    let predicate = predicate
+        .transform(|expr| match expr {
+            Expr::Column(column) => Ok(Transformed::yes(Expr::Column(Column::new_unqualified(
+                column.name,
+            )))),
+            expr => Ok(Transformed::no(expr)),
+        })?
+        .data;
+
+    Ok(predicate)
+}
+
+fn reads_scan(plan: &LogicalPlan) -> bool {
+    match plan {
+        LogicalPlan::TableScan(_) => true,
+        LogicalPlan::SubqueryAlias(alias) => reads_scan(&alias.input),
+        _ => false,
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FilterCompileError {
+    #[error("Filter has no query to compile")]
+    MissingQuery,
+    #[error("Filters of type {0} can't be compiled")]
+    Unsupported(&'static str),
+    #[error("Field {0} does not exist in the stream")]
+    UnknownField(String),
+    #[error("Operator {0} is not supported")]
+    UnknownOperator(String),
+    #[error("Combinator {0} is not supported, expected and or or")]
+    UnknownCombinator(String),
+    #[error("Value {value} is not valid for field {field} of type {data_type}")]
+    InvalidValue {
+        field: String,
+        value: String,
+        data_type: DataType,
+    },
+    #[error("Query of the filter should read a single stream, without joins or subqueries")]
+    NotSingleTable,
+    #[error("Filter should evaluate to a boolean, got {0}")]
+    NotBoolean(DataType),
+    #[error("{0}")]
+    StreamNotFound(#[from] StreamNotFound),
+    #[error("{0}")]
+    DataFusion(#[from] DataFusionError),
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct FilterQuery {
     pub filter_type: FilterType,
@@ -108,6 +232,106 @@ pub struct Rules {
     pub operator: String,
 }
 
+impl FilterBuilder {
+    /// Combines the groups of rules, an empty builder matches everything
+    pub fn to_expr(&self, schema: &Schema) -> Result<Expr, FilterCompileError> {
+        let groups = self
+            .rules
+            .iter()
+            .filter(|group| !group.rules.is_empty())
+            .map(|group| group.to_expr(schema))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(combine(&self.combinator, groups)?.unwrap_or_else(|| lit(true)))
+    }
+}
+
+impl FilterRules {
+    fn to_expr(&self, schema: &Schema) -> Result<Expr, FilterCompileError> {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| rule.to_expr(schema))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(combine(&self.combinator, rules)?.unwrap_or_else(|| lit(true)))
+    }
+}
+
+fn combine(combinator: &str, exprs: Vec<Expr>) -> Result<Option<Expr>, FilterCompileError> {
+    let op: fn(Expr, Expr) -> Expr = match combinator.to_lowercase().as_str() {
+        "and" => Expr::and,
+        "or" => Expr::or,
+        _ => return Err(FilterCompileError::UnknownCombinator(combinator.to_owned())),
+    };
+
+    Ok(exprs.into_iter().reduce(op))
+}
+
+impl Rules {
+    /// Accepts the operators of the query builder of the UI, e.g. `beginsWith`,
+    /// as well as the operators of alert conditions, e.g. `begins with`
+    fn to_expr(&self, schema: &Schema) -> Result<Expr, FilterCompileError> {
+        let field = schema
+            .field_with_name(&self.field)
+            .map_err(|_| FilterCompileError::UnknownField(self.field.clone()))?;
+        let column = ident(&self.field);
+
+        let like = |negated: bool, pattern: String, case_insensitive: bool| {
+            // non string fields are matched on their string representation
+            let column = match field.data_type() {
+                DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => column.clone(),
+                _ => cast(column.clone(), DataType::Utf8),
+            };
+            Expr::Like(Like::new(
+                negated,
+                Box::new(column),
+                Box::new(lit(pattern)),
+                Some('\\'),
+                case_insensitive,
+            ))
+        };
+        let value = || {
+            ScalarValue::try_from_string(self.value.clone(), field.data_type())
+                .map(lit)
+                .map_err(|_| FilterCompileError::InvalidValue {
+                    field: self.field.clone(),
+                    value: self.value.clone(),
+                    data_type: field.data_type().clone(),
+                })
+        };
+        let escaped = escape_like(&self.value);
+
+        let expr = match self.operator.as_str() {
+            "=" => column.clone().eq(value()?),
+            "!=" => column.clone().not_eq(value()?),
+            "<" => column.clone().lt(value()?),
+            ">" => column.clone().gt(value()?),
+            "<=" => column.clone().lt_eq(value()?),
+            ">=" => column.clone().gt_eq(value()?),
+            "null" | "is null" => column.clone().is_null(),
+            "notNull" | "is not null" => column.clone().is_not_null(),
+            "contains" => like(false, format!("%{escaped}%"), false),
+            "doesNotContain" | "does not contain" => like(true, format!("%{escaped}%"), false),
+            "beginsWith" | "begins with" => like(false, format!("{escaped}%"), false),
+            "doesNotBeginWith" | "does not begin with" => like(true, format!("{escaped}%"), false),
+            "endsWith" | "ends with" => like(false, format!("%{escaped}"), false),
+            "doesNotEndWith" | "does not end with" => like(true, format!("%{escaped}"), false),
+            "ilike" => like(false, format!("%{escaped}%"), true),
+            operator => return Err(FilterCompileError::UnknownOperator(operator.to_owned())),
+        };
+
+        Ok(expr)
+    }
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
 #[derive(Debug, Default)]
 pub struct Filters(RwLock<Vec<Filter>>);
 
@@ -132,6 +356,16 @@ impl Filters {
         s.retain(|f| f.filter_id != Some(filter_id.to_string()));
     }
 
+    /// Get a filter by ID, independent of the user who saved it
+    pub async fn get_filter_by_id(&self, filter_id: &str) -> Option<Filter> {
+        self.0
+            .read()
+            .await
+            .iter()
+            .find(|f| f.filter_id.as_deref() == Some(filter_id))
+            .cloned()
+    }
+
     pub async fn get_filter(
         &self,
         filter_id: &str,
@@ -191,3 +425,127 @@ pub fn migrate_v1_v2(mut filter_meta: Value) -> Value {
 
     filter_meta
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::Field;
+    use datafusion::{
+        datasource::{empty::EmptyTable, provider_as_source},
+        functions_aggregate::count::count,
+        logical_expr::{JoinType, LogicalPlanBuilder},
+        prelude::col,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("status", DataType::Int64, true),
+            Field::new("host", DataType::Utf8, true),
+        ])
+    }
+
+    fn builder(rules: Value) -> FilterBuilder {
+        serde_json::from_value(json!({
+            "id": "1",
+            "combinator": "and",
+            "rules": [{"id": "2", "combinator": "or", "rules": rules}]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn compiles_builder_rules() {
+        let builder = builder(json!([
+            {"id": "3", "field": "status", "value": "500", "operator": ">="},
+            {"id": "4", "field": "host", "value": "web_1", "operator": "beginsWith"}
+        ]));
+        let expr = builder.to_expr(&schema()).unwrap();
+
+        let expected = ident("status").gt_eq(lit(500i64)).or(Expr::Like(Like::new(
+            false,
+            Box::new(ident("host")),
+            Box::new(lit("web\\_1%")),
+            Some('\\'),
+            false,
+        )));
+        assert_eq!(expr, expected);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let unknown_field = builder(json!([
+            {"id": "3", "field": "missing", "value": "1", "operator": "="}
+        ]));
+        assert!(matches!(
+            unknown_field.to_expr(&schema()),
+            Err(FilterCompileError::UnknownField(_))
+        ));
+
+        let invalid_value = builder(json!([
+            {"id": "3", "field": "status", "value": "ok", "operator": "="}
+        ]));
+        assert!(matches!(
+            invalid_value.to_expr(&schema()),
+            Err(FilterCompileError::InvalidValue { .. })
+        ));
+
+        let unknown_operator = builder(json!([
+            {"id": "3", "field": "status", "value": "1", "operator": "between"}
+        ]));
+        assert!(matches!(
+            unknown_operator.to_expr(&schema()),
+            Err(FilterCompileError::UnknownOperator(_))
+        ));
+    }
+
+    fn scan(table: &str) -> LogicalPlanBuilder {
+        let source = provider_as_source(Arc::new(EmptyTable::new(Arc::new(schema()))));
+        LogicalPlanBuilder::scan(table, source, None).unwrap()
+    }
+
+    #[test]
+    fn takes_the_where_clause_of_the_scan() {
+        // SELECT host, count(*) FROM app AS a WHERE a.status >= 500 GROUP BY host HAVING count(*) > 1
+        let plan = scan("app")
+            .alias("a")
+            .unwrap()
+            .filter(col("a.status").gt_eq(lit(500i64)))
+            .unwrap()
+            .aggregate(vec![col("host")], vec![count(lit(1)).alias("hits")])
+            .unwrap()
+            .filter(col("hits").gt(lit(1i64)))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            where_predicate(&plan).unwrap(),
+            ident("status").gt_eq(lit(500i64))
+        );
+
+        let plan = scan("app").build().unwrap();
+        assert_eq!(where_predicate(&plan).unwrap(), lit(true));
+    }
+
+    #[test]
+    fn rejects_plans_reading_many_tables() {
+        let plan = scan("app")
+            .join_on(
+                scan("other").build().unwrap(),
+                JoinType::Inner,
+                vec![col("app.host").eq(col("other.host"))],
+            )
+            .unwrap()
+            .filter(col("app.status").gt_eq(lit(500i64)))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(matches!(
+            where_predicate(&plan),
+            Err(FilterCompileError::NotSingleTable)
+        ));
+    }
+}
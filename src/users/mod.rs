@@ -16,8 +16,11 @@
  *
  */
 
+pub mod bundles;
 pub mod dashboards;
 pub mod filters;
+pub mod reports;
+pub mod variables;
 
 use serde::{Deserialize, Serialize};
 
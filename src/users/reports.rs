@@ -0,0 +1,714 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Scheduled delivery of report dashboards. The query of every tile runs over the
+//! time range of the schedule, with the default values of the dashboard variables,
+//! and the results are sent to alert targets. The outcome of every run is kept in
+//! the metastore.
+
+use std::{collections::HashMap, time::Duration};
+
+use actix_web::Either;
+use anyhow::anyhow;
+use chrono::{DateTime, TimeDelta, Utc};
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+use ulid::Ulid;
+
+use crate::{
+    alerts::target::TARGETS,
+    handlers::http::{
+        cluster::send_query_request,
+        modal::query_server::QUERIER_META,
+        query::{Query, create_streams_for_distributed},
+        users::dashboards::DashboardError,
+    },
+    metastore::metastore_traits::MetastoreObject,
+    option::Mode,
+    parseable::PARSEABLE,
+    query::{QUERY_SESSION, execute, resolve_stream_names},
+    rbac::{Users, apply_access_policies, map::SessionKey, reads_unrestricted, role::Permission},
+    storage::object_storage::{report_lease_json_path, report_run_json_path},
+    utils::{
+        arrow::record_batches_to_json, time::TimeRange, user_auth_for_datasets, user_auth_for_query,
+    },
+};
+
+use super::dashboards::{DASHBOARDS, Dashboard, Tile};
+
+/// Number of rows of a tile shown in the inline summary
+const MAX_SUMMARY_ROWS: usize = 5;
+
+/// Time given to the other queriers to claim a run before reading the lease back
+const LEASE_SETTLE_TIME: Duration = Duration::from_secs(5);
+
+/// Last run of every scheduled report, loaded from the run history when first seen
+static LAST_RUNS: Lazy<RwLock<HashMap<Ulid, DateTime<Utc>>>> = Lazy::new(RwLock::default);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ReportFormat {
+    /// Rows of the tiles inline in the message
+    #[default]
+    Summary,
+    /// A CSV attachment per tile
+    Csv,
+    /// A JSON attachment per tile
+    Json,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportSchedule {
+    /// Minutes between two deliveries, e.g. 10080 for a weekly report
+    pub frequency: u64,
+    /// Time range the tile queries run over, ending at the time of delivery, e.g. `7d`
+    pub time_range: String,
+    #[serde(default)]
+    pub format: ReportFormat,
+    /// Webhook and slack targets the report is delivered to
+    pub targets: Vec<Ulid>,
+}
+
+impl ReportSchedule {
+    /// Checks the schedule, that its targets can receive reports,
+    /// and that the user is allowed to run the queries of the tiles
+    pub async fn validate(
+        &self,
+        dashboard: &Dashboard,
+        session_key: &SessionKey,
+    ) -> Result<(), DashboardError> {
+        if !dashboard.is_report() {
+            return Err(DashboardError::Metadata(
+                "Only report dashboards can be scheduled",
+            ));
+        }
+        self.validate_config()?;
+
+        for target_id in &self.targets {
+            let target = TARGETS
+                .get_target_by_id(target_id)
+                .await
+                .map_err(|err| DashboardError::Custom(err.to_string()))?;
+            if !target.target.supports_reports() {
+                return Err(DashboardError::Metadata(
+                    "Reports can only be delivered to webhook, slack, teams and email targets",
+                ));
+            }
+        }
+
+        for tile in dashboard.tiles.iter().flatten() {
+            let Some(query) = dashboard.tile_query(tile, &HashMap::new()) else {
+                continue;
+            };
+            user_auth_for_query(session_key, &query?)
+                .await
+                .map_err(|_| DashboardError::Unauthorized)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the schedule itself, independent of the targets and the tiles
+    pub fn validate_config(&self) -> Result<(), DashboardError> {
+        if self.frequency == 0 {
+            return Err(DashboardError::Metadata(
+                "Report frequency should be greater than zero",
+            ));
+        }
+        if humantime::parse_duration(&self.time_range).is_err() {
+            return Err(DashboardError::Metadata(
+                "Report time range should be of type humantime, e.g. 7d",
+            ));
+        }
+        if self.targets.is_empty() {
+            return Err(DashboardError::Metadata(
+                "Report should be delivered to at least one target",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn is_due(&self, last_run: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        last_run.is_none_or(|last_run| {
+            now - last_run >= TimeDelta::minutes(self.frequency.min(i64::MAX as u64) as i64)
+        })
+    }
+}
+
+/// Outcome of a run of a scheduled report, persisted in the metastore
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportRun {
+    pub run_id: Ulid,
+    pub dashboard_id: Ulid,
+    pub run_at: DateTime<Utc>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub tiles: Vec<TileRun>,
+    pub deliveries: Vec<DeliveryRun>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TileRun {
+    pub tile_id: Ulid,
+    pub title: String,
+    pub rows: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryRun {
+    pub target_id: Ulid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl MetastoreObject for ReportRun {
+    fn get_object_path(&self) -> String {
+        report_run_json_path(&self.dashboard_id, &self.run_id).to_string()
+    }
+
+    fn get_object_id(&self) -> String {
+        self.run_id.to_string()
+    }
+}
+
+/// Claim of a querier on the latest run of a report, persisted in the metastore
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct ReportLease {
+    dashboard_id: Ulid,
+    node_id: String,
+    claimed_at: DateTime<Utc>,
+}
+
+impl MetastoreObject for ReportLease {
+    fn get_object_path(&self) -> String {
+        report_lease_json_path(&self.dashboard_id).to_string()
+    }
+
+    fn get_object_id(&self) -> String {
+        self.dashboard_id.to_string()
+    }
+}
+
+/// Content of a report as sent to targets, slack and teams targets only receive the summary
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportDelivery {
+    pub dashboard_id: Ulid,
+    pub title: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub summary: String,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: &'static str,
+    pub content: String,
+}
+
+/// Results of the query of a tile
+struct TileResult {
+    tile_id: Ulid,
+    title: String,
+    rows: Result<Vec<Map<String, Value>>, String>,
+}
+
+pub fn init_scheduler() {
+    info!("Setting up report scheduler");
+    tokio::spawn(async {
+        loop {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            run_due_reports().await;
+        }
+    });
+}
+
+async fn run_due_reports() {
+    let now = Utc::now();
+    for dashboard in DASHBOARDS.list_dashboards(0).await {
+        let (Some(dashboard_id), Some(schedule)) = (dashboard.dashboard_id, &dashboard.schedule)
+        else {
+            continue;
+        };
+        if !dashboard.is_report() {
+            continue;
+        }
+
+        let cached = LAST_RUNS.read().await.get(&dashboard_id).copied();
+        let last_run = match cached {
+            Some(last_run) => Some(last_run),
+            None => match list_runs(dashboard_id).await {
+                Ok(runs) => runs.first().map(|run| run.run_at),
+                Err(err) => {
+                    warn!("Failed to load runs of report {dashboard_id}: {err}");
+                    continue;
+                }
+            },
+        };
+        if !schedule.is_due(last_run, now) {
+            LAST_RUNS
+                .write()
+                .await
+                .insert(dashboard_id, last_run.unwrap_or(now));
+            continue;
+        }
+
+        // recorded before running, so that a slow run isn't started twice
+        LAST_RUNS.write().await.insert(dashboard_id, now);
+        let frequency = schedule.frequency;
+        tokio::spawn(async move {
+            match claim_run(dashboard_id, frequency).await {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(err) => {
+                    error!("Failed to claim run of report {dashboard_id}: {err}");
+                    return;
+                }
+            }
+            if let Err(err) = run_report(&dashboard).await {
+                error!("Failed to run report {dashboard_id}: {err}");
+            }
+        });
+    }
+}
+
+/// Claims the run of a report which is due, so that a single querier delivers it. Every
+/// querier writes its claim unless another one claimed a run less than a period ago, and
+/// the claim read back once all of them are written wins.
+async fn claim_run(dashboard_id: Ulid, frequency: u64) -> Result<bool, DashboardError> {
+    if PARSEABLE.options.mode != Mode::Query {
+        return Ok(true);
+    }
+    let node_id = QUERIER_META
+        .get()
+        .map(|meta| meta.get_node_id())
+        .unwrap_or_default();
+    let period = TimeDelta::minutes(frequency.min(i64::MAX as u64) as i64);
+
+    if let Some(lease) = get_lease(&dashboard_id).await?
+        && lease.node_id != node_id
+        && Utc::now() - lease.claimed_at < period
+    {
+        return Ok(false);
+    }
+    let lease = ReportLease {
+        dashboard_id,
+        node_id,
+        claimed_at: Utc::now(),
+    };
+    PARSEABLE.metastore.put_report_lease(&lease).await?;
+
+    tokio::time::sleep(LEASE_SETTLE_TIME).await;
+    Ok(get_lease(&dashboard_id).await? == Some(lease))
+}
+
+async fn get_lease(dashboard_id: &Ulid) -> Result<Option<ReportLease>, DashboardError> {
+    Ok(PARSEABLE
+        .metastore
+        .get_report_lease(dashboard_id)
+        .await?
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok()))
+}
+
+/// Runs the queries of the tiles of the report as its author, delivers the results
+/// to the targets of its schedule and records the run
+pub async fn run_report(dashboard: &Dashboard) -> Result<ReportRun, DashboardError> {
+    let (Some(dashboard_id), Some(schedule)) = (dashboard.dashboard_id, &dashboard.schedule) else {
+        return Err(DashboardError::Metadata("Dashboard has no report schedule"));
+    };
+    // the access of the author is checked on every run, it may have changed since scheduling
+    let author = dashboard
+        .author
+        .as_deref()
+        .and_then(|author| Users.get_userid_from_hash(author))
+        .ok_or(DashboardError::Metadata(
+            "Author of the report does not exist",
+        ))?;
+    let permissions = Users.get_user_permissions(&author);
+    let time_range = TimeRange::parse_human_time(&schedule.time_range, "now")
+        .map_err(|err| DashboardError::Custom(err.to_string()))?;
+
+    let mut tiles = vec![];
+    for tile in dashboard.tiles.iter().flatten() {
+        let Some(query) = dashboard.tile_query(tile, &HashMap::new()) else {
+            continue;
+        };
+        let rows = match query {
+            Ok(query) => match authorize_tile_query(&query, &permissions).await {
+                Ok(()) => execute_tile_query(&query, &time_range, &permissions)
+                    .await
+                    .map_err(|err| err.to_string()),
+                Err(err) => Err(err),
+            },
+            Err(err) => Err(err.to_string()),
+        };
+        tiles.push(TileResult {
+            tile_id: tile.tile_id,
+            title: tile_title(tile),
+            rows,
+        });
+    }
+
+    let delivery = ReportDelivery {
+        dashboard_id,
+        title: dashboard.title.clone(),
+        start_time: time_range.start,
+        end_time: time_range.end,
+        summary: summary(&dashboard.title, &time_range, &tiles),
+        attachments: attachments(schedule.format, &tiles),
+    };
+
+    let mut deliveries = vec![];
+    for target_id in &schedule.targets {
+        let result = match TARGETS.get_target_by_id(target_id).await {
+            Ok(target) => target.target.deliver_report(&delivery).await,
+            Err(err) => Err(anyhow!(err.to_string())),
+        };
+        if let Err(err) = &result {
+            warn!("Failed to deliver report {dashboard_id} to target {target_id}: {err}");
+        }
+        deliveries.push(DeliveryRun {
+            target_id: *target_id,
+            error: result.err().map(|err| err.to_string()),
+        });
+    }
+
+    let run = ReportRun {
+        run_id: Ulid::new(),
+        dashboard_id,
+        run_at: Utc::now(),
+        start_time: time_range.start,
+        end_time: time_range.end,
+        tiles: tiles
+            .into_iter()
+            .map(|tile| {
+                let (rows, error) = match tile.rows {
+                    Ok(rows) => (rows.len(), None),
+                    Err(err) => (0, Some(err)),
+                };
+                TileRun {
+                    tile_id: tile.tile_id,
+                    title: tile.title,
+                    rows,
+                    error,
+                }
+            })
+            .collect(),
+        deliveries,
+    };
+    PARSEABLE.metastore.put_report_run(&run).await?;
+    LAST_RUNS.write().await.insert(dashboard_id, run.run_at);
+
+    Ok(run)
+}
+
+/// Checks that the permissions allow querying all the streams of the query of a tile
+async fn authorize_tile_query(query: &str, permissions: &[Permission]) -> Result<(), String> {
+    let tables = resolve_stream_names(query).map_err(|err| err.to_string())?;
+    user_auth_for_datasets(permissions, &tables)
+        .await
+        .map_err(|_| "Author of the report is not authorized to run this query".to_owned())
+}
+
+/// Runs of the report, latest first
+pub async fn list_runs(dashboard_id: Ulid) -> Result<Vec<ReportRun>, DashboardError> {
+    let mut runs = PARSEABLE
+        .metastore
+        .get_report_runs(&dashboard_id)
+        .await?
+        .iter()
+        .filter_map(|bytes| serde_json::from_slice::<ReportRun>(bytes).ok())
+        .collect_vec();
+    runs.sort_by_key(|run| std::cmp::Reverse(run.run_at));
+
+    Ok(runs)
+}
+
+fn tile_title(tile: &Tile) -> String {
+    tile.other_fields
+        .as_ref()
+        .and_then(|fields| fields.get("title"))
+        .and_then(Value::as_str)
+        .map(str::to_owned)
+        .unwrap_or_else(|| tile.tile_id.to_string())
+}
+
+/// Executes the query of a tile, locally or on a querier in Prism mode, restricted
+/// to the rows and columns the permissions can read
+pub async fn execute_tile_query(
+    query: &str,
+    time_range: &TimeRange,
+    permissions: &[Permission],
+) -> anyhow::Result<Vec<Map<String, Value>>> {
+    match PARSEABLE.options.mode {
+        Mode::All | Mode::Query => {
+            let tables = resolve_stream_names(query)?;
+            create_streams_for_distributed(tables).await?;
+
+            let raw_logical_plan = QUERY_SESSION.state().create_logical_plan(query).await?;
+            let raw_logical_plan = apply_access_policies(raw_logical_plan, permissions)?;
+            let query = crate::query::Query {
+                raw_logical_plan,
+                time_range: time_range.clone(),
+                filter_tag: None,
+                bytes_scanned: Default::default(),
+            };
+            match execute(query, false).await? {
+                (Either::Left(records), _) => record_batches_to_json(&records),
+                (Either::Right(_), _) => Err(anyhow!("Query returned no results")),
+            }
+        }
+        Mode::Prism => {
+            // the querier runs the query with the access of the cluster,
+            // row filters and column masks can't be applied there
+            let tables = resolve_stream_names(query)?;
+            if !tables
+                .iter()
+                .all(|stream| reads_unrestricted(permissions, stream))
+            {
+                return Err(anyhow!(
+                    "Queries restricted by row filters or column masks can't run in Prism mode"
+                ));
+            }
+            let query_request = Query {
+                query: query.to_string(),
+                start_time: time_range.start.to_rfc3339(),
+                end_time: time_range.end.to_rfc3339(),
+                streaming: false,
+                send_null: false,
+                fields: false,
+                filter_tags: None,
+                filter_id: None,
+            };
+            let (result, _) = send_query_request(&query_request).await?;
+            Ok(match result {
+                Value::Array(rows) => rows
+                    .into_iter()
+                    .filter_map(|row| match row {
+                        Value::Object(row) => Some(row),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => vec![],
+            })
+        }
+        mode => Err(anyhow!("Unsupported mode '{mode:?}' for reports")),
+    }
+}
+
+fn summary(title: &str, time_range: &TimeRange, tiles: &[TileResult]) -> String {
+    let mut summary = format!(
+        "Report {title} from {} to {}\n",
+        time_range.start.to_rfc3339(),
+        time_range.end.to_rfc3339()
+    );
+
+    for tile in tiles {
+        summary.push_str(&format!("\n{}\n", tile.title));
+        match &tile.rows {
+            Err(err) => summary.push_str(&format!("  failed: {err}\n")),
+            Ok(rows) if rows.is_empty() => summary.push_str("  no results\n"),
+            Ok(rows) => {
+                for row in rows.iter().take(MAX_SUMMARY_ROWS) {
+                    let row = row
+                        .iter()
+                        .map(|(key, value)| format!("{key}: {}", display_value(value)))
+                        .join(", ");
+                    summary.push_str(&format!("  {row}\n"));
+                }
+                if rows.len() > MAX_SUMMARY_ROWS {
+                    summary.push_str(&format!(
+                        "  and {} more rows\n",
+                        rows.len() - MAX_SUMMARY_ROWS
+                    ));
+                }
+            }
+        }
+    }
+
+    summary
+}
+
+fn attachments(format: ReportFormat, tiles: &[TileResult]) -> Vec<Attachment> {
+    let (extension, content_type) = match format {
+        ReportFormat::Summary => return vec![],
+        ReportFormat::Csv => ("csv", "text/csv"),
+        ReportFormat::Json => ("json", "application/json"),
+    };
+
+    tiles
+        .iter()
+        .filter_map(|tile| {
+            let rows = tile.rows.as_ref().ok()?;
+            let content = match format {
+                ReportFormat::Csv => to_csv(rows),
+                _ => serde_json::to_string(rows).ok()?,
+            };
+            let name = tile
+                .title
+                .chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                .collect::<String>();
+            Some(Attachment {
+                filename: format!("{name}.{extension}"),
+                content_type,
+                content,
+            })
+        })
+        .collect()
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Renders rows as CSV, the header holds the fields of all the rows in order of appearance
+fn to_csv(rows: &[Map<String, Value>]) -> String {
+    let header = rows
+        .iter()
+        .flat_map(|row| row.keys())
+        .unique()
+        .collect_vec();
+
+    let escape = |field: String| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field
+        }
+    };
+
+    let mut csv = header.iter().map(|key| escape(key.to_string())).join(",");
+    csv.push('\n');
+    for row in rows {
+        let line = header
+            .iter()
+            .map(|key| escape(row.get(*key).map(display_value).unwrap_or_default()))
+            .join(",");
+        csv.push_str(&line);
+        csv.push('\n');
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn rows(value: Value) -> Vec<Map<String, Value>> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn leases_read_back_equal() {
+        // a claim wins only if it reads back equal to the one written
+        let lease = ReportLease {
+            dashboard_id: Ulid::new(),
+            node_id: "querier-1".to_owned(),
+            claimed_at: Utc::now(),
+        };
+        let bytes = serde_json::to_vec(&lease).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<ReportLease>(&bytes).unwrap(),
+            lease
+        );
+    }
+
+    #[test]
+    fn renders_csv() {
+        let rows = rows(json!([
+            {"status": 200, "path": "/a,b"},
+            {"status": 500, "error": "say \"hi\""}
+        ]));
+        assert_eq!(
+            to_csv(&rows),
+            "path,status,error\n\"/a,b\",200,\n,500,\"say \"\"hi\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn summarizes_tiles() {
+        let time_range = TimeRange::parse_human_time("7d", "now").unwrap();
+        let tiles = vec![
+            TileResult {
+                tile_id: Ulid::new(),
+                title: "errors".to_owned(),
+                rows: Ok(rows(json!([{"count": 42}]))),
+            },
+            TileResult {
+                tile_id: Ulid::new(),
+                title: "latency".to_owned(),
+                rows: Err("stream not found".to_owned()),
+            },
+        ];
+
+        let summary = summary("weekly", &time_range, &tiles);
+        assert!(summary.starts_with("Report weekly from "));
+        assert!(summary.contains("\nerrors\n  count: 42\n"));
+        assert!(summary.contains("\nlatency\n  failed: stream not found\n"));
+
+        let attachments = attachments(ReportFormat::Csv, &tiles);
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].filename, "errors.csv");
+    }
+
+    #[test]
+    fn validates_schedule() {
+        let schedule: ReportSchedule = serde_json::from_value(json!({
+            "frequency": 10080,
+            "timeRange": "7d",
+            "targets": [Ulid::new()]
+        }))
+        .unwrap();
+        assert!(schedule.validate_config().is_ok());
+        assert_eq!(schedule.format, ReportFormat::Summary);
+
+        let now = Utc::now();
+        assert!(schedule.is_due(None, now));
+        assert!(!schedule.is_due(Some(now - TimeDelta::days(6)), now));
+        assert!(schedule.is_due(Some(now - TimeDelta::days(7)), now));
+
+        let invalid = ReportSchedule {
+            time_range: "a week".to_owned(),
+            ..schedule
+        };
+        assert!(invalid.validate_config().is_err());
+    }
+}
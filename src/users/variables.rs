@@ -0,0 +1,446 @@
+/*
+ * Parseable Server (C) 2022 - 2025 Parseable, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Dashboard variables, referenced as `$name` or `${name}` in the queries of the tiles.
+//! Selected values are substituted as escaped SQL literals or identifiers, never as raw
+//! SQL, so that a selection can't change the structure of the query.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::rbac::role::Permission;
+use crate::utils::time::TimeRange;
+
+use super::reports::execute_tile_query;
+
+/// How the selected value of a variable is written in the query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VariableKind {
+    /// quoted string literal, e.g. `'checkout'`
+    #[default]
+    Literal,
+    /// quoted identifier of a column, e.g. `"service.name"`
+    Identifier,
+    /// numeric literal
+    Number,
+    /// humantime duration written as an interval, e.g. `5m` as `INTERVAL '300 seconds'`
+    Interval,
+}
+
+impl VariableKind {
+    fn render(self, name: &str, value: &str) -> Result<String, VariableError> {
+        let invalid = |reason: &str| VariableError::InvalidValue {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        match self {
+            // backslashes are left out as some dialects read them as escapes within literals
+            Self::Literal => {
+                if value.chars().any(|c| c == '\\' || c.is_control()) {
+                    return Err(invalid("backslashes and control characters aren't allowed"));
+                }
+                Ok(format!("'{}'", value.replace('\'', "''")))
+            }
+            Self::Identifier => {
+                if value.is_empty() {
+                    return Err(invalid("identifier can't be empty"));
+                }
+                if value.chars().any(char::is_control) {
+                    return Err(invalid("control characters aren't allowed"));
+                }
+                Ok(format!("\"{}\"", value.replace('"', "\"\"")))
+            }
+            Self::Number => {
+                let value = value.trim();
+                match value.parse::<f64>() {
+                    // signed numbers are parenthesized, e.g. so that `a -$n` doesn't read `a --1`
+                    Ok(number) if number.is_finite() && value.starts_with(['-', '+']) => {
+                        Ok(format!("({value})"))
+                    }
+                    Ok(number) if number.is_finite() => Ok(value.to_owned()),
+                    _ => Err(invalid("expected a number")),
+                }
+            }
+            Self::Interval => match humantime::parse_duration(value) {
+                Ok(duration) if duration.as_secs() > 0 => {
+                    Ok(format!("INTERVAL '{} seconds'", duration.as_secs()))
+                }
+                _ => Err(invalid("expected a duration of at least a second, e.g. 5m")),
+            },
+        }
+    }
+}
+
+/// Selected value of a variable, a list if the variable allows selecting many
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum VariableValue {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Variable {
+    pub name: String,
+    #[serde(default)]
+    pub kind: VariableKind,
+    /// fixed options of the variable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    /// query selecting a single column, the distinct values of which are the options
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// value used when none is selected, e.g. by reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<VariableValue>,
+    /// whether many values can be selected, written as a comma separated list
+    #[serde(default)]
+    pub multi: bool,
+}
+
+impl Variable {
+    /// Writes the selected value as SQL
+    fn render(&self, value: &VariableValue) -> Result<String, VariableError> {
+        let values = match value {
+            VariableValue::One(value) => vec![value],
+            VariableValue::Many(values) if self.multi || values.len() == 1 => {
+                values.iter().collect()
+            }
+            VariableValue::Many(_) => return Err(VariableError::NotMulti(self.name.clone())),
+        };
+        if values.is_empty() {
+            return Err(VariableError::Missing(self.name.clone()));
+        }
+
+        let rendered = values
+            .into_iter()
+            .map(|value| self.kind.render(&self.name, value))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rendered.join(", "))
+    }
+
+    /// Options of the variable, the results of its query as read with the permissions if it has one
+    pub async fn options(
+        &self,
+        time_range: &TimeRange,
+        permissions: &[Permission],
+    ) -> Result<Vec<String>, VariableError> {
+        let Some(query) = &self.query else {
+            return Ok(self.options.clone());
+        };
+
+        let rows = execute_tile_query(query, time_range, permissions).await?;
+        let mut options = vec![];
+        let mut seen = HashSet::new();
+        for row in rows {
+            let mut values = row.into_values();
+            let (Some(value), None) = (values.next(), values.next()) else {
+                return Err(VariableError::OptionsColumns(self.name.clone()));
+            };
+            let option = match value {
+                Value::Null => continue,
+                Value::String(value) => value,
+                value => value.to_string(),
+            };
+            if seen.insert(option.clone()) {
+                options.push(option);
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Checks the names of the variables, and that their options and defaults are valid values
+pub fn validate(variables: &[Variable]) -> Result<(), VariableError> {
+    let mut names = HashSet::new();
+    for variable in variables {
+        let name = &variable.name;
+        let mut chars = name.chars();
+        let valid_name = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(VariableError::Invalid(format!(
+                "Variable name {name:?} should be made of letters, digits and underscores, starting with a letter or an underscore"
+            )));
+        }
+        if !names.insert(name) {
+            return Err(VariableError::Invalid(format!(
+                "Variable ${name} is defined more than once"
+            )));
+        }
+        if !variable.options.is_empty() && variable.query.is_some() {
+            return Err(VariableError::Invalid(format!(
+                "Options of variable ${name} should come either from a list or from a query"
+            )));
+        }
+        for option in &variable.options {
+            variable.kind.render(name, option)?;
+        }
+        if let Some(default) = &variable.default {
+            variable.render(default)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the variables referenced in the query with the selected values, or their defaults.
+/// References within quoted strings and identifiers, and within comments, are left as they
+/// are, as are positional parameters like `$1`.
+pub fn substitute(
+    query: &str,
+    variables: &[Variable],
+    selected: &HashMap<String, VariableValue>,
+) -> Result<String, VariableError> {
+    let mut resolved = String::with_capacity(query.len());
+    let mut chars = query.chars().peekable();
+    let mut quote = None;
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            // a doubled quote closes and reopens the quoted text, which keeps this in sync
+            if c == q {
+                quote = None;
+            }
+            resolved.push(c);
+            continue;
+        }
+
+        match c {
+            '\'' | '"' => {
+                quote = Some(c);
+                resolved.push(c);
+            }
+            '-' if chars.next_if_eq(&'-').is_some() => {
+                resolved.push_str("--");
+                while let Some(c) = chars.next_if(|c| *c != '\n') {
+                    resolved.push(c);
+                }
+            }
+            '/' if chars.next_if_eq(&'*').is_some() => {
+                resolved.push_str("/*");
+                let mut star = false;
+                for c in chars.by_ref() {
+                    resolved.push(c);
+                    if star && c == '/' {
+                        break;
+                    }
+                    star = c == '*';
+                }
+            }
+            '$' => {
+                let braced = chars.next_if_eq(&'{').is_some();
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                let closed = !braced || chars.next_if_eq(&'}').is_some();
+
+                if !closed || !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                    resolved.push('$');
+                    if braced {
+                        resolved.push('{');
+                    }
+                    resolved.push_str(&name);
+                    continue;
+                }
+
+                let variable = variables
+                    .iter()
+                    .find(|variable| variable.name == name)
+                    .ok_or_else(|| VariableError::Undefined(name.clone()))?;
+                let value = selected
+                    .get(&name)
+                    .or(variable.default.as_ref())
+                    .ok_or(VariableError::Missing(name))?;
+                resolved.push_str(&variable.render(value)?);
+            }
+            _ => resolved.push(c),
+        }
+    }
+
+    Ok(resolved)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VariableError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("Variable ${0} is not defined on the dashboard")]
+    Undefined(String),
+    #[error("No value is selected for variable ${0}")]
+    Missing(String),
+    #[error("Variable ${0} takes a single value")]
+    NotMulti(String),
+    #[error("Invalid value {value:?} of variable ${name}: {reason}")]
+    InvalidValue {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    #[error("Options query of variable ${0} should select a single column")]
+    OptionsColumns(String),
+    #[error("Failed to run the options query: {0}")]
+    Query(#[from] anyhow::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(name: &str, kind: VariableKind, multi: bool) -> Variable {
+        Variable {
+            name: name.to_owned(),
+            kind,
+            options: vec![],
+            query: None,
+            default: None,
+            multi,
+        }
+    }
+
+    #[test]
+    fn substitutes_escaped_values() {
+        let variables = vec![
+            variable("service", VariableKind::Literal, true),
+            variable("field", VariableKind::Identifier, false),
+            Variable {
+                default: Some(VariableValue::One("5m".to_owned())),
+                ..variable("interval", VariableKind::Interval, false)
+            },
+        ];
+        let selected = HashMap::from([
+            (
+                "service".to_owned(),
+                VariableValue::Many(vec!["api".to_owned(), "o'brien".to_owned()]),
+            ),
+            ("field".to_owned(), VariableValue::One("a\"b".to_owned())),
+        ]);
+
+        let query = r#"SELECT date_bin($interval, p_timestamp), ${field} FROM app WHERE service IN ($service) AND msg = '$service' AND "$field" > $1"#;
+        assert_eq!(
+            substitute(query, &variables, &selected).unwrap(),
+            r#"SELECT date_bin(INTERVAL '300 seconds', p_timestamp), "a""b" FROM app WHERE service IN ('api', 'o''brien') AND msg = '$service' AND "$field" > $1"#
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_selections() {
+        let variables = vec![
+            variable("env", VariableKind::Literal, false),
+            variable("limit", VariableKind::Number, false),
+        ];
+        let selected = HashMap::from([
+            (
+                "env".to_owned(),
+                VariableValue::Many(vec!["a".to_owned(), "b".to_owned()]),
+            ),
+            ("limit".to_owned(), VariableValue::One("1; DROP".to_owned())),
+        ]);
+
+        assert!(matches!(
+            substitute("SELECT $env", &variables, &selected),
+            Err(VariableError::NotMulti(_))
+        ));
+        assert!(matches!(
+            substitute("LIMIT $limit", &variables, &selected),
+            Err(VariableError::InvalidValue { .. })
+        ));
+        let negative = HashMap::from([("limit".to_owned(), VariableValue::One("-1".to_owned()))]);
+        assert_eq!(
+            substitute("SELECT a -$limit", &variables, &negative).unwrap(),
+            "SELECT a -(-1)"
+        );
+        assert!(matches!(
+            substitute("SELECT $other", &variables, &selected),
+            Err(VariableError::Undefined(_))
+        ));
+        assert!(matches!(
+            substitute("SELECT $env", &variables, &HashMap::new()),
+            Err(VariableError::Missing(_))
+        ));
+    }
+
+    #[test]
+    fn skips_comments_and_quoted_identifiers() {
+        let variables = vec![variable("env", VariableKind::Literal, false)];
+        let selected = HashMap::from([("env".to_owned(), VariableValue::One("prod".to_owned()))]);
+
+        let query = "SELECT \"$env\" -- by $env\nFROM app /* $env */ WHERE env = $env / 2 - 1";
+        assert_eq!(
+            substitute(query, &variables, &selected).unwrap(),
+            "SELECT \"$env\" -- by $env\nFROM app /* $env */ WHERE env = 'prod' / 2 - 1"
+        );
+        // an unterminated comment runs to the end of the query
+        assert_eq!(
+            substitute("SELECT 1 /* $env", &variables, &selected).unwrap(),
+            "SELECT 1 /* $env"
+        );
+    }
+
+    #[test]
+    fn rejects_escapes_in_literals() {
+        let variables = vec![
+            variable("env", VariableKind::Literal, false),
+            variable("field", VariableKind::Identifier, false),
+        ];
+        for value in ["a\\", "a\nb", "a\0"] {
+            let selected =
+                HashMap::from([("env".to_owned(), VariableValue::One(value.to_owned()))]);
+            assert!(matches!(
+                substitute("SELECT $env", &variables, &selected),
+                Err(VariableError::InvalidValue { .. })
+            ));
+        }
+        let selected = HashMap::from([("field".to_owned(), VariableValue::One("a\nb".to_owned()))]);
+        assert!(matches!(
+            substitute("SELECT $field", &variables, &selected),
+            Err(VariableError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn validates_variables() {
+        assert!(validate(&[variable("service_name", VariableKind::Literal, false)]).is_ok());
+        assert!(validate(&[variable("1st", VariableKind::Literal, false)]).is_err());
+        assert!(
+            validate(&[
+                variable("env", VariableKind::Literal, false),
+                variable("env", VariableKind::Literal, false),
+            ])
+            .is_err()
+        );
+        assert!(
+            validate(&[Variable {
+                default: Some(VariableValue::One("soon".to_owned())),
+                ..variable("interval", VariableKind::Interval, false)
+            }])
+            .is_err()
+        );
+    }
+}
@@ -22,9 +22,9 @@ use actix_web::{
     dev::ServiceRequest,
     error::{ErrorUnauthorized, ErrorUnprocessableEntity},
 };
-use actix_web_httpauth::extractors::basic::BasicAuth;
+use actix_web_httpauth::extractors::{basic::BasicAuth, bearer::BearerAuth};
 
-use crate::rbac::map::SessionKey;
+use crate::rbac::{api_key::API_KEY_PREFIX, map::SessionKey};
 
 pub fn extract_session_key(req: &mut ServiceRequest) -> Result<SessionKey, Error> {
     // Extract username and password from the request using basic auth extractor.
@@ -36,9 +36,14 @@ pub fn extract_session_key(req: &mut ServiceRequest) -> Result<SessionKey, Error
         let password = creds.password().unwrap_or("").trim().to_owned();
         SessionKey::BasicAuth { username, password }
     });
+    let bearer = req.extract::<BearerAuth>().into_inner();
 
     if let Ok(basic) = basic {
         Ok(basic)
+    } else if let Ok(bearer) = bearer
+        && bearer.token().starts_with(API_KEY_PREFIX)
+    {
+        Ok(SessionKey::ApiKey(bearer.token().to_owned()))
     } else if let Some(cookie) = req.cookie("session") {
         let ulid = ulid::Ulid::from_string(cookie.value())
             .map_err(|_| ErrorUnprocessableEntity("Cookie is tampered with or invalid"))?;
@@ -58,9 +63,14 @@ pub fn extract_session_key_from_req(req: &HttpRequest) -> Result<SessionKey, Err
         let password = creds.password().unwrap_or("").trim().to_owned();
         SessionKey::BasicAuth { username, password }
     });
+    let bearer = BearerAuth::extract(req).into_inner();
 
     if let Ok(basic) = basic {
         Ok(basic)
+    } else if let Ok(bearer) = bearer
+        && bearer.token().starts_with(API_KEY_PREFIX)
+    {
+        Ok(SessionKey::ApiKey(bearer.token().to_owned()))
     } else if let Some(cookie) = req.cookie("session") {
         let ulid = ulid::Ulid::from_string(cookie.value())
             .map_err(|_| ErrorUnprocessableEntity("Cookie is tampered with or invalid"))?;
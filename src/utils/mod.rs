@@ -26,6 +26,7 @@ pub mod time;
 pub mod uid;
 pub mod update;
 
+use crate::audit::AUDIT_STREAM_NAME;
 use crate::handlers::http::rbac::RBACError;
 use crate::parseable::PARSEABLE;
 use crate::query::resolve_stream_names;
@@ -91,6 +92,16 @@ pub async fn user_auth_for_datasets(
     tables: &[String],
 ) -> Result<(), actix_web::error::Error> {
     for table_name in tables {
+        // the audit log records what every user did, only admins can read it
+        if table_name == AUDIT_STREAM_NAME {
+            if !is_admin_permissions(permissions) {
+                return Err(actix_web::error::ErrorUnauthorized(format!(
+                    "Only admins have access to stream- {table_name}"
+                )));
+            }
+            continue;
+        }
+
         let mut authorized = false;
 
         // in permission check if user can run query on the stream.
@@ -101,7 +112,8 @@ pub async fn user_auth_for_datasets(
                     authorized = true;
                     break;
                 }
-                Permission::Resource(Action::Query, ParseableResourceType::Stream(stream)) => {
+                Permission::Resource(Action::Query, ParseableResourceType::Stream(stream))
+                | Permission::RestrictedQuery { stream, .. } => {
                     if !PARSEABLE.check_or_load_stream(stream).await {
                         return Err(actix_web::error::ErrorUnauthorized(format!(
                             "Stream not found: {table_name}"
@@ -151,17 +163,19 @@ pub fn is_admin(req: &HttpRequest) -> Result<bool, anyhow::Error> {
     let session_key =
         extract_session_key_from_req(req).map_err(|e| anyhow::Error::msg(e.to_string()))?;
 
-    let permissions = Users.get_permissions(&session_key);
+    Ok(is_admin_session(&session_key))
+}
 
-    // Check if user has admin permissions (Action::All on All resources)
-    for permission in permissions.iter() {
-        match permission {
-            Permission::Resource(Action::All, ParseableResourceType::All) => {
-                return Ok(true);
-            }
-            _ => continue,
-        }
-    }
+pub fn is_admin_session(session_key: &SessionKey) -> bool {
+    is_admin_permissions(&Users.get_permissions(session_key))
+}
 
-    Ok(false)
+pub fn is_admin_permissions(permissions: &[Permission]) -> bool {
+    // Check if user has admin permissions (Action::All on All resources)
+    permissions.iter().any(|permission| {
+        matches!(
+            permission,
+            Permission::Resource(Action::All, ParseableResourceType::All)
+        )
+    })
 }